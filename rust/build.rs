@@ -0,0 +1,9 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // protox compiles the schema in pure Rust, so no system `protoc` is needed.
+    let fds = protox::compile(["proto/cardvault.proto"], ["proto"])?;
+    tonic_build::configure()
+        .build_client(false)
+        .compile_fds(fds)?;
+    println!("cargo:rerun-if-changed=proto");
+    Ok(())
+}
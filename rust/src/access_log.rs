@@ -0,0 +1,61 @@
+use std::time::Instant;
+
+use axum::{
+    body::HttpBody,
+    extract::{MatchedPath, Request},
+    http::{header, HeaderMap},
+    middleware::Next,
+    response::Response,
+};
+use tracing::info;
+
+use crate::client_ip::ClientIp;
+
+fn header_or_dash(headers: &HeaderMap, name: header::HeaderName) -> &str {
+    headers.get(name).and_then(|v| v.to_str().ok()).unwrap_or("-")
+}
+
+/// Logs each request once, after it is answered, as fields: the client
+/// address (the one forwarded by a trusted proxy), method, path with the
+/// query, route pattern, status, response size in bytes before compression
+/// (left out if not known up front), time to the response in milliseconds,
+/// user agent, and referer. For a streamed response, such as the change
+/// feed, the time is until it started.
+pub async fn middleware(request: Request, next: Next) -> Response {
+    let started = Instant::now();
+    let client = request
+        .extensions()
+        .get::<ClientIp>()
+        .map_or_else(|| "-".to_string(), |ClientIp(ip)| ip.to_string());
+    let method = request.method().clone();
+    let target = request
+        .uri()
+        .path_and_query()
+        .map_or_else(|| request.uri().path().to_string(), |pq| pq.to_string());
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or_else(|| "-".to_string(), |path| path.as_str().to_string());
+    let user_agent = header_or_dash(request.headers(), header::USER_AGENT).to_string();
+    let referer = header_or_dash(request.headers(), header::REFERER).to_string();
+
+    let response = next.run(request).await;
+
+    let size = response
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok()?.parse::<u64>().ok())
+        .or_else(|| response.body().size_hint().exact());
+    info!(
+        client,
+        method = %method,
+        path = target,
+        route,
+        status = response.status().as_u16(),
+        size,
+        duration_ms = started.elapsed().as_millis() as u64,
+        user_agent,
+        referer,
+    );
+    response
+}
@@ -0,0 +1,38 @@
+/// The MIME type of an uploaded file: by its magic bytes where they tell,
+/// else by its name, else `application/octet-stream`.
+pub fn detect_mime(filename: &str, data: &[u8]) -> String {
+    match infer::get(data) {
+        Some(kind) => kind.mime_type().to_string(),
+        None => mime_guess::from_path(filename).first_or_octet_stream().to_string(),
+    }
+}
+
+/// The name a file is kept under: its last path component, without
+/// control characters, and `attachment` if nothing is left.
+pub fn clean_filename(filename: &str) -> String {
+    let name = filename.rsplit(['/', '\\']).next().unwrap_or_default();
+    let name: String = name.chars().filter(|c| !c.is_control()).collect();
+    match name.trim() {
+        "" | "." | ".." => "attachment".to_string(),
+        name => name.to_string(),
+    }
+}
+
+/// `Content-Disposition` for downloading a file as `filename`: a plain
+/// ASCII name for old clients and the exact one, percent-encoded, for the
+/// rest (RFC 6266).
+pub fn content_disposition(filename: &str) -> String {
+    let ascii: String = filename
+        .chars()
+        .map(|c| if c.is_ascii_graphic() && c != '"' && c != '\\' || c == ' ' { c } else { '_' })
+        .collect();
+    let mut encoded = String::new();
+    for byte in filename.bytes() {
+        if byte.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    format!("attachment; filename=\"{ascii}\"; filename*=UTF-8''{encoded}")
+}
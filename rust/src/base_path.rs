@@ -0,0 +1,45 @@
+use std::sync::OnceLock;
+
+use anyhow::{bail, Result};
+
+/// Path prefix the app is served under, e.g. `/cardvault` behind a reverse
+/// proxy, set once at startup from `--base-path`. Empty at the root.
+static BASE_PATH: OnceLock<String> = OnceLock::new();
+
+/// Sets the prefix, given as `/cardvault` or `cardvault/`. Empty or `/` is
+/// the root. Fails on characters a URL path would need escaped.
+pub fn set(path: &str) -> Result<()> {
+    let path = path.trim().trim_matches('/');
+    if path.is_empty() {
+        let _ = BASE_PATH.set(String::new());
+        return Ok(());
+    }
+    let valid_segment = |segment: &str| {
+        !matches!(segment, "" | "." | "..")
+            && segment
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '~'))
+    };
+    if !path.split('/').all(valid_segment) {
+        bail!("invalid base path '/{path}'");
+    }
+    let _ = BASE_PATH.set(format!("/{path}"));
+    Ok(())
+}
+
+/// The prefix, `""` at the root.
+pub fn get() -> &'static str {
+    BASE_PATH.get().map_or("", String::as_str)
+}
+
+/// The public URL of the app's own `path`, e.g. `/uploads/abc.jpg` →
+/// `/cardvault/uploads/abc.jpg`.
+pub fn url(path: &str) -> String {
+    format!("{}{path}", get())
+}
+
+/// `url` with the prefix taken off again, for URLs the app handed out and
+/// is given back.
+pub fn strip(url: &str) -> &str {
+    url.strip_prefix(get()).unwrap_or(url)
+}
@@ -0,0 +1,36 @@
+use axum::{
+    extract::Request,
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+
+/// Room above a file's own size limit for the rest of the multipart form
+/// carrying it.
+pub const MULTIPART_FRAMING: usize = 64 * 1024;
+
+/// Turns the plain-text 413 axum's extractors answer with, when a body runs
+/// past its route's `DefaultBodyLimit`, into the JSON error body every other
+/// response carries. 413s the handlers write themselves, which name the
+/// limit, pass through.
+pub async fn json_errors(request: Request, next: Next) -> Response {
+    let response = next.run(request).await;
+    if response.status() != StatusCode::PAYLOAD_TOO_LARGE {
+        return response;
+    }
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("application/json"));
+    if is_json {
+        return response;
+    }
+    (
+        StatusCode::PAYLOAD_TOO_LARGE,
+        Json(json!({"error": "request body too large"})),
+    )
+        .into_response()
+}
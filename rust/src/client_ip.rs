@@ -0,0 +1,155 @@
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap},
+    middleware::Next,
+    response::Response,
+};
+
+/// The address a request came from: the peer's, or behind a trusted proxy
+/// the client's it forwarded. In the request's extensions.
+#[derive(Clone, Copy, Debug)]
+pub struct ClientIp(pub IpAddr);
+
+/// An address, or a network in CIDR notation (`10.0.0.0/8`, `fd00::/8`).
+#[derive(Clone, Copy, Debug)]
+pub struct Network {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl FromStr for Network {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let s = s.trim();
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+        let addr: IpAddr = addr.parse().map_err(|_| format!("`{s}` is not an address or network"))?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .parse::<u8>()
+                .ok()
+                .filter(|p| *p <= max)
+                .ok_or_else(|| format!("`{s}` has a bad prefix length"))?,
+            None => max,
+        };
+        Ok(Network { addr, prefix })
+    }
+}
+
+impl Network {
+    fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Proxies whose `Forwarded` and `X-Forwarded-For` headers are believed.
+pub struct TrustedProxies(Vec<Network>);
+
+impl TrustedProxies {
+    pub fn new(networks: Vec<Network>) -> Self {
+        TrustedProxies(networks)
+    }
+
+    fn trusts(&self, ip: IpAddr) -> bool {
+        self.0.iter().any(|net| net.contains(ip))
+    }
+
+    /// The client behind `peer`. The forwarded chain is read from the
+    /// nearest hop back, stopping at the first address that is not a
+    /// trusted proxy: hops before it could have been made up by the client.
+    /// A Unix socket peer has no address and is trusted, as only local
+    /// processes the socket's permissions let in can connect.
+    fn client(&self, peer: Option<IpAddr>, headers: &HeaderMap) -> Option<IpAddr> {
+        let mut client = peer;
+        for hop in forwarded_chain(headers).into_iter().rev() {
+            if client.is_some_and(|ip| !self.trusts(ip)) {
+                break;
+            }
+            match hop {
+                Some(ip) => client = Some(ip),
+                // An obfuscated or unknown hop ends what can be told
+                None => break,
+            }
+        }
+        client
+    }
+}
+
+/// The client addresses a request passed through, oldest first, from the
+/// `Forwarded` header, else `X-Forwarded-For`. `None` stands for a hop
+/// given as `unknown` or an obfuscated name.
+fn forwarded_chain(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    let values = |name| {
+        headers
+            .get_all(name)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(String::from)
+            .collect::<Vec<_>>()
+    };
+    let forwarded = values(header::FORWARDED);
+    if !forwarded.is_empty() {
+        return forwarded
+            .iter()
+            .map(|element| {
+                element
+                    .split(';')
+                    .filter_map(|pair| pair.trim().split_once('='))
+                    .find(|(key, _)| key.eq_ignore_ascii_case("for"))
+                    .and_then(|(_, node)| parse_node(node))
+            })
+            .collect();
+    }
+    values(header::HeaderName::from_static("x-forwarded-for"))
+        .iter()
+        .map(|node| parse_node(node))
+        .collect()
+}
+
+/// An address as proxies write it: bare, quoted, with a port, or as an
+/// IPv6 address in brackets (`"[2001:db8::1]:4711"`).
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+    if let Ok(ip) = node.parse() {
+        return Some(ip);
+    }
+    if let Some(rest) = node.strip_prefix('[') {
+        return rest.split_once(']').and_then(|(ip, _)| ip.parse().ok());
+    }
+    node.parse::<SocketAddr>().ok().map(|addr| addr.ip())
+}
+
+/// Records the request's `ClientIp`. Forwarding headers are only read from
+/// a peer in `--trusted-proxies`, or on a Unix socket; anyone else could
+/// send them.
+pub async fn middleware(State(proxies): State<Arc<TrustedProxies>>, mut request: Request, next: Next) -> Response {
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip().to_canonical());
+    if let Some(client) = proxies.client(peer, request.headers()) {
+        request.extensions_mut().insert(ClientIp(client));
+    }
+    next.run(request).await
+}
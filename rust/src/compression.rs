@@ -0,0 +1,38 @@
+use axum::http::{header, Extensions, HeaderMap, StatusCode, Version};
+use tower_http::compression::{
+    predicate::{DefaultPredicate, Predicate, SizeAbove},
+    CompressionLayer,
+};
+
+/// Content types worth compressing: JSON and the text the web UI, exports,
+/// and feeds are made of. Photos are already compressed.
+const COMPRESSIBLE: &[&str] = &[
+    "application/json",
+    "application/javascript",
+    "application/xml",
+    "image/svg+xml",
+    "text/",
+];
+
+/// Whether a response's type is in `COMPRESSIBLE`. Partial responses are
+/// left alone, as their `Content-Range` counts bytes of the uncompressed
+/// file.
+fn compressible(status: StatusCode, _: Version, headers: &HeaderMap, _: &Extensions) -> bool {
+    if status == StatusCode::PARTIAL_CONTENT || headers.contains_key(header::CONTENT_RANGE) {
+        return false;
+    }
+    let Some(content_type) = headers.get(header::CONTENT_TYPE).and_then(|v| v.to_str().ok()) else {
+        return false;
+    };
+    COMPRESSIBLE.iter().any(|prefix| content_type.starts_with(prefix))
+}
+
+/// gzip or brotli, as the client accepts, for compressible responses of at
+/// least `min_size` bytes. The default predicate keeps out the event stream
+/// and gRPC.
+pub fn layer(min_size: u16) -> CompressionLayer<impl Predicate> {
+    CompressionLayer::new()
+        .gzip(true)
+        .br(true)
+        .compress_when(DefaultPredicate::new().and(SizeAbove::new(min_size)).and(compressible))
+}
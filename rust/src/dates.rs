@@ -0,0 +1,109 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use chrono::{Datelike, Days, NaiveDate};
+use rusqlite::Connection;
+
+use crate::{
+    models::{CardRelations, ReminderDue, UpcomingDate},
+    store,
+};
+
+/// Canonical form of a birthday or anniversary: `YYYY-MM-DD`, or `--MM-DD`
+/// when the year is unknown. Empty stays empty. `None` if it is neither.
+pub fn canonical(date: &str) -> Option<String> {
+    let date = date.trim();
+    if date.is_empty() {
+        return Some(String::new());
+    }
+    if let Some(month_day) = date.strip_prefix("--") {
+        // 2000 is a leap year, so --02-29 is accepted.
+        let parsed = NaiveDate::parse_from_str(&format!("2000-{month_day}"), "%Y-%m-%d").ok()?;
+        return Some(parsed.format("--%m-%d").to_string());
+    }
+    let parsed = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+    Some(parsed.format("%Y-%m-%d").to_string())
+}
+
+/// Splits a canonical date into its year (if known), month, and day.
+fn parts(date: &str) -> Option<(Option<i32>, u32, u32)> {
+    if let Some(month_day) = date.strip_prefix("--") {
+        let (m, d) = month_day.split_once('-')?;
+        return Some((None, m.parse().ok()?, d.parse().ok()?));
+    }
+    let parsed = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+    Some((Some(parsed.year()), parsed.month(), parsed.day()))
+}
+
+/// The month and day in `year`; February 29 falls on the 28th in other years.
+fn in_year(year: i32, month: u32, day: u32) -> Option<NaiveDate> {
+    NaiveDate::from_ymd_opt(year, month, day).or_else(|| NaiveDate::from_ymd_opt(year, month, day - 1))
+}
+
+/// The first occurrence of `date` on or after `today`, with the years since
+/// the original date when its year is known.
+fn next_occurrence(date: &str, today: NaiveDate) -> Option<(NaiveDate, Option<i64>)> {
+    let (year, month, day) = parts(date)?;
+    let mut next = in_year(today.year(), month, day)?;
+    if next < today {
+        next = in_year(today.year() + 1, month, day)?;
+    }
+    let years = year.map(|y| i64::from(next.year() - y)).filter(|&n| n >= 0);
+    Some((next, years))
+}
+
+/// Today's date in UTC.
+pub fn today() -> NaiveDate {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    NaiveDate::default() + Days::new(secs / 86_400)
+}
+
+/// Birthdays and anniversaries of active cards falling within the next
+/// `days` days (UTC), today included, soonest first.
+pub fn upcoming(conn: &Arc<Mutex<Connection>>, days: i64) -> Result<Vec<UpcomingDate>> {
+    let today = today();
+    let mut found = Vec::new();
+    for (id, birthday, anniversary) in store::card_dates(conn)? {
+        for (kind, date) in [("birthday", birthday), ("anniversary", anniversary)] {
+            let Some((next, years)) = next_occurrence(&date, today) else {
+                continue;
+            };
+            let days_until = next.signed_duration_since(today).num_days();
+            if days_until < days {
+                found.push((id, kind, next, days_until, years));
+            }
+        }
+    }
+    found.sort_by_key(|&(id, kind, next, ..)| (next, id, kind));
+
+    let ids: Vec<i64> = found.iter().map(|f| f.0).collect();
+    let cards = store::get_cards_by_ids(conn, &ids, CardRelations::ALL)?;
+    Ok(found
+        .into_iter()
+        .filter_map(|(id, kind, next, days_until, years)| {
+            let card = cards.iter().find(|c| c.id == id)?.clone();
+            Some(UpcomingDate {
+                kind: kind.to_string(),
+                date: next.format("%Y-%m-%d").to_string(),
+                days_until,
+                years,
+                card,
+            })
+        })
+        .collect())
+}
+
+/// The due dates (`YYYY-MM-DD`) covered by `due`, as an inclusive lower and
+/// an exclusive upper bound.
+pub fn due_range(due: ReminderDue) -> (Option<String>, Option<String>) {
+    let today = today();
+    let day = |offset: u64| Some((today + Days::new(offset)).format("%Y-%m-%d").to_string());
+    match due {
+        ReminderDue::Overdue => (None, day(0)),
+        ReminderDue::Today => (day(0), day(1)),
+        ReminderDue::Week => (day(0), day(7)),
+    }
+}
@@ -0,0 +1,33 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use tracing::{info, warn};
+
+use crate::{events, handlers::AppState, resumable, store, telemetry};
+
+/// Puts the vault back to the sample cards: every change, upload, webhook,
+/// and share link is gone.
+pub async fn reset(state: &Arc<AppState>) -> Result<()> {
+    let conn = state.conn.clone();
+    let (files, uploads) = telemetry::spawn_db(move || store::reset_demo(&conn)).await??;
+    let removed = files.len();
+    state.remove_files(files).await;
+    resumable::remove(&state.uploads_dir, &uploads).await;
+    state.events.publish(events::RESYNC, None);
+    info!("Demo vault reset ({removed} files removed)");
+    Ok(())
+}
+
+/// Resets the vault every `every`, the first time one interval from now.
+pub fn spawn(state: Arc<AppState>, every: Duration) {
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval_at(tokio::time::Instant::now() + every, every);
+        loop {
+            ticks.tick().await;
+            if let Err(e) = reset(&state).await {
+                warn!("demo reset failed: {e:#}");
+            }
+        }
+    });
+}
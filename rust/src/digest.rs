@@ -0,0 +1,158 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use chrono::{Datelike, Days, NaiveDateTime, NaiveTime, Weekday};
+use clap::ValueEnum;
+use handlebars::Handlebars;
+use rusqlite::Connection;
+use serde_json::{json, Value};
+use tracing::{info, warn};
+
+use crate::{
+    dates,
+    mail::{self, Message, SmtpConfig},
+    models::DueReminder,
+    store, telemetry,
+};
+
+const HTML_TEMPLATE: &str = include_str!("templates/digest.html.hbs");
+const TEXT_TEMPLATE: &str = include_str!("templates/digest.txt.hbs");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DigestFrequency {
+    /// Every day, covering that day
+    Daily,
+    /// Every Monday, covering the week ahead
+    Weekly,
+}
+
+impl DigestFrequency {
+    /// Days ahead the digest looks for due reminders and birthdays.
+    fn days(self) -> u64 {
+        match self {
+            Self::Daily => 1,
+            Self::Weekly => 7,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DigestOptions {
+    pub smtp: SmtpConfig,
+    pub to: Vec<String>,
+    pub frequency: DigestFrequency,
+    /// Hour of the day (UTC) the digest goes out
+    pub hour: u32,
+}
+
+/// Sends the digest on schedule for as long as the server runs.
+pub fn spawn(conn: Arc<Mutex<Connection>>, opts: DigestOptions) {
+    tokio::spawn(async move {
+        loop {
+            let now = now();
+            let next = next_run(now, opts.frequency, opts.hour);
+            info!("next {:?} digest at {next} UTC", opts.frequency);
+            let wait = (next - now).to_std().unwrap_or_default();
+            tokio::time::sleep(wait).await;
+            if let Err(e) = send(&conn, &opts).await {
+                warn!("digest failed: {e:#}");
+            }
+        }
+    });
+}
+
+/// Emails the digest now. Nothing is sent when there is nothing to report;
+/// returns whether an email went out.
+pub async fn send(conn: &Arc<Mutex<Connection>>, opts: &DigestOptions) -> Result<bool> {
+    let conn = conn.clone();
+    let (frequency, to) = (opts.frequency, opts.to.clone());
+    let Some(message) = telemetry::spawn_db(move || compose(&conn, frequency, to)).await??
+    else {
+        info!("digest skipped: nothing overdue or coming up");
+        return Ok(false);
+    };
+    mail::send(&opts.smtp, &message).await?;
+    info!("digest sent to {}", message.to.join(", "));
+    Ok(true)
+}
+
+/// Builds the digest, or `None` if it would be empty.
+fn compose(
+    conn: &Arc<Mutex<Connection>>,
+    frequency: DigestFrequency,
+    to: Vec<String>,
+) -> Result<Option<Message>> {
+    let today = dates::today();
+    let day = |offset: u64| (today + Days::new(offset)).format("%Y-%m-%d").to_string();
+    let overdue = store::due_reminders(conn, None, Some(&day(0)))?;
+    let due = store::due_reminders(conn, Some(&day(0)), Some(&day(frequency.days())))?;
+    let birthdays: Vec<Value> = dates::upcoming(conn, frequency.days() as i64)?
+        .into_iter()
+        .filter(|d| d.kind == "birthday")
+        .map(|d| json!({ "name": d.card.name, "date": d.date, "years": d.years }))
+        .collect();
+    if overdue.is_empty() && due.is_empty() && birthdays.is_empty() {
+        return Ok(None);
+    }
+
+    let (title, due_heading) = match frequency {
+        DigestFrequency::Daily => ("Your day", "Due today"),
+        DigestFrequency::Weekly => ("Your week", "Due this week"),
+    };
+    let data = json!({
+        "title": title,
+        "date": today.format("%A, %-d %B %Y").to_string(),
+        "overdue": overdue.iter().map(reminder).collect::<Vec<_>>(),
+        "due_heading": due_heading,
+        "due": due.iter().map(reminder).collect::<Vec<_>>(),
+        "birthdays": birthdays,
+    });
+
+    let mut html = Handlebars::new();
+    html.set_strict_mode(true);
+    html.register_template_string("digest", HTML_TEMPLATE)?;
+    let mut text = Handlebars::new();
+    text.set_strict_mode(true);
+    text.register_escape_fn(handlebars::no_escape);
+    text.register_template_string("digest", TEXT_TEMPLATE)?;
+
+    Ok(Some(Message {
+        to,
+        subject: format!("CardVault: {} — {}", title, data["date"].as_str().unwrap_or("")),
+        text: text.render("digest", &data)?,
+        html: Some(html.render("digest", &data)?),
+        attachments: Vec::new(),
+    }))
+}
+
+fn reminder(due: &DueReminder) -> Value {
+    json!({
+        "title": due.reminder.title,
+        "due_date": due.reminder.due_date,
+        "name": due.card.name,
+        "company": due.card.company,
+    })
+}
+
+fn now() -> NaiveDateTime {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    chrono::DateTime::from_timestamp(secs as i64, 0)
+        .unwrap_or_default()
+        .naive_utc()
+}
+
+/// The first time after `now` at `hour` o'clock, on a Monday for weekly
+/// digests.
+fn next_run(now: NaiveDateTime, frequency: DigestFrequency, hour: u32) -> NaiveDateTime {
+    let at = NaiveTime::from_hms_opt(hour, 0, 0).unwrap_or_default();
+    let mut next = now.date().and_time(at);
+    while next <= now
+        || (frequency == DigestFrequency::Weekly && next.weekday() != Weekday::Mon)
+    {
+        next = next + Days::new(1);
+    }
+    next
+}
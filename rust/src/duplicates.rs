@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use rusqlite::Connection;
+
+use crate::{
+    models::{Card, CardQuery, DuplicateGroup, DuplicateMatch},
+    phone::to_e164,
+    search::{fold, levenshtein},
+    store,
+};
+
+/// How sure each kind of evidence makes us that two cards are one person.
+const EMAIL_CONFIDENCE: f64 = 0.95;
+const PHONE_CONFIDENCE: f64 = 0.9;
+/// A matching name counts for this much on its own, or with the company
+/// weighed in when both cards have one.
+const NAME_ONLY_WEIGHT: f64 = 0.7;
+const NAME_WEIGHT: f64 = 0.6;
+const COMPANY_WEIGHT: f64 = 0.4;
+/// Names less similar than this are not compared further.
+const MIN_NAME_SIMILARITY: f64 = 0.8;
+
+/// Phone numbers are compared on their last digits, so `+65 9123 4567` and
+/// `91234567` match; shorter numbers are too ambiguous to compare.
+const PHONE_DIGITS: usize = 8;
+
+/// Groups of active cards that look like the same contact, most likely
+/// first. Cards are paired on a shared email, a shared phone number, or a
+/// similar name (and company); pairs with confidence below `min_confidence`
+/// are dropped, and cards linked by the remaining pairs form a group.
+pub fn find_duplicates(
+    conn: &Arc<Mutex<Connection>>,
+    min_confidence: f64,
+) -> Result<Vec<DuplicateGroup>> {
+    let mut cards = store::list_cards(conn, &CardQuery::default())?;
+    // Index order is id order, so each pair and group lists older cards first.
+    cards.sort_by_key(|c| c.id);
+    let mut matches = Vec::new();
+    for (key, reason, confidence) in [
+        (email_keys as fn(&Card) -> Vec<String>, "email", EMAIL_CONFIDENCE),
+        (phone_keys, "phone", PHONE_CONFIDENCE),
+    ] {
+        let mut by_key: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, card) in cards.iter().enumerate() {
+            for k in key(card) {
+                let ids = by_key.entry(k).or_default();
+                if !ids.contains(&i) {
+                    ids.push(i);
+                }
+            }
+        }
+        for (value, ids) in by_key {
+            for (n, &a) in ids.iter().enumerate() {
+                for &b in &ids[n + 1..] {
+                    matches.push((a, b, reason, value.clone(), confidence));
+                }
+            }
+        }
+    }
+    let names: Vec<String> = cards.iter().map(|c| name_key(&c.name)).collect();
+    for a in 0..cards.len() {
+        for b in a + 1..cards.len() {
+            if let Some(confidence) = name_confidence(&cards[a], &cards[b], &names[a], &names[b]) {
+                matches.push((a, b, "name", cards[a].name.clone(), confidence));
+            }
+        }
+    }
+    matches.retain(|m| m.4 >= min_confidence);
+
+    let mut parent: Vec<usize> = (0..cards.len()).collect();
+    for &(a, b, ..) in &matches {
+        let (ra, rb) = (root(&mut parent, a), root(&mut parent, b));
+        parent[ra.max(rb)] = ra.min(rb);
+    }
+    let mut groups: HashMap<usize, (Vec<usize>, Vec<DuplicateMatch>)> = HashMap::new();
+    for (a, b, reason, value, confidence) in matches {
+        let (members, found) = groups.entry(root(&mut parent, a)).or_default();
+        for i in [a, b] {
+            if !members.contains(&i) {
+                members.push(i);
+            }
+        }
+        found.push(DuplicateMatch {
+            a: cards[a].id,
+            b: cards[b].id,
+            reason: reason.to_string(),
+            value,
+            confidence: round(confidence),
+        });
+    }
+
+    let mut result: Vec<DuplicateGroup> = groups
+        .into_values()
+        .map(|(mut members, mut found)| {
+            members.sort_unstable();
+            found.sort_by(|x, y| y.confidence.total_cmp(&x.confidence).then(x.a.cmp(&y.a)));
+            DuplicateGroup {
+                confidence: found.iter().map(|m| m.confidence).fold(0.0, f64::max),
+                cards: members.into_iter().map(|i| cards[i].clone()).collect(),
+                matches: found,
+            }
+        })
+        .collect();
+    result.sort_by(|x, y| {
+        y.confidence
+            .total_cmp(&x.confidence)
+            .then(x.cards[0].id.cmp(&y.cards[0].id))
+    });
+    Ok(result)
+}
+
+fn root(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
+
+fn round(x: f64) -> f64 {
+    (x * 100.0).round() / 100.0
+}
+
+/// The address lower-cased with any `+suffix` on the local part removed,
+/// or `None` if it is not of the form `local@domain`. Also available in SQL
+/// as `email_key(text)`.
+pub fn email_key(address: &str) -> Option<String> {
+    let address = address.trim().to_lowercase();
+    let (local, domain) = address.split_once('@')?;
+    let local = local.split('+').next().unwrap_or(local);
+    (!local.is_empty() && !domain.is_empty()).then(|| format!("{local}@{domain}"))
+}
+
+/// The last `PHONE_DIGITS` digits of the number, or `None` if it has fewer.
+/// Numbers that parse are taken in E.164 form, so extensions and other
+/// trailing junk do not count. Also available in SQL as `phone_key(text)`.
+pub fn phone_key(number: &str) -> Option<String> {
+    let normalized = to_e164(number);
+    let digits: Vec<char> = normalized
+        .as_deref()
+        .unwrap_or(number)
+        .chars()
+        .filter(char::is_ascii_digit)
+        .collect();
+    (digits.len() >= PHONE_DIGITS).then(|| digits[digits.len() - PHONE_DIGITS..].iter().collect())
+}
+
+fn email_keys(card: &Card) -> Vec<String> {
+    card.emails.iter().filter_map(|e| email_key(&e.address)).collect()
+}
+
+fn phone_keys(card: &Card) -> Vec<String> {
+    card.phones.iter().filter_map(|p| phone_key(&p.number)).collect()
+}
+
+/// Folded name words in sorted order, so "Tan Wei Ming" and "Wei Ming Tan"
+/// compare equal.
+fn name_key(name: &str) -> String {
+    let mut words: Vec<String> = name
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(fold)
+        .collect();
+    words.sort();
+    words.join(" ")
+}
+
+fn similarity(a: &str, b: &str) -> f64 {
+    let len = a.chars().count().max(b.chars().count());
+    if len == 0 {
+        return 0.0;
+    }
+    1.0 - levenshtein(a, b) as f64 / len as f64
+}
+
+fn name_confidence(a: &Card, b: &Card, name_a: &str, name_b: &str) -> Option<f64> {
+    let name = similarity(name_a, name_b);
+    if name < MIN_NAME_SIMILARITY {
+        return None;
+    }
+    let (company_a, company_b) = (fold(a.company.trim()), fold(b.company.trim()));
+    Some(if company_a.is_empty() || company_b.is_empty() {
+        name * NAME_ONLY_WEIGHT
+    } else {
+        name * NAME_WEIGHT + similarity(&company_a, &company_b) * COMPANY_WEIGHT
+    })
+}
@@ -0,0 +1,174 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use reqwest::StatusCode;
+use serde_json::Value;
+
+use crate::{
+    models::{Card, CardFormSocialInput, CardPatch, EnrichField, EnrichProposal, EnrichValue},
+    scan, social,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(15);
+
+/// What a card is looked up by. `domain` comes from the website, or failing
+/// that a work email; either it or `company` is set.
+pub struct Lookup {
+    pub domain: String,
+    pub company: String,
+    pub email: String,
+}
+
+impl Lookup {
+    /// `None` if the card has neither a company nor a domain to go by.
+    pub fn for_card(card: &Card) -> Option<Self> {
+        let email = card
+            .emails
+            .iter()
+            .map(|e| e.address.trim().to_lowercase())
+            .find(|address| address.split_once('@').is_some_and(|(_, d)| !scan::is_webmail(d)))
+            .unwrap_or_default();
+        let from_website = url::Url::parse(&card.website)
+            .ok()
+            .and_then(|url| url.host_str().map(|h| h.trim_start_matches("www.").to_string()));
+        let domain = from_website
+            .or_else(|| email.split_once('@').map(|(_, d)| d.to_string()))
+            .unwrap_or_default();
+        let company = card.company.trim().to_string();
+        (!domain.is_empty() || !company.is_empty()).then_some(Self { domain, company, email })
+    }
+}
+
+/// What an enrichment provider knows about a contact's company. Empty
+/// strings for what it does not know.
+#[derive(Debug, Default)]
+pub struct Profile {
+    pub industry: String,
+    /// Headcount, as the provider words it: `51-200`, `1200`
+    pub company_size: String,
+    /// A personal LinkedIn profile (`linkedin.com/in/...`)
+    pub linkedin: String,
+}
+
+/// Looks contacts up in an outside data source.
+#[async_trait]
+pub trait Enricher: Send + Sync {
+    /// `None` if the provider has nothing on the contact.
+    async fn lookup(&self, query: &Lookup) -> Result<Option<Profile>>;
+}
+
+/// A JSON enrichment API: `GET url?domain=..&company=..&email=..` with an
+/// optional bearer token, answering `{"industry", "company_size",
+/// "linkedin"}` or 404.
+pub struct HttpEnricher {
+    client: reqwest::Client,
+    url: String,
+    api_key: Option<String>,
+}
+
+impl HttpEnricher {
+    pub fn new(url: String, api_key: Option<String>) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(TIMEOUT)
+            .user_agent(concat!("CardVault/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .expect("failed to build enrichment HTTP client");
+        Self { client, url, api_key }
+    }
+}
+
+#[async_trait]
+impl Enricher for HttpEnricher {
+    async fn lookup(&self, query: &Lookup) -> Result<Option<Profile>> {
+        let params: Vec<(&str, &str)> = [
+            ("domain", query.domain.as_str()),
+            ("company", query.company.as_str()),
+            ("email", query.email.as_str()),
+        ]
+        .into_iter()
+        .filter(|(_, v)| !v.is_empty())
+        .collect();
+        let mut request = self.client.get(&self.url).query(&params);
+        if let Some(key) = &self.api_key {
+            request = request.bearer_auth(key);
+        }
+        let response = request.send().await.context("enrichment service unreachable")?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let body: Value = response.error_for_status()?.json().await?;
+        // Sizes come as numbers from some providers.
+        let text = |key: &str| match &body[key] {
+            Value::String(s) => s.trim().to_string(),
+            Value::Number(n) => n.to_string(),
+            _ => String::new(),
+        };
+        Ok(Some(Profile {
+            industry: text("industry"),
+            company_size: text("company_size"),
+            linkedin: text("linkedin"),
+        }))
+    }
+}
+
+/// The profile's values that differ from the card's. A LinkedIn value is
+/// only kept if it is a personal profile, which is what the card's
+/// `linkedin` social holds.
+pub fn proposals(card: &Card, profile: Profile) -> Vec<EnrichProposal> {
+    let linkedin = social::canonical_handle("linkedin", &profile.linkedin);
+    let linkedin = if linkedin.contains('/') { String::new() } else { linkedin };
+    [
+        (EnrichField::Industry, profile.industry),
+        (EnrichField::CompanySize, profile.company_size),
+        (EnrichField::Linkedin, linkedin),
+    ]
+    .into_iter()
+    .filter(|(_, proposed)| !proposed.is_empty())
+    .map(|(field, proposed)| EnrichProposal { current: current(card, field), field, proposed })
+    .filter(|p| p.current != p.proposed)
+    .collect()
+}
+
+/// The card's present value of `field`.
+fn current(card: &Card, field: EnrichField) -> String {
+    match field.custom_field() {
+        Some((key, _)) => card.custom.get(key).cloned().unwrap_or_default(),
+        None => card
+            .socials
+            .iter()
+            .find(|s| s.platform == "linkedin")
+            .map(|s| s.handle.clone())
+            .unwrap_or_default(),
+    }
+}
+
+/// The patch writing the accepted values into the card: industry and size
+/// as custom fields, LinkedIn as the card's `linkedin` social.
+pub fn accept(card: &Card, values: &[EnrichValue]) -> CardPatch {
+    let mut patch = CardPatch::default();
+    for value in values {
+        match value.field.custom_field() {
+            Some((key, _)) => {
+                patch
+                    .custom
+                    .get_or_insert_with(|| card.custom.clone())
+                    .insert(key.to_string(), value.value.clone());
+            }
+            None => {
+                let mut socials: Vec<CardFormSocialInput> = card
+                    .socials
+                    .iter()
+                    .filter(|s| s.platform != "linkedin")
+                    .map(|s| CardFormSocialInput { platform: s.platform.clone(), handle: s.handle.clone() })
+                    .collect();
+                socials.push(CardFormSocialInput {
+                    platform: "linkedin".to_string(),
+                    handle: value.value.clone(),
+                });
+                patch.socials = Some(socials);
+            }
+        }
+    }
+    patch
+}
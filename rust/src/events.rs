@@ -0,0 +1,54 @@
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+pub const CARD_CREATED: &str = "card.created";
+pub const CARD_UPDATED: &str = "card.updated";
+pub const CARD_DELETED: &str = "card.deleted";
+pub const PHOTO_UPDATED: &str = "card.photo_updated";
+pub const PHOTO_DELETED: &str = "card.photo_deleted";
+pub const ATTACHMENT_ADDED: &str = "card.attachment_added";
+pub const ATTACHMENT_DELETED: &str = "card.attachment_deleted";
+pub const TAGS_CHANGED: &str = "tags.changed";
+/// Anything may have changed, as after a `--demo` reset; subscribers reload.
+pub const RESYNC: &str = "resync";
+
+/// Events a webhook may subscribe to.
+pub const CARD_EVENTS: &[&str] = &[
+    CARD_CREATED,
+    CARD_UPDATED,
+    CARD_DELETED,
+    PHOTO_UPDATED,
+    PHOTO_DELETED,
+    ATTACHMENT_ADDED,
+    ATTACHMENT_DELETED,
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChangeEvent {
+    pub event: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub card_id: Option<i64>,
+}
+
+/// In-process broadcast of mutations, consumed by the `/api/events` SSE feed.
+/// Publishing never blocks; slow subscribers simply lag and are told to resync.
+#[derive(Clone)]
+pub struct EventBus {
+    tx: broadcast::Sender<ChangeEvent>,
+}
+
+impl EventBus {
+    pub fn new(capacity: usize) -> Self {
+        let (tx, _) = broadcast::channel(capacity);
+        Self { tx }
+    }
+
+    pub fn publish(&self, event: &'static str, card_id: Option<i64>) {
+        // An error only means nobody is listening right now.
+        let _ = self.tx.send(ChangeEvent { event, card_id });
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<ChangeEvent> {
+        self.tx.subscribe()
+    }
+}
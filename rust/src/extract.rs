@@ -0,0 +1,183 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Context};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use clap::ValueEnum;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{models::CardInput, ocr::OcrEngine, scan};
+
+/// Vision models can take a while over a whole card photo.
+const TIMEOUT: Duration = Duration::from_secs(90);
+
+/// What the model is asked for. The field list mirrors `CardInput`; the
+/// rest of it is left for the user to fill in.
+const PROMPT: &str = "You read business cards. Reply with one JSON object: \
+{\"text\": <every line of text on the card, as written>, \"card\": {\"name\", \"given_name\", \
+\"family_name\", \"name_phonetic\", \"title\", \"company\", \"department\", \"website\", \
+\"phones\": [{\"label\": \"mobile\"|\"work\"|\"home\"|\"fax\", \"number\"}], \
+\"emails\": [{\"label\": \"work\"|\"personal\", \"address\"}], \
+\"addresses\": [{\"label\": \"office\"|\"home\"|\"other\", \"street\", \"city\", \"postal\", \"country\"}], \
+\"socials\": [{\"platform\", \"handle\"}]}}. \
+Use strings for every value. Leave out fields that are not on the card; never guess.";
+
+/// Which extractor turns photos and pasted text into cards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExtractorKind {
+    /// OCR, then pattern matching on the text; runs locally
+    Heuristic,
+    /// An OpenAI-compatible chat completions API (`--llm-url`), with
+    /// vision for photos
+    Llm,
+}
+
+/// What to extract a card from.
+pub enum Source<'a> {
+    Text(&'a str),
+    Image { data: &'a [u8], mime: &'a str },
+}
+
+/// The text read from the source and the card found in it.
+pub struct Extraction {
+    pub text: String,
+    pub card: CardInput,
+}
+
+#[derive(Debug)]
+pub enum ExtractError {
+    /// This extractor cannot read this kind of source (photos without an
+    /// OCR engine)
+    Unsupported(&'static str),
+    Failed(anyhow::Error),
+}
+
+impl From<anyhow::Error> for ExtractError {
+    fn from(e: anyhow::Error) -> Self {
+        Self::Failed(e)
+    }
+}
+
+/// Turns a card photo or pasted text into a card body for the user to
+/// confirm.
+#[async_trait]
+pub trait Extractor: Send + Sync {
+    async fn extract(&self, source: Source<'_>) -> Result<Extraction, ExtractError>;
+}
+
+/// OCR (if photos are to be read) followed by [`scan::candidates`]; pasted
+/// text goes through [`scan::signature`].
+pub struct Heuristic {
+    pub ocr: Option<Box<dyn OcrEngine>>,
+}
+
+#[async_trait]
+impl Extractor for Heuristic {
+    async fn extract(&self, source: Source<'_>) -> Result<Extraction, ExtractError> {
+        match source {
+            Source::Text(text) => Ok(Extraction {
+                text: text.to_string(),
+                card: scan::signature(text),
+            }),
+            Source::Image { data, mime } => {
+                let ocr = self
+                    .ocr
+                    .as_ref()
+                    .ok_or(ExtractError::Unsupported("card scanning is not enabled on this server"))?;
+                let text = ocr.recognize(data, mime).await?;
+                let card = scan::candidates(&text);
+                Ok(Extraction { text, card })
+            }
+        }
+    }
+}
+
+/// A language model behind an OpenAI-compatible `chat/completions`
+/// endpoint. Photos go in as data URLs, so the model must accept images.
+pub struct Llm {
+    client: reqwest::Client,
+    url: String,
+    api_key: Option<String>,
+    model: String,
+}
+
+impl Llm {
+    pub fn new(url: String, api_key: Option<String>, model: String) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(TIMEOUT)
+            .user_agent(concat!("CardVault/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .expect("failed to build LLM HTTP client");
+        Self { client, url, api_key, model }
+    }
+
+    async fn ask(&self, source: Source<'_>) -> anyhow::Result<Extraction> {
+        let content = match &source {
+            Source::Text(text) => json!(format!("The card's text:\n\n{text}")),
+            Source::Image { data, mime } => json!([
+                {"type": "text", "text": "The photo of the card:"},
+                {"type": "image_url", "image_url": {"url": format!("data:{mime};base64,{}", BASE64.encode(data))}},
+            ]),
+        };
+        let body = json!({
+            "model": self.model,
+            "response_format": {"type": "json_object"},
+            "messages": [
+                {"role": "system", "content": PROMPT},
+                {"role": "user", "content": content},
+            ],
+        });
+        let mut request = self.client.post(&self.url).json(&body);
+        if let Some(key) = &self.api_key {
+            request = request.bearer_auth(key);
+        }
+        let response: Value = request
+            .send()
+            .await
+            .context("LLM service unreachable")?
+            .error_for_status()?
+            .json()
+            .await?;
+        let answer = response["choices"][0]["message"]["content"]
+            .as_str()
+            .ok_or_else(|| anyhow!("LLM reply has no message content"))?;
+        let answer = answer.trim().trim_start_matches("```json").trim_matches('`');
+        let mut reply: Value = serde_json::from_str(answer).context("LLM reply is not JSON")?;
+        drop_nulls(&mut reply);
+        let reply: Reply = serde_json::from_value(reply).context("LLM reply is not the card JSON")?;
+        let text = match source {
+            Source::Text(text) => text.to_string(),
+            Source::Image { .. } => reply.text,
+        };
+        Ok(Extraction { text, card: reply.card })
+    }
+}
+
+#[derive(Deserialize)]
+struct Reply {
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    card: CardInput,
+}
+
+#[async_trait]
+impl Extractor for Llm {
+    async fn extract(&self, source: Source<'_>) -> Result<Extraction, ExtractError> {
+        Ok(self.ask(source).await?)
+    }
+}
+
+/// Removes `null` members, which models use for "not on the card" however
+/// they are asked.
+fn drop_nulls(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            map.retain(|_, v| !v.is_null());
+            map.values_mut().for_each(drop_nulls);
+        }
+        Value::Array(items) => items.iter_mut().for_each(drop_nulls),
+        _ => {}
+    }
+}
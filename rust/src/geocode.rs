@@ -0,0 +1,212 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use clap::ValueEnum;
+use rusqlite::Connection;
+use serde_json::Value;
+use tokio::sync::Notify;
+use tracing::{info, warn};
+
+use crate::store;
+
+const TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Nominatim's usage policy allows one request a second; other services
+/// are held to the same pace.
+const PACE: Duration = Duration::from_secs(1);
+
+/// Which service resolves addresses to coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum GeocoderKind {
+    /// OpenStreetMap's Nominatim, or a self-hosted instance
+    Nominatim,
+    /// The Google Geocoding API; needs `--geocoder-api-key`
+    Google,
+    /// No geocoding
+    None,
+}
+
+impl GeocoderKind {
+    pub fn default_url(self) -> &'static str {
+        match self {
+            Self::Nominatim => "https://nominatim.openstreetmap.org/search",
+            Self::Google => "https://maps.googleapis.com/maps/api/geocode/json",
+            Self::None => "",
+        }
+    }
+}
+
+/// Resolves a one-line address to `(latitude, longitude)`.
+#[async_trait]
+pub trait Geocoder: Send + Sync {
+    /// `None` if the service does not know the address.
+    async fn geocode(&self, address: &str) -> Result<Option<(f64, f64)>>;
+}
+
+fn client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(TIMEOUT)
+        .user_agent(concat!("CardVault/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .expect("failed to build geocoder HTTP client")
+}
+
+/// Nominatim's `/search` endpoint.
+pub struct Nominatim {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl Nominatim {
+    pub fn new(url: String) -> Self {
+        Self { client: client(), url }
+    }
+}
+
+#[async_trait]
+impl Geocoder for Nominatim {
+    async fn geocode(&self, address: &str) -> Result<Option<(f64, f64)>> {
+        let results: Value = self
+            .client
+            .get(&self.url)
+            .query(&[("q", address), ("format", "jsonv2"), ("limit", "1")])
+            .send()
+            .await
+            .context("geocoder unreachable")?
+            .error_for_status()?
+            .json()
+            .await?;
+        let Some(first) = results.get(0) else {
+            return Ok(None);
+        };
+        // Nominatim sends coordinates as strings.
+        let coordinate = |key: &str| first[key].as_str().and_then(|s| s.parse::<f64>().ok());
+        match (coordinate("lat"), coordinate("lon")) {
+            (Some(lat), Some(lng)) => Ok(Some((lat, lng))),
+            _ => bail!("geocoder reply has no coordinates"),
+        }
+    }
+}
+
+/// The Google Geocoding API.
+pub struct Google {
+    client: reqwest::Client,
+    url: String,
+    api_key: String,
+}
+
+impl Google {
+    pub fn new(url: String, api_key: String) -> Self {
+        Self { client: client(), url, api_key }
+    }
+}
+
+#[async_trait]
+impl Geocoder for Google {
+    async fn geocode(&self, address: &str) -> Result<Option<(f64, f64)>> {
+        let reply: Value = self
+            .client
+            .get(&self.url)
+            .query(&[("address", address), ("key", self.api_key.as_str())])
+            .send()
+            .await
+            .context("geocoder unreachable")?
+            .error_for_status()?
+            .json()
+            .await?;
+        match reply["status"].as_str().unwrap_or_default() {
+            "OK" => {}
+            "ZERO_RESULTS" => return Ok(None),
+            status => bail!(
+                "geocoder answered {status}: {}",
+                reply["error_message"].as_str().unwrap_or_default()
+            ),
+        }
+        let location = &reply["results"][0]["geometry"]["location"];
+        match (location["lat"].as_f64(), location["lng"].as_f64()) {
+            (Some(lat), Some(lng)) => Ok(Some((lat, lng))),
+            _ => bail!("geocoder reply has no coordinates"),
+        }
+    }
+}
+
+/// Geocodes addresses in the background. Every lookup is cached by the
+/// address text, misses included, so saving a card again (which rewrites
+/// its address rows) costs no requests.
+#[derive(Clone)]
+pub struct GeocodeQueue {
+    wake: Arc<Notify>,
+}
+
+impl GeocodeQueue {
+    /// Starts the worker; it runs a pass whenever woken.
+    pub fn spawn(conn: Arc<Mutex<Connection>>, geocoder: Box<dyn Geocoder>) -> Self {
+        let wake = Arc::new(Notify::new());
+        let queue = Self { wake: wake.clone() };
+        tokio::spawn(async move {
+            loop {
+                wake.notified().await;
+                if let Err(e) = run(&conn, geocoder.as_ref()).await {
+                    warn!("geocoding stopped: {e:#}");
+                }
+            }
+        });
+        queue
+    }
+
+    /// Asks for a pass over the addresses without coordinates.
+    pub fn wake(&self) {
+        self.wake.notify_one();
+    }
+}
+
+async fn run(conn: &Arc<Mutex<Connection>>, geocoder: &dyn Geocoder) -> Result<()> {
+    let pending = store::addresses_to_geocode(conn)?;
+    let mut looked_up = 0;
+    for (id, address) in pending {
+        let coordinates = match store::cached_geocode(conn, &address)? {
+            Some(cached) => cached,
+            None => {
+                if looked_up > 0 {
+                    tokio::time::sleep(PACE).await;
+                }
+                looked_up += 1;
+                let found = geocoder.geocode(&address).await?;
+                store::cache_geocode(conn, &address, found)?;
+                found
+            }
+        };
+        if let Some(coordinates) = coordinates {
+            store::set_address_coordinates(conn, id, coordinates)?;
+        }
+    }
+    if looked_up > 0 {
+        info!("geocoded {looked_up} addresses");
+    }
+    Ok(())
+}
+
+/// Mean radius of the Earth.
+const EARTH_RADIUS_KM: f64 = 6371.0088;
+
+/// Great-circle distance between two points, by the haversine formula.
+pub fn distance_km(lat1: f64, lng1: f64, lat2: f64, lng2: f64) -> f64 {
+    let (phi1, phi2) = (lat1.to_radians(), lat2.to_radians());
+    let d_phi = (lat2 - lat1).to_radians();
+    let d_lambda = (lng2 - lng1).to_radians();
+    let a = (d_phi / 2.0).sin().powi(2) + phi1.cos() * phi2.cos() * (d_lambda / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().min(1.0).asin()
+}
+
+/// The address on one line, as sent to the geocoder and used as the cache
+/// key; empty if it has no parts.
+pub fn query(street: &str, city: &str, postal: &str, country: &str) -> String {
+    [street, city, postal, country]
+        .iter()
+        .map(|part| part.trim())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,220 @@
+use std::sync::Arc;
+
+use async_graphql::{Context, EmptySubscription, InputObject, Object, Result, Schema};
+
+use crate::{
+    events,
+    handlers::AppState,
+    models::{
+        parse_timestamp, Card, CardDetail, CardInput, CardQuery, CardSort, TagCount,
+    },
+    search, store, telemetry, validation,
+};
+
+pub type CardVaultSchema = Schema<QueryRoot, MutationRoot, EmptySubscription>;
+
+pub fn build_schema(state: Arc<AppState>) -> CardVaultSchema {
+    Schema::build(QueryRoot, MutationRoot, EmptySubscription)
+        .data(state)
+        .finish()
+}
+
+/// Runs a blocking store call off the async runtime, flattening join errors.
+async fn blocking<T, F>(f: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> anyhow::Result<T> + Send + 'static,
+{
+    telemetry::spawn_db(f)
+        .await
+        .map_err(|e| async_graphql::Error::new(e.to_string()))?
+        .map_err(|e| async_graphql::Error::new(e.to_string()))
+}
+
+/// Extra card filters, shared by `cards` and `cardCount`.
+#[derive(InputObject, Default)]
+pub struct CardFilter {
+    /// Match `q` despite typos; results are then ordered by relevance and
+    /// `sort` is ignored.
+    #[graphql(default)]
+    fuzzy: bool,
+    /// Match `q` against how names sound rather than how they are spelt.
+    #[graphql(default)]
+    phonetic: bool,
+    /// Only cards carrying all of these tags (in addition to `tag`).
+    #[graphql(default)]
+    tags: Vec<String>,
+    /// Only cards carrying none of these tags.
+    #[graphql(default)]
+    not_tags: Vec<String>,
+    /// Only cards with no tags at all.
+    #[graphql(default)]
+    untagged: bool,
+    /// Only cards with an address in this country (case-insensitive).
+    country: Option<String>,
+    /// Only cards with an address in this city (case-insensitive).
+    city: Option<String>,
+    /// `false` lists cards without a photo, `true` those with one.
+    has_photo: Option<bool>,
+    /// `true` lists only favorites, `false` only the rest.
+    favorite: Option<bool>,
+    /// List archived cards instead of active ones.
+    #[graphql(default)]
+    archived: bool,
+    /// Only cards lacking every one of these details.
+    #[graphql(default)]
+    missing: Vec<CardDetail>,
+    /// Dates (`YYYY-MM-DD`) or RFC 3339 timestamps; `*After` is inclusive,
+    /// `*Before` exclusive.
+    created_after: Option<String>,
+    created_before: Option<String>,
+    updated_after: Option<String>,
+    updated_before: Option<String>,
+}
+
+impl CardFilter {
+    fn into_query(self, q: Option<String>, tag: Option<String>) -> Result<CardQuery> {
+        let timestamp = |value: Option<String>| -> Result<Option<String>> {
+            match value {
+                Some(v) => parse_timestamp(&v).map(Some).ok_or_else(|| {
+                    format!("'{v}' is not a date (YYYY-MM-DD) or RFC 3339 timestamp").into()
+                }),
+                None => Ok(None),
+            }
+        };
+        Ok(CardQuery {
+            q,
+            fuzzy: self.fuzzy,
+            phonetic: self.phonetic,
+            tags: tag.into_iter().chain(self.tags).collect(),
+            not_tags: self.not_tags,
+            untagged: self.untagged,
+            country: self.country,
+            city: self.city,
+            has_photo: self.has_photo,
+            favorite: self.favorite,
+            archived: self.archived,
+            missing: self.missing,
+            created_after: timestamp(self.created_after)?,
+            created_before: timestamp(self.created_before)?,
+            updated_after: timestamp(self.updated_after)?,
+            updated_before: timestamp(self.updated_before)?,
+            ..Default::default()
+        })
+    }
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// Cards, optionally searched, filtered by tag, sorted, and paged. `sort`
+    /// takes `name`, `family_name`, `company`, `created_at`, or `updated_at`,
+    /// prefixed with `-` for descending; the default is best match first when
+    /// `q` is given and most recently updated first otherwise.
+    #[allow(clippy::too_many_arguments)]
+    async fn cards(
+        &self,
+        ctx: &Context<'_>,
+        q: Option<String>,
+        tag: Option<String>,
+        #[graphql(default)] filter: CardFilter,
+        sort: Option<String>,
+        limit: Option<i64>,
+        #[graphql(default)] offset: i64,
+    ) -> Result<Vec<Card>> {
+        let conn = ctx.data::<Arc<AppState>>()?.conn.clone();
+        let rank = sort.is_none() && q.as_deref().is_some_and(|q| !q.is_empty());
+        let sort: CardSort = match sort {
+            Some(s) => s.parse()?,
+            None => CardSort::default(),
+        };
+        let mut query = CardQuery {
+            sort,
+            limit,
+            offset: offset.max(0),
+            ..filter.into_query(q, tag)?
+        };
+        query.rank = rank && !query.fuzzy && !query.phonetic;
+        blocking(move || Ok(search::find_cards(&conn, &query)?.1)).await
+    }
+
+    /// Number of cards matching the same filters as `cards`.
+    async fn card_count(
+        &self,
+        ctx: &Context<'_>,
+        q: Option<String>,
+        tag: Option<String>,
+        #[graphql(default)] filter: CardFilter,
+    ) -> Result<i64> {
+        let conn = ctx.data::<Arc<AppState>>()?.conn.clone();
+        let query = filter.into_query(q, tag)?;
+        let query = CardQuery {
+            limit: Some(0),
+            ..query
+        };
+        blocking(move || Ok(search::find_cards(&conn, &query)?.0)).await
+    }
+
+    async fn card(&self, ctx: &Context<'_>, id: i64) -> Result<Option<Card>> {
+        let conn = ctx.data::<Arc<AppState>>()?.conn.clone();
+        blocking(move || store::get_card(&conn, id)).await
+    }
+
+    async fn tags(&self, ctx: &Context<'_>) -> Result<Vec<TagCount>> {
+        let conn = ctx.data::<Arc<AppState>>()?.conn.clone();
+        blocking(move || store::list_tags(&conn)).await
+    }
+}
+
+pub struct MutationRoot;
+
+#[Object]
+impl MutationRoot {
+    async fn create_card(&self, ctx: &Context<'_>, mut input: CardInput) -> Result<Card> {
+        let state = ctx.data::<Arc<AppState>>()?;
+        let conn = state.conn.clone();
+        let schema = blocking(move || store::field_definitions(&conn)).await?;
+        validation::card_input(&mut input, &schema).map_err(|e| validation::message(&e))?;
+        let conn = state.conn.clone();
+        let card = blocking(move || {
+            let id = store::create_card(&conn, &input)?;
+            store::get_card(&conn, id)
+        })
+        .await?
+        .ok_or("card created but not found")?;
+        state.notify(events::CARD_CREATED, card.id, Some(card.clone()));
+        Ok(card)
+    }
+
+    async fn update_card(&self, ctx: &Context<'_>, id: i64, mut input: CardInput) -> Result<Card> {
+        let state = ctx.data::<Arc<AppState>>()?;
+        let conn = state.conn.clone();
+        let schema = blocking(move || store::field_definitions(&conn)).await?;
+        validation::card_input(&mut input, &schema).map_err(|e| validation::message(&e))?;
+        let conn = state.conn.clone();
+        let card = blocking(move || {
+            if store::get_card(&conn, id)?.is_none() {
+                return Ok(None);
+            }
+            store::update_card(&conn, id, &input)?;
+            store::get_card(&conn, id)
+        })
+        .await?
+        .ok_or("card not found")?;
+        state.notify(events::CARD_UPDATED, card.id, Some(card.clone()));
+        Ok(card)
+    }
+
+    /// Deletes a card and its photo. Returns false if the card did not exist.
+    async fn delete_card(&self, ctx: &Context<'_>, id: i64) -> Result<bool> {
+        let state = ctx.data::<Arc<AppState>>()?;
+        let conn = state.conn.clone();
+        let Some(old_photos) = blocking(move || store::delete_card(&conn, id)).await? else {
+            return Ok(false);
+        };
+        state.remove_files(old_photos).await;
+        state.notify(events::CARD_DELETED, id, None);
+        Ok(true)
+    }
+}
@@ -0,0 +1,411 @@
+// tonic::Status is large by design and every RPC returns it.
+#![allow(clippy::result_large_err)]
+
+use std::pin::Pin;
+use std::sync::Arc;
+
+use futures::Stream;
+use tonic::{Request, Response, Status};
+
+use crate::{events, handlers::AppState, models, search, store, telemetry, validation};
+
+pub mod pb {
+    tonic::include_proto!("cardvault.v1");
+}
+
+use pb::card_service_server::{CardService, CardServiceServer};
+
+pub fn service(state: Arc<AppState>) -> CardServiceServer<CardServiceImpl> {
+    CardServiceServer::new(CardServiceImpl { state })
+}
+
+pub struct CardServiceImpl {
+    state: Arc<AppState>,
+}
+
+/// Runs a blocking store call off the async runtime, mapping version
+/// conflicts to `ABORTED` and other failures to `INTERNAL`.
+async fn blocking<T, F>(f: F) -> Result<T, Status>
+where
+    T: Send + 'static,
+    F: FnOnce() -> anyhow::Result<T> + Send + 'static,
+{
+    telemetry::spawn_db(f)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?
+        .map_err(|e| {
+            if e.downcast_ref::<store::VersionConflict>().is_some() {
+                Status::aborted(e.to_string())
+            } else {
+                Status::internal(e.to_string())
+            }
+        })
+}
+
+/// Refuses a change on a `--read-only` server.
+fn writable(state: &AppState) -> Result<(), Status> {
+    if state.read_only {
+        return Err(Status::permission_denied("this vault is read-only"));
+    }
+    Ok(())
+}
+
+/// Converts and validates a card, including its custom fields.
+async fn card_input(
+    state: &AppState,
+    input: Option<pb::CardInput>,
+) -> Result<models::CardInput, Status> {
+    let input = input.ok_or_else(|| Status::invalid_argument("card is required"))?;
+    let mut input = models::CardInput::from(input);
+    let conn = state.conn.clone();
+    let schema = blocking(move || store::field_definitions(&conn)).await?;
+    validation::card_input(&mut input, &schema)
+        .map_err(|e| Status::invalid_argument(validation::message(&e)))?;
+    Ok(input)
+}
+
+fn card_query(req: pb::ListCardsRequest) -> Result<models::CardQuery, Status> {
+    let rank = req.sort.is_none() && req.q.as_deref().is_some_and(|q| !q.is_empty());
+    let sort: models::CardSort = match req.sort.as_deref() {
+        Some(s) => s.parse().map_err(Status::invalid_argument)?,
+        None => models::CardSort::default(),
+    };
+    if req.fuzzy && req.cursor.is_some() {
+        return Err(Status::invalid_argument("cursor cannot be combined with fuzzy search"));
+    }
+    let after = match req.cursor.as_deref() {
+        Some(token) => match models::CardCursor::decode(token) {
+            Some(c) if c.sort == sort => Some(c),
+            Some(_) => return Err(Status::invalid_argument("cursor was issued for a different sort")),
+            None => return Err(Status::invalid_argument("invalid cursor")),
+        },
+        None => None,
+    };
+    let rank = rank && !req.fuzzy && !req.phonetic;
+    if rank && after.is_some() {
+        return Err(Status::invalid_argument(
+            "cursor needs an explicit sort when searching with q",
+        ));
+    }
+    let missing = req
+        .missing
+        .iter()
+        .map(|m| m.parse())
+        .collect::<Result<Vec<models::CardDetail>, _>>()
+        .map_err(Status::invalid_argument)?;
+    let timestamp = |value: Option<String>| match value {
+        Some(v) => models::parse_timestamp(&v).map(Some).ok_or_else(|| {
+            Status::invalid_argument(format!("'{v}' is not a date or RFC 3339 timestamp"))
+        }),
+        None => Ok(None),
+    };
+    Ok(models::CardQuery {
+        created_after: timestamp(req.created_after)?,
+        created_before: timestamp(req.created_before)?,
+        updated_after: timestamp(req.updated_after)?,
+        updated_before: timestamp(req.updated_before)?,
+        q: req.q,
+        fuzzy: req.fuzzy,
+        phonetic: req.phonetic,
+        rank,
+        tags: req.tag.into_iter().collect(),
+        not_tags: req.not_tags,
+        untagged: req.untagged,
+        country: req.country,
+        city: req.city,
+        has_photo: req.has_photo,
+        favorite: req.favorite,
+        archived: req.archived,
+        missing,
+        sort,
+        limit: req.limit.filter(|l| *l > 0),
+        offset: req.offset.max(0),
+        after,
+        ..Default::default()
+    })
+}
+
+#[tonic::async_trait]
+impl CardService for CardServiceImpl {
+    async fn get_card(
+        &self,
+        request: Request<pb::GetCardRequest>,
+    ) -> Result<Response<pb::Card>, Status> {
+        let id = request.into_inner().id;
+        let conn = self.state.conn.clone();
+        let card = blocking(move || store::get_card(&conn, id))
+            .await?
+            .ok_or_else(|| Status::not_found("card not found"))?;
+        Ok(Response::new(card.into()))
+    }
+
+    async fn list_cards(
+        &self,
+        request: Request<pb::ListCardsRequest>,
+    ) -> Result<Response<pb::ListCardsResponse>, Status> {
+        let req = request.into_inner();
+        let conn = self.state.conn.clone();
+        let query = card_query(req)?;
+        let (limit, sort) = (query.limit.filter(|_| !query.fuzzy && !query.rank), query.sort);
+        let (total, cards) = blocking(move || {
+            search::find_cards(&conn, &query)
+        })
+        .await?;
+        let next_cursor = match (cards.last(), limit) {
+            (Some(last), Some(limit)) if cards.len() as i64 == limit => {
+                Some(models::CardCursor::for_card(last, sort).encode())
+            }
+            _ => None,
+        };
+        Ok(Response::new(pb::ListCardsResponse {
+            cards: cards.into_iter().map(Into::into).collect(),
+            total,
+            next_cursor,
+        }))
+    }
+
+    type StreamCardsStream = Pin<Box<dyn Stream<Item = Result<pb::Card, Status>> + Send>>;
+
+    async fn stream_cards(
+        &self,
+        request: Request<pb::ListCardsRequest>,
+    ) -> Result<Response<Self::StreamCardsStream>, Status> {
+        let req = request.into_inner();
+        let conn = self.state.conn.clone();
+        let query = card_query(req)?;
+        let (_, cards) = blocking(move || search::find_cards(&conn, &query)).await?;
+        let stream = futures::stream::iter(cards.into_iter().map(|c| Ok(c.into())));
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn create_card(
+        &self,
+        request: Request<pb::CreateCardRequest>,
+    ) -> Result<Response<pb::Card>, Status> {
+        writable(&self.state)?;
+        let input = card_input(&self.state, request.into_inner().card).await?;
+        let conn = self.state.conn.clone();
+        let card = blocking(move || {
+            let id = store::create_card(&conn, &input)?;
+            store::get_card(&conn, id)
+        })
+        .await?
+        .ok_or_else(|| Status::internal("card created but not found"))?;
+        self.state
+            .notify(events::CARD_CREATED, card.id, Some(card.clone()));
+        Ok(Response::new(card.into()))
+    }
+
+    async fn update_card(
+        &self,
+        request: Request<pb::UpdateCardRequest>,
+    ) -> Result<Response<pb::Card>, Status> {
+        writable(&self.state)?;
+        let req = request.into_inner();
+        let id = req.id;
+        let input = card_input(&self.state, req.card).await?;
+        let conn = self.state.conn.clone();
+        let card = blocking(move || {
+            if store::get_card(&conn, id)?.is_none() {
+                return Ok(None);
+            }
+            store::update_card(&conn, id, &input)?;
+            store::get_card(&conn, id)
+        })
+        .await?
+        .ok_or_else(|| Status::not_found("card not found"))?;
+        self.state
+            .notify(events::CARD_UPDATED, card.id, Some(card.clone()));
+        Ok(Response::new(card.into()))
+    }
+
+    async fn delete_card(
+        &self,
+        request: Request<pb::DeleteCardRequest>,
+    ) -> Result<Response<pb::DeleteCardResponse>, Status> {
+        writable(&self.state)?;
+        let id = request.into_inner().id;
+        let conn = self.state.conn.clone();
+        let old_photos = blocking(move || store::delete_card(&conn, id))
+            .await?
+            .ok_or_else(|| Status::not_found("card not found"))?;
+        self.state.remove_files(old_photos).await;
+        self.state.notify(events::CARD_DELETED, id, None);
+        Ok(Response::new(pb::DeleteCardResponse {}))
+    }
+}
+
+// ────────────────────────────────────────────────────────────────────────────
+// Conversions between protobuf messages and models
+// ────────────────────────────────────────────────────────────────────────────
+
+impl From<models::Card> for pb::Card {
+    fn from(c: models::Card) -> Self {
+        pb::Card {
+            id: c.id,
+            name: c.name,
+            name_prefix: c.name_prefix,
+            given_name: c.given_name,
+            middle_name: c.middle_name,
+            family_name: c.family_name,
+            name_suffix: c.name_suffix,
+            name_phonetic: c.name_phonetic,
+            birthday: c.birthday,
+            anniversary: c.anniversary,
+            title: c.title,
+            company: c.company,
+            department: c.department,
+            role_seniority: c.role_seniority,
+            assistant_name: c.assistant_name,
+            assistant_phone: c.assistant_phone,
+            reports_to: c.reports_to,
+            last_contacted: c.last_contacted,
+            timezone: c.timezone,
+            local_time: c.local_time,
+            website: c.website,
+            notes: c.notes,
+            photo_url: c.photo_url,
+            photos: c
+                .photos
+                .into_iter()
+                .map(|p| pb::CardPhoto {
+                    id: p.id,
+                    role: p.role.as_str().to_string(),
+                    position: p.position,
+                    url: p.url,
+                })
+                .collect(),
+            phones: c
+                .phones
+                .into_iter()
+                .map(|p| pb::Phone {
+                    id: p.id,
+                    label: p.label,
+                    number: p.number,
+                    e164: p.e164,
+                })
+                .collect(),
+            emails: c
+                .emails
+                .into_iter()
+                .map(|e| pb::Email {
+                    id: e.id,
+                    label: e.label,
+                    address: e.address,
+                })
+                .collect(),
+            addresses: c
+                .addresses
+                .into_iter()
+                .map(|a| pb::Address {
+                    id: a.id,
+                    label: a.label,
+                    street: a.street,
+                    city: a.city,
+                    country: a.country,
+                    postal: a.postal,
+                })
+                .collect(),
+            socials: c
+                .socials
+                .into_iter()
+                .map(|s| pb::Social {
+                    id: s.id,
+                    platform: s.platform,
+                    handle: s.handle,
+                    url: s.url,
+                })
+                .collect(),
+            ims: c
+                .ims
+                .into_iter()
+                .map(|i| pb::Im {
+                    id: i.id,
+                    label: i.label,
+                    protocol: i.protocol,
+                    handle: i.handle,
+                })
+                .collect(),
+            tags: c.tags,
+            custom: c.custom.into_iter().collect(),
+            created_at: c.created_at,
+            updated_at: c.updated_at,
+            version: c.version,
+            favorite: c.favorite,
+            archived_at: c.archived_at,
+        }
+    }
+}
+
+impl From<pb::CardInput> for models::CardInput {
+    fn from(c: pb::CardInput) -> Self {
+        models::CardInput {
+            name: c.name,
+            name_prefix: c.name_prefix,
+            given_name: c.given_name,
+            middle_name: c.middle_name,
+            family_name: c.family_name,
+            name_suffix: c.name_suffix,
+            name_phonetic: c.name_phonetic,
+            birthday: c.birthday,
+            anniversary: c.anniversary,
+            title: c.title,
+            company: c.company,
+            department: c.department,
+            role_seniority: c.role_seniority,
+            assistant_name: c.assistant_name,
+            assistant_phone: c.assistant_phone,
+            reports_to: c.reports_to,
+            timezone: c.timezone,
+            website: c.website,
+            notes: c.notes,
+            phones: c
+                .phones
+                .into_iter()
+                .map(|p| models::CardFormPhoneInput {
+                    label: p.label,
+                    number: p.number,
+                })
+                .collect(),
+            emails: c
+                .emails
+                .into_iter()
+                .map(|e| models::CardFormEmailInput {
+                    label: e.label,
+                    address: e.address,
+                })
+                .collect(),
+            addresses: c
+                .addresses
+                .into_iter()
+                .map(|a| models::CardFormAddressInput {
+                    label: a.label,
+                    street: a.street,
+                    city: a.city,
+                    country: a.country,
+                    postal: a.postal,
+                })
+                .collect(),
+            socials: c
+                .socials
+                .into_iter()
+                .map(|s| models::CardFormSocialInput {
+                    platform: s.platform,
+                    handle: s.handle,
+                })
+                .collect(),
+            ims: c
+                .ims
+                .into_iter()
+                .map(|i| models::CardFormImInput {
+                    label: i.label,
+                    protocol: i.protocol,
+                    handle: i.handle,
+                })
+                .collect(),
+            tags: c.tags,
+            custom: c.custom.into_iter().collect(),
+            version: c.version,
+        }
+    }
+}
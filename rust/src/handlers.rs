@@ -1,26 +1,140 @@
+use std::collections::BTreeMap;
 use std::sync::{Arc, Mutex};
 
 use axum::{
     body::Body,
-    extract::{Multipart, Path, Query, State},
-    http::{header, HeaderMap, Method, StatusCode, Uri},
-    response::{IntoResponse, Response},
+    extract::{Extension, FromRequest, Multipart, Path, Request, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode, Uri},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
     Json,
 };
+// Unlike axum's, this Query accepts repeated keys (`?tag=a&tag=b`).
+use axum_extra::extract::Query;
 use rusqlite::Connection;
 use serde::Deserialize;
 use serde_json::{json, Value};
-use tokio::fs;
-use tracing::error;
+use sha2::{Digest, Sha256};
+use tracing::{error, warn};
+use utoipa::IntoParams;
 
 use crate::{
-    models::{CardFormAddressInput, CardFormEmailInput, CardFormPhoneInput, CardInput, HealthResponse},
-    store,
+    base_path, body_limit,
+    models::{
+        parse_timestamp, Attachment, UploadSession, UploadStart, BulkTagInput, BulkTagResult, CardCursor, CompanyLogo, EnrichAccept, Enrichment, FieldType, GeocodeBackfill, NearbyCard, CardMerge, CardPhoto, CardRevision, DuplicateGroup, CardDetail, CardFormAddressInput, CardFormEmailInput,
+        CardFormPhoneInput, CardFormSocialInput, CardFormImInput, CardInput, FieldDefinition, FieldDefinitionInput, FieldSchema, Interaction, InteractionInput, OnDuplicate, OrgChart, QrFormat, QrImportInput, ScanResult, SignatureInput, TextImportInput, DueReminder, Reminder, ReminderDue, ReminderInput, SendCardInput, ShareInput, ShareLink, CardPatch, UpcomingDate, CardQuery, CardRelations, CardSort,
+        HealthResponse, PhotoRole, PhotoUpdate, SuggestField, Suggestion, TagAliasInput, TagCount, TagMerge, TagUpdate, ThumbSize, UndoResult, VaultStats, Webhook,
+        WebhookInput,
+    },
+    openapi::{AttachmentForm, CardForm, CardPhotoForm, ErrorBody, PhotoForm, PhotoUrl, ValidationErrorBody},
+    events::{self, EventBus},
+    graphql::CardVaultSchema,
+    avatar::AvatarLookup,
+    logo::LogoFetcher,
+    enrich::{self, Enricher},
+    geocode::GeocodeQueue,
+    attachment, dates, duplicates, range, resumable, history, ical, mail, mecard, photo, qr, scan, search, share, vcard,
+    models::Card,
+    store, telemetry,
+    extract::{ExtractError, Extractor, Source},
+    ratelimit::RateLimiter,
+    read_only,
+    uploads::PhotoStorage,
+    validation::{self, FieldError, FieldErrors},
+    webhooks::WebhookDispatcher,
 };
 
 pub struct AppState {
     pub conn: Arc<Mutex<Connection>>,
+    /// Local directory for the chunks of resumable uploads, and for stored
+    /// files too with `--storage local`
     pub uploads_dir: String,
+    /// Where photos, attachments, and logos are stored
+    pub uploads: Box<dyn PhotoStorage>,
+    pub webhooks: WebhookDispatcher,
+    pub events: EventBus,
+    /// Outgoing mail server, if configured
+    pub smtp: Option<mail::SmtpConfig>,
+    /// Caps how many cards `POST /cards/:id/send` emails per hour
+    pub send_limit: RateLimiter,
+    /// Reads card photos (`POST /scan`) and pasted text (`POST /import/text`)
+    pub extractor: Box<dyn Extractor>,
+    /// Gravatar-style photo lookup by email, if enabled
+    pub avatars: Option<AvatarLookup>,
+    /// Downloads company logos from websites
+    pub logos: LogoFetcher,
+    /// Contact enrichment provider, if configured
+    pub enricher: Option<Box<dyn Enricher>>,
+    /// Background address geocoding, if configured
+    pub geocoder: Option<GeocodeQueue>,
+    /// Size uploaded photos are scaled down to
+    pub photo_limits: photo::Limits,
+    /// Command that converts HEIC photos to JPEG
+    pub heif_convert: String,
+    /// Largest file, in bytes, that can be attached to a card
+    pub attachment_max_size: usize,
+    /// Largest photo, in bytes, that can be uploaded
+    pub photo_max_size: usize,
+    /// Largest body, in bytes, of requests that carry no file
+    pub max_body_size: usize,
+    /// `--read-only`: changes are refused and the database opened read-only
+    pub read_only: bool,
+}
+
+impl AppState {
+    /// The largest body any API route takes with `content_type`: a form
+    /// with the largest photo or attachment, a resumable upload chunk, or
+    /// otherwise `--max-body-mb`. The route's own limit still applies when
+    /// its handler reads the body.
+    pub fn body_limit(&self, content_type: &str) -> usize {
+        if content_type.starts_with("multipart/") {
+            self.photo_max_size.max(self.attachment_max_size) + body_limit::MULTIPART_FRAMING
+        } else if content_type.starts_with(resumable::CHUNK_CONTENT_TYPE) {
+            resumable::MAX_CHUNK
+        } else {
+            self.max_body_size
+        }
+    }
+
+    /// Removes the stored files of `paths` that nothing uses any more, as
+    /// returned when rows let go of them. The database is held from the
+    /// check until the files are gone, so an upload of the same bytes
+    /// meanwhile waits and then stores its file again (`keep_stored`).
+    pub async fn remove_files(self: &Arc<Self>, paths: Vec<String>) {
+        if paths.iter().all(|path| path.is_empty()) {
+            return;
+        }
+        let state = self.clone();
+        let removed = telemetry::spawn_db(move || {
+            let runtime = tokio::runtime::Handle::current();
+            store::remove_unused_files(&state.conn, &paths, |path| runtime.block_on(state.uploads.remove(path)))
+        })
+        .await;
+        match removed {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => warn!("remove stored files: {e:#}"),
+            Err(e) => warn!("remove stored files: {e}"),
+        }
+    }
+
+    /// Announces a card mutation to SSE subscribers and registered webhooks.
+    pub fn notify(&self, event: &'static str, card_id: i64, card: Option<Card>) {
+        self.events.publish(event, Some(card_id));
+        if matches!(
+            event,
+            events::CARD_CREATED | events::CARD_UPDATED | events::CARD_DELETED
+        ) {
+            self.events.publish(events::TAGS_CHANGED, None);
+        }
+        if let Some(geocoder) = &self.geocoder {
+            if matches!(event, events::CARD_CREATED | events::CARD_UPDATED) {
+                geocoder.wake();
+            }
+        }
+        self.webhooks.dispatch(event, card_id, card);
+    }
 }
 
 fn internal_error(msg: impl std::fmt::Display) -> (StatusCode, Json<Value>) {
@@ -39,28 +153,116 @@ fn bad_request(msg: &str) -> (StatusCode, Json<Value>) {
     (StatusCode::BAD_REQUEST, Json(json!({"error": msg})))
 }
 
-// ────────────────────────────────────────────────────────────────────────────
-// Request logging (nginx-style)
-// ────────────────────────────────────────────────────────────────────────────
+/// 413 naming the limit `what` ran over.
+fn too_large(what: &str, max_size: usize) -> Response {
+    let message = format!("{what} exceeds {}MB limit", max_size / (1024 * 1024));
+    (StatusCode::PAYLOAD_TOO_LARGE, Json(json!({"error": message}))).into_response()
+}
 
-fn log_request(method: &Method, uri: &Uri, headers: &HeaderMap, status: StatusCode) {
-    let user_agent = headers
-        .get("user-agent")
-        .and_then(|v| v.to_str().ok())
-        .unwrap_or("-");
-    let referer = headers
-        .get("referer")
-        .and_then(|v| v.to_str().ok())
-        .unwrap_or("-");
-    tracing::info!(
-        "{} {} {} {} \"{}\" \"{}\"",
-        method.as_str(),
-        uri.path(),
-        uri.query().unwrap_or(""),
-        status.as_u16(),
-        user_agent,
-        referer
-    );
+/// 422 listing every problem with the request body; `error` joins them
+/// into one line for clients that only show a message.
+fn validation_error(errors: FieldErrors) -> Response {
+    (
+        StatusCode::UNPROCESSABLE_ENTITY,
+        Json(json!({"error": validation::message(&errors), "errors": errors})),
+    )
+        .into_response()
+}
+
+/// The custom field definitions card bodies are checked against.
+async fn field_schema(state: &AppState) -> anyhow::Result<Vec<FieldDefinition>> {
+    let conn = state.conn.clone();
+    telemetry::spawn_db(move || store::field_definitions(&conn)).await?
+}
+
+/// Maps a store error to 409 for version and tag conflicts, 404 for unknown
+/// cards in a bulk change, 422 for a bad `reports_to`, 500 otherwise.
+fn store_error(e: anyhow::Error) -> Response {
+    if let Some(conflict) = e.downcast_ref::<store::VersionConflict>() {
+        return (
+            StatusCode::CONFLICT,
+            Json(json!({"error": conflict.to_string(), "current_version": conflict.current})),
+        )
+            .into_response();
+    }
+    if let Some(exists) = e.downcast_ref::<store::TagExists>() {
+        return (StatusCode::CONFLICT, Json(json!({"error": exists.to_string()}))).into_response();
+    }
+    if let Some(missing) = e.downcast_ref::<store::CardsNotFound>() {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": missing.to_string(), "missing_ids": missing.ids})),
+        )
+            .into_response();
+    }
+    if let Some(full) = e.downcast_ref::<store::PhotosFull>() {
+        return validation_error(vec![FieldError {
+            field: "photo".to_string(),
+            index: None,
+            code: "too_many".to_string(),
+            message: full.to_string(),
+        }]);
+    }
+    if let Some(deletion) = e.downcast_ref::<store::UndoDeletion>() {
+        return (
+            StatusCode::CONFLICT,
+            Json(json!({"error": deletion.to_string(), "deleted_ids": deletion.ids})),
+        )
+            .into_response();
+    }
+    if let Some(invalid) = e.downcast_ref::<store::InvalidReportsTo>() {
+        return validation_error(vec![FieldError {
+            field: "reports_to".to_string(),
+            index: None,
+            code: "invalid".to_string(),
+            message: invalid.reason.to_string(),
+        }]);
+    }
+    internal_error(e).into_response()
+}
+
+/// A card body with its version as a strong `ETag`.
+fn card_response(status: StatusCode, card: &Card) -> Response {
+    (
+        status,
+        [(header::ETAG, format!("\"{}\"", card.version))],
+        Json(json!(card)),
+    )
+        .into_response()
+}
+
+/// Whether `If-None-Match` lists `etag` or is `*`; weak validators match
+/// too, as the header's comparison is weak.
+fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    let Some(raw) = headers.get(header::IF_NONE_MATCH).and_then(|v| v.to_str().ok()) else {
+        return false;
+    };
+    raw.split(',')
+        .map(|tag| tag.trim().trim_start_matches("W/"))
+        .any(|tag| tag == "*" || tag == etag)
+}
+
+/// Parses `If-Match: "3"` (weak validators and bare numbers are accepted
+/// too). `None` without the header; 400 for `*`, a list, or anything else
+/// that does not name one version, rather than skipping the check.
+fn if_match_version(headers: &HeaderMap) -> Result<Option<i64>, (StatusCode, Json<Value>)> {
+    let Some(raw) = headers.get(header::IF_MATCH) else {
+        return Ok(None);
+    };
+    raw.to_str()
+        .ok()
+        .and_then(|raw| raw.trim().trim_start_matches("W/").trim_matches('"').parse().ok())
+        .map(Some)
+        .ok_or_else(|| bad_request("If-Match must name one card version, e.g. \"3\""))
+}
+
+/// 428 for a card update that names no version to check against.
+fn precondition_required() -> Response {
+    (
+        StatusCode::PRECONDITION_REQUIRED,
+        Json(json!({"error": "send the card's version as If-Match or `version`"})),
+    )
+        .into_response()
 }
 
 // ────────────────────────────────────────────────────────────────────────────
@@ -72,33 +274,38 @@ struct MultipartFields {
     photo: Option<(String, Vec<u8>)>, // (original filename, bytes)
 }
 
-async fn collect_multipart(mut multipart: Multipart) -> Result<MultipartFields, String> {
+/// Reads a form's text fields and its `photo`, answering 413 once the photo
+/// is larger than `max_size` or the body runs past the route's limit.
+async fn collect_multipart(mut multipart: Multipart, max_size: usize) -> Result<MultipartFields, Response> {
     let mut text = std::collections::HashMap::new();
     let mut photo: Option<(String, Vec<u8>)> = None;
+    let multipart_error = |what: &str, e: axum::extract::multipart::MultipartError| match e.status() {
+        StatusCode::PAYLOAD_TOO_LARGE => too_large("photo", max_size),
+        _ => bad_request(&format!("{what} error: {e}")).into_response(),
+    };
 
-    while let Some(field) = multipart
+    while let Some(mut field) = multipart
         .next_field()
         .await
-        .map_err(|e| format!("multipart error: {e}"))?
+        .map_err(|e| multipart_error("multipart", e))?
     {
         let name = field.name().unwrap_or("").to_string();
         let filename = field.file_name().map(|s| s.to_string());
 
-        if name == "photo" && filename.is_some() {
-            let fname = filename.unwrap();
-            let data = field
-                .bytes()
-                .await
-                .map_err(|e| format!("read photo error: {e}"))?;
-            if data.len() > 5 * 1024 * 1024 {
-                return Err("photo exceeds 5MB limit".to_string());
+        if let (true, Some(fname)) = (name == "photo", filename) {
+            let mut data = Vec::new();
+            while let Some(chunk) = field.chunk().await.map_err(|e| multipart_error("read photo", e))? {
+                data.extend_from_slice(&chunk);
+                if data.len() > max_size {
+                    return Err(too_large("photo", max_size));
+                }
             }
-            photo = Some((fname, data.to_vec()));
+            photo = Some((fname, data));
         } else {
             let value = field
                 .text()
                 .await
-                .map_err(|e| format!("read field error: {e}"))?;
+                .map_err(|e| multipart_error("read field", e))?;
             text.insert(name, value);
         }
     }
@@ -107,12 +314,7 @@ async fn collect_multipart(mut multipart: Multipart) -> Result<MultipartFields,
 }
 
 fn parse_card_input(fields: &MultipartFields) -> Result<CardInput, String> {
-    let name = fields
-        .text
-        .get("name")
-        .cloned()
-        .filter(|s| !s.trim().is_empty())
-        .ok_or_else(|| "name is required".to_string())?;
+    let name = fields.text.get("name").cloned().unwrap_or_default();
 
     let phones: Vec<CardFormPhoneInput> = fields
         .text
@@ -132,182 +334,691 @@ fn parse_card_input(fields: &MultipartFields) -> Result<CardInput, String> {
         .map(|s| serde_json::from_str(s).unwrap_or_default())
         .unwrap_or_default();
 
+    let socials: Vec<CardFormSocialInput> = fields
+        .text
+        .get("socials")
+        .map(|s| serde_json::from_str(s).unwrap_or_default())
+        .unwrap_or_default();
+
+    let ims: Vec<CardFormImInput> = fields
+        .text
+        .get("ims")
+        .map(|s| serde_json::from_str(s).unwrap_or_default())
+        .unwrap_or_default();
+
     let tags: Vec<String> = fields
         .text
         .get("tags")
         .map(|s| serde_json::from_str(s).unwrap_or_default())
         .unwrap_or_default();
 
+    let custom: BTreeMap<String, String> = fields
+        .text
+        .get("custom")
+        .map(|s| serde_json::from_str(s).unwrap_or_default())
+        .unwrap_or_default();
+
+    let text = |key: &str| fields.text.get(key).cloned().unwrap_or_default();
     Ok(CardInput {
         name,
+        name_prefix: text("name_prefix"),
+        given_name: text("given_name"),
+        middle_name: text("middle_name"),
+        family_name: text("family_name"),
+        name_suffix: text("name_suffix"),
+        name_phonetic: text("name_phonetic"),
+        birthday: text("birthday"),
+        anniversary: text("anniversary"),
         title: fields.text.get("title").cloned().unwrap_or_default(),
         company: fields.text.get("company").cloned().unwrap_or_default(),
+        department: text("department"),
+        role_seniority: text("role_seniority"),
+        assistant_name: text("assistant_name"),
+        assistant_phone: text("assistant_phone"),
+        reports_to: fields.text.get("reports_to").and_then(|v| v.trim().parse().ok()),
+        timezone: text("timezone"),
         website: fields.text.get("website").cloned().unwrap_or_default(),
         notes: fields.text.get("notes").cloned().unwrap_or_default(),
         phones,
         emails,
         addresses,
+        socials,
+        ims,
         tags,
+        custom,
+        version: fields.text.get("version").and_then(|v| v.trim().parse().ok()),
     })
 }
 
-async fn save_photo(
-    uploads_dir: &str,
-    card_id: i64,
-    filename: &str,
-    data: &[u8],
-) -> Result<String, String> {
-    // validate extension
-    let ext = std::path::Path::new(filename)
+/// Reads a card from either an `application/json` body (API clients) or the
+/// multipart form used by the UI, which may also carry a photo of at most
+/// `max_size` bytes.
+async fn read_card_payload(
+    request: Request,
+    max_size: usize,
+) -> Result<(CardInput, Option<(String, Vec<u8>)>), Response> {
+    let is_json = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("application/json"));
+
+    let rejected = |status: StatusCode, text: String| match status {
+        StatusCode::PAYLOAD_TOO_LARGE => too_large("request body", max_size),
+        _ => bad_request(&text).into_response(),
+    };
+    if is_json {
+        let Json(input) = Json::<CardInput>::from_request(request, &())
+            .await
+            .map_err(|e| rejected(e.status(), e.body_text()))?;
+        Ok((input, None))
+    } else {
+        let multipart = Multipart::from_request(request, &())
+            .await
+            .map_err(|e| rejected(e.status(), e.body_text()))?;
+        let fields = collect_multipart(multipart, max_size).await?;
+        let input = parse_card_input(&fields).map_err(|e| bad_request(&e).into_response())?;
+        Ok((input, fields.photo))
+    }
+}
+
+/// Stores a file with `put`, then runs `save` to have a row take it. The
+/// file is put again once the row holds it, in case a request dropping its
+/// last reference removed it in between; from then on nothing removes it,
+/// as `AppState::remove_files` checks for rows under the same lock. If
+/// `save` fails the file is removed again, unless another row uses it.
+async fn keep_stored<T, Put, Fut, Save>(state: &Arc<AppState>, put: Put, save: Save) -> anyhow::Result<(String, T)>
+where
+    Put: Fn() -> Fut,
+    Fut: std::future::Future<Output = anyhow::Result<String>>,
+    Save: FnOnce(&Arc<Mutex<Connection>>, &str) -> anyhow::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    let path = put().await?;
+    let conn = state.conn.clone();
+    let stored = path.clone();
+    match telemetry::spawn_db(move || save(&conn, &stored)).await? {
+        Ok(saved) => {
+            put().await?;
+            Ok((path, saved))
+        }
+        Err(e) => {
+            state.remove_files(vec![path]).await;
+            Err(e)
+        }
+    }
+}
+
+/// Stores `data` as a file in `subdir` (none if empty), returning its path.
+async fn store_file(state: &AppState, subdir: &str, data: &[u8], ext: &str) -> anyhow::Result<String> {
+    Ok(state.uploads.store(subdir, data, ext).await?.0)
+}
+
+/// An uploaded photo checked and made ready to store.
+struct PreparedPhoto {
+    data: Vec<u8>,
+    ext: String,
+}
+
+/// Checks an uploaded photo and makes it ready to store, before any row is
+/// written for it, so a photo that is refused leaves nothing behind. A
+/// photo that is not what its name says or cannot be read gets a 400.
+async fn prepare_photo(state: &AppState, filename: &str, data: &[u8]) -> Result<PreparedPhoto, Response> {
+    photo::check_type(filename, data).map_err(|e| bad_request(&e).into_response())?;
+    let mut ext = std::path::Path::new(filename)
         .extension()
         .and_then(|e| e.to_str())
         .map(|e| e.to_lowercase())
         .unwrap_or_default();
+    let limits = state.photo_limits;
 
-    if !matches!(ext.as_str(), "jpg" | "jpeg" | "png" | "webp") {
-        return Err("only jpg, png, webp photos are allowed".to_string());
+    // HEIC is stored as JPEG, which every browser shows.
+    let mut owned = data.to_vec();
+    if photo::is_heif_name(filename) {
+        owned = match photo::heif_to_jpeg(&state.heif_convert, data, limits.quality).await {
+            Ok(converted) => converted,
+            Err(photo::ConvertError::Rejected(reason)) => {
+                return Err(bad_request(&format!("HEIC photo could not be read: {reason}")).into_response())
+            }
+            Err(photo::ConvertError::Unavailable(e)) => {
+                return Err(internal_error(format!("convert HEIC photo: {e:#}")).into_response())
+            }
+        };
+        ext = "jpg".to_string();
     }
 
-    let timestamp = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_millis();
-
-    let new_filename = format!("card_{card_id}_{timestamp}.{ext}");
-    let path = std::path::Path::new(uploads_dir).join(&new_filename);
-
-    fs::create_dir_all(uploads_dir)
-        .await
-        .map_err(|e| format!("create uploads dir: {e}"))?;
-
-    fs::write(&path, data)
-        .await
-        .map_err(|e| format!("write photo: {e}"))?;
+    // Stored upright, scaled down if oversized, and without metadata.
+    match tokio::task::spawn_blocking(move || photo::prepare(&owned, limits)).await {
+        Ok(Ok(data)) => Ok(PreparedPhoto { data, ext }),
+        Ok(Err(e)) => Err(bad_request(&format!("photo could not be read: {e:#}")).into_response()),
+        Err(e) => Err(internal_error(e).into_response()),
+    }
+}
 
-    Ok(format!("uploads/{new_filename}"))
+/// Stores a prepared photo, returning its path.
+async fn save_photo(state: &AppState, photo: &PreparedPhoto) -> anyhow::Result<String> {
+    state.uploads.save_photo(&photo.data, &photo.ext).await
 }
 
-async fn remove_file_if_exists(uploads_dir: &str, photo_path: &str) {
-    if photo_path.is_empty() {
-        return;
+/// The MIME type and bytes of a card's photo, if it has one stored.
+async fn read_photo(state: &AppState, photo_url: &str) -> Option<(String, Vec<u8>)> {
+    let filename = base_path::strip(photo_url).trim_start_matches('/').strip_prefix("uploads/")?;
+    if filename.is_empty() || filename.contains("..") || filename.contains('/') {
+        return None;
     }
-    // photo_path stored as "uploads/filename"
-    let filename = photo_path.trim_start_matches("uploads/");
-    let path = std::path::Path::new(uploads_dir).join(filename);
-    let _ = fs::remove_file(path).await;
+    let data = state.uploads.read(photo_url).await.ok()??;
+    let mime = mime_guess::from_path(filename).first_or_octet_stream().to_string();
+    Some((mime, data))
 }
 
 // ────────────────────────────────────────────────────────────────────────────
 // Handlers
 // ────────────────────────────────────────────────────────────────────────────
 
-#[derive(Deserialize)]
+/// Top-level card keys that `?fields=` may select.
+const CARD_FIELDS: &[&str] = &[
+    "id", "name", "name_prefix", "given_name", "middle_name", "family_name", "name_suffix",
+    "name_phonetic", "birthday", "anniversary", "title", "company", "department",
+    "role_seniority", "assistant_name", "assistant_phone", "reports_to", "last_contacted", "timezone",
+    "local_time", "website", "notes", "photo_url", "photos", "phones", "emails",
+    "addresses", "socials", "ims", "tags", "custom", "created_at", "updated_at", "version", "favorite",
+    "archived_at", "score", "matches",
+];
+
+/// A `?fields=id,name,...` selection. Only the listed keys are returned, and
+/// nested collections that were not asked for are never loaded.
+struct Fieldset(Vec<String>);
+
+impl Fieldset {
+    fn parse(raw: Option<&str>) -> Result<Option<Self>, String> {
+        let Some(raw) = raw else { return Ok(None) };
+        let mut fields = Vec::new();
+        for f in raw.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+            if !CARD_FIELDS.contains(&f) {
+                return Err(format!("unknown field '{f}'"));
+            }
+            if !fields.iter().any(|x| x == f) {
+                fields.push(f.to_string());
+            }
+        }
+        if fields.is_empty() {
+            return Err("fields must name at least one field".to_string());
+        }
+        Ok(Some(Self(fields)))
+    }
+
+    fn has(&self, field: &str) -> bool {
+        self.0.iter().any(|f| f == field)
+    }
+
+    fn relations(&self) -> CardRelations {
+        // Search matches can come from any nested value.
+        if self.has("matches") {
+            return CardRelations::ALL;
+        }
+        CardRelations {
+            phones: self.has("phones"),
+            emails: self.has("emails"),
+            addresses: self.has("addresses"),
+            socials: self.has("socials"),
+            ims: self.has("ims"),
+            tags: self.has("tags"),
+            photos: self.has("photos"),
+        }
+    }
+
+    fn select(&self, card: &Card) -> Value {
+        let mut full = match json!(card) {
+            Value::Object(map) => map,
+            _ => unreachable!("cards serialize as objects"),
+        };
+        let picked: serde_json::Map<String, Value> = self
+            .0
+            .iter()
+            .filter_map(|f| full.remove(f).map(|v| (f.clone(), v)))
+            .collect();
+        Value::Object(picked)
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct FieldsParams {
+    /// Comma-separated card keys to return, e.g. `id,name,company,photo_url`
+    pub fields: Option<String>,
+}
+
+/// Largest page a client may request.
+const MAX_PAGE_SIZE: i64 = 500;
+
+/// Page size used when only `page` is given.
+const DEFAULT_PAGE_SIZE: i64 = 50;
+
+#[derive(Deserialize, IntoParams)]
 pub struct SearchParams {
+    /// Matches name, company, title, notes, email, street, or city
+    /// (substring), or phone number (digits only)
     pub q: Option<String>,
-    pub tag: Option<String>,
+    /// `true` matches `q` despite typos and orders results by relevance
+    #[serde(default)]
+    pub fuzzy: bool,
+    /// `true` matches names that sound like `q` ("Jon Smyth" finds "John Smith")
+    #[serde(default)]
+    pub phonetic: bool,
+    /// Only cards carrying this tag; repeat to require several
+    #[serde(default)]
+    pub tag: Vec<String>,
+    /// Exclude cards carrying this tag; may be repeated
+    #[serde(default)]
+    pub not_tag: Vec<String>,
+    /// `true` lists only cards without any tags
+    #[serde(default)]
+    pub untagged: bool,
+    /// Only cards with an address in this country (case-insensitive)
+    pub country: Option<String>,
+    /// Only cards with an address in this city (case-insensitive)
+    pub city: Option<String>,
+    /// `false` lists cards without a photo, `true` those with one
+    pub has_photo: Option<bool>,
+    /// `true` lists only favorites, `false` only the rest
+    pub favorite: Option<bool>,
+    /// `true` lists archived cards instead of active ones
+    #[serde(default)]
+    pub archived: bool,
+    /// Comma-separated details the card must lack: email, phone, address,
+    /// tag, photo, title, company, website
+    pub missing: Option<String>,
+    /// Created at or after this date or RFC 3339 timestamp
+    pub created_after: Option<String>,
+    /// Created before this date or RFC 3339 timestamp
+    pub created_before: Option<String>,
+    /// Updated at or after this date or RFC 3339 timestamp
+    pub updated_after: Option<String>,
+    /// Updated before this date or RFC 3339 timestamp
+    pub updated_before: Option<String>,
+    /// `name`, `family_name`, `company`, `created_at`, or `updated_at`; prefix with `-` for
+    /// descending (default `-updated_at`, or relevance when `q` is given)
+    pub sort: Option<String>,
+    /// Maximum number of cards to return (at most 500); all cards if omitted
+    pub limit: Option<i64>,
+    /// Number of matching cards to skip
+    pub offset: Option<i64>,
+    /// 1-based page number, as an alternative to `offset`
+    pub page: Option<i64>,
+    /// Page size when using `page` (default 50)
+    pub per_page: Option<i64>,
+    /// Opaque token from `X-Next-Cursor`; continues after the previous page
+    pub cursor: Option<String>,
+    /// Comma-separated card keys to return, e.g. `id,name,company,photo_url`
+    pub fields: Option<String>,
+}
+
+impl SearchParams {
+    fn to_query(&self) -> Result<CardQuery, String> {
+        let missing = match self.missing.as_deref() {
+            Some(list) => list
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::parse)
+                .collect::<Result<Vec<CardDetail>, _>>()?,
+            None => vec![],
+        };
+        let timestamp = |name: &str, value: &Option<String>| match value.as_deref() {
+            Some(v) => parse_timestamp(v)
+                .map(Some)
+                .ok_or_else(|| format!("{name} must be a date (YYYY-MM-DD) or RFC 3339 timestamp")),
+            None => Ok(None),
+        };
+        let created_after = timestamp("created_after", &self.created_after)?;
+        let created_before = timestamp("created_before", &self.created_before)?;
+        let updated_after = timestamp("updated_after", &self.updated_after)?;
+        let updated_before = timestamp("updated_before", &self.updated_before)?;
+        let sort: CardSort = match self.sort.as_deref() {
+            Some(s) => s.parse()?,
+            None => CardSort::default(),
+        };
+        let after = match self.cursor.as_deref() {
+            Some(token) => match CardCursor::decode(token) {
+                Some(c) if c.sort == sort => Some(c),
+                Some(_) => return Err("cursor was issued for a different sort".to_string()),
+                None => return Err("invalid cursor".to_string()),
+            },
+            None => None,
+        };
+        if self.fuzzy && self.phonetic {
+            return Err("choose either fuzzy or phonetic search".to_string());
+        }
+        // A plain search without an explicit sort is ordered by relevance.
+        let rank = self.sort.is_none()
+            && !self.fuzzy
+            && !self.phonetic
+            && self.q.as_deref().is_some_and(|q| !q.is_empty());
+        if after.is_some() && self.fuzzy {
+            return Err("cursor cannot be combined with fuzzy search".to_string());
+        }
+        if after.is_some() && rank {
+            return Err("cursor needs an explicit sort when searching with q".to_string());
+        }
+        if after.is_some() && (self.offset.is_some() || self.page.is_some()) {
+            return Err("cursor cannot be combined with offset or page".to_string());
+        }
+
+        let mut limit = self.limit.or(self.per_page);
+        if (self.page.is_some() || after.is_some()) && limit.is_none() {
+            limit = Some(DEFAULT_PAGE_SIZE);
+        }
+        if let Some(l) = limit {
+            if !(1..=MAX_PAGE_SIZE).contains(&l) {
+                return Err(format!("limit must be between 1 and {MAX_PAGE_SIZE}"));
+            }
+        }
+
+        let offset = match (self.offset, self.page) {
+            (Some(o), _) if o < 0 => return Err("offset must not be negative".to_string()),
+            (Some(o), _) => o,
+            (None, Some(p)) if p < 1 => return Err("page starts at 1".to_string()),
+            (None, Some(p)) => (p - 1) * limit.unwrap_or(DEFAULT_PAGE_SIZE),
+            (None, None) => 0,
+        };
+
+        Ok(CardQuery {
+            q: self.q.clone(),
+            fuzzy: self.fuzzy,
+            phonetic: self.phonetic,
+            rank,
+            tags: self.tag.clone(),
+            not_tags: self.not_tag.clone(),
+            untagged: self.untagged,
+            country: self.country.clone(),
+            city: self.city.clone(),
+            has_photo: self.has_photo,
+            favorite: self.favorite,
+            archived: self.archived,
+            missing,
+            created_after,
+            created_before,
+            updated_after,
+            updated_before,
+            sort,
+            limit,
+            offset,
+            after,
+            ..Default::default()
+        })
+    }
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/cards",
+    tag = "cards",
+    params(SearchParams),
+    responses(
+        (status = 200, description = "Cards in the requested order", body = [Card],
+            headers(
+                ("X-Total-Count" = i64, description = "Matching cards before paging"),
+                ("X-Next-Cursor" = String, description = "Pass as `cursor` to fetch the next page; absent on the last page")
+            )),
+        (status = 400, body = ErrorBody)
+    )
+)]
 pub async fn list_cards(
     State(state): State<Arc<AppState>>,
     Query(params): Query<SearchParams>,
 ) -> impl IntoResponse {
+    let (mut query, fields) = match params
+        .to_query()
+        .and_then(|q| Ok((q, Fieldset::parse(params.fields.as_deref())?)))
+    {
+        Ok(parsed) => parsed,
+        Err(e) => return bad_request(&e).into_response(),
+    };
+    if let Some(f) = &fields {
+        query.relations = f.relations();
+    }
     let conn = state.conn.clone();
-    let q = params.q.clone();
-    let tag = params.tag.clone();
+    // Relevance order has no stable key to resume from, so no cursor.
+    let (limit, sort) = (query.limit.filter(|_| !query.fuzzy && !query.rank), query.sort);
 
-    let result = tokio::task::spawn_blocking(move || {
-        store::list_cards(&conn, q.as_deref(), tag.as_deref())
-    })
-    .await;
+    let result = telemetry::spawn_db(move || search::find_cards(&conn, &query)).await;
 
     match result {
-        Ok(Ok(cards)) => (StatusCode::OK, Json(json!(cards))).into_response(),
+        Ok(Ok((total, cards))) => {
+            let mut headers = HeaderMap::new();
+            headers.insert("x-total-count", HeaderValue::from(total));
+            // A short page is the last one; a full page may have more behind it.
+            if let (Some(last), Some(limit)) = (cards.last(), limit) {
+                if cards.len() as i64 == limit {
+                    let next = CardCursor::for_card(last, sort).encode();
+                    headers.insert("x-next-cursor", HeaderValue::from_str(&next).unwrap());
+                }
+            }
+            let body = match &fields {
+                Some(f) => Value::Array(cards.iter().map(|c| f.select(c)).collect()),
+                None => json!(cards),
+            };
+            (StatusCode::OK, headers, Json(body)).into_response()
+        }
         Ok(Err(e)) => internal_error(e).into_response(),
         Err(e) => internal_error(e).into_response(),
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/cards/{id}",
+    tag = "cards",
+    params(("id" = i64, Path, description = "Card id"), FieldsParams),
+    responses(
+        (status = 200, body = Card),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody)
+    )
+)]
 pub async fn get_card(
     State(state): State<Arc<AppState>>,
     Path(id): Path<i64>,
+    Query(params): Query<FieldsParams>,
 ) -> impl IntoResponse {
+    let fields = match Fieldset::parse(params.fields.as_deref()) {
+        Ok(f) => f,
+        Err(e) => return bad_request(&e).into_response(),
+    };
+    let relations = fields.as_ref().map_or(CardRelations::ALL, Fieldset::relations);
     let conn = state.conn.clone();
 
     let result =
-        tokio::task::spawn_blocking(move || store::get_card(&conn, id)).await;
+        telemetry::spawn_db(move || store::get_card_with(&conn, id, relations)).await;
 
     match result {
-        Ok(Ok(Some(card))) => (StatusCode::OK, Json(json!(card))).into_response(),
+        // A partial representation gets no ETag; select `version` instead.
+        Ok(Ok(Some(card))) => match &fields {
+            Some(f) => (StatusCode::OK, Json(f.select(&card))).into_response(),
+            None => card_response(StatusCode::OK, &card),
+        },
         Ok(Ok(None)) => not_found("card not found").into_response(),
         Ok(Err(e)) => internal_error(e).into_response(),
         Err(e) => internal_error(e).into_response(),
     }
 }
 
+#[derive(Deserialize, IntoParams)]
+pub struct CreateParams {
+    /// What to do if a card with the same email or phone exists:
+    /// `allow` (default), `reject`, or `merge`
+    #[param(inline)]
+    pub on_duplicate: Option<OnDuplicate>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/cards",
+    tag = "cards",
+    params(CreateParams),
+    request_body(content(
+        (CardInput = "application/json"),
+        (CardForm = "multipart/form-data")
+    )),
+    responses(
+        (status = 201, body = Card),
+        (status = 200, description = "Merged into an existing card (`on_duplicate=merge`)", body = Card),
+        (status = 400, body = ErrorBody),
+        (status = 413, description = "The photo is larger than `--max-upload-mb`", body = ErrorBody),
+        (status = 422, description = "Missing or malformed fields", body = ValidationErrorBody),
+        (status = 409, description = "A card with the same email or phone exists (`on_duplicate=reject`); it is returned as `card`", body = ErrorBody)
+    )
+)]
 pub async fn create_card(
     State(state): State<Arc<AppState>>,
-    multipart: Multipart,
+    Query(params): Query<CreateParams>,
+    request: Request,
 ) -> impl IntoResponse {
-    let fields = match collect_multipart(multipart).await {
-        Ok(f) => f,
-        Err(e) => return bad_request(&e).into_response(),
+    let (input, photo_data) = match read_card_payload(request, state.photo_max_size).await {
+        Ok(p) => p,
+        Err(response) => return response,
     };
+    insert_card(state, params.on_duplicate.unwrap_or_default(), input, photo_data).await
+}
 
-    let input = match parse_card_input(&fields) {
-        Ok(i) => i,
-        Err(e) => return bad_request(&e).into_response(),
+/// Validates and saves a new card, honouring `on_duplicate`; shared by
+/// card creation and imports.
+async fn insert_card(
+    state: Arc<AppState>,
+    on_duplicate: OnDuplicate,
+    mut input: CardInput,
+    photo_data: Option<(String, Vec<u8>)>,
+) -> Response {
+    let schema = match field_schema(&state).await {
+        Ok(schema) => schema,
+        Err(e) => return internal_error(e).into_response(),
+    };
+    if let Err(errors) = validation::card_input(&mut input, &schema) {
+        return validation_error(errors);
+    }
+
+    let existing = if on_duplicate == OnDuplicate::Allow {
+        None
+    } else {
+        let conn = state.conn.clone();
+        let emails: Vec<String> = input.emails.iter().map(|e| e.address.clone()).collect();
+        let phones: Vec<String> = input.phones.iter().map(|p| p.number.clone()).collect();
+        let found = telemetry::spawn_db(move || {
+            let Some(id) = store::find_card_id_by_contact(&conn, &emails, &phones)? else {
+                return Ok(None);
+            };
+            store::get_card(&conn, id)
+        })
+        .await;
+        match found {
+            Ok(Ok(existing)) => existing,
+            Ok(Err(e)) => return internal_error(e).into_response(),
+            Err(e) => return internal_error(e).into_response(),
+        }
+    };
+    if let (OnDuplicate::Reject, Some(card)) = (on_duplicate, &existing) {
+        return (
+            StatusCode::CONFLICT,
+            Json(json!({
+                "error": "a card with the same email or phone already exists",
+                "card": card,
+            })),
+        )
+            .into_response();
+    }
+
+    let photo = match photo_data {
+        Some((filename, data)) => match prepare_photo(&state, &filename, &data).await {
+            Ok(photo) => Some(photo),
+            Err(response) => return response,
+        },
+        None => None,
     };
 
-    let photo_data = fields.photo;
     let conn = state.conn.clone();
-    let uploads_dir = state.uploads_dir.clone();
 
     // Insert card first to get the ID
     let result =
-        tokio::task::spawn_blocking(move || store::create_card(&conn, &input)).await;
+        telemetry::spawn_db(move || store::create_card(&conn, &input)).await;
 
     let card_id = match result {
         Ok(Ok(id)) => id,
-        Ok(Err(e)) => return internal_error(e).into_response(),
+        Ok(Err(e)) => return store_error(e),
         Err(e) => return internal_error(e).into_response(),
     };
 
     // Save photo if provided
-    if let Some((filename, data)) = photo_data {
-        match save_photo(&uploads_dir, card_id, &filename, &data).await {
-            Ok(photo_path) => {
-                let conn2 = state.conn.clone();
-                let path_clone = photo_path.clone();
-                let _ = tokio::task::spawn_blocking(move || {
-                    store::update_card_photo(&conn2, card_id, &path_clone)
-                })
-                .await;
-            }
-            Err(e) => return internal_error(e).into_response(),
+    if let Some(photo) = photo {
+        let saved = keep_stored(&state, || save_photo(&state, &photo), move |conn, path| {
+            store::update_card_photo(conn, card_id, path)
+        })
+        .await;
+        if let Err(e) = saved {
+            return internal_error(e).into_response();
         }
     }
 
+    // Fold the new card into the existing one; its photo goes after the
+    // existing card's
+    if let Some(existing) = existing {
+        let conn3 = state.conn.clone();
+        let result = telemetry::spawn_db(move || {
+            store::merge_cards(&conn3, existing.id, card_id, false, None)?;
+            store::get_card(&conn3, existing.id)?.ok_or_else(|| anyhow::anyhow!("card not found"))
+        })
+        .await;
+        return match result {
+            Ok(Ok(card)) => {
+                state.notify(events::CARD_UPDATED, card.id, Some(card.clone()));
+                card_response(StatusCode::OK, &card)
+            }
+            Ok(Err(e)) => internal_error(e).into_response(),
+            Err(e) => internal_error(e).into_response(),
+        };
+    }
+
     // Fetch and return
     let conn3 = state.conn.clone();
     let result =
-        tokio::task::spawn_blocking(move || store::get_card(&conn3, card_id)).await;
+        telemetry::spawn_db(move || store::get_card(&conn3, card_id)).await;
 
     match result {
-        Ok(Ok(Some(card))) => (StatusCode::CREATED, Json(json!(card))).into_response(),
+        Ok(Ok(Some(card))) => {
+            state.notify(events::CARD_CREATED, card.id, Some(card.clone()));
+            card_response(StatusCode::CREATED, &card)
+        }
         Ok(Ok(None)) => internal_error("card created but not found").into_response(),
         Ok(Err(e)) => internal_error(e).into_response(),
         Err(e) => internal_error(e).into_response(),
     }
 }
 
+#[utoipa::path(
+    put,
+    path = "/api/v1/cards/{id}",
+    tag = "cards",
+    params(("id" = i64, Path, description = "Card id")),
+    request_body(content(
+        (CardInput = "application/json"),
+        (CardForm = "multipart/form-data")
+    )),
+    responses(
+        (status = 200, body = Card),
+        (status = 400, body = ErrorBody),
+        (status = 413, description = "The photo is larger than `--max-upload-mb`", body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 409, description = "Version in `If-Match` / `version` is stale", body = ErrorBody),
+        (status = 428, description = "Neither `If-Match` nor `version` was sent", body = ErrorBody),
+        (status = 422, description = "Missing or malformed fields", body = ValidationErrorBody)
+    )
+)]
 pub async fn update_card(
     State(state): State<Arc<AppState>>,
     Path(id): Path<i64>,
-    multipart: Multipart,
+    request: Request,
 ) -> impl IntoResponse {
     // Verify card exists
     let conn = state.conn.clone();
-    let exists = tokio::task::spawn_blocking(move || store::get_card(&conn, id)).await;
+    let exists = telemetry::spawn_db(move || store::get_card(&conn, id)).await;
     match exists {
         Ok(Ok(None)) => return not_found("card not found").into_response(),
         Ok(Err(e)) => return internal_error(e).into_response(),
@@ -315,50 +1026,52 @@ pub async fn update_card(
         _ => {}
     }
 
-    let fields = match collect_multipart(multipart).await {
-        Ok(f) => f,
-        Err(e) => return bad_request(&e).into_response(),
+    let if_match = match if_match_version(request.headers()) {
+        Ok(version) => version,
+        Err(e) => return e.into_response(),
     };
-
-    let input = match parse_card_input(&fields) {
-        Ok(i) => i,
-        Err(e) => return bad_request(&e).into_response(),
+    let (mut input, photo_data) = match read_card_payload(request, state.photo_max_size).await {
+        Ok(p) => p,
+        Err(response) => return response,
+    };
+    let schema = match field_schema(&state).await {
+        Ok(schema) => schema,
+        Err(e) => return internal_error(e).into_response(),
+    };
+    if let Err(errors) = validation::card_input(&mut input, &schema) {
+        return validation_error(errors);
+    }
+    input.version = if_match.or(input.version);
+    if input.version.is_none() {
+        return precondition_required();
+    }
+    let photo = match photo_data {
+        Some((filename, data)) => match prepare_photo(&state, &filename, &data).await {
+            Ok(photo) => Some(photo),
+            Err(response) => return response,
+        },
+        None => None,
     };
-
-    let photo_data = fields.photo;
-    let uploads_dir = state.uploads_dir.clone();
 
     let conn2 = state.conn.clone();
     let update_result =
-        tokio::task::spawn_blocking(move || store::update_card(&conn2, id, &input)).await;
+        telemetry::spawn_db(move || store::update_card(&conn2, id, &input)).await;
 
     match update_result {
         Ok(Ok(())) => {}
-        Ok(Err(e)) => return internal_error(e).into_response(),
+        Ok(Err(e)) => return store_error(e),
         Err(e) => return internal_error(e).into_response(),
     }
 
-    // Save new photo if provided
-    if let Some((filename, data)) = photo_data {
-        // Get old photo path (so we can delete the file), then clear it
-        let conn3 = state.conn.clone();
-        let old_path = tokio::task::spawn_blocking(move || store::delete_card_photo(&conn3, id))
-            .await
-            .ok()
-            .and_then(|r| r.ok())
-            .flatten()
-            .unwrap_or_default();
-        remove_file_if_exists(&uploads_dir, &old_path).await;
-
-        match save_photo(&uploads_dir, id, &filename, &data).await {
-            Ok(photo_path) => {
-                let conn4 = state.conn.clone();
-                let path_clone = photo_path.clone();
-                let _ = tokio::task::spawn_blocking(move || {
-                    store::update_card_photo(&conn4, id, &path_clone)
-                })
-                .await;
-            }
+    // Save new photo if provided; it replaces the first photo, whose file
+    // is then removed
+    if let Some(photo) = photo {
+        let saved = keep_stored(&state, || save_photo(&state, &photo), move |conn, path| {
+            store::update_card_photo(conn, id, path)
+        })
+        .await;
+        match saved {
+            Ok((_, old_path)) => state.remove_files(vec![old_path]).await,
             Err(e) => return internal_error(e).into_response(),
         }
     }
@@ -366,55 +1079,120 @@ pub async fn update_card(
     // Fetch and return updated card
     let conn5 = state.conn.clone();
     let result =
-        tokio::task::spawn_blocking(move || store::get_card(&conn5, id)).await;
+        telemetry::spawn_db(move || store::get_card(&conn5, id)).await;
 
     match result {
-        Ok(Ok(Some(card))) => (StatusCode::OK, Json(json!(card))).into_response(),
+        Ok(Ok(Some(card))) => {
+            state.notify(events::CARD_UPDATED, card.id, Some(card.clone()));
+            card_response(StatusCode::OK, &card)
+        }
         Ok(Ok(None)) => not_found("card not found").into_response(),
         Ok(Err(e)) => internal_error(e).into_response(),
         Err(e) => internal_error(e).into_response(),
     }
 }
 
-pub async fn delete_card(
+#[utoipa::path(
+    patch,
+    path = "/api/v1/cards/{id}",
+    tag = "cards",
+    params(("id" = i64, Path, description = "Card id")),
+    request_body = CardPatch,
+    responses(
+        (status = 200, body = Card),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 409, description = "Version in `If-Match` / `version` is stale", body = ErrorBody),
+        (status = 428, description = "Neither `If-Match` nor `version` was sent", body = ErrorBody),
+        (status = 422, description = "Missing or malformed fields", body = ValidationErrorBody)
+    )
+)]
+pub async fn patch_card(
     State(state): State<Arc<AppState>>,
     Path(id): Path<i64>,
-    method: Method,
-    uri: Uri,
     headers: HeaderMap,
+    Json(mut patch): Json<CardPatch>,
 ) -> impl IntoResponse {
-    let conn = state.conn.clone();
-    let uploads_dir = state.uploads_dir.clone();
+    patch.version = match if_match_version(&headers) {
+        Ok(version) => version.or(patch.version),
+        Err(e) => return e.into_response(),
+    };
+    if patch.version.is_none() {
+        return precondition_required();
+    }
 
-    let result =
-        tokio::task::spawn_blocking(move || store::delete_card(&conn, id)).await;
+    let schema = match field_schema(&state).await {
+        Ok(schema) => schema,
+        Err(e) => return internal_error(e).into_response(),
+    };
+    if let Err(errors) = validation::card_patch(&mut patch, &schema) {
+        return validation_error(errors);
+    }
 
-    let (response, status) = match result {
-        Ok(Ok(Some(old_photo))) => {
-            remove_file_if_exists(&uploads_dir, &old_photo).await;
-            (StatusCode::NO_CONTENT.into_response(), StatusCode::NO_CONTENT)
-        }
-        Ok(Ok(None)) => {
-            let resp = not_found("card not found").into_response();
-            let status = StatusCode::NOT_FOUND;
-            (resp, status)
-        }
-        Ok(Err(e)) => {
-            let resp = internal_error(e).into_response();
-            let status = StatusCode::INTERNAL_SERVER_ERROR;
-            (resp, status)
+    let conn = state.conn.clone();
+    let result = telemetry::spawn_db(move || {
+        if !store::patch_card(&conn, id, &patch)? {
+            return Ok(None);
         }
-        Err(e) => {
-            let resp = internal_error(e).into_response();
-            let status = StatusCode::INTERNAL_SERVER_ERROR;
-            (resp, status)
+        store::get_card(&conn, id)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(Some(card))) => {
+            state.notify(events::CARD_UPDATED, card.id, Some(card.clone()));
+            card_response(StatusCode::OK, &card)
         }
-    };
+        Ok(Ok(None)) => not_found("card not found").into_response(),
+        Ok(Err(e)) => store_error(e),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
 
-    log_request(&method, &uri, &headers, status);
-    response
+#[utoipa::path(
+    delete,
+    path = "/api/v1/cards/{id}",
+    tag = "cards",
+    params(("id" = i64, Path, description = "Card id")),
+    responses(
+        (status = 204, description = "Card deleted"),
+        (status = 404, body = ErrorBody)
+    )
+)]
+pub async fn delete_card(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    let conn = state.conn.clone();
+
+    let result =
+        telemetry::spawn_db(move || store::delete_card(&conn, id)).await;
+
+    match result {
+        Ok(Ok(Some(old_photos))) => {
+            state.remove_files(old_photos).await;
+            state.notify(events::CARD_DELETED, id, None);
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Ok(Ok(None)) => not_found("card not found").into_response(),
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/v1/cards/{id}/photo",
+    tag = "photos",
+    params(("id" = i64, Path, description = "Card id")),
+    request_body(content = PhotoForm, content_type = "multipart/form-data"),
+    responses(
+        (status = 200, body = PhotoUrl),
+        (status = 400, body = ErrorBody),
+        (status = 413, description = "The photo is larger than `--max-upload-mb`", body = ErrorBody),
+        (status = 404, body = ErrorBody)
+    )
+)]
 pub async fn upload_photo(
     State(state): State<Arc<AppState>>,
     Path(id): Path<i64>,
@@ -423,7 +1201,7 @@ pub async fn upload_photo(
     // Verify card exists
     let conn = state.conn.clone();
     let exists =
-        tokio::task::spawn_blocking(move || store::get_card(&conn, id)).await;
+        telemetry::spawn_db(move || store::get_card(&conn, id)).await;
     match exists {
         Ok(Ok(None)) => return not_found("card not found").into_response(),
         Ok(Err(e)) => return internal_error(e).into_response(),
@@ -431,64 +1209,69 @@ pub async fn upload_photo(
         _ => {}
     }
 
-    let fields = match collect_multipart(multipart).await {
+    let fields = match collect_multipart(multipart, state.photo_max_size).await {
         Ok(f) => f,
-        Err(e) => return bad_request(&e).into_response(),
+        Err(response) => return response,
     };
 
-    let (filename, data) = match fields.photo {
-        Some(p) => p,
-        None => return bad_request("no photo field provided").into_response(),
+    let upload = fields.text.get("upload").cloned();
+    let (filename, data) = match (fields.photo, &upload) {
+        (Some(p), _) => p,
+        (None, Some(upload)) => match open_upload(&state, upload, state.photo_max_size).await {
+            Ok(file) => file,
+            Err(response) => return response,
+        },
+        (None, None) => return bad_request("no photo field provided").into_response(),
     };
-
-    let uploads_dir = state.uploads_dir.clone();
-
-    // Delete old photo
-    let conn2 = state.conn.clone();
-    let old_path = tokio::task::spawn_blocking(move || store::delete_card_photo(&conn2, id))
-        .await
-        .ok()
-        .and_then(|r| r.ok())
-        .flatten()
-        .unwrap_or_default();
-    remove_file_if_exists(&uploads_dir, &old_path).await;
-
-    // Save new photo
-    let photo_path = match save_photo(&uploads_dir, id, &filename, &data).await {
-        Ok(p) => p,
-        Err(e) => return internal_error(e).into_response(),
+    let photo = match prepare_photo(&state, &filename, &data).await {
+        Ok(photo) => photo,
+        Err(response) => return response,
     };
 
-    let conn3 = state.conn.clone();
-    let path_clone = photo_path.clone();
-    let result = tokio::task::spawn_blocking(move || {
-        store::update_card_photo(&conn3, id, &path_clone)
+    // Save new photo
+    let result = keep_stored(&state, || save_photo(&state, &photo), move |conn, path| {
+        store::update_card_photo(conn, id, path)
     })
     .await;
 
     match result {
-        Ok(Ok(())) => {
-            let photo_url = format!("/{photo_path}");
+        Ok((photo_path, old_path)) => {
+            // The replaced photo's file
+            state.remove_files(vec![old_path]).await;
+            if let Some(upload) = &upload {
+                close_upload(&state, upload).await;
+            }
+            state.notify(events::PHOTO_UPDATED, id, None);
+            let photo_url = base_path::url(&format!("/{photo_path}"));
             (StatusCode::OK, Json(json!({"photo_url": photo_url}))).into_response()
         }
-        Ok(Err(e)) => internal_error(e).into_response(),
         Err(e) => internal_error(e).into_response(),
     }
 }
 
+#[utoipa::path(
+    delete,
+    path = "/api/v1/cards/{id}/photo",
+    tag = "photos",
+    params(("id" = i64, Path, description = "Card id")),
+    responses(
+        (status = 204, description = "Photo removed"),
+        (status = 404, body = ErrorBody)
+    )
+)]
 pub async fn delete_photo(
     State(state): State<Arc<AppState>>,
     Path(id): Path<i64>,
 ) -> impl IntoResponse {
     let conn = state.conn.clone();
-    let uploads_dir = state.uploads_dir.clone();
 
     let result =
-        tokio::task::spawn_blocking(move || store::delete_card_photo(&conn, id)).await;
+        telemetry::spawn_db(move || store::delete_card_photo(&conn, id)).await;
 
     match result {
         Ok(Ok(Some(old_path))) => {
-            remove_file_if_exists(&uploads_dir, &old_path).await;
+            state.remove_files(vec![old_path]).await;
+            state.notify(events::PHOTO_DELETED, id, None);
             StatusCode::NO_CONTENT.into_response()
         }
         Ok(Ok(None)) => not_found("card not found").into_response(),
@@ -497,22 +1280,2733 @@ pub async fn delete_photo(
     }
 }
 
-pub async fn list_tags(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    let conn = state.conn.clone();
-    let result =
-        tokio::task::spawn_blocking(move || store::list_tags(&conn)).await;
-
-    match result {
-        Ok(Ok(tags)) => (StatusCode::OK, Json(json!(tags))).into_response(),
-        Ok(Err(e)) => internal_error(e).into_response(),
-        Err(e) => internal_error(e).into_response(),
-    }
+#[derive(Deserialize, IntoParams)]
+pub struct ThumbParams {
+    /// `small` (default, 128 px) or `medium` (512 px)
+    #[param(inline)]
+    pub size: Option<ThumbSize>,
 }
 
-pub async fn health(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+#[utoipa::path(
+    get,
+    path = "/api/v1/cards/{id}/photo/thumb",
+    tag = "photos",
+    params(("id" = i64, Path, description = "Card id"), ThumbParams),
+    responses(
+        (status = 200, description = "Thumbnail of the card's photo, in the photo's format"),
+        (status = 404, description = "No such card, or it has no photo", body = ErrorBody)
+    )
+)]
+pub async fn photo_thumb(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    Query(params): Query<ThumbParams>,
+) -> Response {
     let conn = state.conn.clone();
-    let db_ok = tokio::task::spawn_blocking(move || {
-        let c = conn.lock().unwrap();
+    let first = match telemetry::spawn_db(move || store::list_photos(&conn, id)).await {
+        Ok(Ok(Some(photos))) => photos.into_iter().next(),
+        Ok(Ok(None)) => return not_found("card not found").into_response(),
+        Ok(Err(e)) => return internal_error(e).into_response(),
+        Err(e) => return internal_error(e).into_response(),
+    };
+    let Some(first) = first else {
+        return not_found("card has no photo").into_response();
+    };
+    let size = params.size.unwrap_or_default();
+    match state.uploads.thumbnail(&first.url, size).await {
+        Ok(Some(thumb)) => {
+            let mime = mime_guess::from_path(&first.url).first_or_octet_stream().to_string();
+            ([(header::CONTENT_TYPE, mime)], thumb).into_response()
+        }
+        Ok(None) => not_found("photo file is missing").into_response(),
+        Err(e) => internal_error(format!("{e:#}")).into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/cards/{id}/photos",
+    tag = "photos",
+    params(("id" = i64, Path, description = "Card id")),
+    responses(
+        (status = 200, description = "The card's photos in order", body = [CardPhoto]),
+        (status = 404, body = ErrorBody)
+    )
+)]
+pub async fn list_photos(State(state): State<Arc<AppState>>, Path(id): Path<i64>) -> impl IntoResponse {
+    let conn = state.conn.clone();
+    match telemetry::spawn_db(move || store::list_photos(&conn, id)).await {
+        Ok(Ok(Some(photos))) => Json(json!(photos)).into_response(),
+        Ok(Ok(None)) => not_found("card not found").into_response(),
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/cards/{id}/photos",
+    tag = "photos",
+    params(("id" = i64, Path, description = "Card id")),
+    request_body(content = CardPhotoForm, content_type = "multipart/form-data"),
+    responses(
+        (status = 201, description = "The photo, after the card's others", body = CardPhoto),
+        (status = 400, body = ErrorBody),
+        (status = 413, description = "The photo is larger than `--max-upload-mb`", body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 422, description = "Unknown role, or the card has its 10 photos", body = ValidationErrorBody)
+    )
+)]
+pub async fn add_photo(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    multipart: Multipart,
+) -> impl IntoResponse {
+    let fields = match collect_multipart(multipart, state.photo_max_size).await {
+        Ok(f) => f,
+        Err(response) => return response,
+    };
+    let upload = fields.text.get("upload").cloned();
+    let (filename, data) = match (fields.photo, &upload) {
+        (Some(p), _) => p,
+        (None, Some(upload)) => match open_upload(&state, upload, state.photo_max_size).await {
+            Ok(file) => file,
+            Err(response) => return response,
+        },
+        (None, None) => return bad_request("no photo field provided").into_response(),
+    };
+    let role = match fields.text.get("role").map(|r| r.trim()).filter(|r| !r.is_empty()) {
+        None => PhotoRole::Front,
+        Some(role) => match role.parse() {
+            Ok(role) => role,
+            Err(message) => {
+                return validation_error(vec![FieldError {
+                    field: "role".to_string(),
+                    index: None,
+                    code: "invalid".to_string(),
+                    message,
+                }])
+            }
+        },
+    };
+
+    let photo = match prepare_photo(&state, &filename, &data).await {
+        Ok(photo) => photo,
+        Err(response) => return response,
+    };
+
+    let saved = keep_stored(&state, || save_photo(&state, &photo), move |conn, path| {
+        store::add_photo(conn, id, role, path)
+    })
+    .await;
+    match saved {
+        Ok((_, Some(photo))) => {
+            if let Some(upload) = &upload {
+                close_upload(&state, upload).await;
+            }
+            state.notify(events::PHOTO_UPDATED, id, None);
+            (StatusCode::CREATED, Json(json!(photo))).into_response()
+        }
+        Ok((photo_path, None)) => {
+            state.remove_files(vec![photo_path]).await;
+            not_found("card not found").into_response()
+        }
+        Err(e) => store_error(e),
+    }
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/v1/cards/{id}/photos/{photo_id}",
+    tag = "photos",
+    params(
+        ("id" = i64, Path, description = "Card id"),
+        ("photo_id" = i64, Path, description = "Photo id")
+    ),
+    request_body = PhotoUpdate,
+    responses(
+        (status = 200, body = CardPhoto),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody)
+    )
+)]
+pub async fn update_photo(
+    State(state): State<Arc<AppState>>,
+    Path((id, photo_id)): Path<(i64, i64)>,
+    Json(update): Json<PhotoUpdate>,
+) -> impl IntoResponse {
+    let conn = state.conn.clone();
+    let result =
+        telemetry::spawn_db(move || store::update_photo(&conn, id, photo_id, &update)).await;
+
+    match result {
+        Ok(Ok(Some(photo))) => {
+            state.notify(events::PHOTO_UPDATED, id, None);
+            Json(json!(photo)).into_response()
+        }
+        Ok(Ok(None)) => not_found("photo not found").into_response(),
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/cards/{id}/photos/{photo_id}",
+    tag = "photos",
+    params(
+        ("id" = i64, Path, description = "Card id"),
+        ("photo_id" = i64, Path, description = "Photo id")
+    ),
+    responses(
+        (status = 204, description = "Photo removed; the ones after it move up"),
+        (status = 404, body = ErrorBody)
+    )
+)]
+pub async fn remove_photo(
+    State(state): State<Arc<AppState>>,
+    Path((id, photo_id)): Path<(i64, i64)>,
+) -> impl IntoResponse {
+    let conn = state.conn.clone();
+    let result =
+        telemetry::spawn_db(move || store::delete_photo(&conn, id, photo_id)).await;
+
+    match result {
+        Ok(Ok(Some(old_path))) => {
+            state.remove_files(vec![old_path]).await;
+            state.notify(events::PHOTO_DELETED, id, None);
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Ok(Ok(None)) => not_found("photo not found").into_response(),
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/cards/{id}/photo/fetch-avatar",
+    tag = "photos",
+    params(("id" = i64, Path, description = "Card id")),
+    responses(
+        (status = 200, description = "The avatar of the first of the card's emails that has one, now the card's headshot photo", body = PhotoUrl),
+        (status = 404, description = "No such card, or none of its emails has an avatar", body = ErrorBody),
+        (status = 409, description = "The card already has a headshot", body = ErrorBody),
+        (status = 422, description = "The card has no email, or has its 10 photos", body = ValidationErrorBody),
+        (status = 502, description = "The avatar service failed", body = ErrorBody),
+        (status = 503, description = "Avatar lookup is off (`--avatars none`)", body = ErrorBody)
+    )
+)]
+pub async fn fetch_avatar(State(state): State<Arc<AppState>>, Path(id): Path<i64>) -> Response {
+    let Some(avatars) = &state.avatars else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({"error": "avatar lookup is not enabled on this server"})),
+        )
+            .into_response();
+    };
+    let conn = state.conn.clone();
+    let card = match telemetry::spawn_db(move || store::get_card(&conn, id)).await {
+        Ok(Ok(Some(card))) => card,
+        Ok(Ok(None)) => return not_found("card not found").into_response(),
+        Ok(Err(e)) => return internal_error(e).into_response(),
+        Err(e) => return internal_error(e).into_response(),
+    };
+    if card.photos.iter().any(|p| p.role == PhotoRole::Headshot) {
+        return (StatusCode::CONFLICT, Json(json!({"error": "card already has a headshot"}))).into_response();
+    }
+    if card.emails.is_empty() {
+        return validation_error(vec![FieldError {
+            field: "emails".to_string(),
+            index: None,
+            code: "required".to_string(),
+            message: "the card has no email to look an avatar up by".to_string(),
+        }]);
+    }
+
+    let mut found = None;
+    for email in &card.emails {
+        match avatars.fetch(&email.address).await {
+            Ok(Some(avatar)) => {
+                found = Some(avatar);
+                break;
+            }
+            Ok(None) => {}
+            Err(e) => {
+                error!("avatar lookup failed: {e:#}");
+                return (
+                    StatusCode::BAD_GATEWAY,
+                    Json(json!({"error": format!("avatar lookup failed: {e}")})),
+                )
+                    .into_response();
+            }
+        }
+    }
+    let Some(avatar) = found else {
+        return not_found("no avatar found for the card's emails").into_response();
+    };
+
+    let filename = format!("avatar.{}", avatar.ext);
+    let photo = match prepare_photo(&state, &filename, &avatar.data).await {
+        Ok(photo) => photo,
+        Err(response) => return response,
+    };
+    let added = keep_stored(&state, || save_photo(&state, &photo), move |conn, path| {
+        store::add_photo(conn, id, PhotoRole::Headshot, path)
+    });
+    match added.await {
+        Ok((_, Some(photo))) => {
+            state.notify(events::PHOTO_UPDATED, id, None);
+            Json(json!({"photo_url": photo.url})).into_response()
+        }
+        Ok((photo_path, None)) => {
+            state.remove_files(vec![photo_path]).await;
+            not_found("card not found").into_response()
+        }
+        Err(e) => store_error(e),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/cards/{id}/fetch-logo",
+    tag = "cards",
+    params(("id" = i64, Path, description = "Card id")),
+    responses(
+        (status = 200, description = "The logo now shown on every card with this company", body = CompanyLogo),
+        (status = 404, description = "No such card, or no icon on the website", body = ErrorBody),
+        (status = 422, description = "The card has no website or no company", body = ValidationErrorBody),
+        (status = 502, description = "The website could not be reached", body = ErrorBody)
+    )
+)]
+pub async fn fetch_logo(State(state): State<Arc<AppState>>, Path(id): Path<i64>) -> Response {
+    let conn = state.conn.clone();
+    let card = match telemetry::spawn_db(move || store::get_card(&conn, id)).await {
+        Ok(Ok(Some(card))) => card,
+        Ok(Ok(None)) => return not_found("card not found").into_response(),
+        Ok(Err(e)) => return internal_error(e).into_response(),
+        Err(e) => return internal_error(e).into_response(),
+    };
+    let company = card.company.trim().to_string();
+    let missing: Vec<FieldError> = [("website", card.website.trim()), ("company", company.as_str())]
+        .into_iter()
+        .filter(|(_, value)| value.is_empty())
+        .map(|(field, _)| FieldError {
+            field: field.to_string(),
+            index: None,
+            code: "required".to_string(),
+            message: format!("the card needs a {field} to fetch a logo for"),
+        })
+        .collect();
+    if !missing.is_empty() {
+        return validation_error(missing);
+    }
+
+    let logo = match state.logos.fetch(&card.website).await {
+        Ok(Some(logo)) => logo,
+        Ok(None) => return not_found("no icon found on the website").into_response(),
+        Err(e) => {
+            return (
+                StatusCode::BAD_GATEWAY,
+                Json(json!({"error": format!("could not fetch the logo: {e:#}")})),
+            )
+                .into_response()
+        }
+    };
+
+    let (name, source) = (company.clone(), logo.source_url.clone());
+    let result = keep_stored(&state, || store_file(&state, "", &logo.data, logo.ext), move |conn, path| {
+        store::set_company_logo(conn, &name, path, &source)
+    })
+    .await;
+    match result {
+        Ok((logo_path, (old_path, card_ids))) => {
+            state.remove_files(old_path.into_iter().collect()).await;
+            for card_id in card_ids {
+                state.notify(events::CARD_UPDATED, card_id, None);
+            }
+            Json(CompanyLogo {
+                company,
+                logo_url: base_path::url(&format!("/{logo_path}")),
+                source_url: logo.source_url,
+            })
+            .into_response()
+        }
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+// ────────────────────────────────────────────────────────────────────────────
+// Attachments
+// ────────────────────────────────────────────────────────────────────────────
+
+/// The `file` field of an attachment upload: its name and bytes, and the
+/// resumable upload they came from if the form names one in its `upload`
+/// field instead. Reading stops once the file is larger than `max_size`.
+async fn read_attachment(
+    state: &AppState,
+    mut multipart: Multipart,
+    max_size: usize,
+) -> Result<(String, Vec<u8>, Option<String>), Response> {
+    // The body limit cuts off the stream of a much larger file.
+    let multipart_error = |e: axum::extract::multipart::MultipartError| match e.status() {
+        StatusCode::PAYLOAD_TOO_LARGE => too_large("attachment", max_size),
+        status => (status, Json(json!({"error": e.body_text()}))).into_response(),
+    };
+    while let Some(mut field) = multipart.next_field().await.map_err(multipart_error)? {
+        if field.name() == Some("upload") && field.file_name().is_none() {
+            let upload = field.text().await.map_err(multipart_error)?;
+            let (filename, data) = open_upload(state, &upload, max_size).await?;
+            return Ok((filename, data, Some(upload)));
+        }
+        let Some(filename) = field.file_name().filter(|_| field.name() == Some("file")).map(String::from) else {
+            continue;
+        };
+        let mut data = Vec::new();
+        while let Some(chunk) = field.chunk().await.map_err(multipart_error)? {
+            data.extend_from_slice(&chunk);
+            if data.len() > max_size {
+                return Err(too_large("attachment", max_size));
+            }
+        }
+        return Ok((filename, data, None));
+    }
+    Err(bad_request("no file field provided").into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/cards/{id}/attachments",
+    tag = "attachments",
+    params(("id" = i64, Path, description = "Card id")),
+    responses(
+        (status = 200, description = "The card's attachments, oldest first", body = [Attachment]),
+        (status = 404, body = ErrorBody)
+    )
+)]
+pub async fn list_attachments(State(state): State<Arc<AppState>>, Path(id): Path<i64>) -> impl IntoResponse {
+    let conn = state.conn.clone();
+    match telemetry::spawn_db(move || store::list_attachments(&conn, id)).await {
+        Ok(Ok(Some(attachments))) => Json(json!(attachments)).into_response(),
+        Ok(Ok(None)) => not_found("card not found").into_response(),
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/cards/{id}/attachments",
+    tag = "attachments",
+    params(("id" = i64, Path, description = "Card id")),
+    request_body(content = AttachmentForm, content_type = "multipart/form-data"),
+    responses(
+        (status = 201, body = Attachment),
+        (status = 400, description = "No `file` field", body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 413, description = "The file is larger than `--attachment-max-mb`", body = ErrorBody)
+    )
+)]
+pub async fn add_attachment(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    multipart: Multipart,
+) -> impl IntoResponse {
+    let (filename, data, upload) = match read_attachment(&state, multipart, state.attachment_max_size).await {
+        Ok(file) => file,
+        Err(response) => return response,
+    };
+    let filename = attachment::clean_filename(&filename);
+    let mime = attachment::detect_mime(&filename, &data);
+    let size = data.len() as i64;
+
+    // Kept apart from photos, so `/uploads/:filename` never serves them.
+    let result = keep_stored(&state, || store_file(&state, "attachments", &data, ""), move |conn, path| {
+        store::add_attachment(conn, id, &filename, &mime, size, path)
+    })
+    .await;
+    match result {
+        Ok((_, Some(attachment))) => {
+            if let Some(upload) = &upload {
+                close_upload(&state, upload).await;
+            }
+            state.notify(events::ATTACHMENT_ADDED, id, None);
+            (StatusCode::CREATED, Json(json!(attachment))).into_response()
+        }
+        Ok((path, None)) => {
+            state.remove_files(vec![path]).await;
+            not_found("card not found").into_response()
+        }
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/cards/{id}/attachments/{attachment_id}",
+    tag = "attachments",
+    params(
+        ("id" = i64, Path, description = "Card id"),
+        ("attachment_id" = i64, Path, description = "Attachment id")
+    ),
+    responses(
+        (status = 200, description = "The file, as a download under its uploaded name"),
+        (status = 206, description = "The part of the file asked for with `Range`"),
+        (status = 404, body = ErrorBody),
+        (status = 416, description = "`Range` starts past the end of the file")
+    )
+)]
+pub async fn download_attachment(
+    State(state): State<Arc<AppState>>,
+    Path((id, attachment_id)): Path<(i64, i64)>,
+    headers: HeaderMap,
+) -> Response {
+    let conn = state.conn.clone();
+    let (attachment, path) =
+        match telemetry::spawn_db(move || store::get_attachment(&conn, id, attachment_id)).await {
+            Ok(Ok(Some(found))) => found,
+            Ok(Ok(None)) => return not_found("attachment not found").into_response(),
+            Ok(Err(e)) => return internal_error(e).into_response(),
+            Err(e) => return internal_error(e).into_response(),
+        };
+    let data = match state.uploads.read(&path).await {
+        Ok(Some(data)) => data,
+        Ok(None) => return not_found("attachment file is missing").into_response(),
+        Err(e) => return internal_error(format!("read attachment: {e:#}")).into_response(),
+    };
+    // Always a download: an uploaded HTML file must not run as this site.
+    let disposition = attachment::content_disposition(&attachment.filename);
+    let mut response = file_response(&headers, &attachment.mime, data);
+    if let Ok(value) = HeaderValue::from_str(&disposition) {
+        response.headers_mut().insert(header::CONTENT_DISPOSITION, value);
+    }
+    response
+        .headers_mut()
+        .insert(header::X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+    response
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/cards/{id}/attachments/{attachment_id}",
+    tag = "attachments",
+    params(
+        ("id" = i64, Path, description = "Card id"),
+        ("attachment_id" = i64, Path, description = "Attachment id")
+    ),
+    responses(
+        (status = 204, description = "Attachment and its file removed"),
+        (status = 404, body = ErrorBody)
+    )
+)]
+pub async fn remove_attachment(
+    State(state): State<Arc<AppState>>,
+    Path((id, attachment_id)): Path<(i64, i64)>,
+) -> impl IntoResponse {
+    let conn = state.conn.clone();
+    let result =
+        telemetry::spawn_db(move || store::delete_attachment(&conn, id, attachment_id)).await;
+
+    match result {
+        Ok(Ok(Some(path))) => {
+            state.remove_files(vec![path]).await;
+            state.notify(events::ATTACHMENT_DELETED, id, None);
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Ok(Ok(None)) => not_found("attachment not found").into_response(),
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+// ────────────────────────────────────────────────────────────────────────────
+// Resumable uploads
+// ────────────────────────────────────────────────────────────────────────────
+
+/// The name and bytes of the finished resumable upload `id`, if they fit
+/// in `max_size`. The upload is kept until `close_upload`, so a request
+/// that fails can be sent again with it.
+async fn open_upload(state: &AppState, id: &str, max_size: usize) -> Result<(String, Vec<u8>), Response> {
+    let conn = state.conn.clone();
+    let key = id.trim().to_string();
+    let session = match telemetry::spawn_db(move || store::get_upload_session(&conn, &key)).await {
+        Ok(Ok(Some(session))) => session,
+        Ok(Ok(None)) => return Err(bad_request("unknown upload").into_response()),
+        Ok(Err(e)) => return Err(internal_error(e).into_response()),
+        Err(e) => return Err(internal_error(e).into_response()),
+    };
+    if session.offset < session.size {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(json!({"error": "upload is not finished", "offset": session.offset})),
+        )
+            .into_response());
+    }
+    if session.size as usize > max_size {
+        return Err(too_large("upload", max_size));
+    }
+    let path = resumable::partial_path(&state.uploads_dir, &session.id);
+    match resumable::read(&path, session.size as u64).await {
+        Ok(data) => Ok((session.filename, data)),
+        Err(e) => Err(internal_error(format!("read upload: {e}")).into_response()),
+    }
+}
+
+/// Forgets a resumable upload whose file has been stored.
+async fn close_upload(state: &AppState, id: &str) {
+    let conn = state.conn.clone();
+    let key = id.trim().to_string();
+    match telemetry::spawn_db(move || store::delete_upload_session(&conn, &key)).await {
+        Ok(Ok(true)) => resumable::remove(&state.uploads_dir, &[id.trim().to_string()]).await,
+        Ok(Ok(false)) => {}
+        Ok(Err(e)) => warn!("close upload {id}: {e}"),
+        Err(e) => warn!("close upload {id}: {e}"),
+    }
+}
+
+/// An upload's state, with its offset also as `Upload-Offset`.
+fn upload_response(status: StatusCode, session: &UploadSession) -> Response {
+    (status, [("upload-offset", session.offset.to_string())], Json(json!(session))).into_response()
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/uploads",
+    tag = "uploads",
+    request_body = UploadStart,
+    responses(
+        (status = 201, description = "The upload, ready for its first chunk", body = UploadSession),
+        (status = 413, description = "Larger than any photo or attachment may be", body = ErrorBody),
+        (status = 422, body = ValidationErrorBody)
+    )
+)]
+pub async fn create_upload(State(state): State<Arc<AppState>>, Json(input): Json<UploadStart>) -> Response {
+    if input.size < 0 {
+        return validation_error(vec![FieldError {
+            field: "size".to_string(),
+            index: None,
+            code: "invalid".to_string(),
+            message: "size must not be negative".to_string(),
+        }]);
+    }
+    let max_size = state.attachment_max_size.max(state.photo_max_size);
+    if input.size as u64 > max_size as u64 {
+        return too_large("upload", max_size);
+    }
+    let filename = attachment::clean_filename(&input.filename);
+    let id = uuid::Uuid::new_v4().simple().to_string();
+    let conn = state.conn.clone();
+    let result = telemetry::spawn_db(move || {
+        store::create_upload_session(&conn, &id, &filename, input.size, resumable::TTL_HOURS)
+    })
+    .await;
+    match result {
+        Ok(Ok((session, expired))) => {
+            resumable::remove(&state.uploads_dir, &expired).await;
+            upload_response(StatusCode::CREATED, &session)
+        }
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/uploads/{upload_id}",
+    tag = "uploads",
+    params(("upload_id" = String, Path, description = "Upload id")),
+    responses(
+        (status = 200, description = "How much has arrived; resume from `offset`", body = UploadSession),
+        (status = 404, body = ErrorBody)
+    )
+)]
+pub async fn get_upload(State(state): State<Arc<AppState>>, Path(upload_id): Path<String>) -> Response {
+    let conn = state.conn.clone();
+    match telemetry::spawn_db(move || store::get_upload_session(&conn, &upload_id)).await {
+        Ok(Ok(Some(session))) => upload_response(StatusCode::OK, &session),
+        Ok(Ok(None)) => not_found("upload not found").into_response(),
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/v1/uploads/{upload_id}",
+    tag = "uploads",
+    params(
+        ("upload_id" = String, Path, description = "Upload id"),
+        ("Upload-Offset" = i64, Header, description = "Where in the file the chunk starts")
+    ),
+    request_body(content = Vec<u8>, content_type = "application/offset+octet-stream"),
+    responses(
+        (status = 200, description = "The chunk was stored", body = UploadSession),
+        (status = 400, description = "No `Upload-Offset`", body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 409, description = "`Upload-Offset` is not where the upload stands; `offset` says where", body = ErrorBody),
+        (status = 413, description = "The chunk runs past the file's size", body = ErrorBody)
+    )
+)]
+pub async fn append_upload(
+    State(state): State<Arc<AppState>>,
+    Path(upload_id): Path<String>,
+    headers: HeaderMap,
+    body: axum::body::Bytes,
+) -> Response {
+    let Some(offset) = headers
+        .get("upload-offset")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<i64>().ok())
+    else {
+        return bad_request("Upload-Offset header is required").into_response();
+    };
+    let conn = state.conn.clone();
+    let id = upload_id.clone();
+    let session = match telemetry::spawn_db(move || store::get_upload_session(&conn, &id)).await {
+        Ok(Ok(Some(session))) => session,
+        Ok(Ok(None)) => return not_found("upload not found").into_response(),
+        Ok(Err(e)) => return internal_error(e).into_response(),
+        Err(e) => return internal_error(e).into_response(),
+    };
+    let conflict = |current: i64| {
+        (
+            StatusCode::CONFLICT,
+            Json(json!({"error": "Upload-Offset does not match the upload", "offset": current})),
+        )
+            .into_response()
+    };
+    if offset != session.offset {
+        return conflict(session.offset);
+    }
+    let end = offset + body.len() as i64;
+    if end > session.size {
+        return (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            Json(json!({"error": "chunk runs past the end of the upload"})),
+        )
+            .into_response();
+    }
+
+    let path = resumable::partial_path(&state.uploads_dir, &session.id);
+    if let Err(e) = resumable::write_chunk(&path, offset as u64, &body).await {
+        return internal_error(format!("write chunk: {e}")).into_response();
+    }
+    let conn = state.conn.clone();
+    let id = session.id.clone();
+    match telemetry::spawn_db(move || store::advance_upload_session(&conn, &id, offset, end)).await {
+        Ok(Ok(Some(session))) => upload_response(StatusCode::OK, &session),
+        Ok(Ok(None)) => {
+            let conn = state.conn.clone();
+            match telemetry::spawn_db(move || store::get_upload_session(&conn, &upload_id)).await {
+                Ok(Ok(Some(session))) => conflict(session.offset),
+                _ => not_found("upload not found").into_response(),
+            }
+        }
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/uploads/{upload_id}",
+    tag = "uploads",
+    params(("upload_id" = String, Path, description = "Upload id")),
+    responses(
+        (status = 204, description = "Upload abandoned and its bytes removed"),
+        (status = 404, body = ErrorBody)
+    )
+)]
+pub async fn cancel_upload(State(state): State<Arc<AppState>>, Path(upload_id): Path<String>) -> Response {
+    let conn = state.conn.clone();
+    let id = upload_id.clone();
+    match telemetry::spawn_db(move || store::delete_upload_session(&conn, &id)).await {
+        Ok(Ok(true)) => {
+            resumable::remove(&state.uploads_dir, &[upload_id]).await;
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Ok(Ok(false)) => not_found("upload not found").into_response(),
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+/// Shared body of the archive and unarchive endpoints.
+async fn set_archived(state: Arc<AppState>, id: i64, archived: bool) -> Response {
+    let conn = state.conn.clone();
+    let result = telemetry::spawn_db(move || {
+        if !store::set_archived(&conn, id, archived)? {
+            return Ok(None);
+        }
+        store::get_card(&conn, id)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(Some(card))) => {
+            state.notify(events::CARD_UPDATED, card.id, Some(card.clone()));
+            card_response(StatusCode::OK, &card)
+        }
+        Ok(Ok(None)) => not_found("card not found").into_response(),
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/cards/{id}/archive",
+    tag = "cards",
+    params(("id" = i64, Path, description = "Card id")),
+    responses(
+        (status = 200, description = "The archived card", body = Card),
+        (status = 404, body = ErrorBody)
+    )
+)]
+pub async fn archive_card(State(state): State<Arc<AppState>>, Path(id): Path<i64>) -> Response {
+    set_archived(state, id, true).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/cards/{id}/unarchive",
+    tag = "cards",
+    params(("id" = i64, Path, description = "Card id")),
+    responses(
+        (status = 200, description = "The restored card", body = Card),
+        (status = 404, body = ErrorBody)
+    )
+)]
+pub async fn unarchive_card(State(state): State<Arc<AppState>>, Path(id): Path<i64>) -> Response {
+    set_archived(state, id, false).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/cards/{id}/favorite",
+    tag = "cards",
+    params(("id" = i64, Path, description = "Card id")),
+    responses(
+        (status = 200, description = "The card with its favorite flag flipped", body = Card),
+        (status = 404, body = ErrorBody)
+    )
+)]
+pub async fn toggle_favorite(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> Response {
+    let conn = state.conn.clone();
+    let result = telemetry::spawn_db(move || {
+        if !store::toggle_favorite(&conn, id)? {
+            return Ok(None);
+        }
+        store::get_card(&conn, id)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(Some(card))) => {
+            state.notify(events::CARD_UPDATED, card.id, Some(card.clone()));
+            card_response(StatusCode::OK, &card)
+        }
+        Ok(Ok(None)) => not_found("card not found").into_response(),
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/cards/{id}/merge",
+    tag = "cards",
+    request_body = CardMerge,
+    params(
+        ("id" = i64, Path, description = "Card that absorbs the source"),
+        ("If-Match" = Option<String>, Header, description = "Expected current version (ETag) of the target")
+    ),
+    responses(
+        (status = 200, description = "The merged card", body = Card),
+        (status = 400, body = ErrorBody),
+        (status = 404, description = "One of the cards does not exist", body = ErrorBody),
+        (status = 409, description = "Target changed since the given version", body = ErrorBody)
+    )
+)]
+pub async fn merge_cards(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    headers: HeaderMap,
+    Json(input): Json<CardMerge>,
+) -> Response {
+    if input.source == id {
+        return bad_request("cannot merge a card into itself").into_response();
+    }
+    let expected = match if_match_version(&headers) {
+        Ok(version) => version,
+        Err(e) => return e.into_response(),
+    };
+    let conn = state.conn.clone();
+    let result = telemetry::spawn_db(move || {
+        store::merge_cards(&conn, id, input.source, input.prefer_source_photo, expected)?;
+        store::get_card(&conn, id)?.ok_or_else(|| anyhow::anyhow!("card not found"))
+    })
+    .await;
+
+    match result {
+        Ok(Ok(card)) => {
+            state.notify(events::CARD_DELETED, input.source, None);
+            state.notify(events::CARD_UPDATED, card.id, Some(card.clone()));
+            card_response(StatusCode::OK, &card)
+        }
+        Ok(Err(e)) => store_error(e),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/cards/{id}/org",
+    tag = "cards",
+    params(("id" = i64, Path, description = "Card id")),
+    responses(
+        (status = 200, description = "The card with its manager and direct reports", body = OrgChart),
+        (status = 404, body = ErrorBody)
+    )
+)]
+pub async fn org_chart(State(state): State<Arc<AppState>>, Path(id): Path<i64>) -> Response {
+    let conn = state.conn.clone();
+    let result = telemetry::spawn_db(move || store::org_chart(&conn, id)).await;
+
+    match result {
+        Ok(Ok(Some(chart))) => (StatusCode::OK, Json(json!(chart))).into_response(),
+        Ok(Ok(None)) => not_found("card not found").into_response(),
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/cards/{id}/history",
+    tag = "cards",
+    params(("id" = i64, Path, description = "Card id")),
+    responses(
+        (status = 200, description = "Revisions, newest first, with what each changed", body = [CardRevision]),
+        (status = 404, body = ErrorBody)
+    )
+)]
+pub async fn card_history(State(state): State<Arc<AppState>>, Path(id): Path<i64>) -> Response {
+    let conn = state.conn.clone();
+    let result = telemetry::spawn_db(move || history::card_history(&conn, id)).await;
+
+    match result {
+        Ok(Ok(revisions)) if revisions.is_empty() => not_found("card not found").into_response(),
+        Ok(Ok(revisions)) => (StatusCode::OK, Json(json!(revisions))).into_response(),
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/cards/{id}/revert/{rev}",
+    tag = "cards",
+    params(
+        ("id" = i64, Path, description = "Card id"),
+        ("rev" = i64, Path, description = "Version to restore"),
+        ("If-Match" = Option<String>, Header, description = "Expected current version (ETag)")
+    ),
+    responses(
+        (status = 200, description = "The card with the old contents saved as a new version", body = Card),
+        (status = 404, body = ErrorBody),
+        (status = 409, description = "Card changed since the given version", body = ErrorBody)
+    )
+)]
+pub async fn revert_card(
+    State(state): State<Arc<AppState>>,
+    Path((id, rev)): Path<(i64, i64)>,
+    headers: HeaderMap,
+) -> Response {
+    let expected = match if_match_version(&headers) {
+        Ok(version) => version,
+        Err(e) => return e.into_response(),
+    };
+    let conn = state.conn.clone();
+    let result = telemetry::spawn_db(move || {
+        if store::get_card(&conn, id)?.is_none() {
+            return Ok(Err("card not found"));
+        }
+        let Some(snapshot) = store::card_revision(&conn, id, rev)? else {
+            return Ok(Err("revision not found"));
+        };
+        let input = CardInput {
+            version: expected,
+            ..snapshot.into()
+        };
+        store::update_card(&conn, id, &input)?;
+        Ok(store::get_card(&conn, id)?.ok_or("card not found"))
+    })
+    .await;
+
+    match result {
+        Ok(Ok(Ok(card))) => {
+            state.notify(events::CARD_UPDATED, card.id, Some(card.clone()));
+            card_response(StatusCode::OK, &card)
+        }
+        Ok(Ok(Err(msg))) => not_found(msg).into_response(),
+        Ok(Err(e)) => store_error(e),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+/// How long after an operation `POST /api/v1/undo` can still reverse it.
+const UNDO_WINDOW_SECS: i64 = 300;
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/undo",
+    tag = "cards",
+    responses(
+        (status = 200, description = "The most recent change was reversed", body = UndoResult),
+        (status = 404, description = "Nothing recent to undo", body = ErrorBody),
+        (status = 409, description = "The most recent change deleted a card, by a delete or merge", body = ErrorBody)
+    )
+)]
+pub async fn undo(State(state): State<Arc<AppState>>) -> Response {
+    let conn = state.conn.clone();
+    let result = telemetry::spawn_db(move || {
+        let Some(outcome) = store::undo_last(&conn, UNDO_WINDOW_SECS)? else {
+            return Ok(None);
+        };
+        let cards = store::get_cards_by_ids(&conn, &outcome.updated, CardRelations::ALL)?;
+        anyhow::Ok(Some((outcome, cards)))
+    })
+    .await;
+
+    match result {
+        Ok(Ok(Some((outcome, cards)))) => {
+            for card in &cards {
+                state.notify(events::CARD_UPDATED, card.id, Some(card.clone()));
+            }
+            let mut deleted = Vec::new();
+            for (id, photo_paths) in outcome.deleted {
+                state.remove_files(photo_paths).await;
+                state.notify(events::CARD_DELETED, id, None);
+                deleted.push(id);
+            }
+            (StatusCode::OK, Json(json!(UndoResult { cards, deleted }))).into_response()
+        }
+        Ok(Ok(None)) => not_found("nothing to undo").into_response(),
+        Ok(Err(e)) => store_error(e),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/tags",
+    tag = "tags",
+    responses((status = 200, body = [TagCount]))
+)]
+pub async fn list_tags(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let conn = state.conn.clone();
+    let result =
+        telemetry::spawn_db(move || store::list_tags(&conn)).await;
+
+    match result {
+        Ok(Ok(tags)) => (StatusCode::OK, Json(json!(tags))).into_response(),
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+/// Announces cards whose tags changed through the tag endpoints. When no
+/// card changed (an unused tag, or only its color), the tag list change is
+/// announced on its own.
+async fn notify_retagged(state: &Arc<AppState>, card_ids: Vec<i64>) {
+    if card_ids.is_empty() {
+        state.events.publish(events::TAGS_CHANGED, None);
+        return;
+    }
+    let conn = state.conn.clone();
+    let cards = telemetry::spawn_db(move || {
+        store::get_cards_by_ids(&conn, &card_ids, CardRelations::ALL)
+    })
+    .await;
+    match cards {
+        Ok(Ok(cards)) => {
+            for card in cards {
+                state.notify(events::CARD_UPDATED, card.id, Some(card));
+            }
+        }
+        Ok(Err(e)) => error!("failed to load retagged cards: {e}"),
+        Err(e) => error!("failed to load retagged cards: {e}"),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/tags/{name}",
+    tag = "tags",
+    params(("name" = String, Path, description = "Current tag name")),
+    request_body = TagUpdate,
+    responses(
+        (status = 200, description = "Updated tag", body = TagCount),
+        (status = 400, body = ErrorBody),
+        (status = 422, description = "Missing or malformed fields", body = ValidationErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 409, description = "Another tag already has the new name", body = ErrorBody)
+    )
+)]
+pub async fn update_tag(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    Json(input): Json<TagUpdate>,
+) -> Response {
+    let mut update = input;
+    if let Err(errors) = validation::tag_update(&mut update) {
+        return validation_error(errors);
+    }
+
+    let conn = state.conn.clone();
+    let result =
+        telemetry::spawn_db(move || store::update_tag(&conn, &name, &update)).await;
+
+    match result {
+        Ok(Ok(Some((tag, card_ids)))) => {
+            notify_retagged(&state, card_ids).await;
+            (StatusCode::OK, Json(json!(tag))).into_response()
+        }
+        Ok(Ok(None)) => not_found("tag not found").into_response(),
+        Ok(Err(e)) => store_error(e),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/tags/{name}",
+    tag = "tags",
+    params(("name" = String, Path, description = "Tag name")),
+    responses(
+        (status = 204, description = "Tag removed from every card"),
+        (status = 404, body = ErrorBody)
+    )
+)]
+pub async fn delete_tag(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> impl IntoResponse {
+    let conn = state.conn.clone();
+    let result = telemetry::spawn_db(move || store::delete_tag(&conn, &name)).await;
+
+    match result {
+        Ok(Ok(Some(card_ids))) => {
+            notify_retagged(&state, card_ids).await;
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Ok(Ok(None)) => not_found("tag not found").into_response(),
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/tags/{name}/aliases",
+    tag = "tags",
+    params(("name" = String, Path, description = "Tag name")),
+    request_body = TagAliasInput,
+    responses(
+        (status = 200, description = "The tag with its aliases", body = TagCount),
+        (status = 400, body = ErrorBody),
+        (status = 422, description = "Missing or malformed fields", body = ValidationErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 409, description = "The alias is already another tag or alias", body = ErrorBody)
+    )
+)]
+pub async fn add_tag_alias(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    Json(input): Json<TagAliasInput>,
+) -> Response {
+    if let Err(errors) = validation::tag_alias(&input.alias) {
+        return validation_error(errors);
+    }
+
+    let conn = state.conn.clone();
+    let result =
+        telemetry::spawn_db(move || store::add_tag_alias(&conn, &name, &input.alias))
+            .await;
+
+    match result {
+        Ok(Ok(Some(tag))) => {
+            state.events.publish(events::TAGS_CHANGED, None);
+            (StatusCode::OK, Json(json!(tag))).into_response()
+        }
+        Ok(Ok(None)) => not_found("tag not found").into_response(),
+        Ok(Err(e)) => store_error(e),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/tags/{name}/aliases/{alias}",
+    tag = "tags",
+    params(
+        ("name" = String, Path, description = "Tag name"),
+        ("alias" = String, Path, description = "Alias to remove")
+    ),
+    responses(
+        (status = 204, description = "Alias removed"),
+        (status = 404, body = ErrorBody)
+    )
+)]
+pub async fn delete_tag_alias(
+    State(state): State<Arc<AppState>>,
+    Path((name, alias)): Path<(String, String)>,
+) -> impl IntoResponse {
+    let conn = state.conn.clone();
+    let result =
+        telemetry::spawn_db(move || store::remove_tag_alias(&conn, &name, &alias)).await;
+
+    match result {
+        Ok(Ok(true)) => {
+            state.events.publish(events::TAGS_CHANGED, None);
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Ok(Ok(false)) => not_found("alias not found").into_response(),
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/tags/merge",
+    tag = "tags",
+    request_body = TagMerge,
+    responses(
+        (status = 200, description = "The merged tag", body = TagCount),
+        (status = 400, body = ErrorBody),
+        (status = 422, description = "Missing or malformed fields", body = ValidationErrorBody),
+        (status = 404, description = "One of `from` does not exist", body = ErrorBody)
+    )
+)]
+pub async fn merge_tags(
+    State(state): State<Arc<AppState>>,
+    Json(input): Json<TagMerge>,
+) -> impl IntoResponse {
+    if let Err(errors) = validation::tag_merge(&input) {
+        return validation_error(errors);
+    }
+    let into = input.into.trim().to_string();
+
+    let conn = state.conn.clone();
+    let result =
+        telemetry::spawn_db(move || store::merge_tags(&conn, &input.from, &into)).await;
+
+    match result {
+        Ok(Ok(Some((tag, card_ids)))) => {
+            notify_retagged(&state, card_ids).await;
+            (StatusCode::OK, Json(json!(tag))).into_response()
+        }
+        Ok(Ok(None)) => not_found("tag not found").into_response(),
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/cards/bulk/tags",
+    tag = "tags",
+    request_body = BulkTagInput,
+    responses(
+        (status = 200, body = BulkTagResult),
+        (status = 400, body = ErrorBody),
+        (status = 422, description = "Missing or malformed fields", body = ValidationErrorBody),
+        (status = 404, description = "Some ids are unknown; nothing was changed", body = ErrorBody)
+    )
+)]
+pub async fn bulk_tag_cards(
+    State(state): State<Arc<AppState>>,
+    Json(input): Json<BulkTagInput>,
+) -> Response {
+    if let Err(errors) = validation::bulk_tags(&input) {
+        return validation_error(errors);
+    }
+
+    let conn = state.conn.clone();
+    let result = telemetry::spawn_db(move || {
+        store::bulk_tag_cards(&conn, &input.ids, &input.add, &input.remove)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(updated)) => {
+            notify_retagged(&state, updated.clone()).await;
+            (StatusCode::OK, Json(json!(BulkTagResult { updated }))).into_response()
+        }
+        Ok(Err(e)) => store_error(e),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/stats",
+    tag = "cards",
+    responses((status = 200, body = VaultStats))
+)]
+pub async fn stats(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let conn = state.conn.clone();
+    let result = telemetry::spawn_db(move || store::stats(&conn)).await;
+
+    match result {
+        Ok(Ok(stats)) => (StatusCode::OK, Json(json!(stats))).into_response(),
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct DuplicatesParams {
+    /// Leave out matches less certain than this, from 0 to 1 (default 0.5)
+    pub min_confidence: Option<f64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/duplicates",
+    tag = "cards",
+    params(DuplicatesParams),
+    responses(
+        (status = 200, description = "Likely duplicates, most certain first", body = [DuplicateGroup]),
+        (status = 400, body = ErrorBody)
+    )
+)]
+pub async fn duplicates(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<DuplicatesParams>,
+) -> Response {
+    let min_confidence = params.min_confidence.unwrap_or(0.5);
+    if !(0.0..=1.0).contains(&min_confidence) {
+        return bad_request("min_confidence must be between 0 and 1").into_response();
+    }
+    let conn = state.conn.clone();
+    let result =
+        telemetry::spawn_db(move || duplicates::find_duplicates(&conn, min_confidence))
+            .await;
+
+    match result {
+        Ok(Ok(groups)) => (StatusCode::OK, Json(json!(groups))).into_response(),
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+/// Longest window `GET /cards/upcoming` accepts.
+const MAX_UPCOMING_DAYS: i64 = 366;
+
+#[derive(Deserialize, IntoParams)]
+pub struct UpcomingParams {
+    /// How many days ahead to look, today included (default 30, at most 366)
+    pub days: Option<i64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/cards/upcoming",
+    tag = "cards",
+    params(UpcomingParams),
+    responses(
+        (status = 200, description = "Birthdays and anniversaries in the window, soonest first", body = [UpcomingDate]),
+        (status = 400, body = ErrorBody)
+    )
+)]
+pub async fn upcoming(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<UpcomingParams>,
+) -> Response {
+    let days = params.days.unwrap_or(30);
+    if !(1..=MAX_UPCOMING_DAYS).contains(&days) {
+        return bad_request(&format!("days must be between 1 and {MAX_UPCOMING_DAYS}"))
+            .into_response();
+    }
+    let conn = state.conn.clone();
+    let result = telemetry::spawn_db(move || dates::upcoming(&conn, days)).await;
+
+    match result {
+        Ok(Ok(dates)) => (StatusCode::OK, Json(json!(dates))).into_response(),
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+/// Widest radius `GET /cards/nearby` accepts, about half the Earth's
+/// circumference.
+const MAX_RADIUS_KM: f64 = 20_000.0;
+
+#[derive(Deserialize, IntoParams)]
+pub struct NearbyParams {
+    /// Latitude of the point, -90 to 90
+    pub lat: f64,
+    /// Longitude of the point, -180 to 180
+    pub lng: f64,
+    /// How far to look (default 25, at most 20000)
+    pub radius_km: Option<f64>,
+    /// Maximum cards (default 50, at most 500)
+    pub limit: Option<i64>,
+}
+
+/// Only addresses with coordinates count, so geocoding (`--geocoder`) must
+/// be on.
+#[utoipa::path(
+    get,
+    path = "/api/v1/cards/nearby",
+    tag = "cards",
+    params(NearbyParams),
+    responses(
+        (status = 200, description = "Unarchived cards within the radius, nearest first", body = [NearbyCard]),
+        (status = 400, body = ErrorBody)
+    )
+)]
+pub async fn nearby(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<NearbyParams>,
+) -> Response {
+    let radius_km = params.radius_km.unwrap_or(25.0);
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    if !(-90.0..=90.0).contains(&params.lat) || !(-180.0..=180.0).contains(&params.lng) {
+        return bad_request("lat must be between -90 and 90, and lng between -180 and 180").into_response();
+    }
+    if !(radius_km > 0.0 && radius_km <= MAX_RADIUS_KM) {
+        return bad_request(&format!("radius_km must be above 0 and at most {MAX_RADIUS_KM}")).into_response();
+    }
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+        return bad_request(&format!("limit must be between 1 and {MAX_PAGE_SIZE}")).into_response();
+    }
+    let conn = state.conn.clone();
+    let result = telemetry::spawn_db(move || {
+        store::nearby_cards(&conn, params.lat, params.lng, radius_km, limit)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(cards)) => (StatusCode::OK, Json(json!(cards))).into_response(),
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct AutocompleteParams {
+    /// `company`, `title`, `tag`, `city`, or `country`
+    pub field: SuggestField,
+    /// What the user has typed so far (case-insensitive)
+    #[serde(default)]
+    pub prefix: String,
+    /// Maximum suggestions (default 10, at most 50)
+    pub limit: Option<i64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/autocomplete",
+    tag = "cards",
+    params(AutocompleteParams),
+    responses(
+        (status = 200, description = "Existing values, most used first", body = [Suggestion]),
+        (status = 400, body = ErrorBody)
+    )
+)]
+pub async fn autocomplete(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<AutocompleteParams>,
+) -> impl IntoResponse {
+    let limit = params.limit.unwrap_or(10);
+    if !(1..=50).contains(&limit) {
+        return bad_request("limit must be between 1 and 50").into_response();
+    }
+    let conn = state.conn.clone();
+    let result = telemetry::spawn_db(move || {
+        store::suggest(&conn, params.field, &params.prefix, limit)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(suggestions)) => (StatusCode::OK, Json(json!(suggestions))).into_response(),
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+// ────────────────────────────────────────────────────────────────────────────
+// Custom field schema
+// ────────────────────────────────────────────────────────────────────────────
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/schema",
+    tag = "schema",
+    responses((status = 200, body = FieldSchema))
+)]
+pub async fn get_schema(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    match field_schema(&state).await {
+        Ok(fields) => (StatusCode::OK, Json(json!(FieldSchema { fields }))).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/schema/fields/{key}",
+    tag = "schema",
+    params(("key" = String, Path, description = "Field key, as used in a card's `custom`")),
+    request_body = FieldDefinitionInput,
+    responses(
+        (status = 201, description = "Field defined", body = FieldDefinition),
+        (status = 200, description = "Definition replaced", body = FieldDefinition),
+        (status = 400, body = ErrorBody),
+        (status = 422, description = "Missing or malformed fields", body = ValidationErrorBody)
+    )
+)]
+pub async fn put_schema_field(
+    State(state): State<Arc<AppState>>,
+    Path(key): Path<String>,
+    Json(mut input): Json<FieldDefinitionInput>,
+) -> impl IntoResponse {
+    if let Err(errors) = validation::field_definition(&key, &mut input) {
+        return validation_error(errors);
+    }
+
+    let conn = state.conn.clone();
+    let result =
+        telemetry::spawn_db(move || store::put_field_definition(&conn, &key, &input)).await;
+
+    match result {
+        Ok(Ok((field, true))) => (StatusCode::CREATED, Json(json!(field))).into_response(),
+        Ok(Ok((field, false))) => (StatusCode::OK, Json(json!(field))).into_response(),
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/schema/fields/{key}",
+    tag = "schema",
+    params(("key" = String, Path, description = "Field key")),
+    responses(
+        (status = 204, description = "Field removed, along with its value on every card"),
+        (status = 404, body = ErrorBody)
+    )
+)]
+pub async fn delete_schema_field(
+    State(state): State<Arc<AppState>>,
+    Path(key): Path<String>,
+) -> impl IntoResponse {
+    let conn = state.conn.clone();
+    let result =
+        telemetry::spawn_db(move || store::delete_field_definition(&conn, &key)).await;
+
+    match result {
+        Ok(Ok(Some(card_ids))) => {
+            if !card_ids.is_empty() {
+                notify_retagged(&state, card_ids).await;
+            }
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Ok(Ok(None)) => not_found("field not found").into_response(),
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+// ────────────────────────────────────────────────────────────────────────────
+// Interactions
+// ────────────────────────────────────────────────────────────────────────────
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/cards/{id}/interactions",
+    tag = "interactions",
+    params(("id" = i64, Path, description = "Card id")),
+    responses(
+        (status = 200, description = "The card's interactions, most recent first", body = [Interaction]),
+        (status = 404, body = ErrorBody)
+    )
+)]
+pub async fn list_interactions(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    let conn = state.conn.clone();
+    let result = telemetry::spawn_db(move || store::list_interactions(&conn, id)).await;
+
+    match result {
+        Ok(Ok(Some(interactions))) => (StatusCode::OK, Json(json!(interactions))).into_response(),
+        Ok(Ok(None)) => not_found("card not found").into_response(),
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/cards/{id}/interactions",
+    tag = "interactions",
+    params(("id" = i64, Path, description = "Card id")),
+    request_body = InteractionInput,
+    responses(
+        (status = 201, body = Interaction),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 422, description = "Missing or malformed fields", body = ValidationErrorBody)
+    )
+)]
+pub async fn create_interaction(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    Json(mut input): Json<InteractionInput>,
+) -> impl IntoResponse {
+    if let Err(errors) = validation::interaction(&mut input) {
+        return validation_error(errors);
+    }
+
+    let conn = state.conn.clone();
+    let result =
+        telemetry::spawn_db(move || store::add_interaction(&conn, id, &input)).await;
+
+    match result {
+        Ok(Ok(Some(interaction))) => (StatusCode::CREATED, Json(json!(interaction))).into_response(),
+        Ok(Ok(None)) => not_found("card not found").into_response(),
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/cards/{id}/interactions/{interaction_id}",
+    tag = "interactions",
+    params(
+        ("id" = i64, Path, description = "Card id"),
+        ("interaction_id" = i64, Path, description = "Interaction id")
+    ),
+    request_body = InteractionInput,
+    responses(
+        (status = 200, body = Interaction),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 422, description = "Missing or malformed fields", body = ValidationErrorBody)
+    )
+)]
+pub async fn update_interaction(
+    State(state): State<Arc<AppState>>,
+    Path((id, interaction_id)): Path<(i64, i64)>,
+    Json(mut input): Json<InteractionInput>,
+) -> impl IntoResponse {
+    if let Err(errors) = validation::interaction(&mut input) {
+        return validation_error(errors);
+    }
+
+    let conn = state.conn.clone();
+    let result = telemetry::spawn_db(move || {
+        store::update_interaction(&conn, id, interaction_id, &input)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(Some(interaction))) => (StatusCode::OK, Json(json!(interaction))).into_response(),
+        Ok(Ok(None)) => not_found("interaction not found").into_response(),
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/cards/{id}/interactions/{interaction_id}",
+    tag = "interactions",
+    params(
+        ("id" = i64, Path, description = "Card id"),
+        ("interaction_id" = i64, Path, description = "Interaction id")
+    ),
+    responses(
+        (status = 204, description = "Interaction deleted"),
+        (status = 404, body = ErrorBody)
+    )
+)]
+pub async fn delete_interaction(
+    State(state): State<Arc<AppState>>,
+    Path((id, interaction_id)): Path<(i64, i64)>,
+) -> impl IntoResponse {
+    let conn = state.conn.clone();
+    let result = telemetry::spawn_db(move || {
+        store::delete_interaction(&conn, id, interaction_id)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(true)) => StatusCode::NO_CONTENT.into_response(),
+        Ok(Ok(false)) => not_found("interaction not found").into_response(),
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+// ────────────────────────────────────────────────────────────────────────────
+// Reminders
+// ────────────────────────────────────────────────────────────────────────────
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/cards/{id}/reminders",
+    tag = "reminders",
+    params(("id" = i64, Path, description = "Card id")),
+    responses(
+        (status = 200, description = "The card's reminders, open ones first, by due date", body = [Reminder]),
+        (status = 404, body = ErrorBody)
+    )
+)]
+pub async fn list_reminders(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    let conn = state.conn.clone();
+    let result = telemetry::spawn_db(move || store::list_reminders(&conn, id)).await;
+
+    match result {
+        Ok(Ok(Some(reminders))) => (StatusCode::OK, Json(json!(reminders))).into_response(),
+        Ok(Ok(None)) => not_found("card not found").into_response(),
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/cards/{id}/reminders",
+    tag = "reminders",
+    params(("id" = i64, Path, description = "Card id")),
+    request_body = ReminderInput,
+    responses(
+        (status = 201, body = Reminder),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 422, description = "Missing or malformed fields", body = ValidationErrorBody)
+    )
+)]
+pub async fn create_reminder(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    Json(mut input): Json<ReminderInput>,
+) -> impl IntoResponse {
+    if let Err(errors) = validation::reminder(&mut input) {
+        return validation_error(errors);
+    }
+
+    let conn = state.conn.clone();
+    let result = telemetry::spawn_db(move || store::add_reminder(&conn, id, &input)).await;
+
+    match result {
+        Ok(Ok(Some(reminder))) => (StatusCode::CREATED, Json(json!(reminder))).into_response(),
+        Ok(Ok(None)) => not_found("card not found").into_response(),
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/cards/{id}/reminders/{reminder_id}",
+    tag = "reminders",
+    params(
+        ("id" = i64, Path, description = "Card id"),
+        ("reminder_id" = i64, Path, description = "Reminder id")
+    ),
+    request_body = ReminderInput,
+    responses(
+        (status = 200, body = Reminder),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 422, description = "Missing or malformed fields", body = ValidationErrorBody)
+    )
+)]
+pub async fn update_reminder(
+    State(state): State<Arc<AppState>>,
+    Path((id, reminder_id)): Path<(i64, i64)>,
+    Json(mut input): Json<ReminderInput>,
+) -> impl IntoResponse {
+    if let Err(errors) = validation::reminder(&mut input) {
+        return validation_error(errors);
+    }
+
+    let conn = state.conn.clone();
+    let result = telemetry::spawn_db(move || {
+        store::update_reminder(&conn, id, reminder_id, &input)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(Some(reminder))) => (StatusCode::OK, Json(json!(reminder))).into_response(),
+        Ok(Ok(None)) => not_found("reminder not found").into_response(),
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/cards/{id}/reminders/{reminder_id}",
+    tag = "reminders",
+    params(
+        ("id" = i64, Path, description = "Card id"),
+        ("reminder_id" = i64, Path, description = "Reminder id")
+    ),
+    responses(
+        (status = 204, description = "Reminder deleted"),
+        (status = 404, body = ErrorBody)
+    )
+)]
+pub async fn delete_reminder(
+    State(state): State<Arc<AppState>>,
+    Path((id, reminder_id)): Path<(i64, i64)>,
+) -> impl IntoResponse {
+    let conn = state.conn.clone();
+    let result =
+        telemetry::spawn_db(move || store::delete_reminder(&conn, id, reminder_id)).await;
+
+    match result {
+        Ok(Ok(true)) => StatusCode::NO_CONTENT.into_response(),
+        Ok(Ok(false)) => not_found("reminder not found").into_response(),
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct DueParams {
+    /// `overdue`, `today`, or `week`; every open reminder if omitted
+    #[param(inline)]
+    pub due: Option<ReminderDue>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/reminders",
+    tag = "reminders",
+    params(DueParams),
+    responses(
+        (status = 200, description = "Open reminders of active cards, soonest first", body = [DueReminder]),
+        (status = 400, body = ErrorBody)
+    )
+)]
+pub async fn due_reminders(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<DueParams>,
+) -> Response {
+    let (from, before) = params.due.map(dates::due_range).unwrap_or_default();
+    let conn = state.conn.clone();
+    let result = telemetry::spawn_db(move || {
+        store::due_reminders(&conn, from.as_deref(), before.as_deref())
+    })
+    .await;
+
+    match result {
+        Ok(Ok(reminders)) => (StatusCode::OK, Json(json!(reminders))).into_response(),
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/reminders.ics",
+    tag = "reminders",
+    responses((status = 200, description = "iCalendar feed of open reminders and yearly birthdays, for calendar subscriptions", content_type = "text/calendar"))
+)]
+pub async fn reminders_calendar(State(state): State<Arc<AppState>>) -> Response {
+    let conn = state.conn.clone();
+    let result = telemetry::spawn_db(move || ical::feed(&conn)).await;
+
+    match result {
+        Ok(Ok(calendar)) => Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "text/calendar; charset=utf-8")
+            .header(header::CONTENT_DISPOSITION, "inline; filename=\"cardvault.ics\"")
+            .body(Body::from(calendar))
+            .unwrap(),
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+// ────────────────────────────────────────────────────────────────────────────
+// Share links
+// ────────────────────────────────────────────────────────────────────────────
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn format_unix(secs: u64) -> String {
+    chrono::DateTime::from_timestamp(secs as i64, 0)
+        .unwrap_or_default()
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/cards/{id}/share",
+    tag = "sharing",
+    params(("id" = i64, Path, description = "Card id")),
+    request_body(content = Option<ShareInput>, description = "Optional; the link lasts 7 days by default"),
+    responses(
+        (status = 201, description = "A signed link anyone can open until it expires", body = ShareLink),
+        (status = 404, body = ErrorBody),
+        (status = 422, description = "Lifetime out of range", body = ValidationErrorBody)
+    )
+)]
+pub async fn create_share(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    input: Option<Json<ShareInput>>,
+) -> Response {
+    let input = input.map(|Json(input)| input).unwrap_or_default();
+    if let Err(errors) = validation::share_input(&input) {
+        return validation_error(errors);
+    }
+    let days = input.expires_in_days.unwrap_or(share::DEFAULT_DAYS);
+    let expires = unix_now() + u64::from(days) * 86_400;
+
+    let conn = state.conn.clone();
+    let result = telemetry::spawn_db(move || -> anyhow::Result<Option<String>> {
+        if store::get_card_with(&conn, id, CardRelations::default())?.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(share::mint(&store::mint_share_secret(&conn)?, id, expires)))
+    })
+    .await;
+
+    match result {
+        Ok(Ok(Some(token))) => {
+            let link = ShareLink {
+                url: base_path::url(&format!("/share/{token}")),
+                token,
+                expires_at: format_unix(expires),
+            };
+            (StatusCode::CREATED, Json(json!(link))).into_response()
+        }
+        Ok(Ok(None)) => not_found("card not found").into_response(),
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+/// The card a share token grants, stripped to its public view, with the
+/// link's expiry. Archived and deleted cards are no longer shared.
+async fn shared_card(state: &AppState, token: String) -> Result<(Card, u64), Response> {
+    let conn = state.conn.clone();
+    let result = telemetry::spawn_db(move || -> anyhow::Result<_> {
+        // No key yet means no link was ever made.
+        let Some(secret) = store::share_secret(&conn)? else {
+            return Ok(Err(share::TokenError::Invalid));
+        };
+        Ok(match share::verify(&secret, &token, unix_now()) {
+            Ok((id, expires)) => Ok((store::get_card(&conn, id)?, expires)),
+            Err(e) => Err(e),
+        })
+    })
+    .await;
+
+    match result {
+        Ok(Ok(Ok((Some(card), expires)))) if card.archived_at.is_none() => {
+            Ok((share::public_view(card), expires))
+        }
+        Ok(Ok(Err(share::TokenError::Expired))) => {
+            Err((StatusCode::GONE, "this link has expired").into_response())
+        }
+        Ok(Ok(_)) => Err((StatusCode::NOT_FOUND, "link not found").into_response()),
+        Ok(Err(e)) => Err(internal_error(e).into_response()),
+        Err(e) => Err(internal_error(e).into_response()),
+    }
+}
+
+/// Public page of a shared card.
+pub async fn share_page(
+    State(state): State<Arc<AppState>>,
+    Path(token): Path<String>,
+) -> Response {
+    let (card, expires) = match shared_card(&state, token.clone()).await {
+        Ok(shared) => shared,
+        Err(response) => return response,
+    };
+    match share::page(&card, &token, &format_unix(expires)) {
+        Ok(html) => Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+            .header(header::CACHE_CONTROL, "private, no-store")
+            .body(Body::from(html))
+            .unwrap(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+/// vCard download of a shared card, with its photo embedded.
+pub async fn share_vcard(
+    State(state): State<Arc<AppState>>,
+    Path(token): Path<String>,
+) -> Response {
+    let (card, _) = match shared_card(&state, token).await {
+        Ok(shared) => shared,
+        Err(response) => return response,
+    };
+    let photo = read_photo(&state, &card.photo_url).await;
+    let photo = photo.as_ref().map(|(mime, data)| vcard::Photo { mime, data });
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/vcard; charset=utf-8")
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}\"", vcard::filename(&card)),
+        )
+        .header(header::CACHE_CONTROL, "private, no-store")
+        .body(Body::from(vcard::render(&card, photo)))
+        .unwrap()
+}
+
+// ────────────────────────────────────────────────────────────────────────────
+// Sending cards by email
+// ────────────────────────────────────────────────────────────────────────────
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/cards/{id}/send",
+    tag = "sharing",
+    params(("id" = i64, Path, description = "Card id")),
+    request_body = SendCardInput,
+    responses(
+        (status = 204, description = "Email accepted by the SMTP server"),
+        (status = 404, body = ErrorBody),
+        (status = 422, description = "Invalid recipient or note", body = ValidationErrorBody),
+        (status = 429, description = "Hourly send limit reached; see `Retry-After`", body = ErrorBody),
+        (status = 502, description = "The SMTP server refused the email", body = ErrorBody),
+        (status = 503, description = "No SMTP server configured", body = ErrorBody)
+    )
+)]
+pub async fn send_card(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    Json(mut input): Json<SendCardInput>,
+) -> Response {
+    let Some(smtp) = &state.smtp else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({"error": "email is not configured on this server"})),
+        )
+            .into_response();
+    };
+    if let Err(errors) = validation::send_card_input(&mut input) {
+        return validation_error(errors);
+    }
+
+    let conn = state.conn.clone();
+    let card = match telemetry::spawn_db(move || store::get_card(&conn, id)).await {
+        Ok(Ok(Some(card))) => share::public_view(card),
+        Ok(Ok(None)) => return not_found("card not found").into_response(),
+        Ok(Err(e)) => return internal_error(e).into_response(),
+        Err(e) => return internal_error(e).into_response(),
+    };
+
+    if let Err(retry_after) = state.send_limit.check() {
+        let mut response = (
+            StatusCode::TOO_MANY_REQUESTS,
+            Json(json!({"error": "too many cards sent; try again later"})),
+        )
+            .into_response();
+        if let Ok(value) = HeaderValue::from_str(&retry_after.as_secs().max(1).to_string()) {
+            response.headers_mut().insert(header::RETRY_AFTER, value);
+        }
+        return response;
+    }
+
+    let photo = if input.include_photo {
+        read_photo(&state, &card.photo_url).await
+    } else {
+        None
+    };
+    let vcf = vcard::render(&card, photo.as_ref().map(|(mime, data)| vcard::Photo { mime, data }));
+    let mut attachments = vec![mail::Attachment {
+        filename: vcard::filename(&card),
+        mime: "text/vcard; charset=utf-8".to_string(),
+        data: vcf.into_bytes(),
+    }];
+    if let Some((mime, data)) = photo {
+        let ext = card.photo_url.rsplit('.').next().unwrap_or("jpg");
+        let stem = vcard::filename(&card);
+        attachments.push(mail::Attachment {
+            filename: format!("{}.{ext}", stem.trim_end_matches(".vcf")),
+            mime,
+            data,
+        });
+    }
+    let mut text = String::new();
+    if !input.message.is_empty() {
+        text.push_str(&input.message);
+        text.push_str("\n\n");
+    }
+    text.push_str(&format!(
+        "The contact card of {} is attached. Open it to add them to your address book.\n",
+        card.name
+    ));
+    let message = mail::Message {
+        to: vec![input.to.clone()],
+        subject: format!("Contact card: {}", card.name),
+        text,
+        html: None,
+        attachments,
+    };
+
+    match mail::send(smtp, &message).await {
+        Ok(()) => {
+            tracing::info!("card {id} sent to {}", input.to);
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(e) => {
+            error!("sending card {id} failed: {e:#}");
+            (
+                StatusCode::BAD_GATEWAY,
+                Json(json!({"error": format!("the mail server refused the email: {e}")})),
+            )
+                .into_response()
+        }
+    }
+}
+
+// ────────────────────────────────────────────────────────────────────────────
+// QR codes
+// ────────────────────────────────────────────────────────────────────────────
+
+#[derive(Deserialize, IntoParams)]
+pub struct QrParams {
+    /// `vcard` (default) or `mecard`
+    #[param(inline)]
+    pub format: Option<QrFormat>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/cards/{id}/qr",
+    tag = "sharing",
+    params(("id" = i64, Path, description = "Card id"), QrParams),
+    responses(
+        (status = 200, description = "QR code of the card, as SVG", content_type = "image/svg+xml", body = String),
+        (status = 404, body = ErrorBody),
+        (status = 422, description = "The card does not fit in a QR code", body = ValidationErrorBody)
+    )
+)]
+pub async fn card_qr(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    Query(params): Query<QrParams>,
+) -> Response {
+    let conn = state.conn.clone();
+    let card = match telemetry::spawn_db(move || store::get_card(&conn, id)).await {
+        Ok(Ok(Some(card))) => share::public_view(card),
+        Ok(Ok(None)) => return not_found("card not found").into_response(),
+        Ok(Err(e)) => return internal_error(e).into_response(),
+        Err(e) => return internal_error(e).into_response(),
+    };
+    let payload = match params.format.unwrap_or_default() {
+        QrFormat::Vcard => vcard::render(&card, None),
+        QrFormat::Mecard => mecard::render(&card),
+    };
+    let Ok(code) = qr::QrCode::encode(payload.as_bytes()) else {
+        return validation_error(vec![FieldError {
+            field: "format".to_string(),
+            index: None,
+            code: "too_long".to_string(),
+            message: "the card is too long for a QR code; try format=mecard".to_string(),
+        }]);
+    };
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "image/svg+xml")
+        .body(Body::from(code.svg()))
+        .unwrap()
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/import/qr",
+    tag = "cards",
+    params(CreateParams),
+    request_body = QrImportInput,
+    responses(
+        (status = 200, description = "The pre-filled card form (without `create`)", body = CardInput),
+        (status = 201, description = "The saved card (`create: true`)", body = Card),
+        (status = 200, description = "Merged into an existing card (`create: true`, `on_duplicate=merge`)", body = Card),
+        (status = 409, description = "A card with the same email or phone exists (`on_duplicate=reject`)", body = ErrorBody),
+        (status = 422, description = "Not a vCard or MECARD, or the card is invalid", body = ValidationErrorBody)
+    )
+)]
+pub async fn import_qr(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<CreateParams>,
+    Json(input): Json<QrImportInput>,
+) -> Response {
+    let Some(card) = vcard::parse(&input.payload).or_else(|| mecard::parse(&input.payload)) else {
+        return validation_error(vec![FieldError {
+            field: "payload".to_string(),
+            index: None,
+            code: "unsupported".to_string(),
+            message: "the QR code does not contain a vCard or MECARD".to_string(),
+        }]);
+    };
+    if !input.create {
+        return Json(card).into_response();
+    }
+    insert_card(state, params.on_duplicate.unwrap_or_default(), card, None).await
+}
+
+// ────────────────────────────────────────────────────────────────────────────
+// Card scanning and text import
+// ────────────────────────────────────────────────────────────────────────────
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/scan",
+    tag = "cards",
+    request_body(content = PhotoForm, content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "The text read from the photo and the fields found in it", body = ScanResult),
+        (status = 400, body = ErrorBody),
+        (status = 413, description = "The photo is larger than `--max-upload-mb`", body = ErrorBody),
+        (status = 502, description = "The OCR engine or extractor failed", body = ErrorBody),
+        (status = 503, description = "Scanning is disabled (`--ocr none` with the heuristic extractor)", body = ErrorBody)
+    )
+)]
+pub async fn scan_card(State(state): State<Arc<AppState>>, multipart: Multipart) -> Response {
+    let fields = match collect_multipart(multipart, state.photo_max_size).await {
+        Ok(f) => f,
+        Err(response) => return response,
+    };
+    let Some((filename, data)) = fields.photo else {
+        return bad_request("no photo field provided").into_response();
+    };
+    let mime = mime_guess::from_path(&filename).first_or_octet_stream();
+
+    let source = Source::Image { data: &data, mime: mime.essence_str() };
+    match state.extractor.extract(source).await {
+        Ok(found) => Json(ScanResult { text: found.text, card: found.card }).into_response(),
+        Err(e) => extract_error(e),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/import/text",
+    tag = "cards",
+    params(CreateParams),
+    request_body = TextImportInput,
+    responses(
+        (status = 200, description = "The pre-filled card form (without `create`)", body = CardInput),
+        (status = 201, description = "The saved card (`create: true`)", body = Card),
+        (status = 200, description = "Merged into an existing card (`create: true`, `on_duplicate=merge`)", body = Card),
+        (status = 409, description = "A card with the same email or phone exists (`on_duplicate=reject`)", body = ErrorBody),
+        (status = 422, description = "No text, or the card is invalid", body = ValidationErrorBody),
+        (status = 502, description = "The extractor failed", body = ErrorBody)
+    )
+)]
+pub async fn import_text(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<CreateParams>,
+    Json(input): Json<TextImportInput>,
+) -> Response {
+    if input.text.trim().is_empty() {
+        return validation_error(vec![FieldError {
+            field: "text".to_string(),
+            index: None,
+            code: "required".to_string(),
+            message: "text is required".to_string(),
+        }]);
+    }
+    let card = match state.extractor.extract(Source::Text(&input.text)).await {
+        Ok(found) => found.card,
+        Err(e) => return extract_error(e),
+    };
+    if !input.create {
+        return Json(card).into_response();
+    }
+    insert_card(state, params.on_duplicate.unwrap_or_default(), card, None).await
+}
+
+/// Always the local pattern matching, whatever `--extractor` is: cheap
+/// enough to run as the user types, and no text leaves the server.
+#[utoipa::path(
+    post,
+    path = "/api/v1/parse/signature",
+    tag = "cards",
+    request_body = SignatureInput,
+    responses(
+        (status = 200, description = "A draft card to review and `POST /api/v1/cards`", body = CardInput),
+        (status = 422, description = "No text", body = ValidationErrorBody)
+    )
+)]
+pub async fn parse_signature(Json(input): Json<SignatureInput>) -> Response {
+    if input.text.trim().is_empty() {
+        return validation_error(vec![FieldError {
+            field: "text".to_string(),
+            index: None,
+            code: "required".to_string(),
+            message: "text is required".to_string(),
+        }]);
+    }
+    Json(scan::signature(&input.text)).into_response()
+}
+
+fn extract_error(e: ExtractError) -> Response {
+    match e {
+        ExtractError::Unsupported(reason) => {
+            (StatusCode::SERVICE_UNAVAILABLE, Json(json!({"error": reason}))).into_response()
+        }
+        ExtractError::Failed(e) => {
+            error!("card extraction failed: {e:#}");
+            (
+                StatusCode::BAD_GATEWAY,
+                Json(json!({"error": format!("could not read the card: {e}")})),
+            )
+                .into_response()
+        }
+    }
+}
+
+// ────────────────────────────────────────────────────────────────────────────
+// Enrichment
+// ────────────────────────────────────────────────────────────────────────────
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/cards/{id}/enrich",
+    tag = "cards",
+    params(("id" = i64, Path, description = "Card id")),
+    responses(
+        (status = 200, description = "Proposed updates; nothing is saved", body = Enrichment),
+        (status = 404, body = ErrorBody),
+        (status = 422, description = "The card has no company, website, or work email to look up", body = ValidationErrorBody),
+        (status = 502, description = "The enrichment service failed", body = ErrorBody),
+        (status = 503, description = "Enrichment is not configured (`--enrich-url`)", body = ErrorBody)
+    )
+)]
+pub async fn enrich_card(State(state): State<Arc<AppState>>, Path(id): Path<i64>) -> Response {
+    let Some(enricher) = &state.enricher else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({"error": "enrichment is not enabled on this server"})),
+        )
+            .into_response();
+    };
+    let conn = state.conn.clone();
+    let card = match telemetry::spawn_db(move || store::get_card(&conn, id)).await {
+        Ok(Ok(Some(card))) => card,
+        Ok(Ok(None)) => return not_found("card not found").into_response(),
+        Ok(Err(e)) => return internal_error(e).into_response(),
+        Err(e) => return internal_error(e).into_response(),
+    };
+    let Some(lookup) = enrich::Lookup::for_card(&card) else {
+        return validation_error(vec![FieldError {
+            field: "company".to_string(),
+            index: None,
+            code: "required".to_string(),
+            message: "the card needs a company, website, or work email to look up".to_string(),
+        }]);
+    };
+    let proposals = match enricher.lookup(&lookup).await {
+        Ok(Some(profile)) => enrich::proposals(&card, profile),
+        Ok(None) => Vec::new(),
+        Err(e) => {
+            error!("enrichment failed: {e:#}");
+            return (
+                StatusCode::BAD_GATEWAY,
+                Json(json!({"error": format!("enrichment failed: {e}")})),
+            )
+                .into_response();
+        }
+    };
+    Json(Enrichment { version: card.version, proposals }).into_response()
+}
+
+/// Writes the accepted proposals with the same checks as `PATCH`. The
+/// `industry` and `company_size` custom fields are defined as text fields
+/// the first time they are needed.
+#[utoipa::path(
+    post,
+    path = "/api/v1/cards/{id}/enrich/accept",
+    tag = "cards",
+    params(("id" = i64, Path, description = "Card id")),
+    request_body = EnrichAccept,
+    responses(
+        (status = 200, body = Card),
+        (status = 404, body = ErrorBody),
+        (status = 409, description = "The card changed since the proposals were made", body = ErrorBody),
+        (status = 422, body = ValidationErrorBody)
+    )
+)]
+pub async fn accept_enrichment(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    headers: HeaderMap,
+    Json(input): Json<EnrichAccept>,
+) -> Response {
+    if input.accept.is_empty() {
+        return validation_error(vec![FieldError {
+            field: "accept".to_string(),
+            index: None,
+            code: "required".to_string(),
+            message: "no proposals accepted".to_string(),
+        }]);
+    }
+    let conn = state.conn.clone();
+    let card = match telemetry::spawn_db(move || store::get_card(&conn, id)).await {
+        Ok(Ok(Some(card))) => card,
+        Ok(Ok(None)) => return not_found("card not found").into_response(),
+        Ok(Err(e)) => return internal_error(e).into_response(),
+        Err(e) => return internal_error(e).into_response(),
+    };
+    // Checked here too, so a stale accept defines no fields.
+    let version = match if_match_version(&headers) {
+        Ok(version) => version.or(input.version),
+        Err(e) => return e.into_response(),
+    };
+    if version.is_some_and(|v| v != card.version) {
+        return store_error(store::VersionConflict { current: card.version }.into());
+    }
+
+    let mut schema = match field_schema(&state).await {
+        Ok(schema) => schema,
+        Err(e) => return internal_error(e).into_response(),
+    };
+    for (key, label) in input.accept.iter().filter_map(|v| v.field.custom_field()) {
+        if schema.iter().any(|f| f.key == key) {
+            continue;
+        }
+        let definition = FieldDefinitionInput {
+            label: label.to_string(),
+            field_type: FieldType::Text,
+            required: false,
+            options: Vec::new(),
+        };
+        let conn = state.conn.clone();
+        match telemetry::spawn_db(move || store::put_field_definition(&conn, key, &definition)).await {
+            Ok(Ok((field, _))) => schema.push(field),
+            Ok(Err(e)) => return internal_error(e).into_response(),
+            Err(e) => return internal_error(e).into_response(),
+        }
+    }
+
+    let mut patch = enrich::accept(&card, &input.accept);
+    patch.version = version;
+    if let Err(errors) = validation::card_patch(&mut patch, &schema) {
+        return validation_error(errors);
+    }
+    let conn = state.conn.clone();
+    let result = telemetry::spawn_db(move || {
+        if !store::patch_card(&conn, id, &patch)? {
+            return Ok(None);
+        }
+        store::get_card(&conn, id)
+    })
+    .await;
+    match result {
+        Ok(Ok(Some(card))) => {
+            state.notify(events::CARD_UPDATED, card.id, Some(card.clone()));
+            card_response(StatusCode::OK, &card)
+        }
+        Ok(Ok(None)) => not_found("card not found").into_response(),
+        Ok(Err(e)) => store_error(e),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+// ────────────────────────────────────────────────────────────────────────────
+// Geocoding
+// ────────────────────────────────────────────────────────────────────────────
+
+#[derive(Deserialize, IntoParams)]
+pub struct BackfillParams {
+    /// `true` also retries the addresses the geocoder could not find before
+    #[serde(default)]
+    pub retry: bool,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/geocode/backfill",
+    tag = "cards",
+    params(BackfillParams),
+    responses(
+        (status = 202, description = "Geocoding started in the background", body = GeocodeBackfill),
+        (status = 503, description = "Geocoding is off (`--geocoder none`)", body = ErrorBody)
+    )
+)]
+pub async fn geocode_backfill(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<BackfillParams>,
+) -> Response {
+    let Some(geocoder) = &state.geocoder else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({"error": "geocoding is not enabled on this server"})),
+        )
+            .into_response();
+    };
+    let conn = state.conn.clone();
+    let result = telemetry::spawn_db(move || {
+        if params.retry {
+            store::forget_geocode_misses(&conn)?;
+        }
+        store::addresses_to_geocode(&conn)
+    })
+    .await;
+    match result {
+        Ok(Ok(pending)) => {
+            geocoder.wake();
+            (StatusCode::ACCEPTED, Json(GeocodeBackfill { pending: pending.len() })).into_response()
+        }
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+// ────────────────────────────────────────────────────────────────────────────
+// Webhooks
+// ────────────────────────────────────────────────────────────────────────────
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/webhooks",
+    tag = "webhooks",
+    responses((status = 200, body = [Webhook]))
+)]
+pub async fn list_webhooks(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let conn = state.conn.clone();
+    let result =
+        telemetry::spawn_db(move || store::list_webhooks(&conn)).await;
+
+    match result {
+        Ok(Ok(hooks)) => (StatusCode::OK, Json(json!(hooks))).into_response(),
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/webhooks/{id}",
+    tag = "webhooks",
+    params(("id" = i64, Path, description = "Webhook id")),
+    responses(
+        (status = 200, body = Webhook),
+        (status = 404, body = ErrorBody)
+    )
+)]
+pub async fn get_webhook(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    let conn = state.conn.clone();
+    let result =
+        telemetry::spawn_db(move || store::get_webhook(&conn, id)).await;
+
+    match result {
+        Ok(Ok(Some(hook))) => (StatusCode::OK, Json(json!(hook))).into_response(),
+        Ok(Ok(None)) => not_found("webhook not found").into_response(),
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/webhooks",
+    tag = "webhooks",
+    request_body = WebhookInput,
+    responses(
+        (status = 201, description = "Webhook created; the only response that includes `secret`", body = Webhook),
+        (status = 400, body = ErrorBody),
+        (status = 422, description = "Missing or malformed fields", body = ValidationErrorBody)
+    )
+)]
+pub async fn create_webhook(
+    State(state): State<Arc<AppState>>,
+    Json(input): Json<WebhookInput>,
+) -> impl IntoResponse {
+    if let Err(errors) = validation::webhook_input(&input) {
+        return validation_error(errors);
+    }
+
+    let secret = input
+        .secret
+        .clone()
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| uuid::Uuid::new_v4().simple().to_string());
+
+    let conn = state.conn.clone();
+    let secret_clone = secret.clone();
+    let result = telemetry::spawn_db(move || {
+        let id = store::create_webhook(&conn, &input, &secret_clone)?;
+        store::get_webhook(&conn, id)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(Some(mut hook))) => {
+            hook.secret = Some(secret);
+            (StatusCode::CREATED, Json(json!(hook))).into_response()
+        }
+        Ok(Ok(None)) => internal_error("webhook created but not found").into_response(),
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/webhooks/{id}",
+    tag = "webhooks",
+    params(("id" = i64, Path, description = "Webhook id")),
+    request_body = WebhookInput,
+    responses(
+        (status = 200, body = Webhook),
+        (status = 400, body = ErrorBody),
+        (status = 422, description = "Missing or malformed fields", body = ValidationErrorBody),
+        (status = 404, body = ErrorBody)
+    )
+)]
+pub async fn update_webhook(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    Json(input): Json<WebhookInput>,
+) -> impl IntoResponse {
+    if let Err(errors) = validation::webhook_input(&input) {
+        return validation_error(errors);
+    }
+
+    let conn = state.conn.clone();
+    let result = telemetry::spawn_db(move || {
+        if !store::update_webhook(&conn, id, &input)? {
+            return Ok(None);
+        }
+        store::get_webhook(&conn, id)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(Some(hook))) => (StatusCode::OK, Json(json!(hook))).into_response(),
+        Ok(Ok(None)) => not_found("webhook not found").into_response(),
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/webhooks/{id}",
+    tag = "webhooks",
+    params(("id" = i64, Path, description = "Webhook id")),
+    responses(
+        (status = 204, description = "Webhook deleted"),
+        (status = 404, body = ErrorBody)
+    )
+)]
+pub async fn delete_webhook(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    let conn = state.conn.clone();
+    let result =
+        telemetry::spawn_db(move || store::delete_webhook(&conn, id)).await;
+
+    match result {
+        Ok(Ok(true)) => StatusCode::NO_CONTENT.into_response(),
+        Ok(Ok(false)) => not_found("webhook not found").into_response(),
+        Ok(Err(e)) => internal_error(e).into_response(),
+        Err(e) => internal_error(e).into_response(),
+    }
+}
+
+// ────────────────────────────────────────────────────────────────────────────
+// Change feed (Server-Sent Events)
+// ────────────────────────────────────────────────────────────────────────────
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/events",
+    tag = "events",
+    responses((status = 200, description = "Server-Sent Events stream of card and tag changes", content_type = "text/event-stream"))
+)]
+pub async fn events_feed(
+    State(state): State<Arc<AppState>>,
+) -> Sse<impl futures::Stream<Item = Result<Event, std::convert::Infallible>>> {
+    use futures::StreamExt;
+    use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
+
+    let stream = BroadcastStream::new(state.events.subscribe()).map(|msg| {
+        let event = match msg {
+            Ok(change) => Event::default()
+                .event(change.event)
+                .json_data(&change)
+                .unwrap_or_default(),
+            // The subscriber fell behind; tell it to reload instead of
+            // replaying a partial history.
+            Err(BroadcastStreamRecvError::Lagged(_)) => Event::default().event(events::RESYNC).data("{}"),
+        };
+        Ok(event)
+    });
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+// ────────────────────────────────────────────────────────────────────────────
+// GraphQL
+// ────────────────────────────────────────────────────────────────────────────
+
+pub async fn graphql(
+    State(state): State<Arc<AppState>>,
+    Extension(schema): Extension<CardVaultSchema>,
+    Json(request): Json<async_graphql::Request>,
+) -> Response {
+    if state.read_only && read_only::is_mutation(&request) {
+        return read_only::forbidden();
+    }
+    Json(schema.execute(request).await).into_response()
+}
+
+pub async fn graphiql() -> impl IntoResponse {
+    axum::response::Html(
+        async_graphql::http::GraphiQLSource::build()
+            .endpoint(&base_path::url("/graphql"))
+            .finish(),
+    )
+}
+
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "system",
+    responses((status = 200, body = HealthResponse))
+)]
+pub async fn health(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let conn = state.conn.clone();
+    let db_ok = telemetry::spawn_db(move || {
+        let c = conn.lock().unwrap();
         c.execute_batch("SELECT 1").is_ok()
     })
     .await
@@ -529,31 +4023,92 @@ pub async fn health(State(state): State<Arc<AppState>>) -> impl IntoResponse {
 // Static file serving
 // ────────────────────────────────────────────────────────────────────────────
 
+/// A stored file as `mime`, or the part of it a `Range` header asks for
+/// (206), so large files can be resumed and media seeked.
+fn file_response(headers: &HeaderMap, mime: &str, data: Vec<u8>) -> Response {
+    let len = data.len();
+    let requested = range::parse(headers.get(header::RANGE).and_then(|v| v.to_str().ok()), len);
+    let builder = Response::builder()
+        .header(header::CONTENT_TYPE, mime)
+        .header(header::ACCEPT_RANGES, "bytes");
+    match requested {
+        range::Requested::Full => builder.status(StatusCode::OK).body(Body::from(data)),
+        range::Requested::Part(part) => builder
+            .status(StatusCode::PARTIAL_CONTENT)
+            .header(header::CONTENT_RANGE, format!("bytes {}-{}/{len}", part.start, part.end - 1))
+            .body(Body::from(data[part].to_vec())),
+        range::Requested::Unsatisfiable => builder
+            .status(StatusCode::RANGE_NOT_SATISFIABLE)
+            .header(header::CONTENT_RANGE, format!("bytes */{len}"))
+            .body(Body::empty()),
+    }
+    .unwrap()
+}
+
+#[utoipa::path(
+    get,
+    path = "/uploads/{filename}",
+    tag = "photos",
+    params(("filename" = String, Path, description = "Stored photo filename")),
+    responses(
+        (status = 200, description = "Photo bytes, with a strong `ETag` and cached for a year"),
+        (status = 206, description = "The part of the photo asked for with `Range`"),
+        (status = 304, description = "`If-None-Match` holds the photo's `ETag`"),
+        (status = 404, description = "File not found"),
+        (status = 416, description = "`Range` starts past the end of the file")
+    )
+)]
 pub async fn serve_uploads(
     State(state): State<Arc<AppState>>,
     Path(filename): Path<String>,
+    headers: HeaderMap,
 ) -> impl IntoResponse {
     // Prevent path traversal
     if filename.contains("..") || filename.contains('/') {
         return (StatusCode::BAD_REQUEST, "invalid filename").into_response();
     }
 
-    let path = std::path::Path::new(&state.uploads_dir).join(&filename);
-
-    match fs::read(&path).await {
-        Ok(data) => {
-            let mime = mime_guess::from_path(&path)
+    match state.uploads.read(&filename).await {
+        Ok(Some(data)) => {
+            // Stored files never change: a new upload gets a new name.
+            let etag = format!("\"{}\"", hex::encode(Sha256::digest(&data)));
+            let cache = [
+                (header::ETAG, etag.clone()),
+                (header::CACHE_CONTROL, "public, max-age=31536000, immutable".to_string()),
+            ];
+            if if_none_match(&headers, &etag) {
+                return (StatusCode::NOT_MODIFIED, cache).into_response();
+            }
+            let mime = mime_guess::from_path(&filename)
                 .first_or_octet_stream()
                 .to_string();
-            Response::builder()
-                .status(StatusCode::OK)
-                .header(header::CONTENT_TYPE, mime)
-                .body(Body::from(data))
-                .unwrap()
-                .into_response()
+            (cache, file_response(&headers, &mime, data)).into_response()
         }
-        Err(_) => (StatusCode::NOT_FOUND, "file not found").into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "file not found").into_response(),
+        Err(e) => internal_error(format!("{e:#}")).into_response(),
+    }
+}
+
+/// Paths served by the server itself; the web UI's routes are the rest.
+const SERVER_PREFIXES: &[&str] = &["/api", "/graphql", "/uploads", "/static", "/share", "/health"];
+
+/// Serves the web UI for GET paths no route matches, so a client-side route
+/// such as `/cards/42` opens the app when linked to or reloaded. Other
+/// methods, the server's own paths, and paths that look like files still
+/// get 404.
+pub async fn spa_fallback(method: Method, uri: Uri) -> Response {
+    let path = uri.path();
+    let server_path = SERVER_PREFIXES
+        .iter()
+        .any(|prefix| path == *prefix || path.starts_with(&format!("{prefix}/")));
+    let file_like = path.rsplit('/').next().is_some_and(|last| last.contains('.'));
+    if (method == Method::GET || method == Method::HEAD) && !server_path && !file_like {
+        return serve_index().await.into_response();
+    }
+    if path.starts_with("/api/") {
+        return not_found("not found").into_response();
     }
+    (StatusCode::NOT_FOUND, "not found").into_response()
 }
 
 pub async fn serve_index() -> impl IntoResponse {
@@ -561,11 +4116,18 @@ pub async fn serve_index() -> impl IntoResponse {
 
     match Asset::get("index.html") {
         Some(content) => {
-            let bytes = content.data.into_owned();
+            // Asset URLs and the prefix the script calls the API under
+            // follow `--base-path`
+            let html = String::from_utf8_lossy(&content.data)
+                .replace("\"/static/", &format!("\"{}/static/", base_path::get()))
+                .replace(
+                    r#"<meta name="base-path" content="">"#,
+                    &format!(r#"<meta name="base-path" content="{}">"#, base_path::get()),
+                );
             Response::builder()
                 .status(StatusCode::OK)
                 .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
-                .body(Body::from(bytes))
+                .body(Body::from(html))
                 .unwrap()
                 .into_response()
         }
@@ -0,0 +1,96 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use rusqlite::Connection;
+use serde_json::{Map, Value};
+
+use crate::{
+    models::{Card, CardRevision, FieldChange},
+    store,
+};
+
+/// Bookkeeping fields that change on every write, and rollups that change
+/// without one, are left out of diffs.
+const IGNORED_FIELDS: [&str; 9] = [
+    "id",
+    "version",
+    "created_at",
+    "updated_at",
+    "score",
+    "matches",
+    "last_contacted",
+    "company_logo_url",
+    "local_time",
+];
+
+/// The card's revisions, newest first, each with its field-level changes.
+/// Empty if the card never existed.
+pub fn card_history(conn: &Arc<Mutex<Connection>>, id: i64) -> Result<Vec<CardRevision>> {
+    let revisions = store::card_revisions(conn, id)?;
+    let mut history = Vec::with_capacity(revisions.len());
+    let mut previous: Option<Map<String, Value>> = None;
+    for revision in revisions {
+        let fields = diffable(&revision.card)?;
+        history.push(CardRevision {
+            version: revision.version,
+            kind: revision.kind,
+            created_at: revision.created_at,
+            changes: diff(previous.as_ref(), &fields),
+        });
+        previous = Some(fields);
+    }
+    history.reverse();
+    Ok(history)
+}
+
+/// The card as a JSON object without bookkeeping fields. Phone, email and
+/// address rows are rewritten with new ids on every save, so their ids are
+/// dropped too.
+fn diffable(card: &Card) -> Result<Map<String, Value>> {
+    let Value::Object(mut fields) = serde_json::to_value(card)? else {
+        anyhow::bail!("card did not serialize to an object");
+    };
+    for field in IGNORED_FIELDS {
+        fields.remove(field);
+    }
+    for value in fields.values_mut() {
+        if let Value::Array(items) = value {
+            for item in items {
+                if let Value::Object(item) = item {
+                    item.remove("id");
+                    // Geocoded after the save, so not part of the edit.
+                    item.remove("latitude");
+                    item.remove("longitude");
+                }
+            }
+        }
+    }
+    Ok(fields)
+}
+
+fn is_blank(value: &Value) -> bool {
+    match value {
+        Value::Null | Value::Bool(false) => true,
+        Value::String(s) => s.is_empty(),
+        Value::Array(a) => a.is_empty(),
+        _ => false,
+    }
+}
+
+fn diff(before: Option<&Map<String, Value>>, after: &Map<String, Value>) -> Vec<FieldChange> {
+    after
+        .iter()
+        .filter_map(|(field, new)| {
+            let old = before.and_then(|b| b.get(field)).cloned().unwrap_or(Value::Null);
+            let changed = match before {
+                Some(_) => old != *new,
+                None => !is_blank(new),
+            };
+            changed.then(|| FieldChange {
+                field: field.clone(),
+                before: old,
+                after: new.clone(),
+            })
+        })
+        .collect()
+}
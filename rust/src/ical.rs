@@ -0,0 +1,142 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use rusqlite::Connection;
+
+use crate::{
+    models::{Card, CardRelations, DueReminder},
+    store,
+};
+
+/// Domain part of every event UID. UIDs only depend on row ids, so calendar
+/// clients update an event in place rather than duplicating it on refresh.
+const UID_DOMAIN: &str = "cardvault";
+
+/// An iCalendar (RFC 5545) feed of the open reminders and the birthdays of
+/// active cards. Birthdays recur yearly; reminders are all-day events on
+/// their due date.
+pub fn feed(conn: &Arc<Mutex<Connection>>) -> Result<String> {
+    let reminders = store::due_reminders(conn, None, None)?;
+    let ids: Vec<i64> = store::card_dates(conn)?
+        .into_iter()
+        .filter(|(_, birthday, _)| !birthday.is_empty())
+        .map(|(id, ..)| id)
+        .collect();
+    let cards = store::get_cards_by_ids(conn, &ids, CardRelations::ALL)?;
+    Ok(render(&reminders, &cards))
+}
+
+fn render(reminders: &[DueReminder], birthdays: &[Card]) -> String {
+    let mut out = String::new();
+    for line in [
+        "BEGIN:VCALENDAR",
+        "VERSION:2.0",
+        "PRODID:-//CardVault//Reminders//EN",
+        "CALSCALE:GREGORIAN",
+        "METHOD:PUBLISH",
+        "X-WR-CALNAME:CardVault",
+    ] {
+        push(&mut out, line);
+    }
+
+    for DueReminder { reminder, card } in reminders {
+        let mut summary = reminder.title.clone();
+        if !card.name.is_empty() {
+            summary.push_str(&format!(" ({})", card.name));
+        }
+        push(&mut out, "BEGIN:VEVENT");
+        push(&mut out, &format!("UID:reminder-{}@{UID_DOMAIN}", reminder.id));
+        push(&mut out, &format!("DTSTAMP:{}", stamp(&reminder.created_at)));
+        push(&mut out, &format!("DTSTART;VALUE=DATE:{}", reminder.due_date.replace('-', "")));
+        push(&mut out, &format!("SUMMARY:{}", escape(&summary)));
+        if let Some(description) = describe(card) {
+            push(&mut out, &format!("DESCRIPTION:{}", escape(&description)));
+        }
+        push(&mut out, "END:VEVENT");
+    }
+
+    for card in birthdays {
+        let Some((start, rule)) = yearly(&card.birthday) else {
+            continue;
+        };
+        push(&mut out, "BEGIN:VEVENT");
+        push(&mut out, &format!("UID:birthday-{}@{UID_DOMAIN}", card.id));
+        push(&mut out, &format!("DTSTAMP:{}", stamp(&card.updated_at)));
+        push(&mut out, &format!("DTSTART;VALUE=DATE:{start}"));
+        push(&mut out, &format!("RRULE:{rule}"));
+        push(&mut out, &format!("SUMMARY:{}", escape(&format!("{}'s birthday", card.name))));
+        push(&mut out, "TRANSP:TRANSPARENT");
+        push(&mut out, "END:VEVENT");
+    }
+
+    push(&mut out, "END:VCALENDAR");
+    out
+}
+
+/// Start date and recurrence rule of a yearly event on a canonical date.
+/// Without a year the series starts in 2000, a leap year so that February
+/// 29 is valid; that day falls on the 28th in other years.
+fn yearly(date: &str) -> Option<(String, String)> {
+    let (year, month_day) = match date.strip_prefix("--") {
+        Some(month_day) => ("2000", month_day),
+        None => date.split_once('-')?,
+    };
+    let (month, day) = month_day.split_once('-')?;
+    let rule = if (month, day) == ("02", "29") {
+        "FREQ=YEARLY;BYMONTH=2;BYMONTHDAY=-1"
+    } else {
+        "FREQ=YEARLY"
+    };
+    Some((format!("{year}{month}{day}"), rule.to_string()))
+}
+
+/// Who a reminder is about, beyond the name already in its summary.
+fn describe(card: &Card) -> Option<String> {
+    let role = [card.title.as_str(), card.company.as_str()]
+        .into_iter()
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join(", ");
+    (!role.is_empty()).then_some(role)
+}
+
+/// A stored `YYYY-MM-DD HH:MM:SS` UTC timestamp in iCalendar form.
+pub fn stamp(timestamp: &str) -> String {
+    let digits: String = timestamp.chars().filter(char::is_ascii_digit).collect();
+    match digits.len() {
+        14 => format!("{}T{}Z", &digits[..8], &digits[8..]),
+        _ => "19700101T000000Z".to_string(),
+    }
+}
+
+/// Escapes a TEXT value. vCard uses the same rules.
+pub fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Appends a content line, folded at 75 octets, with a CRLF ending, as
+/// both iCalendar and vCard require.
+pub fn push(out: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
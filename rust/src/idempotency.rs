@@ -0,0 +1,275 @@
+use std::sync::{Arc, Mutex};
+
+use axum::{
+    body::{to_bytes, Body, Bytes},
+    extract::{FromRequest, Multipart, Request, State},
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use rusqlite::Connection;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use tracing::error;
+
+use crate::{handlers::AppState, store, telemetry};
+
+/// How long a key is remembered.
+const KEY_TTL_HOURS: i64 = 24;
+
+/// Identifies a request, so a retry can be told from a different request
+/// under the same key. A multipart form is hashed by each part's name,
+/// filename, and content: clients pick a new boundary for every retry, and
+/// it appears in the body as well as in `Content-Type`. Other bodies are
+/// hashed as they are.
+async fn request_hash(method: &str, path: &str, content_type: &str, bytes: &Bytes) -> String {
+    let mut hasher = Sha256::new();
+    field(&mut hasher, method.as_bytes());
+    field(&mut hasher, path.as_bytes());
+    field(&mut hasher, content_type.split(';').next().unwrap_or_default().trim().as_bytes());
+    match form_parts(content_type, bytes).await {
+        Some(parts) => {
+            for (name, filename, content) in parts {
+                field(&mut hasher, name.as_deref().unwrap_or_default().as_bytes());
+                field(&mut hasher, filename.as_deref().unwrap_or_default().as_bytes());
+                field(&mut hasher, &content);
+            }
+        }
+        None => hasher.update(bytes),
+    }
+    hex::encode(hasher.finalize())
+}
+
+/// Adds `value` to the hash with its length, so values cannot run into
+/// each other.
+fn field(hasher: &mut Sha256, value: &[u8]) {
+    hasher.update((value.len() as u64).to_be_bytes());
+    hasher.update(value);
+}
+
+/// The name, filename, and content of each part of a multipart body;
+/// `None` if it is not one or does not parse.
+async fn form_parts(content_type: &str, bytes: &Bytes) -> Option<Vec<(Option<String>, Option<String>, Bytes)>> {
+    if !content_type.starts_with("multipart/") {
+        return None;
+    }
+    let request = Request::builder()
+        .header(header::CONTENT_TYPE, content_type)
+        .body(Body::from(bytes.clone()))
+        .ok()?;
+    let mut multipart = Multipart::from_request(request, &()).await.ok()?;
+    let mut parts = Vec::new();
+    while let Some(part) = multipart.next_field().await.ok()? {
+        let name = part.name().map(String::from);
+        let filename = part.file_name().map(String::from);
+        parts.push((name, filename, part.bytes().await.ok()?));
+    }
+    Some(parts)
+}
+
+/// A claimed key, released when dropped unless its response was stored.
+/// The request holding it can be cut off without finishing, by
+/// `--request-timeout` or a client that goes away; the key would otherwise
+/// answer 409 until it expires.
+struct Claim {
+    conn: Arc<Mutex<Connection>>,
+    key: String,
+    method: String,
+    path: String,
+    stored: bool,
+}
+
+impl Claim {
+    /// Stores `response` for retries to replay.
+    async fn store(mut self, response: store::StoredResponse) -> anyhow::Result<()> {
+        self.stored = true;
+        let (conn, key, method, path) = (self.conn.clone(), self.key.clone(), self.method.clone(), self.path.clone());
+        telemetry::spawn_db(move || store::complete_idempotency_key(&conn, &key, &method, &path, &response)).await?
+    }
+}
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        if self.stored {
+            return;
+        }
+        let (conn, key, method, path) = (self.conn.clone(), self.key.clone(), self.method.clone(), self.path.clone());
+        telemetry::spawn_db(move || {
+            if let Err(e) = store::release_idempotency_key(&conn, &key, &method, &path) {
+                error!("releasing Idempotency-Key failed: {e}");
+            }
+        });
+    }
+}
+
+/// Honors an `Idempotency-Key` header on mutating requests.
+///
+/// The first request with a given key runs normally and its response is
+/// stored; retries with the same key and an identical body get that stored
+/// response back instead of running again (so a retried `POST /cards` never
+/// creates a second card). Reusing a key for a different body is rejected
+/// with 422, and a retry that arrives while the original is still running
+/// gets 409. Server errors are not remembered, so the client can retry them.
+pub async fn middleware(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if request.method() == Method::GET || request.method() == Method::HEAD {
+        return next.run(request).await;
+    }
+    let Some(key) = request
+        .headers()
+        .get("idempotency-key")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+    else {
+        return next.run(request).await;
+    };
+
+    let method = request.method().to_string();
+    let path = request.uri().path().to_string();
+
+    let (parts, body) = request.into_parts();
+    let content_type = parts
+        .headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    let bytes = match to_bytes(body, state.body_limit(content_type)).await {
+        Ok(b) => b,
+        Err(_) => {
+            return (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                Json(json!({"error": "request body too large"})),
+            )
+                .into_response()
+        }
+    };
+
+    let request_hash = request_hash(&method, &path, content_type, &bytes).await;
+
+    let conn = state.conn.clone();
+    let (k, m, p, h) = (key.clone(), method.clone(), path.clone(), request_hash);
+    let claim = telemetry::spawn_db(move || {
+        store::claim_idempotency_key(&conn, &k, &m, &p, &h, KEY_TTL_HOURS)
+    })
+    .await;
+
+    let claim = match claim {
+        Ok(Ok(store::IdempotencyClaim::Claimed)) => Claim {
+            conn: state.conn.clone(),
+            key,
+            method,
+            path,
+            stored: false,
+        },
+        Ok(Ok(store::IdempotencyClaim::InFlight)) => {
+            return (
+                StatusCode::CONFLICT,
+                Json(json!({"error": "a request with this Idempotency-Key is still in progress"})),
+            )
+                .into_response()
+        }
+        Ok(Ok(store::IdempotencyClaim::Mismatch)) => {
+            return (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(json!({"error": "Idempotency-Key was already used with a different request"})),
+            )
+                .into_response()
+        }
+        Ok(Ok(store::IdempotencyClaim::Replay(stored))) => {
+            let mut response = Response::new(Body::from(stored.body));
+            *response.status_mut() =
+                StatusCode::from_u16(stored.status).unwrap_or(StatusCode::OK);
+            let headers = response.headers_mut();
+            if let Ok(v) = HeaderValue::from_str(&stored.content_type) {
+                headers.insert(header::CONTENT_TYPE, v);
+            }
+            if !stored.etag.is_empty() {
+                if let Ok(v) = HeaderValue::from_str(&stored.etag) {
+                    headers.insert(header::ETAG, v);
+                }
+            }
+            headers.insert("idempotent-replayed", HeaderValue::from_static("true"));
+            return response;
+        }
+        Ok(Err(e)) => {
+            error!("idempotency lookup failed: {e}");
+            return next.run(Request::from_parts(parts, Body::from(bytes))).await;
+        }
+        Err(e) => {
+            error!("idempotency lookup failed: {e}");
+            return next.run(Request::from_parts(parts, Body::from(bytes))).await;
+        }
+    };
+
+    let response = next.run(Request::from_parts(parts, Body::from(bytes))).await;
+
+    // The handlers' own responses, which are small
+    let (parts, body) = response.into_parts();
+    let bytes = match to_bytes(body, usize::MAX).await {
+        Ok(b) => b,
+        Err(e) => {
+            error!("buffering idempotent response failed: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let status = parts.status;
+    let header_str = |name| {
+        parts
+            .headers
+            .get(name)
+            .and_then(|v: &HeaderValue| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string()
+    };
+    let stored = store::StoredResponse {
+        status: status.as_u16(),
+        content_type: header_str(header::CONTENT_TYPE),
+        etag: header_str(header::ETAG),
+        body: bytes.to_vec(),
+    };
+    // A server error is not kept: dropping the claim releases the key
+    if !status.is_server_error() {
+        if let Err(e) = claim.store(stored).await {
+            error!("storing idempotent response failed: {e}");
+        }
+    }
+
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn form(boundary: &str, name: &str) -> (String, Bytes) {
+        let body = format!(
+            "--{boundary}\r\n\
+             Content-Disposition: form-data; name=\"name\"\r\n\r\n\
+             {name}\r\n\
+             --{boundary}\r\n\
+             Content-Disposition: form-data; name=\"photo\"; filename=\"a.jpg\"\r\n\
+             Content-Type: image/jpeg\r\n\r\n\
+             \u{ff}\u{d8}jpeg\r\n\
+             --{boundary}--\r\n"
+        );
+        (format!("multipart/form-data; boundary={boundary}"), Bytes::from(body))
+    }
+
+    #[tokio::test]
+    async fn retried_form_with_new_boundary_matches() {
+        let (first_type, first) = form("----first", "Ada");
+        let (retry_type, retry) = form("----retry", "Ada");
+        let (other_type, other) = form("----other", "Grace");
+        let hash = |content_type, body| request_hash("POST", "/api/v1/cards", content_type, body);
+
+        assert_ne!(first, retry);
+        assert_eq!(hash(&first_type, &first).await, hash(&retry_type, &retry).await);
+        assert_ne!(hash(&first_type, &first).await, hash(&other_type, &other).await);
+    }
+}
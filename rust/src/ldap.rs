@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result};
+use ldap3::{LdapConnAsync, Scope, SearchEntry};
+use rusqlite::Connection;
+use tracing::info;
+
+use crate::{
+    models::{CardFormAddressInput, CardFormEmailInput, CardFormPhoneInput, CardInput},
+    store, telemetry,
+};
+
+/// Attributes requested from the directory; everything else is ignored.
+const ATTRS: &[&str] = &[
+    "cn",
+    "displayName",
+    "givenName",
+    "sn",
+    "title",
+    "o",
+    "company",
+    "department",
+    "mail",
+    "telephoneNumber",
+    "mobile",
+    "facsimileTelephoneNumber",
+    "street",
+    "l",
+    "c",
+    "co",
+    "postalCode",
+    "labeledURI",
+    "wWWHomePage",
+    "description",
+];
+
+#[derive(Debug, Clone)]
+pub struct LdapImportOptions {
+    pub url: String,
+    pub bind_dn: Option<String>,
+    pub bind_password: Option<String>,
+    pub base_dn: String,
+    pub filter: String,
+    pub source_tag: String,
+}
+
+#[derive(Debug, Default)]
+pub struct LdapImportSummary {
+    pub imported: usize,
+    pub skipped: usize,
+}
+
+pub async fn import(
+    conn: &Arc<Mutex<Connection>>,
+    opts: &LdapImportOptions,
+) -> Result<LdapImportSummary> {
+    let (ldap_conn, mut ldap) = LdapConnAsync::new(&opts.url)
+        .await
+        .with_context(|| format!("connect to {}", opts.url))?;
+    ldap3::drive!(ldap_conn);
+
+    if let Some(bind_dn) = &opts.bind_dn {
+        ldap.simple_bind(bind_dn, opts.bind_password.as_deref().unwrap_or(""))
+            .await?
+            .success()
+            .context("LDAP bind failed")?;
+    }
+
+    let (entries, _) = ldap
+        .search(&opts.base_dn, Scope::Subtree, &opts.filter, ATTRS.to_vec())
+        .await?
+        .success()
+        .context("LDAP search failed")?;
+    info!("LDAP search returned {} entries", entries.len());
+
+    let mut summary = LdapImportSummary::default();
+    for entry in entries {
+        let entry = SearchEntry::construct(entry);
+        let Some(input) = entry_to_card_input(&entry.attrs, &opts.source_tag) else {
+            summary.skipped += 1;
+            continue;
+        };
+
+        // Re-running an import must not duplicate people already in the vault.
+        let conn = conn.clone();
+        let created = telemetry::spawn_db(move || -> Result<bool> {
+            for e in &input.emails {
+                if store::find_card_id_by_email(&conn, &e.address)?.is_some() {
+                    return Ok(false);
+                }
+            }
+            store::create_card(&conn, &input)?;
+            Ok(true)
+        })
+        .await??;
+
+        if created {
+            summary.imported += 1;
+        } else {
+            summary.skipped += 1;
+        }
+    }
+
+    ldap.unbind().await?;
+    Ok(summary)
+}
+
+fn first(attrs: &HashMap<String, Vec<String>>, keys: &[&str]) -> String {
+    keys.iter()
+        .find_map(|k| attrs.get(*k).and_then(|v| v.first()))
+        .map(|s| s.trim().to_string())
+        .unwrap_or_default()
+}
+
+fn all(attrs: &HashMap<String, Vec<String>>, key: &str) -> Vec<String> {
+    attrs
+        .get(key)
+        .map(|v| {
+            v.iter()
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Maps an inetOrgPerson (or AD user) entry to a card. Entries without any
+/// usable name are skipped.
+fn entry_to_card_input(attrs: &HashMap<String, Vec<String>>, source_tag: &str) -> Option<CardInput> {
+    let mut name = first(attrs, &["displayName", "cn"]);
+    if name.is_empty() {
+        name = format!("{} {}", first(attrs, &["givenName"]), first(attrs, &["sn"]))
+            .trim()
+            .to_string();
+    }
+    if name.is_empty() {
+        return None;
+    }
+
+    let mut phones = Vec::new();
+    for (attr, label) in [
+        ("telephoneNumber", "work"),
+        ("mobile", "mobile"),
+        ("facsimileTelephoneNumber", "fax"),
+    ] {
+        for number in all(attrs, attr) {
+            phones.push(CardFormPhoneInput {
+                label: label.to_string(),
+                number,
+            });
+        }
+    }
+
+    let emails = all(attrs, "mail")
+        .into_iter()
+        .map(|address| CardFormEmailInput {
+            label: "work".to_string(),
+            address,
+        })
+        .collect();
+
+    let street = first(attrs, &["street"]);
+    let city = first(attrs, &["l"]);
+    let country = first(attrs, &["co", "c"]);
+    let postal = first(attrs, &["postalCode"]);
+    let addresses = if [&street, &city, &country, &postal].iter().all(|s| s.is_empty()) {
+        vec![]
+    } else {
+        vec![CardFormAddressInput {
+            label: "office".to_string(),
+            street,
+            city,
+            country,
+            postal,
+        }]
+    };
+
+    // labeledURI is "<uri> [label]"; keep only the URI part.
+    let website = first(attrs, &["labeledURI", "wWWHomePage"])
+        .split_whitespace()
+        .next()
+        .unwrap_or("")
+        .to_string();
+
+    let tags = if source_tag.trim().is_empty() {
+        vec![]
+    } else {
+        vec![source_tag.trim().to_string()]
+    };
+
+    Some(CardInput {
+        name,
+        title: first(attrs, &["title"]),
+        company: first(attrs, &["o", "company"]),
+        department: first(attrs, &["department"]),
+        website,
+        notes: first(attrs, &["description"]),
+        phones,
+        emails,
+        addresses,
+        tags,
+        version: None,
+        ..Default::default()
+    })
+}
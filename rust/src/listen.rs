@@ -0,0 +1,112 @@
+use std::future::Future;
+use std::net::SocketAddr;
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+use axum::Router;
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo},
+    server::{conn::auto, graceful::GracefulShutdown},
+    service::TowerToHyperService,
+};
+use tokio::net::UnixListener;
+use tracing::debug;
+
+/// Where the server accepts connections.
+#[derive(Clone, Debug)]
+pub enum Listen {
+    /// An address and port, e.g. `127.0.0.1:8080`
+    Tcp(SocketAddr),
+    /// A Unix domain socket, given as `unix:/run/cardvault.sock`
+    Unix(PathBuf),
+}
+
+impl FromStr for Listen {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.strip_prefix("unix:") {
+            Some("") => Err("`unix:` needs a socket path".to_string()),
+            Some(path) => Ok(Listen::Unix(PathBuf::from(path))),
+            None => s
+                .parse()
+                .map(Listen::Tcp)
+                .map_err(|_| format!("`{s}` is neither `unix:<path>` nor an address like 127.0.0.1:8080")),
+        }
+    }
+}
+
+/// Parses a file mode given in octal, e.g. `660`.
+pub fn parse_mode(s: &str) -> Result<u32, String> {
+    u32::from_str_radix(s, 8)
+        .ok()
+        .filter(|mode| *mode <= 0o777)
+        .ok_or_else(|| format!("`{s}` is not an octal file mode like 660"))
+}
+
+/// Binds a socket at `path` that only ever has permissions `mode`. A new
+/// socket gets its permissions from the umask, so it is bound in a
+/// directory only this user can enter, given `mode`, and then renamed into
+/// place, replacing any socket already there.
+fn bind_with_mode(path: &Path, mode: u32) -> Result<UnixListener> {
+    let name = path.file_name().context("--listen unix: must name a socket file")?;
+    let mut private = name.to_os_string();
+    private.push(format!(".{}.tmp", std::process::id()));
+    let private = path.with_file_name(private);
+    std::fs::DirBuilder::new()
+        .mode(0o700)
+        .create(&private)
+        .with_context(|| format!("create {}", private.display()))?;
+    let bound = (|| {
+        let staged = private.join(name);
+        let listener = UnixListener::bind(&staged).with_context(|| format!("bind {}", path.display()))?;
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(mode))?;
+        std::fs::rename(&staged, path).with_context(|| format!("move socket to {}", path.display()))?;
+        Ok(listener)
+    })();
+    let _ = std::fs::remove_dir_all(&private);
+    bound
+}
+
+/// Serves `app` on a Unix domain socket at `path`, with permissions `mode`,
+/// until `shutdown` completes; open connections are then let finish. A
+/// socket left behind by an earlier run is replaced, and the socket is
+/// removed on the way out.
+pub async fn serve_unix(path: &Path, mode: u32, app: Router, shutdown: impl Future<Output = ()>) -> Result<()> {
+    if let Ok(meta) = std::fs::symlink_metadata(path) {
+        anyhow::ensure!(meta.file_type().is_socket(), "{} exists and is not a socket", path.display());
+    }
+    let listener = bind_with_mode(path, mode)?;
+
+    let builder = auto::Builder::new(TokioExecutor::new());
+    let graceful = GracefulShutdown::new();
+    tokio::pin!(shutdown);
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let stream = match accepted {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        debug!("accept on {}: {e}", path.display());
+                        continue;
+                    }
+                };
+                let service = TowerToHyperService::new(app.clone());
+                let connection = builder.serve_connection_with_upgrades(TokioIo::new(stream), service).into_owned();
+                let connection = graceful.watch(connection);
+                tokio::spawn(async move {
+                    if let Err(e) = connection.await {
+                        debug!("connection error: {e}");
+                    }
+                });
+            }
+            _ = &mut shutdown => break,
+        }
+    }
+    drop(listener);
+    graceful.shutdown().await;
+    let _ = std::fs::remove_file(path);
+    Ok(())
+}
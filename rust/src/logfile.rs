@@ -0,0 +1,124 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use tracing_appender::{
+    non_blocking::{NonBlocking, WorkerGuard},
+    rolling::{RollingFileAppender, Rotation},
+};
+
+/// When the log file is started afresh.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogRotation {
+    /// A file per day, named with the date
+    Daily,
+    /// A file per hour, named with the date and hour
+    Hourly,
+    /// When the file reaches `--log-max-size-mb`; older files get `.1`, `.2`, ...
+    Size,
+    /// One file that keeps growing
+    Never,
+}
+
+pub struct LogFileConfig {
+    pub path: PathBuf,
+    pub rotation: LogRotation,
+    /// Size a file may reach, for `LogRotation::Size`
+    pub max_size: u64,
+    /// Rotated files kept besides the current one
+    pub max_files: usize,
+}
+
+/// A writer to the log file that does the writing on its own thread, so
+/// requests never wait on the disk. Lines still queued are written when the
+/// guard is dropped.
+pub fn writer(config: &LogFileConfig) -> Result<(NonBlocking, WorkerGuard)> {
+    let dir = match config.path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    fs::create_dir_all(dir).with_context(|| format!("create log directory {}", dir.display()))?;
+    let name = config
+        .path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .context("--log-file must name a file")?;
+    let rotation = match config.rotation {
+        LogRotation::Daily => Rotation::DAILY,
+        LogRotation::Hourly => Rotation::HOURLY,
+        LogRotation::Never => Rotation::NEVER,
+        LogRotation::Size => {
+            let file = SizeRotating::open(config.path.clone(), config.max_size, config.max_files)?;
+            return Ok(tracing_appender::non_blocking(file));
+        }
+    };
+    let appender = RollingFileAppender::builder()
+        .rotation(rotation)
+        .filename_prefix(name)
+        .max_log_files(config.max_files + 1)
+        .build(dir)
+        .context("open log file")?;
+    Ok(tracing_appender::non_blocking(appender))
+}
+
+/// A log file that is moved aside to `<path>.1` once it would grow past
+/// `max_size`, shifting older ones up to `<path>.<max_files>` and dropping
+/// the oldest.
+struct SizeRotating {
+    path: PathBuf,
+    max_size: u64,
+    max_files: usize,
+    file: File,
+    size: u64,
+}
+
+impl SizeRotating {
+    fn open(path: PathBuf, max_size: u64, max_files: usize) -> Result<Self> {
+        let file = append(&path).with_context(|| format!("open log file {}", path.display()))?;
+        let size = file.metadata()?.len();
+        Ok(SizeRotating { path, max_size, max_files, file, size })
+    }
+
+    fn rotated(&self, n: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{n}"));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.max_files == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            let _ = fs::remove_file(self.rotated(self.max_files));
+            for n in (1..self.max_files).rev() {
+                let _ = fs::rename(self.rotated(n), self.rotated(n + 1));
+            }
+            fs::rename(&self.path, self.rotated(1))?;
+        }
+        self.file = append(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+fn append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+impl Write for SizeRotating {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.size > 0 && self.size + buf.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
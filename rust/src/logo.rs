@@ -0,0 +1,188 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use reqwest::Url;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Icons are small; a home page can be large but the `<link>`s are near
+/// the top.
+const MAX_ICON_SIZE: usize = 1024 * 1024;
+const MAX_PAGE_SIZE: usize = 2 * 1024 * 1024;
+
+/// A downloaded logo: its file extension, bytes, and where it came from.
+pub struct Logo {
+    pub ext: &'static str,
+    pub data: Vec<u8>,
+    pub source_url: String,
+}
+
+/// Finds a company's logo on its website: the home page's
+/// `apple-touch-icon` (usually the largest), then its `icon` links, then
+/// `/apple-touch-icon.png` and `/favicon.ico`.
+pub struct LogoFetcher {
+    client: reqwest::Client,
+}
+
+impl LogoFetcher {
+    pub fn new() -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(TIMEOUT)
+            .user_agent(concat!("CardVault/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .expect("failed to build logo HTTP client");
+        Self { client }
+    }
+
+    /// The first icon the site has, `None` if it has none. Errors only if
+    /// the site cannot be reached at all.
+    pub async fn fetch(&self, website: &str) -> Result<Option<Logo>> {
+        let website = website.trim();
+        let page_url = if website.contains("://") {
+            Url::parse(website)
+        } else {
+            Url::parse(&format!("https://{website}"))
+        }
+        .context("website is not a URL")?;
+        if !matches!(page_url.scheme(), "http" | "https") {
+            anyhow::bail!("website is not an http(s) URL");
+        }
+
+        let page = self.client.get(page_url.clone()).send().await.context("website unreachable")?;
+        // Redirects (to `www.`, to https) move the base for relative links.
+        let base = page.url().clone();
+        let mut candidates = Vec::new();
+        if page.status().is_success() {
+            let (html, _) = read_capped(page, MAX_PAGE_SIZE).await?;
+            let html = String::from_utf8_lossy(&html);
+            candidates.extend(icon_links(&html).iter().filter_map(|href| base.join(href).ok()));
+        }
+        for path in ["/apple-touch-icon.png", "/favicon.ico"] {
+            candidates.extend(base.join(path));
+        }
+        candidates.dedup();
+
+        for url in candidates {
+            if !matches!(url.scheme(), "http" | "https") {
+                continue;
+            }
+            let Ok(response) = self.client.get(url.clone()).send().await else {
+                continue;
+            };
+            if !response.status().is_success()
+                || response.content_length().is_some_and(|len| len > MAX_ICON_SIZE as u64)
+            {
+                continue;
+            }
+            let Ok((data, true)) = read_capped(response, MAX_ICON_SIZE).await else {
+                continue;
+            };
+            // Sites answer missing icons with their HTML 404 page as 200,
+            // so the bytes decide, not the content type.
+            if let Some(ext) = image_ext(&data) {
+                return Ok(Some(Logo { ext, data, source_url: url.to_string() }));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// The body's first `limit` bytes, and whether that is all of it. Reading
+/// stops there, so a huge or endless body costs no more than `limit`.
+async fn read_capped(mut response: reqwest::Response, limit: usize) -> reqwest::Result<(Vec<u8>, bool)> {
+    let mut data = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        let room = limit - data.len();
+        if chunk.len() > room {
+            data.extend_from_slice(&chunk[..room]);
+            return Ok((data, false));
+        }
+        data.extend_from_slice(&chunk);
+    }
+    Ok((data, true))
+}
+
+/// The `href`s of the page's icon `<link>`s, apple-touch-icons first. SVG
+/// icons are skipped: served from the vault's own origin they could run
+/// script.
+fn icon_links(html: &str) -> Vec<String> {
+    let lower = html.to_ascii_lowercase();
+    let mut links = Vec::new();
+    let mut from = 0;
+    while let Some(at) = lower[from..].find("<link") {
+        let start = from + at + "<link".len();
+        let end = lower[start..].find('>').map_or(lower.len(), |i| start + i);
+        from = end;
+        let attrs = attributes(&html[start..end]);
+        let get = |name: &str| attrs.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str());
+        let (Some(rel), Some(href)) = (get("rel"), get("href")) else {
+            continue;
+        };
+        let rel = rel.to_ascii_lowercase();
+        let rank = if rel.contains("apple-touch-icon") {
+            0
+        } else if rel.split_whitespace().any(|r| r == "icon") {
+            1
+        } else {
+            continue;
+        };
+        let svg = get("type").is_some_and(|t| t.contains("svg"))
+            || href.split(['?', '#']).next().unwrap_or_default().ends_with(".svg");
+        if !svg && !href.starts_with("data:") {
+            links.push((rank, href.to_string()));
+        }
+    }
+    links.sort_by_key(|(rank, _)| *rank);
+    links.into_iter().map(|(_, href)| href).collect()
+}
+
+/// `name="value"` pairs of a tag, names lowercased. Values may be single-
+/// or double-quoted or bare.
+fn attributes(tag: &str) -> Vec<(String, String)> {
+    let mut attrs = Vec::new();
+    let mut rest = tag.trim_start();
+    while !rest.is_empty() {
+        let name_end = rest
+            .find(|c: char| c.is_whitespace() || c == '=' || c == '/')
+            .unwrap_or(rest.len());
+        let name = rest[..name_end].to_ascii_lowercase();
+        rest = rest[name_end..].trim_start();
+        let mut value = String::new();
+        if let Some(after) = rest.strip_prefix('=') {
+            let after = after.trim_start();
+            let (v, tail) = match after.chars().next() {
+                Some(q @ ('"' | '\'')) => {
+                    let body = &after[1..];
+                    let close = body.find(q).unwrap_or(body.len());
+                    (&body[..close], body.get(close + 1..).unwrap_or_default())
+                }
+                _ => {
+                    let close = after.find(char::is_whitespace).unwrap_or(after.len());
+                    (&after[..close], &after[close..])
+                }
+            };
+            value = v.to_string();
+            rest = tail;
+        } else if name.is_empty() {
+            // A stray `/` (self-closing tag).
+            rest = &rest[1.min(rest.len())..];
+        }
+        if !name.is_empty() {
+            attrs.push((name, value));
+        }
+        rest = rest.trim_start();
+    }
+    attrs
+}
+
+/// The file extension for image bytes, by their magic number.
+fn image_ext(data: &[u8]) -> Option<&'static str> {
+    match data {
+        [0x89, b'P', b'N', b'G', ..] => Some("png"),
+        [0xFF, 0xD8, 0xFF, ..] => Some("jpg"),
+        [b'G', b'I', b'F', b'8', ..] => Some("gif"),
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some("webp"),
+        [0, 0, 1, 0, ..] => Some("ico"),
+        _ => None,
+    }
+}
@@ -0,0 +1,236 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use clap::ValueEnum;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio_rustls::{
+    rustls::{self, pki_types::ServerName},
+    TlsConnector,
+};
+
+/// Gives up on an unresponsive server rather than stalling the caller.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How the connection to the SMTP server is secured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SmtpSecurity {
+    /// Plain connection upgraded with STARTTLS (usually port 587)
+    Starttls,
+    /// TLS from the first byte (usually port 465)
+    Tls,
+    /// No encryption; only for a relay on a trusted network
+    None,
+}
+
+#[derive(Debug, Clone)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub security: SmtpSecurity,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Envelope and `From:` address
+    pub from: String,
+}
+
+/// An email with a plain-text body, optionally an HTML alternative, and
+/// any attachments.
+#[derive(Debug, Clone)]
+pub struct Message {
+    pub to: Vec<String>,
+    pub subject: String,
+    pub text: String,
+    pub html: Option<String>,
+    pub attachments: Vec<Attachment>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Attachment {
+    pub filename: String,
+    pub mime: String,
+    pub data: Vec<u8>,
+}
+
+trait Stream: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Stream for T {}
+
+/// Delivers `message` through the configured server, authenticating with
+/// AUTH PLAIN when a username is set.
+pub async fn send(config: &SmtpConfig, message: &Message) -> Result<()> {
+    tokio::time::timeout(TIMEOUT, deliver(config, message))
+        .await
+        .context("SMTP server timed out")?
+}
+
+async fn deliver(config: &SmtpConfig, message: &Message) -> Result<()> {
+    if message.to.is_empty() {
+        bail!("email has no recipients");
+    }
+    let tcp = TcpStream::connect((config.host.as_str(), config.port))
+        .await
+        .with_context(|| format!("connect to {}:{}", config.host, config.port))?;
+    let stream: Box<dyn Stream> = match config.security {
+        SmtpSecurity::Tls => Box::new(tls(&config.host, tcp).await?),
+        _ => Box::new(tcp),
+    };
+    let mut session = Session { stream: BufReader::new(stream) };
+    session.expect(2).await?;
+    session.command("EHLO cardvault", 2).await?;
+
+    if config.security == SmtpSecurity::Starttls {
+        session.command("STARTTLS", 2).await?;
+        let stream = tls(&config.host, session.stream.into_inner()).await?;
+        session = Session { stream: BufReader::new(Box::new(stream)) };
+        session.command("EHLO cardvault", 2).await?;
+    }
+
+    if let Some(username) = &config.username {
+        let password = config.password.as_deref().unwrap_or("");
+        let credentials = BASE64.encode(format!("\0{username}\0{password}"));
+        session
+            .command(&format!("AUTH PLAIN {credentials}"), 2)
+            .await
+            .context("SMTP authentication failed")?;
+    }
+
+    session.command(&format!("MAIL FROM:<{}>", config.from), 2).await?;
+    for to in &message.to {
+        session.command(&format!("RCPT TO:<{to}>"), 2).await?;
+    }
+    session.command("DATA", 3).await?;
+    session.write(&format(config, message)).await?;
+    session.command(".", 2).await?;
+    // The message is accepted at this point; a failed QUIT changes nothing.
+    let _ = session.command("QUIT", 2).await;
+    Ok(())
+}
+
+async fn tls<S: Stream + 'static>(host: &str, stream: S) -> Result<impl Stream> {
+    let mut roots = rustls::RootCertStore::empty();
+    roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    let config = rustls::ClientConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()?
+    .with_root_certificates(roots)
+    .with_no_client_auth();
+    let name = ServerName::try_from(host.to_string())?;
+    let stream = TlsConnector::from(Arc::new(config))
+        .connect(name, stream)
+        .await
+        .with_context(|| format!("TLS handshake with {host}"))?;
+    Ok(stream)
+}
+
+struct Session {
+    stream: BufReader<Box<dyn Stream>>,
+}
+
+impl Session {
+    async fn write(&mut self, data: &str) -> Result<()> {
+        self.stream.get_mut().write_all(data.as_bytes()).await?;
+        self.stream.get_mut().flush().await?;
+        Ok(())
+    }
+
+    /// Reads a (possibly multi-line) reply and checks its class: 2 for
+    /// success, 3 for "go on".
+    async fn expect(&mut self, class: u16) -> Result<()> {
+        let mut reply = String::new();
+        loop {
+            let mut line = String::new();
+            if self.stream.read_line(&mut line).await? == 0 {
+                bail!("SMTP server closed the connection");
+            }
+            reply.push_str(&line);
+            // The last line of a reply has a space after the code, the
+            // others a hyphen.
+            if line.as_bytes().get(3) != Some(&b'-') {
+                break;
+            }
+        }
+        let code: u16 = reply.get(..3).and_then(|c| c.parse().ok()).unwrap_or(0);
+        if code / 100 != class {
+            bail!("SMTP server replied: {}", reply.trim_end());
+        }
+        Ok(())
+    }
+
+    async fn command(&mut self, line: &str, class: u16) -> Result<()> {
+        self.write(&format!("{line}\r\n")).await?;
+        self.expect(class).await
+    }
+}
+
+/// The message as sent after DATA: headers, then a multipart/alternative
+/// body, wrapped in multipart/mixed when there are attachments. Parts are
+/// base64 encoded, so no body line can start with a dot.
+fn format(config: &SmtpConfig, message: &Message) -> String {
+    let boundary = format!("cardvault-{}", uuid::Uuid::new_v4().simple());
+    let mixed = format!("cardvault-mixed-{}", uuid::Uuid::new_v4().simple());
+    let domain = config.from.rsplit_once('@').map_or("cardvault", |(_, d)| d);
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let date = chrono::DateTime::from_timestamp(secs as i64, 0).unwrap_or_default().to_rfc2822();
+
+    let mut out = String::new();
+    for (name, value) in [
+        ("From", config.from.clone()),
+        ("To", message.to.join(", ")),
+        ("Subject", header_text(&message.subject)),
+        ("Date", date),
+        ("Message-ID", format!("<{}@{domain}>", uuid::Uuid::new_v4())),
+        ("MIME-Version", "1.0".to_string()),
+    ] {
+        out.push_str(&format!("{name}: {value}\r\n"));
+    }
+    if !message.attachments.is_empty() {
+        out.push_str(&format!("Content-Type: multipart/mixed; boundary=\"{mixed}\"\r\n\r\n"));
+        out.push_str(&format!("--{mixed}\r\n"));
+    }
+    out.push_str(&format!("Content-Type: multipart/alternative; boundary=\"{boundary}\"\r\n\r\n"));
+    let mut bodies = vec![("text/plain", &message.text)];
+    bodies.extend(message.html.as_ref().map(|html| ("text/html", html)));
+    for (mime, body) in bodies {
+        out.push_str(&format!("--{boundary}\r\n"));
+        out.push_str(&format!("Content-Type: {mime}; charset=utf-8\r\n"));
+        push_base64(&mut out, body.as_bytes());
+    }
+    out.push_str(&format!("--{boundary}--\r\n"));
+
+    if !message.attachments.is_empty() {
+        for attachment in &message.attachments {
+            let filename = header_text(&attachment.filename.replace('"', ""));
+            out.push_str(&format!("--{mixed}\r\n"));
+            out.push_str(&format!("Content-Type: {}; name=\"{filename}\"\r\n", attachment.mime));
+            out.push_str(&format!("Content-Disposition: attachment; filename=\"{filename}\"\r\n"));
+            push_base64(&mut out, &attachment.data);
+        }
+        out.push_str(&format!("--{mixed}--\r\n"));
+    }
+    out
+}
+
+/// The transfer-encoding header, a blank line, and `data` in base64 lines.
+fn push_base64(out: &mut String, data: &[u8]) {
+    out.push_str("Content-Transfer-Encoding: base64\r\n\r\n");
+    let encoded = BASE64.encode(data);
+    for chunk in encoded.as_bytes().chunks(76) {
+        out.push_str(std::str::from_utf8(chunk).unwrap_or_default());
+        out.push_str("\r\n");
+    }
+}
+
+/// A header value, RFC 2047 encoded if it is not plain ASCII.
+fn header_text(text: &str) -> String {
+    if text.is_ascii() {
+        text.to_string()
+    } else {
+        format!("=?utf-8?B?{}?=", BASE64.encode(text))
+    }
+}
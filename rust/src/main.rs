@@ -1,21 +1,78 @@
+mod access_log;
+mod attachment;
+mod avatar;
+mod base_path;
+mod body_limit;
+mod client_ip;
+mod compression;
+mod dates;
+mod demo;
+mod digest;
+mod duplicates;
+mod enrich;
+mod events;
+mod extract;
+mod geocode;
+mod graphql;
+mod grpc;
 mod handlers;
+mod history;
+mod ical;
+mod idempotency;
+mod ldap;
+mod listen;
+mod logfile;
+mod logo;
+mod mail;
+mod mecard;
 mod models;
+mod ocr;
+mod openapi;
+mod phone;
+mod photo;
+mod qr;
+mod range;
+mod ratelimit;
+mod read_only;
+mod request_id;
+mod resumable;
+mod scan;
+mod search;
+mod share;
+mod shutdown;
+mod social;
+mod storage;
 mod store;
+mod telemetry;
+mod timeout;
+mod timezone;
+mod tls;
+mod uploads;
+mod validation;
+mod vcard;
+mod versioning;
+mod webhooks;
 
 use std::sync::{Arc, Mutex};
 
 use anyhow::Result;
 use axum::{
-    routing::{get, post},
-    Router,
+    extract::DefaultBodyLimit,
+    http::{header, HeaderName},
+    middleware,
+    routing::{delete, get, patch, post, put},
+    Extension, Router,
 };
-use clap::Parser;
+use anyhow::Context;
+use clap::{Args, Parser, Subcommand};
 use handlers::AppState;
 use rust_embed::RustEmbed;
+use tokio_util::sync::CancellationToken;
 use tower_http::cors::{Any, CorsLayer};
 use tower_http::services::ServeDir;
 use tracing::info;
-use tracing_subscriber::EnvFilter;
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
 
 #[derive(RustEmbed)]
 #[folder = "src/static/"]
@@ -28,33 +85,687 @@ struct Cli {
     #[arg(long, env = "PORT", default_value = "8080")]
     port: u16,
 
+    /// Where to listen instead of every address on `--port`: an address
+    /// like `127.0.0.1:8080`, or a Unix socket like `unix:/run/cardvault.sock`
+    #[arg(long, env = "CARDVAULT_LISTEN")]
+    listen: Option<listen::Listen>,
+
+    /// Permissions of the Unix socket, in octal
+    #[arg(long, env = "CARDVAULT_SOCKET_MODE", default_value = "660", value_parser = listen::parse_mode)]
+    socket_mode: u32,
+
+    /// Path prefix to serve under, e.g. `/cardvault` behind a reverse proxy
+    /// that forwards a sub-path; routes and the URLs handed out carry it
+    #[arg(long, env = "CARDVAULT_BASE_PATH", default_value = "")]
+    base_path: String,
+
+    /// Reverse proxies, as addresses or CIDR networks, comma-separated,
+    /// whose `Forwarded` / `X-Forwarded-For` headers name the client
+    #[arg(long, env = "CARDVAULT_TRUSTED_PROXIES", value_delimiter = ',')]
+    trusted_proxies: Vec<client_ip::Network>,
+
     /// SQLite database path
     #[arg(long, env = "CARDVAULT_DB", default_value = "cardvault.db")]
     db: String,
 
-    /// Directory for uploaded photos
+    /// Directory for uploaded photos and attachments
     #[arg(long, env = "CARDVAULT_UPLOADS", default_value = "uploads")]
     uploads_dir: String,
 
+    /// Largest file, in megabytes, that can be attached to a card
+    #[arg(long, env = "CARDVAULT_ATTACHMENT_MAX_MB", default_value = "20", value_parser = clap::value_parser!(u32).range(1..=1024))]
+    attachment_max_mb: u32,
+
+    /// Largest request body, in megabytes, of requests that carry no file
+    /// (JSON, GraphQL, text imports)
+    #[arg(long, env = "CARDVAULT_MAX_BODY_MB", default_value = "2", value_parser = clap::value_parser!(u32).range(1..=1024))]
+    max_body_mb: u32,
+
+    /// Region (ISO 3166 code, e.g. SG) for phone numbers entered without a
+    /// country code
+    #[arg(long, env = "CARDVAULT_DEFAULT_REGION")]
+    default_region: Option<String>,
+
+    /// Also serve the gRPC CardService on this port
+    #[arg(long, env = "CARDVAULT_GRPC_PORT")]
+    grpc_port: Option<u16>,
+
+    /// Where `POST /cards/:id/photo/fetch-avatar` looks up photos by email
+    #[arg(long, env = "CARDVAULT_AVATARS", value_enum, default_value = "none")]
+    avatars: avatar::AvatarService,
+
+    /// Avatar URL the email hash is appended to, e.g. a self-hosted
+    /// Libravatar's `https://avatars.example.com/avatar/`; overrides
+    /// `--avatars`
+    #[arg(long, env = "CARDVAULT_AVATAR_URL")]
+    avatar_url: Option<String>,
+
+    /// Contact enrichment API for `POST /cards/:id/enrich`; enrichment is
+    /// off unless set
+    #[arg(long, env = "CARDVAULT_ENRICH_URL")]
+    enrich_url: Option<String>,
+
+    /// Bearer token for the enrichment API
+    #[arg(long, env = "CARDVAULT_ENRICH_API_KEY", hide_env_values = true)]
+    enrich_api_key: Option<String>,
+
+    /// Seconds a request may take before it is answered with 503
+    #[arg(long, env = "CARDVAULT_REQUEST_TIMEOUT", default_value = "60", value_parser = clap::value_parser!(u64).range(1..))]
+    request_timeout: u64,
+
+    /// Seconds requests in flight are given to finish once the server is
+    /// told to stop
+    #[arg(long, env = "CARDVAULT_SHUTDOWN_TIMEOUT", default_value = "8")]
+    shutdown_timeout: u64,
+
+    /// Smallest response, in bytes, that is sent compressed (gzip or brotli)
+    /// to clients that accept it
+    #[arg(long, env = "CARDVAULT_COMPRESS_MIN_BYTES", default_value = "1024")]
+    compress_min_bytes: u16,
+
     /// Seed the database with sample data if empty
     #[arg(long, default_value_t = false)]
     seed: bool,
+
+    /// Serve the vault for browsing only: open the database read-only and
+    /// refuse every change with 403
+    #[arg(long, env = "CARDVAULT_READ_ONLY", conflicts_with = "seed")]
+    read_only: bool,
+
+    /// Run as a public demo: replace the vault with the sample cards at
+    /// startup and again every `--demo-reset-minutes`, deleting uploads
+    #[arg(long, env = "CARDVAULT_DEMO", conflicts_with = "read_only")]
+    demo: bool,
+
+    /// Minutes between demo resets
+    #[arg(long, env = "CARDVAULT_DEMO_RESET_MINUTES", default_value = "60", value_parser = clap::value_parser!(u64).range(1..))]
+    demo_reset_minutes: u64,
+
+    #[command(flatten)]
+    mail: MailArgs,
+
+    #[command(flatten)]
+    scan: ScanArgs,
+
+    #[command(flatten)]
+    geo: GeoArgs,
+
+    #[command(flatten)]
+    photos: PhotoArgs,
+
+    #[command(flatten)]
+    storage: StorageArgs,
+
+    #[command(flatten)]
+    telemetry: TelemetryArgs,
+
+    #[command(flatten)]
+    tls: TlsArgs,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// Outgoing email: the digest of overdue reminders and upcoming birthdays,
+/// and cards sent with `POST /cards/:id/send`. Off unless `--smtp-host` is
+/// set.
+#[derive(Args, Debug)]
+struct MailArgs {
+    /// SMTP server to send email through
+    #[arg(long, env = "CARDVAULT_SMTP_HOST")]
+    smtp_host: Option<String>,
+
+    /// SMTP port
+    #[arg(long, env = "CARDVAULT_SMTP_PORT", default_value = "587")]
+    smtp_port: u16,
+
+    /// How the SMTP connection is secured
+    #[arg(long, env = "CARDVAULT_SMTP_SECURITY", value_enum, default_value = "starttls")]
+    smtp_security: mail::SmtpSecurity,
+
+    /// SMTP username (no authentication if omitted)
+    #[arg(long, env = "CARDVAULT_SMTP_USERNAME")]
+    smtp_username: Option<String>,
+
+    /// SMTP password
+    #[arg(long, env = "CARDVAULT_SMTP_PASSWORD", hide_env_values = true)]
+    smtp_password: Option<String>,
+
+    /// Sender address of outgoing email
+    #[arg(long, env = "CARDVAULT_SMTP_FROM")]
+    smtp_from: Option<String>,
+
+    /// Most cards `POST /cards/:id/send` emails per hour
+    #[arg(long, env = "CARDVAULT_SEND_LIMIT", default_value = "20")]
+    send_limit: usize,
+
+    /// Recipients of the digest, comma-separated (no digest if omitted)
+    #[arg(long, env = "CARDVAULT_DIGEST_TO", value_delimiter = ',')]
+    digest_to: Vec<String>,
+
+    /// How often the digest is sent
+    #[arg(long, env = "CARDVAULT_DIGEST", value_enum, default_value = "daily")]
+    digest: digest::DigestFrequency,
+
+    /// Hour of the day (UTC, 0-23) the digest is sent
+    #[arg(long, env = "CARDVAULT_DIGEST_HOUR", default_value = "7", value_parser = clap::value_parser!(u32).range(0..24))]
+    digest_hour: u32,
+}
+
+impl MailArgs {
+    /// The SMTP settings, `None` if no server is configured.
+    fn smtp(&self) -> Result<Option<mail::SmtpConfig>> {
+        let Some(host) = &self.smtp_host else {
+            return Ok(None);
+        };
+        let from = self.smtp_from.clone().context("--smtp-from is required with --smtp-host")?;
+        Ok(Some(mail::SmtpConfig {
+            host: host.clone(),
+            port: self.smtp_port,
+            security: self.smtp_security,
+            username: self.smtp_username.clone(),
+            password: self.smtp_password.clone(),
+            from,
+        }))
+    }
+
+    /// The digest settings, `None` without an SMTP server or recipients.
+    fn digest(&self) -> Result<Option<digest::DigestOptions>> {
+        let Some(smtp) = self.smtp()? else {
+            return Ok(None);
+        };
+        if self.digest_to.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(digest::DigestOptions {
+            smtp,
+            to: self.digest_to.clone(),
+            frequency: self.digest,
+            hour: self.digest_hour,
+        }))
+    }
+}
+
+/// Card scanning and text import: how `POST /scan` and `POST
+/// /import/text` turn photos and pasted text into cards.
+#[derive(Args, Debug)]
+struct ScanArgs {
+    /// What reads cards: local OCR and pattern matching, or a language model
+    #[arg(long, env = "CARDVAULT_EXTRACTOR", value_enum, default_value = "heuristic")]
+    extractor: extract::ExtractorKind,
+
+    /// OpenAI-compatible chat completions URL, for `--extractor llm`
+    #[arg(long, env = "CARDVAULT_LLM_URL", default_value = "https://api.openai.com/v1/chat/completions")]
+    llm_url: String,
+
+    /// API key for the language model service
+    #[arg(long, env = "CARDVAULT_LLM_API_KEY", hide_env_values = true)]
+    llm_api_key: Option<String>,
+
+    /// Model name; it must accept images for scanning
+    #[arg(long, env = "CARDVAULT_LLM_MODEL", default_value = "gpt-4o-mini")]
+    llm_model: String,
+
+    /// OCR engine for card scanning
+    #[arg(long, env = "CARDVAULT_OCR", value_enum, default_value = "tesseract")]
+    ocr: ocr::OcrBackend,
+
+    /// tesseract executable, for `--ocr tesseract`
+    #[arg(long, env = "CARDVAULT_TESSERACT", default_value = "tesseract")]
+    tesseract: String,
+
+    /// Languages tesseract reads, joined with `+` (e.g. eng+chi_sim+jpn)
+    #[arg(long, env = "CARDVAULT_OCR_LANGUAGES", default_value = "eng")]
+    ocr_languages: String,
+
+    /// URL the photo is POSTed to, for `--ocr remote`
+    #[arg(long, env = "CARDVAULT_OCR_URL")]
+    ocr_url: Option<String>,
+
+    /// Bearer token for the OCR service
+    #[arg(long, env = "CARDVAULT_OCR_API_KEY", hide_env_values = true)]
+    ocr_api_key: Option<String>,
+}
+
+impl ScanArgs {
+    fn extractor(&self) -> Result<Box<dyn extract::Extractor>> {
+        Ok(match self.extractor {
+            extract::ExtractorKind::Heuristic => Box::new(extract::Heuristic { ocr: self.ocr()? }),
+            extract::ExtractorKind::Llm => Box::new(extract::Llm::new(
+                self.llm_url.clone(),
+                self.llm_api_key.clone(),
+                self.llm_model.clone(),
+            )),
+        })
+    }
+
+    /// The configured OCR engine, `None` if it is off.
+    fn ocr(&self) -> Result<Option<Box<dyn ocr::OcrEngine>>> {
+        Ok(match self.ocr {
+            ocr::OcrBackend::Tesseract => Some(Box::new(ocr::Tesseract {
+                command: self.tesseract.clone(),
+                languages: self.ocr_languages.clone(),
+            })),
+            ocr::OcrBackend::Remote => {
+                let url = self.ocr_url.clone().context("--ocr remote needs --ocr-url")?;
+                Some(Box::new(ocr::RemoteOcr::new(url, self.ocr_api_key.clone())))
+            }
+            ocr::OcrBackend::None => None,
+        })
+    }
+}
+
+/// Address geocoding: resolves card addresses to coordinates in the
+/// background. Off unless `--geocoder` is set.
+#[derive(Args, Debug)]
+struct GeoArgs {
+    /// Geocoding service for card addresses
+    #[arg(long, env = "CARDVAULT_GEOCODER", value_enum, default_value = "none")]
+    geocoder: geocode::GeocoderKind,
+
+    /// Geocoder endpoint, e.g. a self-hosted Nominatim's `/search`;
+    /// defaults to the public service
+    #[arg(long, env = "CARDVAULT_GEOCODER_URL")]
+    geocoder_url: Option<String>,
+
+    /// API key, for `--geocoder google`
+    #[arg(long, env = "CARDVAULT_GEOCODER_API_KEY", hide_env_values = true)]
+    geocoder_api_key: Option<String>,
+}
+
+impl GeoArgs {
+    /// The configured geocoder, `None` if geocoding is off.
+    fn geocoder(&self) -> Result<Option<Box<dyn geocode::Geocoder>>> {
+        let url = self
+            .geocoder_url
+            .clone()
+            .unwrap_or_else(|| self.geocoder.default_url().to_string());
+        Ok(match self.geocoder {
+            geocode::GeocoderKind::Nominatim => Some(Box::new(geocode::Nominatim::new(url))),
+            geocode::GeocoderKind::Google => {
+                let key = self
+                    .geocoder_api_key
+                    .clone()
+                    .context("--geocoder google needs --geocoder-api-key")?;
+                Some(Box::new(geocode::Google::new(url, key)))
+            }
+            geocode::GeocoderKind::None => None,
+        })
+    }
+}
+
+/// Uploaded photos: they are turned upright, stripped of metadata, and
+/// scaled down if larger than allowed before they are stored.
+#[derive(Args, Debug)]
+struct PhotoArgs {
+    /// Largest photo, in megabytes, that can be uploaded
+    #[arg(long, env = "CARDVAULT_MAX_UPLOAD_MB", default_value = "5", value_parser = clap::value_parser!(u32).range(1..=1024))]
+    max_upload_mb: u32,
+
+    /// Longest side, in pixels, of stored photos
+    #[arg(long, env = "CARDVAULT_PHOTO_MAX_DIMENSION", default_value = "2048", value_parser = clap::value_parser!(u32).range(256..))]
+    photo_max_dimension: u32,
+
+    /// JPEG quality (1-100) of photos that are turned or scaled down, and of
+    /// converted HEIC photos
+    #[arg(long, env = "CARDVAULT_PHOTO_QUALITY", default_value = "85", value_parser = clap::value_parser!(u8).range(1..=100))]
+    photo_quality: u8,
+
+    /// libheif's `heif-convert` executable, which turns HEIC photos into JPEG
+    #[arg(long, env = "CARDVAULT_HEIF_CONVERT", default_value = "heif-convert")]
+    heif_convert: String,
+}
+
+/// Where uploaded photos, attachments, and logos are stored: the uploads
+/// directory, or a cloud bucket that outlives the container.
+#[derive(Args, Debug)]
+struct StorageArgs {
+    /// Storage backend for uploaded files
+    #[arg(long, env = "CARDVAULT_STORAGE", value_enum, default_value = "local")]
+    storage: storage::StorageKind,
+
+    /// Bucket, for `--storage s3`
+    #[arg(long, env = "CARDVAULT_S3_BUCKET")]
+    s3_bucket: Option<String>,
+
+    /// Region of the bucket; defaults to `AWS_REGION`, else us-east-1
+    #[arg(long, env = "CARDVAULT_S3_REGION")]
+    s3_region: Option<String>,
+
+    /// Endpoint of an S3-compatible service, e.g. MinIO's
+    /// `http://minio:9000`
+    #[arg(long, env = "CARDVAULT_S3_ENDPOINT")]
+    s3_endpoint: Option<String>,
+
+    /// Access key id; defaults to `AWS_ACCESS_KEY_ID`
+    #[arg(long, env = "CARDVAULT_S3_ACCESS_KEY_ID")]
+    s3_access_key_id: Option<String>,
+
+    /// Secret access key; defaults to `AWS_SECRET_ACCESS_KEY`
+    #[arg(long, env = "CARDVAULT_S3_SECRET_ACCESS_KEY", hide_env_values = true)]
+    s3_secret_access_key: Option<String>,
+
+    /// Address the bucket in the path rather than the host name, as MinIO
+    /// expects
+    #[arg(long, env = "CARDVAULT_S3_PATH_STYLE", default_value_t = false)]
+    s3_path_style: bool,
+
+    /// Storage account; defaults to `AZURE_STORAGE_ACCOUNT_NAME`
+    #[arg(long, env = "CARDVAULT_AZURE_ACCOUNT")]
+    azure_account: Option<String>,
+
+    /// Container, for `--storage azure`
+    #[arg(long, env = "CARDVAULT_AZURE_CONTAINER")]
+    azure_container: Option<String>,
+
+    /// Account access key; defaults to `AZURE_STORAGE_ACCOUNT_KEY`
+    #[arg(long, env = "CARDVAULT_AZURE_ACCESS_KEY", hide_env_values = true)]
+    azure_access_key: Option<String>,
+
+    /// Bucket, for `--storage gcs`
+    #[arg(long, env = "CARDVAULT_GCS_BUCKET")]
+    gcs_bucket: Option<String>,
+
+    /// Service account key file (JSON); defaults to
+    /// `GOOGLE_APPLICATION_CREDENTIALS`
+    #[arg(long, env = "CARDVAULT_GCS_SERVICE_ACCOUNT")]
+    gcs_service_account: Option<String>,
+}
+
+impl StorageArgs {
+    fn blobs(&self, uploads_dir: &str) -> Result<Box<dyn storage::BlobStore>> {
+        Ok(match self.storage {
+            storage::StorageKind::Local => Box::new(storage::LocalStore::new(uploads_dir)),
+            storage::StorageKind::S3 => Box::new(storage::CloudStore::s3(storage::S3Config {
+                bucket: self.s3_bucket.clone().context("--storage s3 needs --s3-bucket")?,
+                region: self.s3_region.clone(),
+                endpoint: self.s3_endpoint.clone(),
+                access_key_id: self.s3_access_key_id.clone(),
+                secret_access_key: self.s3_secret_access_key.clone(),
+                path_style: self.s3_path_style,
+            })?),
+            storage::StorageKind::Azure => Box::new(storage::CloudStore::azure(storage::AzureConfig {
+                account: self.azure_account.clone(),
+                container: self
+                    .azure_container
+                    .clone()
+                    .context("--storage azure needs --azure-container")?,
+                access_key: self.azure_access_key.clone(),
+            })?),
+            storage::StorageKind::Gcs => Box::new(storage::CloudStore::gcs(storage::GcsConfig {
+                bucket: self.gcs_bucket.clone().context("--storage gcs needs --gcs-bucket")?,
+                service_account: self.gcs_service_account.clone(),
+            })?),
+        })
+    }
+}
+
+/// Logging, to stdout and optionally a rotated file, and trace export:
+/// spans for requests and the SQL they run, sent over OTLP to a collector
+/// such as Jaeger or Tempo. Export is off unless `--otlp-endpoint` is set.
+#[derive(Args, Debug)]
+struct TelemetryArgs {
+    /// How log lines are written
+    #[arg(long, env = "CARDVAULT_LOG_FORMAT", value_enum, default_value = "pretty")]
+    log_format: telemetry::LogFormat,
+
+    /// Also write logs to this file
+    #[arg(long, env = "CARDVAULT_LOG_FILE")]
+    log_file: Option<std::path::PathBuf>,
+
+    /// When the log file is started afresh
+    #[arg(long, env = "CARDVAULT_LOG_ROTATION", value_enum, default_value = "daily")]
+    log_rotation: logfile::LogRotation,
+
+    /// Size, in megabytes, a log file may reach, for `--log-rotation size`
+    #[arg(long, env = "CARDVAULT_LOG_MAX_SIZE_MB", default_value = "100", value_parser = clap::value_parser!(u64).range(1..))]
+    log_max_size_mb: u64,
+
+    /// Rotated log files kept besides the current one
+    #[arg(long, env = "CARDVAULT_LOG_MAX_FILES", default_value = "7")]
+    log_max_files: usize,
+
+    /// OTLP/gRPC collector to send spans to, e.g. `http://localhost:4317`
+    #[arg(long, env = "OTEL_EXPORTER_OTLP_ENDPOINT")]
+    otlp_endpoint: Option<String>,
+
+    /// Service name the spans are filed under
+    #[arg(long, env = "OTEL_SERVICE_NAME", default_value = "cardvault")]
+    otlp_service_name: String,
+}
+
+impl TelemetryArgs {
+    fn log_file(&self) -> Option<logfile::LogFileConfig> {
+        Some(logfile::LogFileConfig {
+            path: self.log_file.clone()?,
+            rotation: self.log_rotation,
+            max_size: self.log_max_size_mb * 1024 * 1024,
+            max_files: self.log_max_files,
+        })
+    }
+
+    fn otlp(&self) -> Option<telemetry::OtlpConfig> {
+        Some(telemetry::OtlpConfig {
+            endpoint: self.otlp_endpoint.clone()?,
+            service_name: self.otlp_service_name.clone(),
+        })
+    }
+}
+
+/// HTTPS: served on `--port` when a certificate and key are given, so the
+/// vault need not sit behind a proxy just for encryption.
+#[derive(Args, Debug)]
+struct TlsArgs {
+    /// PEM certificate chain, server certificate first
+    #[arg(long, env = "CARDVAULT_TLS_CERT", requires = "tls_key")]
+    tls_cert: Option<std::path::PathBuf>,
+
+    /// PEM private key of the certificate
+    #[arg(long, env = "CARDVAULT_TLS_KEY", requires = "tls_cert")]
+    tls_key: Option<std::path::PathBuf>,
+
+    /// PEM file of CA certificates; only clients presenting a certificate
+    /// one of them issued can connect (mutual TLS)
+    #[arg(long, env = "CARDVAULT_TLS_CLIENT_CA", requires = "tls_cert")]
+    tls_client_ca: Option<std::path::PathBuf>,
+
+    /// Also listen for plain HTTP on this port, e.g. 80, and redirect it to
+    /// HTTPS
+    #[arg(long, env = "CARDVAULT_HTTP_REDIRECT_PORT", requires = "tls_cert")]
+    http_redirect_port: Option<u16>,
+}
+
+impl TlsArgs {
+    /// The HTTPS settings, `None` to serve plain HTTP.
+    fn server_config(&self) -> Result<Option<Arc<tokio_rustls::rustls::ServerConfig>>> {
+        match (&self.tls_cert, &self.tls_key) {
+            (Some(cert), Some(key)) => Ok(Some(tls::server_config(cert, key, self.tls_client_ca.as_deref())?)),
+            _ => Ok(None),
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Import people from an LDAP / Active Directory server, then exit
+    ImportLdap {
+        /// LDAP server URL, e.g. ldaps://ldap.example.com:636
+        #[arg(long, env = "CARDVAULT_LDAP_URL")]
+        url: String,
+
+        /// DN to bind as (anonymous bind if omitted)
+        #[arg(long, env = "CARDVAULT_LDAP_BIND_DN")]
+        bind_dn: Option<String>,
+
+        /// Bind password
+        #[arg(long, env = "CARDVAULT_LDAP_BIND_PASSWORD", hide_env_values = true)]
+        bind_password: Option<String>,
+
+        /// Search base, e.g. ou=people,dc=example,dc=com
+        #[arg(long, env = "CARDVAULT_LDAP_BASE_DN")]
+        base_dn: String,
+
+        /// Search filter
+        #[arg(long, default_value = "(objectClass=inetOrgPerson)")]
+        filter: String,
+
+        /// Tag applied to every imported card
+        #[arg(long, default_value = "ldap")]
+        source_tag: String,
+    },
+    /// Send the email digest now, then exit
+    SendDigest,
+}
+
+/// Routes served under `/api/v1` (and, for compatibility, `/api`).
+fn api_v1(state: Arc<AppState>, request_timeout: std::time::Duration) -> Router<Arc<AppState>> {
+    // Forms that may carry a photo, with room for the multipart framing
+    let photo_limit = DefaultBodyLimit::max(state.photo_max_size + body_limit::MULTIPART_FRAMING);
+    let read_only = state.read_only;
+    let router = Router::new()
+        // Cards
+        .route(
+            "/cards",
+            get(handlers::list_cards).post(handlers::create_card).layer(photo_limit),
+        )
+        .route(
+            "/cards/:id",
+            get(handlers::get_card)
+                .put(handlers::update_card)
+                .patch(handlers::patch_card)
+                .delete(handlers::delete_card)
+                .layer(photo_limit),
+        )
+        .route("/cards/:id/favorite", post(handlers::toggle_favorite))
+        .route("/cards/:id/archive", post(handlers::archive_card))
+        .route("/cards/:id/unarchive", post(handlers::unarchive_card))
+        .route("/cards/:id/merge", post(handlers::merge_cards))
+        .route("/cards/:id/org", get(handlers::org_chart))
+        .route("/cards/:id/share", post(handlers::create_share))
+        .route("/cards/:id/send", post(handlers::send_card))
+        .route("/cards/:id/qr", get(handlers::card_qr))
+        .route("/import/qr", post(handlers::import_qr))
+        .route("/import/text", post(handlers::import_text))
+        .route("/parse/signature", post(handlers::parse_signature))
+        .route("/scan", post(handlers::scan_card).layer(photo_limit))
+        .route("/cards/:id/history", get(handlers::card_history))
+        .route("/cards/:id/interactions", get(handlers::list_interactions).post(handlers::create_interaction))
+        .route("/cards/:id/interactions/:interaction_id", put(handlers::update_interaction).delete(handlers::delete_interaction))
+        .route("/cards/:id/reminders", get(handlers::list_reminders).post(handlers::create_reminder))
+        .route("/cards/:id/reminders/:reminder_id", put(handlers::update_reminder).delete(handlers::delete_reminder))
+        .route("/reminders", get(handlers::due_reminders))
+        .route("/reminders.ics", get(handlers::reminders_calendar))
+        .route("/cards/:id/revert/:rev", post(handlers::revert_card))
+        .route("/cards/bulk/tags", post(handlers::bulk_tag_cards))
+        .route("/cards/upcoming", get(handlers::upcoming))
+        .route("/cards/nearby", get(handlers::nearby))
+        .route("/undo", post(handlers::undo))
+        // Photos
+        .route(
+            "/cards/:id/photo",
+            post(handlers::upload_photo).delete(handlers::delete_photo).layer(photo_limit),
+        )
+        .route("/cards/:id/photo/fetch-avatar", post(handlers::fetch_avatar))
+        .route("/cards/:id/photo/thumb", get(handlers::photo_thumb))
+        .route(
+            "/cards/:id/photos",
+            get(handlers::list_photos).post(handlers::add_photo).layer(photo_limit),
+        )
+        .route(
+            "/cards/:id/photos/:photo_id",
+            patch(handlers::update_photo).delete(handlers::remove_photo),
+        )
+        // Attachments
+        .route("/cards/:id/attachments", get(handlers::list_attachments))
+        .route(
+            "/cards/:id/attachments/:attachment_id",
+            get(handlers::download_attachment).delete(handlers::remove_attachment),
+        )
+        // Resumable uploads, sent in chunks and then named in a photo or
+        // attachment form
+        .route("/uploads", post(handlers::create_upload))
+        .route("/uploads/:upload_id", get(handlers::get_upload).delete(handlers::cancel_upload))
+        .route("/cards/:id/fetch-logo", post(handlers::fetch_logo))
+        .route("/cards/:id/enrich", post(handlers::enrich_card))
+        .route("/cards/:id/enrich/accept", post(handlers::accept_enrichment))
+        .route("/geocode/backfill", post(handlers::geocode_backfill))
+        // Tags
+        .route("/tags", get(handlers::list_tags))
+        .route("/tags/merge", post(handlers::merge_tags))
+        .route("/tags/:name", put(handlers::update_tag).delete(handlers::delete_tag))
+        .route("/tags/:name/aliases", post(handlers::add_tag_alias))
+        .route("/tags/:name/aliases/:alias", delete(handlers::delete_tag_alias))
+        .route("/autocomplete", get(handlers::autocomplete))
+        // Stats
+        .route("/stats", get(handlers::stats))
+        .route("/duplicates", get(handlers::duplicates))
+        // Custom fields
+        .route("/schema", get(handlers::get_schema))
+        .route("/schema/fields/:key", put(handlers::put_schema_field).delete(handlers::delete_schema_field))
+        // Webhooks
+        .route("/webhooks", get(handlers::list_webhooks).post(handlers::create_webhook))
+        .route("/webhooks/:id", get(handlers::get_webhook).put(handlers::update_webhook).delete(handlers::delete_webhook))
+        .layer(middleware::from_fn_with_state(request_timeout, timeout::middleware))
+        // Uploads of attachments and chunks, and the change feed, last as
+        // long as their body or stream does, so they are left out of the
+        // request timeout. Attachments get room for the multipart framing
+        .route(
+            "/cards/:id/attachments",
+            post(handlers::add_attachment)
+                .layer(DefaultBodyLimit::max(state.attachment_max_size + body_limit::MULTIPART_FRAMING)),
+        )
+        .route(
+            "/uploads/:upload_id",
+            patch(handlers::append_upload).layer(DefaultBodyLimit::max(resumable::MAX_CHUNK)),
+        )
+        // Change feed
+        .route("/events", get(handlers::events_feed))
+        // Retried mutations with an Idempotency-Key replay the first response
+        .layer(middleware::from_fn_with_state(state, idempotency::middleware));
+    if read_only {
+        router.layer(middleware::from_fn(read_only::middleware))
+    } else {
+        router
+    }
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    tracing_subscriber::fmt()
-        .with_env_filter(EnvFilter::from_default_env().add_directive("cardvault=info".parse()?))
-        .init();
-
     let cli = Cli::parse();
+    let telemetry = telemetry::init(
+        cli.telemetry.log_format,
+        cli.telemetry.log_file().as_ref(),
+        cli.telemetry.otlp().as_ref(),
+    )?;
+    if let Some(region) = &cli.default_region {
+        phone::set_default_region(region)?;
+    }
+    base_path::set(&cli.base_path)?;
+    let tls_config = cli.tls.server_config()?;
+    let listen = cli
+        .listen
+        .clone()
+        .unwrap_or(listen::Listen::Tcp(([0, 0, 0, 0], cli.port).into()));
+    if matches!(listen, listen::Listen::Unix(_)) && tls_config.is_some() {
+        anyhow::bail!("--tls-cert cannot be used with a Unix socket");
+    }
 
     // Open SQLite connection
-    let connection = rusqlite::Connection::open(&cli.db)?;
+    let mut connection = if cli.read_only {
+        rusqlite::Connection::open_with_flags(&cli.db, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .with_context(|| format!("open {} read-only", cli.db))?
+    } else {
+        rusqlite::Connection::open(&cli.db)?
+    };
+    connection.profile(Some(telemetry::record_statement));
     let conn = Arc::new(Mutex::new(connection));
 
-    // Initialize schema
-    store::init_db(&conn)?;
+    // Initialize schema, which a read-only database must already have
+    if cli.read_only {
+        store::init_read_only(&conn)?;
+    } else {
+        store::init_db(&conn)?;
+    }
 
     // Seed if requested and DB is empty
     if cli.seed && store::is_empty(&conn) {
@@ -63,6 +774,39 @@ async fn main() -> Result<()> {
         info!("Seeded 10 contacts.");
     }
 
+    let smtp = cli.mail.smtp()?;
+    let digest_opts = cli.mail.digest()?;
+    if let Some(Command::SendDigest) = cli.command {
+        let opts = digest_opts.context("the digest needs --smtp-host and --digest-to")?;
+        digest::send(&conn, &opts).await?;
+        return Ok(());
+    }
+
+    if let Some(Command::ImportLdap {
+        url,
+        bind_dn,
+        bind_password,
+        base_dn,
+        filter,
+        source_tag,
+    }) = cli.command
+    {
+        let opts = ldap::LdapImportOptions {
+            url,
+            bind_dn,
+            bind_password,
+            base_dn,
+            filter,
+            source_tag,
+        };
+        let summary = ldap::import(&conn, &opts).await?;
+        info!(
+            "LDAP import finished: {} imported, {} skipped",
+            summary.imported, summary.skipped
+        );
+        return Ok(());
+    }
+
     // Ensure uploads directory exists
     tokio::fs::create_dir_all(&cli.uploads_dir).await?;
 
@@ -76,49 +820,189 @@ async fn main() -> Result<()> {
     }
 
     let state = Arc::new(AppState {
-        conn,
+        conn: conn.clone(),
         uploads_dir: cli.uploads_dir.clone(),
+        uploads: Box::new(uploads::Uploads::new(cli.storage.blobs(&cli.uploads_dir)?)),
+        webhooks: webhooks::WebhookDispatcher::new(conn.clone()),
+        events: events::EventBus::new(256),
+        smtp,
+        send_limit: ratelimit::RateLimiter::new(cli.mail.send_limit, std::time::Duration::from_secs(3600)),
+        extractor: cli.scan.extractor()?,
+        avatars: cli
+            .avatar_url
+            .clone()
+            .or_else(|| cli.avatars.base_url().map(String::from))
+            .map(avatar::AvatarLookup::new),
+        logos: logo::LogoFetcher::new(),
+        geocoder: cli.geo.geocoder()?.map(|geocoder| geocode::GeocodeQueue::spawn(conn.clone(), geocoder)),
+        enricher: cli.enrich_url.clone().map(|url| {
+            Box::new(enrich::HttpEnricher::new(url, cli.enrich_api_key.clone())) as Box<dyn enrich::Enricher>
+        }),
+        photo_limits: photo::Limits {
+            max_dimension: cli.photos.photo_max_dimension,
+            quality: cli.photos.photo_quality,
+        },
+        heif_convert: cli.photos.heif_convert.clone(),
+        attachment_max_size: cli.attachment_max_mb as usize * 1024 * 1024,
+        photo_max_size: cli.photos.max_upload_mb as usize * 1024 * 1024,
+        max_body_size: cli.max_body_mb as usize * 1024 * 1024,
+        read_only: cli.read_only,
     });
 
-    // CORS: allow all
+    let schema = graphql::build_schema(state.clone());
+
+    if cli.demo {
+        demo::reset(&state).await?;
+        demo::spawn(state.clone(), std::time::Duration::from_secs(cli.demo_reset_minutes * 60));
+    }
+
+    if let Some(opts) = digest_opts {
+        digest::spawn(conn.clone(), opts);
+    }
+
+    // Cancelled on Ctrl-C or SIGTERM, when the servers stop taking
+    // connections and let the ones open finish
+    let stopping = CancellationToken::new();
+    shutdown::on_signal(stopping.clone());
+
+    let grpc = match cli.grpc_port {
+        Some(grpc_port) => {
+            let addr = format!("0.0.0.0:{grpc_port}").parse()?;
+            let service = grpc::service(state.clone());
+            info!("CardVault gRPC listening on {addr}");
+            let stopped = stopping.clone().cancelled_owned();
+            Some(tokio::spawn(async move {
+                if let Err(e) = tonic::transport::Server::builder()
+                    .add_service(service)
+                    .serve_with_shutdown(addr, stopped)
+                    .await
+                {
+                    tracing::error!("gRPC server error: {e}");
+                }
+            }))
+        }
+        None => None,
+    };
+
+    // CORS: allow all, and let browser clients read the paging,
+    // concurrency, and partial download headers (they are hidden
+    // cross-origin otherwise)
     let cors = CorsLayer::new()
         .allow_origin(Any)
         .allow_methods(Any)
-        .allow_headers(Any);
+        .allow_headers(Any)
+        .expose_headers([
+            HeaderName::from_static("x-total-count"),
+            HeaderName::from_static("x-next-cursor"),
+            header::ETAG,
+            header::CONTENT_RANGE,
+            request_id::X_REQUEST_ID.clone(),
+        ]);
 
-    let app = Router::new()
+    let request_timeout = std::time::Duration::from_secs(cli.request_timeout);
+    let routes = Router::new()
         // Index
         .route("/", get(handlers::serve_index))
         // Static assets (CSS, JS) — served from extracted temp dir
         .nest_service("/static", ServeDir::new(&static_dir))
         // Uploads
-        .route("/uploads/{filename}", get(handlers::serve_uploads))
+        .route("/uploads/:filename", get(handlers::serve_uploads))
+        // Public pages of shared cards
+        .route("/share/:token", get(handlers::share_page))
+        .route("/share/:token/vcard", get(handlers::share_vcard))
         // Health
         .route("/health", get(handlers::health))
-        // Cards
-        .route("/api/cards", get(handlers::list_cards).post(handlers::create_card))
-        .route("/api/cards/:id", get(handlers::get_card).put(handlers::update_card).delete(handlers::delete_card))
-        // Photos
-        .route("/api/cards/:id/photo", post(handlers::upload_photo).delete(handlers::delete_photo))
-        // Tags
-        .route("/api/tags", get(handlers::list_tags))
+        // GraphQL
+        .route("/graphql", get(handlers::graphiql).post(handlers::graphql))
+        .layer(middleware::from_fn_with_state(request_timeout, timeout::middleware))
+        // REST API, versioned, plus the unprefixed compatibility alias,
+        // which time their own routes
+        .nest("/api/v1", api_v1(state.clone(), request_timeout).layer(middleware::from_fn(versioning::v1)))
+        .nest("/api", api_v1(state.clone(), request_timeout).layer(middleware::from_fn(versioning::legacy)))
+        // Anything else is a route of the web UI
+        .fallback(handlers::spa_fallback);
+
+    // Under `--base-path` every route moves below the prefix, and the
+    // OpenAPI document names it as the server so "Try it out" works
+    let mut api_doc = openapi::ApiDoc::openapi();
+    let app = match base_path::get() {
+        "" => routes,
+        base => {
+            api_doc.servers = Some(vec![utoipa::openapi::Server::new(base)]);
+            Router::new()
+                .nest(base, routes)
+                .route(&format!("{base}/"), get(handlers::serve_index))
+        }
+    };
+
+    let app = app
+        // OpenAPI document + Swagger UI
+        .merge(SwaggerUi::new(base_path::url("/api/docs")).url(base_path::url("/api/openapi.json"), api_doc))
         // Middleware
+        .layer(DefaultBodyLimit::max(state.max_body_size))
+        .layer(middleware::from_fn(body_limit::json_errors))
+        .layer(Extension(schema))
         .layer(cors)
+        .layer(middleware::from_fn(access_log::middleware))
+        .layer(middleware::from_fn_with_state(
+            Arc::new(client_ip::TrustedProxies::new(cli.trusted_proxies.clone())),
+            client_ip::middleware,
+        ))
+        .layer(middleware::from_fn(request_id::middleware))
+        .layer(compression::layer(cli.compress_min_bytes))
         .with_state(state);
 
-    let addr = format!("0.0.0.0:{}", cli.port);
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
-
-    info!("CardVault listening on http://localhost:{}", cli.port);
-
-    axum::serve(listener, app)
-        .with_graceful_shutdown(async {
-            tokio::signal::ctrl_c()
-                .await
-                .expect("failed to install CTRL+C signal handler");
-            info!("Shutting down CardVault...");
-        })
-        .await?;
+    let shutdown = stopping.clone().cancelled_owned();
+    let serve = async {
+        match (listen, tls_config) {
+            (listen::Listen::Unix(path), _) => {
+                info!("CardVault listening on unix:{}", path.display());
+                listen::serve_unix(&path, cli.socket_mode, app, shutdown).await?;
+            }
+            (listen::Listen::Tcp(addr), None) => {
+                let listener = tokio::net::TcpListener::bind(addr).await?;
+                info!("CardVault listening on http://localhost:{}{}/", addr.port(), base_path::get());
+                axum::serve(listener, app.into_make_service_with_connect_info::<std::net::SocketAddr>())
+                    .with_graceful_shutdown(shutdown)
+                    .await?;
+            }
+            (listen::Listen::Tcp(addr), Some(config)) => {
+                if let Some(redirect_port) = cli.tls.http_redirect_port {
+                    let https_port = addr.port();
+                    tokio::spawn(async move {
+                        if let Err(e) = tls::redirect_http(addr.ip(), redirect_port, https_port).await {
+                            tracing::error!("HTTP redirect server error: {e}");
+                        }
+                    });
+                }
+                let handle = axum_server::Handle::new();
+                tokio::spawn({
+                    let handle = handle.clone();
+                    async move {
+                        shutdown.await;
+                        handle.graceful_shutdown(None);
+                    }
+                });
+                info!("CardVault listening on https://localhost:{}{}/", addr.port(), base_path::get());
+                axum_server::bind_rustls(addr, axum_server::tls_rustls::RustlsConfig::from_config(config))
+                    .handle(handle)
+                    .serve(app.into_make_service_with_connect_info::<std::net::SocketAddr>())
+                    .await?;
+            }
+        }
+        if let Some(grpc) = grpc {
+            grpc.await?;
+        }
+        Ok::<_, anyhow::Error>(())
+    };
+    shutdown::drain(serve, &stopping, std::time::Duration::from_secs(cli.shutdown_timeout)).await?;
 
+    // Leave the database in one file, without a WAL to replay
+    if !cli.read_only {
+        if let Err(e) = store::checkpoint(&conn) {
+            tracing::error!("{e}");
+        }
+    }
+    telemetry.shutdown()?;
     Ok(())
 }
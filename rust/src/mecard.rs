@@ -0,0 +1,180 @@
+use crate::models::{Card, CardFormAddressInput, CardFormEmailInput, CardFormPhoneInput, CardInput};
+
+/// The card as a MECARD. Much shorter than a vCard, so the QR code stays
+/// small, but it only has room for the basics: name and reading, phones,
+/// emails, addresses, birthday, website, and company.
+pub fn render(card: &Card) -> String {
+    let mut out = String::from("MECARD:");
+    let mut field = |name: &str, value: &str| {
+        if !value.is_empty() {
+            out.push_str(&format!("{name}:{value};"));
+        }
+    };
+    let name = if card.family_name.is_empty() || card.given_name.is_empty() {
+        escape(&card.name)
+    } else {
+        format!("{},{}", escape(&card.family_name), escape(&card.given_name))
+    };
+    field("N", &name);
+    field("SOUND", &escape(&card.name_phonetic));
+    for phone in &card.phones {
+        let number = if phone.e164.is_empty() { &phone.number } else { &phone.e164 };
+        field("TEL", &escape(number));
+    }
+    for email in &card.emails {
+        field("EMAIL", &escape(&email.address));
+    }
+    for address in &card.addresses {
+        let parts = ["", "", &address.street, &address.city, "", &address.postal, &address.country];
+        if parts.iter().any(|p| !p.is_empty()) {
+            field("ADR", &parts.map(escape).join(","));
+        }
+    }
+    // MECARD birthdays need the year.
+    if !card.birthday.starts_with("--") {
+        field("BDAY", &card.birthday.replace('-', ""));
+    }
+    field("URL", &escape(&card.website));
+    field("ORG", &escape(&card.company));
+    field("NOTE", &escape(&card.notes));
+    out.push(';');
+    out
+}
+
+fn escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' | ';' | ':' | ',' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\n' | '\r' => out.push(' '),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Reads a MECARD (`MECARD:N:Doe,John;TEL:...;;`), the compact contact
+/// format many QR codes use instead of a vCard. `None` if `text` is not one.
+pub fn parse(text: &str) -> Option<CardInput> {
+    let text = text.trim();
+    let body = text
+        .get(..7)
+        .filter(|prefix| prefix.eq_ignore_ascii_case("MECARD:"))
+        .map(|_| &text[7..])?;
+
+    let mut input = CardInput::default();
+    for field in fields(body) {
+        let Some((name, value)) = field.split_once(':') else {
+            continue;
+        };
+        let raw = value.trim();
+        let value = raw.replace("\\,", ",");
+        if value.is_empty() {
+            continue;
+        }
+        match name.trim().to_uppercase().as_str() {
+            "N" => match split_commas(raw).as_slice() {
+                // `N:Family,Given`
+                [family, given, ..] => {
+                    input.family_name = family.clone();
+                    input.given_name = given.clone();
+                    input.name = format!("{given} {family}").trim().to_string();
+                }
+                _ => input.name = value,
+            },
+            "SOUND" => input.name_phonetic = value.replace(',', " "),
+            "TEL" | "TEL-AV" => input.phones.push(CardFormPhoneInput {
+                label: "mobile".to_string(),
+                number: value,
+            }),
+            "EMAIL" => input.emails.push(CardFormEmailInput {
+                label: "work".to_string(),
+                address: value,
+            }),
+            "ADR" => input.addresses.push(address(raw)),
+            "URL" if input.website.is_empty() => input.website = value,
+            "ORG" => input.company = value,
+            "TITLE" => input.title = value,
+            "NOTE" | "MEMO" => input.notes = value,
+            "BDAY" => {
+                let digits: String = value.chars().filter(char::is_ascii_digit).collect();
+                input.birthday = if digits.len() == 8 {
+                    format!("{}-{}-{}", &digits[..4], &digits[4..6], &digits[6..])
+                } else {
+                    value
+                };
+            }
+            _ => {}
+        }
+    }
+    Some(input)
+}
+
+/// Splits the body at unescaped `;`, unescaping `\;`, `\:` and `\\`.
+fn fields(body: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut current = String::new();
+    let mut chars = body.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some(next) = chars.next() {
+                    // `\,` stays escaped for the fields that split on commas.
+                    if next == ',' {
+                        current.push('\\');
+                    }
+                    current.push(next);
+                }
+            }
+            ';' => fields.push(std::mem::take(&mut current)),
+            c => current.push(c),
+        }
+    }
+    fields.push(current);
+    fields.retain(|f| !f.is_empty());
+    fields
+}
+
+/// A MECARD address is either free text or the seven vCard ADR components
+/// separated by commas.
+fn address(value: &str) -> CardFormAddressInput {
+    let parts: Vec<String> = split_commas(value);
+    if parts.len() < 7 {
+        return CardFormAddressInput {
+            label: "office".to_string(),
+            street: value.replace("\\,", ","),
+            ..Default::default()
+        };
+    }
+    let street = parts[..3]
+        .iter()
+        .filter(|s| !s.is_empty())
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(" ");
+    CardFormAddressInput {
+        label: "office".to_string(),
+        street,
+        city: parts[3].clone(),
+        postal: parts[5].clone(),
+        country: parts[6].clone(),
+    }
+}
+
+fn split_commas(value: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => current.extend(chars.next()),
+            ',' => parts.push(std::mem::take(&mut current).trim().to_string()),
+            c => current.push(c),
+        }
+    }
+    parts.push(current.trim().to_string());
+    parts
+}
@@ -1,20 +1,30 @@
+use std::collections::BTreeMap;
+
+use async_graphql::{InputObject, SimpleObject};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, SimpleObject, ToSchema)]
 pub struct Phone {
     pub id: i64,
     pub label: String,
+    /// As entered
     pub number: String,
+    /// Normalized E.164 form (`+6591234567`), or empty if the number could
+    /// not be parsed
+    #[serde(default)]
+    pub e164: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, SimpleObject, ToSchema)]
 pub struct Email {
     pub id: i64,
     pub label: String,
     pub address: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, SimpleObject, ToSchema)]
 pub struct Address {
     pub id: i64,
     pub label: String,
@@ -22,50 +32,430 @@ pub struct Address {
     pub city: String,
     pub country: String,
     pub postal: String,
+    /// Filled in by the geocoder (`--geocoder`) shortly after the address
+    /// is saved; `null` until then, or if it could not be found
+    #[serde(default)]
+    pub latitude: Option<f64>,
+    #[serde(default)]
+    pub longitude: Option<f64>,
+}
+
+/// What a card photo shows.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, async_graphql::Enum, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PhotoRole {
+    /// The front of the paper card
+    Front,
+    /// The back of the paper card
+    Back,
+    Logo,
+    /// A picture of the contact
+    Headshot,
+}
+
+impl PhotoRole {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PhotoRole::Front => "front",
+            PhotoRole::Back => "back",
+            PhotoRole::Logo => "logo",
+            PhotoRole::Headshot => "headshot",
+        }
+    }
+}
+
+impl std::str::FromStr for PhotoRole {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "front" => PhotoRole::Front,
+            "back" => PhotoRole::Back,
+            "logo" => PhotoRole::Logo,
+            "headshot" => PhotoRole::Headshot,
+            _ => return Err(format!("role must be front, back, logo, or headshot (got '{s}')")),
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, SimpleObject, ToSchema)]
+pub struct CardPhoto {
+    pub id: i64,
+    pub role: PhotoRole,
+    /// Place among the card's photos, from 0; the first is its `photo_url`
+    pub position: i64,
+    pub url: String,
+}
+
+/// Body of `PATCH /cards/:id/photos/:photo_id`; both fields are optional.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PhotoUpdate {
+    pub role: Option<PhotoRole>,
+    /// Moves the photo there, shifting the others; past the end moves it last
+    pub position: Option<i64>,
+}
+
+/// A file attached to a card, such as a brochure or a signed NDA.
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct Attachment {
+    pub id: i64,
+    pub card_id: i64,
+    /// Name it was uploaded under, and is downloaded as
+    pub filename: String,
+    /// Told from the file's contents where they say, else from its name
+    pub mime: String,
+    /// Bytes
+    pub size: i64,
+    /// Where to download it
+    pub url: String,
+    pub created_at: String,
+}
+
+/// Body of `POST /uploads`, which starts a resumable upload.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UploadStart {
+    /// Name of the file; its extension decides how a photo is read
+    pub filename: String,
+    /// Bytes the whole file will have
+    pub size: i64,
+}
+
+/// A resumable upload: a file sent in chunks, then used by naming its `id`
+/// in the `upload` field of a photo or attachment form.
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct UploadSession {
+    pub id: String,
+    pub filename: String,
+    /// Bytes the whole file will have
+    pub size: i64,
+    /// Bytes received so far; the next chunk starts here
+    pub offset: i64,
+    /// Unfinished uploads are dropped a day after this
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, SimpleObject, ToSchema)]
+pub struct Social {
+    pub id: i64,
+    /// Lowercase: `linkedin`, `twitter`, `github`, `wechat`, ...
+    pub platform: String,
+    /// Username on the platform, without `@`
+    pub handle: String,
+    /// Link to the profile, or empty if the platform has no profile pages
+    #[serde(default)]
+    pub url: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// An instant-messaging account.
+#[derive(Debug, Serialize, Deserialize, Clone, SimpleObject, ToSchema)]
+pub struct Im {
+    pub id: i64,
+    pub label: String,
+    /// Lowercase: `whatsapp`, `telegram`, `signal`, `wechat`, ...
+    pub protocol: String,
+    /// E.164 number for WhatsApp and Signal when it parses as one, else the
+    /// username
+    pub handle: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, SimpleObject, ToSchema)]
 pub struct Card {
     pub id: i64,
+    /// Display name; built from the name parts when a write leaves it empty
     pub name: String,
+    /// "Dr.", "Ms."
+    #[serde(default)]
+    pub name_prefix: String,
+    #[serde(default)]
+    pub given_name: String,
+    #[serde(default)]
+    pub middle_name: String,
+    #[serde(default)]
+    pub family_name: String,
+    /// "Jr.", "PhD"
+    #[serde(default)]
+    pub name_suffix: String,
+    /// How the name is read: furigana, pinyin, or another romanization
+    #[serde(default)]
+    pub name_phonetic: String,
+    /// `YYYY-MM-DD`, or `--MM-DD` if the year is unknown
+    #[serde(default)]
+    pub birthday: String,
+    /// `YYYY-MM-DD`, or `--MM-DD` if the year is unknown
+    #[serde(default)]
+    pub anniversary: String,
     pub title: String,
     pub company: String,
+    #[serde(default)]
+    pub department: String,
+    /// Level of the role, such as "Senior", "Director", or "VP"
+    #[serde(default)]
+    pub role_seniority: String,
+    #[serde(default)]
+    pub assistant_name: String,
+    /// As entered
+    #[serde(default)]
+    pub assistant_phone: String,
+    /// Id of the card of this contact's manager
+    #[serde(default)]
+    pub reports_to: Option<i64>,
+    /// When the latest logged interaction took place
+    #[serde(default)]
+    pub last_contacted: Option<String>,
+    /// IANA time zone, such as `Asia/Singapore`; derived from the addresses
+    /// when left empty
+    #[serde(default)]
+    pub timezone: String,
+    /// The time now where the contact is, with its UTC offset; `null`
+    /// without a `timezone`
+    #[serde(default)]
+    pub local_time: Option<String>,
     pub website: String,
     pub notes: String,
+    /// The first of `photos`, or empty
     pub photo_url: String,
+    /// Sides of the paper card, logo, and headshot, in order
+    #[serde(default)]
+    pub photos: Vec<CardPhoto>,
+    /// Logo of the card's company, fetched from its website with `POST
+    /// /cards/:id/fetch-logo`; shared by every card with that company
+    #[serde(default)]
+    pub company_logo_url: String,
     pub phones: Vec<Phone>,
     pub emails: Vec<Email>,
     pub addresses: Vec<Address>,
+    #[serde(default)]
+    pub socials: Vec<Social>,
+    #[serde(default)]
+    pub ims: Vec<Im>,
     pub tags: Vec<String>,
+    /// Values of the custom fields defined under `/api/schema`, by key
+    #[serde(default)]
+    pub custom: BTreeMap<String, String>,
     pub created_at: String,
     pub updated_at: String,
+    /// Incremented on every change; send it back (or as `If-Match`) to
+    /// detect concurrent edits.
+    pub version: i64,
+    /// Pinned by the user; the UI lists favorites first.
+    pub favorite: bool,
+    /// When the card was archived; archived cards are left out of listings
+    /// unless asked for.
+    pub archived_at: Option<String>,
+    /// How well the card matched `q` (higher is better); only present in
+    /// relevance-ordered search results.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<f64>,
+    /// Why the card matched `q`; only present in search results.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub matches: Vec<SearchMatch>,
+}
+
+/// One place a search query matched a card. `start`/`end` are character
+/// offsets of the matched span within `excerpt`.
+#[derive(Debug, Serialize, Deserialize, Clone, SimpleObject, ToSchema)]
+pub struct SearchMatch {
+    /// `name`, `name_phonetic`, `company`, `title`, `notes`, `email`,
+    /// `phone`, `address`, or `im`
+    pub field: String,
+    /// The matched value, shortened around the match when long
+    pub excerpt: String,
+    pub start: usize,
+    pub end: usize,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, SimpleObject, ToSchema)]
 pub struct TagCount {
     pub name: String,
     pub count: i64,
+    /// `#rrggbb`, or empty for the default look
+    pub color: String,
+    pub description: String,
+    /// Other spellings that resolve to this tag
+    pub aliases: Vec<String>,
+}
+
+/// Body of `PUT /api/tags/{name}`. Omitted fields are left unchanged.
+#[derive(Debug, Deserialize, Clone, ToSchema)]
+pub struct TagUpdate {
+    /// New name, applied to every card carrying the tag
+    pub name: Option<String>,
+    /// `#rrggbb`, or empty to clear
+    pub color: Option<String>,
+    pub description: Option<String>,
+}
+
+/// Body of `POST /api/tags/{name}/aliases`.
+#[derive(Debug, Deserialize, Clone, ToSchema)]
+pub struct TagAliasInput {
+    pub alias: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// Body of `POST /api/tags/merge`.
+#[derive(Debug, Deserialize, Clone, ToSchema)]
+pub struct TagMerge {
+    /// Tags to fold into `into`; they are deleted afterwards
+    pub from: Vec<String>,
+    /// Tag that ends up on every affected card, created if needed
+    pub into: String,
+}
+
+/// Body of `POST /api/cards/:id/merge`.
+#[derive(Debug, Deserialize, Clone, ToSchema)]
+pub struct CardMerge {
+    /// Card to fold into the one in the path; it is deleted afterwards
+    pub source: i64,
+    /// Put the source's photos first, so its photo becomes the target's
+    /// `photo_url`
+    #[serde(default)]
+    pub prefer_source_photo: bool,
+}
+
+/// Body of `POST /api/cards/bulk/tags`.
+#[derive(Debug, Deserialize, Clone, ToSchema)]
+pub struct BulkTagInput {
+    pub ids: Vec<i64>,
+    /// Tags to put on every listed card
+    #[serde(default)]
+    pub add: Vec<String>,
+    /// Tags to take off every listed card
+    #[serde(default)]
+    pub remove: Vec<String>,
+}
+
+/// A birthday or anniversary coming up within the requested window.
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct UpcomingDate {
+    /// `birthday` or `anniversary`
+    pub kind: String,
+    /// Next occurrence, `YYYY-MM-DD`
+    pub date: String,
+    /// 0 if it is today
+    pub days_until: i64,
+    /// Age reached or years married on `date`, if the year is known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub years: Option<i64>,
+    pub card: Card,
+}
+
+/// A card with an address near the point asked about.
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct NearbyCard {
+    /// From the point to `address`, as the crow flies
+    pub distance_km: f64,
+    /// The card's nearest address
+    pub address: Address,
+    pub card: Card,
+}
+
+/// A card with the people around it in its company's reporting lines.
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct OrgChart {
+    pub card: Card,
+    /// The card it `reports_to`, if any
+    pub manager: Option<Card>,
+    /// Cards that report to it directly, by name; archived ones are left out
+    pub reports: Vec<Card>,
+}
+
+/// Outcome of a bulk tag change.
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct BulkTagResult {
+    /// Cards whose tags actually changed
+    pub updated: Vec<i64>,
+}
+
+/// One recorded version of a card and what changed to produce it.
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct CardRevision {
+    pub version: i64,
+    /// `edit`, `delete`, `undo`, or `baseline` (a seeded card, or the card
+    /// as it was when history recording began)
+    pub kind: String,
+    /// When the version was written
+    pub created_at: String,
+    /// Fields that differ from the previous version; on the first version,
+    /// every field that was set
+    pub changes: Vec<FieldChange>,
+}
+
+/// A single field's value before and after a change.
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct FieldChange {
+    pub field: String,
+    #[schema(value_type = Object)]
+    pub before: serde_json::Value,
+    #[schema(value_type = Object)]
+    pub after: serde_json::Value,
+}
+
+/// What `POST /api/v1/undo` reversed.
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct UndoResult {
+    /// Cards as they are after the undo
+    pub cards: Vec<Card>,
+    /// Ids of cards the undone operation had created, now deleted again
+    pub deleted: Vec<i64>,
+}
+
+/// Cards that probably describe the same contact.
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct DuplicateGroup {
+    /// Highest confidence among the group's matches, from 0 to 1
+    pub confidence: f64,
+    pub cards: Vec<Card>,
+    /// The pairs that put the cards in this group
+    pub matches: Vec<DuplicateMatch>,
+}
+
+/// Why two cards look like duplicates.
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct DuplicateMatch {
+    pub a: i64,
+    pub b: i64,
+    /// `email`, `phone`, or `name`
+    pub reason: String,
+    /// The shared email or phone (normalized), or the name of card `a`
+    pub value: String,
+    pub confidence: f64,
+}
+
+/// Aggregate figures for the whole vault.
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct VaultStats {
+    pub total_cards: i64,
+    pub cards_with_photo: i64,
+    /// Distinct non-empty companies, ignoring case and accents
+    pub companies: i64,
+    /// Cards created since Monday 00:00 UTC
+    pub added_this_week: i64,
+    /// Cards created since the 1st of the month, 00:00 UTC
+    pub added_this_month: i64,
+    /// Size of the SQLite database in bytes
+    pub db_size_bytes: i64,
+    /// Cards per tag, most used first
+    pub tags: Vec<TagCount>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
 pub struct HealthResponse {
     pub status: String,
     pub db: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default, InputObject, ToSchema)]
 pub struct CardFormPhoneInput {
     pub label: String,
     pub number: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default, InputObject, ToSchema)]
 pub struct CardFormEmailInput {
     pub label: String,
     pub address: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default, InputObject, ToSchema)]
 pub struct CardFormAddressInput {
     pub label: String,
     pub street: String,
@@ -74,15 +464,984 @@ pub struct CardFormAddressInput {
     pub postal: String,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default, InputObject, ToSchema)]
+pub struct CardFormSocialInput {
+    /// `linkedin`, `twitter` (or `x`), `github`, `wechat`, or any other name
+    pub platform: String,
+    /// Username, or a profile URL on a known platform
+    pub handle: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, InputObject, ToSchema)]
+pub struct CardFormImInput {
+    pub label: String,
+    /// `whatsapp`, `telegram`, `signal`, `wechat`, or any other name
+    pub protocol: String,
+    pub handle: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, InputObject, ToSchema)]
+#[serde(default)]
 pub struct CardInput {
+    /// Display name; may be left empty if the name parts are given
+    #[graphql(default)]
     pub name: String,
+    #[graphql(default)]
+    pub name_prefix: String,
+    #[graphql(default)]
+    pub given_name: String,
+    #[graphql(default)]
+    pub middle_name: String,
+    #[graphql(default)]
+    pub family_name: String,
+    #[graphql(default)]
+    pub name_suffix: String,
+    /// Furigana, pinyin, or another romanization of the name
+    #[graphql(default)]
+    pub name_phonetic: String,
+    /// `YYYY-MM-DD`, or `--MM-DD` if the year is unknown
+    #[graphql(default)]
+    pub birthday: String,
+    #[graphql(default)]
+    pub anniversary: String,
+    #[graphql(default)]
     pub title: String,
+    #[graphql(default)]
     pub company: String,
+    #[graphql(default)]
+    pub department: String,
+    /// "Senior", "Director", "VP", ...
+    #[graphql(default)]
+    pub role_seniority: String,
+    #[graphql(default)]
+    pub assistant_name: String,
+    #[graphql(default)]
+    pub assistant_phone: String,
+    /// Id of the manager's card
+    #[graphql(default)]
+    pub reports_to: Option<i64>,
+    /// IANA time zone; derived from the addresses when left empty
+    #[graphql(default)]
+    pub timezone: String,
+    #[graphql(default)]
     pub website: String,
+    #[graphql(default)]
     pub notes: String,
+    #[graphql(default)]
     pub phones: Vec<CardFormPhoneInput>,
+    #[graphql(default)]
     pub emails: Vec<CardFormEmailInput>,
+    #[graphql(default)]
     pub addresses: Vec<CardFormAddressInput>,
+    #[graphql(default)]
+    pub socials: Vec<CardFormSocialInput>,
+    #[graphql(default)]
+    pub ims: Vec<CardFormImInput>,
+    #[graphql(default)]
     pub tags: Vec<String>,
+    /// Custom field values by key; see `/api/schema`
+    #[graphql(default)]
+    pub custom: BTreeMap<String, String>,
+    /// Expected current version; the update is rejected if the card changed.
+    /// `PUT` and `PATCH` need it, or `If-Match`.
+    #[graphql(default)]
+    pub version: Option<i64>,
+}
+
+impl From<Card> for CardInput {
+    /// The editable fields of a card, as written back by a revert.
+    fn from(c: Card) -> Self {
+        CardInput {
+            name: c.name,
+            name_prefix: c.name_prefix,
+            given_name: c.given_name,
+            middle_name: c.middle_name,
+            family_name: c.family_name,
+            name_suffix: c.name_suffix,
+            name_phonetic: c.name_phonetic,
+            birthday: c.birthday,
+            anniversary: c.anniversary,
+            title: c.title,
+            company: c.company,
+            department: c.department,
+            role_seniority: c.role_seniority,
+            assistant_name: c.assistant_name,
+            assistant_phone: c.assistant_phone,
+            reports_to: c.reports_to,
+            timezone: c.timezone,
+            website: c.website,
+            notes: c.notes,
+            phones: c
+                .phones
+                .into_iter()
+                .map(|p| CardFormPhoneInput {
+                    label: p.label,
+                    number: p.number,
+                })
+                .collect(),
+            emails: c
+                .emails
+                .into_iter()
+                .map(|e| CardFormEmailInput {
+                    label: e.label,
+                    address: e.address,
+                })
+                .collect(),
+            addresses: c
+                .addresses
+                .into_iter()
+                .map(|a| CardFormAddressInput {
+                    label: a.label,
+                    street: a.street,
+                    city: a.city,
+                    country: a.country,
+                    postal: a.postal,
+                })
+                .collect(),
+            socials: c
+                .socials
+                .into_iter()
+                .map(|s| CardFormSocialInput {
+                    platform: s.platform,
+                    handle: s.handle,
+                })
+                .collect(),
+            ims: c
+                .ims
+                .into_iter()
+                .map(|i| CardFormImInput {
+                    label: i.label,
+                    protocol: i.protocol,
+                    handle: i.handle,
+                })
+                .collect(),
+            tags: c.tags,
+            custom: c.custom,
+            version: None,
+        }
+    }
+}
+
+/// An existing value offered while typing, with how many cards use it.
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct Suggestion {
+    pub value: String,
+    pub count: i64,
+}
+
+/// What `POST /api/v1/cards` does when the new card shares an email or
+/// phone number with an existing one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OnDuplicate {
+    /// Create the card anyway
+    #[default]
+    Allow,
+    /// Refuse with 409 and the existing card
+    Reject,
+    /// Merge the new card into the existing one
+    Merge,
+}
+
+/// Fields that `GET /api/v1/autocomplete` can suggest values for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SuggestField {
+    Company,
+    Title,
+    Tag,
+    City,
+    Country,
+}
+
+/// Filters, ordering, and paging for card listings.
+#[derive(Debug, Clone, Default)]
+pub struct CardQuery {
+    pub q: Option<String>,
+    /// Match `q` typo-tolerantly and order by relevance (see `search`).
+    pub fuzzy: bool,
+    /// Match `q` against how card names sound rather than how they are spelt.
+    pub phonetic: bool,
+    /// Order plain `q` matches by relevance instead of `sort`.
+    pub rank: bool,
+    /// Only cards carrying all of these tags.
+    pub tags: Vec<String>,
+    /// Only cards carrying none of these tags.
+    pub not_tags: Vec<String>,
+    /// Only cards with no tags at all.
+    pub untagged: bool,
+    /// Matches any of the card's addresses, case-insensitively.
+    pub country: Option<String>,
+    pub city: Option<String>,
+    /// `Some(false)` lists cards without a photo.
+    pub has_photo: Option<bool>,
+    /// `Some(true)` lists only favorites, `Some(false)` everything else.
+    pub favorite: Option<bool>,
+    /// List archived cards instead of the active ones.
+    pub archived: bool,
+    /// Only cards lacking every one of these details.
+    pub missing: Vec<CardDetail>,
+    /// Timestamp bounds in the stored `YYYY-MM-DD HH:MM:SS` (UTC) form; see
+    /// [`parse_timestamp`]. `*_after` is inclusive, `*_before` exclusive.
+    pub created_after: Option<String>,
+    pub created_before: Option<String>,
+    pub updated_after: Option<String>,
+    pub updated_before: Option<String>,
+    pub sort: CardSort,
+    /// `None` returns every match.
+    pub limit: Option<i64>,
+    pub offset: i64,
+    /// Keyset position: only cards ordered after this one are returned.
+    pub after: Option<CardCursor>,
+    pub relations: CardRelations,
+}
+
+/// The searchable text of one card, as `(field, text)` pairs.
+#[derive(Debug, Clone)]
+pub struct SearchDoc {
+    pub id: i64,
+    pub fields: Vec<(&'static str, String)>,
+}
+
+/// Which nested collections to load with each card. Every one costs an extra
+/// query per card, so clients that only need the flat fields can skip them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CardRelations {
+    pub phones: bool,
+    pub emails: bool,
+    pub addresses: bool,
+    pub socials: bool,
+    pub ims: bool,
+    pub tags: bool,
+    pub photos: bool,
+}
+
+impl CardRelations {
+    pub const ALL: Self = Self {
+        phones: true,
+        emails: true,
+        addresses: true,
+        socials: true,
+        ims: true,
+        tags: true,
+        photos: true,
+    };
+}
+
+impl Default for CardRelations {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+/// Parses an RFC 3339 timestamp (`2026-03-01T09:00:00+08:00`), a naive UTC
+/// one (`2026-03-01 09:00:00`), or a bare date (midnight UTC) into the form
+/// SQLite stores in `created_at`/`updated_at`.
+pub fn parse_timestamp(s: &str) -> Option<String> {
+    const STORED: &str = "%Y-%m-%d %H:%M:%S";
+    let s = s.trim();
+    let utc = if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(s) {
+        dt.naive_utc()
+    } else if let Ok(dt) = chrono::NaiveDateTime::parse_from_str(s, STORED) {
+        dt
+    } else {
+        chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d")
+            .ok()?
+            .and_hms_opt(0, 0, 0)?
+    };
+    Some(utc.format(STORED).to_string())
+}
+
+/// Combining accents and the precomposed lower-case letters they form with
+/// each base letter, for the common Latin cases of NFC composition.
+const COMPOSITIONS: &[(char, &str, &str)] = &[
+    ('\u{300}', "aeiou", "àèìòù"),
+    ('\u{301}', "aeiouycnsz", "áéíóúýćńśź"),
+    ('\u{302}', "aeiou", "âêîôû"),
+    ('\u{303}', "ano", "ãñõ"),
+    ('\u{308}', "aeiouy", "äëïöüÿ"),
+    ('\u{30a}', "au", "åů"),
+    ('\u{30c}', "cenrsz", "čěňřšž"),
+    ('\u{327}', "cs", "çş"),
+];
+
+/// Canonical spelling of a tag: trimmed, lower-cased, and with a letter
+/// followed by a combining accent composed into one character, so "Café"
+/// typed either way is the same tag.
+pub fn normalize_tag(tag: &str) -> String {
+    let mut out = String::new();
+    for c in tag.trim().chars().flat_map(char::to_lowercase) {
+        let composed = out.chars().last().and_then(|base| {
+            let (_, bases, letters) = COMPOSITIONS.iter().find(|(mark, ..)| *mark == c)?;
+            letters.chars().nth(bases.chars().position(|b| b == base)?)
+        });
+        match composed {
+            Some(letter) => {
+                out.pop();
+                out.push(letter);
+            }
+            None => out.push(c),
+        }
+    }
+    out
+}
+
+/// Canonical form of an email address: trimmed, with the domain
+/// lower-cased. `None` if the address is malformed: it must be
+/// `local@domain`, with a dot-separated local part of the usual characters
+/// and a domain of at least two letter-or-digit labels (hyphens inside).
+pub fn canonical_email(address: &str) -> Option<String> {
+    const LOCAL_SYMBOLS: &str = "!#$%&'*+/=?^_`{|}~-";
+    let address = address.trim();
+    let (local, domain) = address.rsplit_once('@')?;
+    let local_ok = local.len() <= 64
+        && local.split('.').all(|part| {
+            !part.is_empty()
+                && part.chars().all(|c| c.is_alphanumeric() || LOCAL_SYMBOLS.contains(c))
+        });
+    let domain = domain.to_lowercase();
+    let labels: Vec<&str> = domain.split('.').collect();
+    let domain_ok = labels.len() >= 2
+        && labels.iter().all(|label| {
+            !label.is_empty()
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_alphanumeric() || c == '-')
+        })
+        && labels.last().is_some_and(|tld| tld.chars().count() >= 2);
+    (local_ok && domain_ok && address.len() <= 254).then(|| format!("{local}@{domain}"))
+}
+
+/// Display name built from name parts, e.g. "Dr. Ada King Lovelace, PhD".
+/// Empty if every part is.
+pub fn display_name(prefix: &str, given: &str, middle: &str, family: &str, suffix: &str) -> String {
+    let mut name = [prefix, given, middle, family]
+        .iter()
+        .map(|p| p.trim())
+        .filter(|p| !p.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let suffix = suffix.trim();
+    if !suffix.is_empty() {
+        if !name.is_empty() {
+            name.push_str(", ");
+        }
+        name.push_str(suffix);
+    }
+    name
+}
+
+/// Query parameters that only track where a visitor came from.
+const TRACKING_PARAMS: [&str; 8] =
+    ["fbclid", "gclid", "dclid", "msclkid", "yclid", "igshid", "mc_cid", "mc_eid"];
+
+/// Canonical form of a website: `https://` added if there is no scheme,
+/// an internationalized host in punycode, and `utm_*` and other tracking
+/// parameters removed. Empty stays empty. `None` if it is not an http(s)
+/// URL with a dotted host name.
+pub fn canonical_website(website: &str) -> Option<String> {
+    let website = website.trim();
+    if website.is_empty() {
+        return Some(String::new());
+    }
+    let with_scheme = if website.contains("://") {
+        website.to_string()
+    } else {
+        format!("https://{website}")
+    };
+    let mut url = url::Url::parse(&with_scheme).ok()?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return None;
+    }
+    match url.host()? {
+        url::Host::Domain(host) if host.contains('.') && !host.ends_with('.') => {}
+        url::Host::Domain(_) => return None,
+        url::Host::Ipv4(_) | url::Host::Ipv6(_) => {}
+    }
+    let kept: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(key, _)| !key.starts_with("utm_") && !TRACKING_PARAMS.contains(&key.as_ref()))
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+    if kept.is_empty() {
+        url.set_query(None);
+    } else {
+        url.query_pairs_mut().clear().extend_pairs(kept);
+    }
+    let mut canonical = url.to_string();
+    // "https://example.com/" reads better without the bare root path.
+    if url.path() == "/" && url.query().is_none() && url.fragment().is_none() {
+        canonical.pop();
+    }
+    Some(canonical)
+}
+
+/// A piece of contact data a card may still be missing after an event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, async_graphql::Enum)]
+pub enum CardDetail {
+    Email,
+    Phone,
+    Address,
+    Tag,
+    Photo,
+    Title,
+    Company,
+    Website,
+}
+
+impl std::str::FromStr for CardDetail {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "email" => CardDetail::Email,
+            "phone" => CardDetail::Phone,
+            "address" => CardDetail::Address,
+            "tag" => CardDetail::Tag,
+            "photo" => CardDetail::Photo,
+            "title" => CardDetail::Title,
+            "company" => CardDetail::Company,
+            "website" => CardDetail::Website,
+            _ => {
+                return Err(format!(
+                    "missing takes email, phone, address, tag, photo, title, company, or website (got '{s}')"
+                ))
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortField {
+    Name,
+    FamilyName,
+    Company,
+    CreatedAt,
+    UpdatedAt,
+}
+
+impl SortField {
+    const ALL: [(SortField, &'static str); 5] = [
+        (SortField::Name, "name"),
+        (SortField::FamilyName, "family_name"),
+        (SortField::Company, "company"),
+        (SortField::CreatedAt, "created_at"),
+        (SortField::UpdatedAt, "updated_at"),
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        Self::ALL.iter().find(|(f, _)| *f == self).unwrap().1
+    }
+
+    /// The card's value for this field, as stored in the database.
+    pub fn value_of(self, card: &Card) -> String {
+        match self {
+            SortField::Name if card.name_phonetic.is_empty() => card.name.clone(),
+            SortField::Name => card.name_phonetic.clone(),
+            SortField::FamilyName if card.family_name.is_empty() => card.name.clone(),
+            SortField::FamilyName => format!("{} {}", card.family_name, card.given_name)
+                .trim_end()
+                .to_string(),
+            SortField::Company => card.company.clone(),
+            SortField::CreatedAt => card.created_at.clone(),
+            SortField::UpdatedAt => card.updated_at.clone(),
+        }
+    }
+}
+
+/// Listing order, written as `name` (ascending) or `-name` (descending).
+/// Ties are broken by card id in the same direction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CardSort {
+    pub field: SortField,
+    pub descending: bool,
+}
+
+impl Default for CardSort {
+    fn default() -> Self {
+        Self {
+            field: SortField::UpdatedAt,
+            descending: true,
+        }
+    }
+}
+
+impl std::fmt::Display for CardSort {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sign = if self.descending { "-" } else { "" };
+        write!(f, "{sign}{}", self.field.as_str())
+    }
+}
+
+impl std::str::FromStr for CardSort {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (descending, name) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        let field = SortField::ALL
+            .iter()
+            .find(|(_, n)| *n == name)
+            .map(|(f, _)| *f)
+            .ok_or_else(|| {
+                let names: Vec<_> = SortField::ALL.iter().map(|(_, n)| *n).collect();
+                format!("sort must be one of {}, optionally prefixed with -", names.join(", "))
+            })?;
+        Ok(Self { field, descending })
+    }
+}
+
+/// Position of a card within a sorted listing. Handed to clients as an
+/// opaque token so later pages stay stable while cards are added or edited.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CardCursor {
+    pub sort: CardSort,
+    pub value: String,
+    pub id: i64,
+}
+
+impl CardCursor {
+    pub fn for_card(card: &Card, sort: CardSort) -> Self {
+        Self {
+            sort,
+            value: sort.field.value_of(card),
+            id: card.id,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(format!("{}|{}|{}", self.sort, self.id, self.value))
+    }
+
+    pub fn decode(token: &str) -> Option<Self> {
+        let raw = String::from_utf8(URL_SAFE_NO_PAD.decode(token).ok()?).ok()?;
+        let mut parts = raw.splitn(3, '|');
+        let sort = parts.next()?.parse().ok()?;
+        let id = parts.next()?.parse().ok()?;
+        let value = parts.next()?.to_string();
+        Some(Self { sort, value, id })
+    }
+}
+
+/// Reads a field that may be `null` as `Some(None)`, so that together with
+/// `#[serde(default)]` an absent field (`None`) can be told from a cleared one.
+fn present<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// Sparse card update for `PATCH`: absent fields (and absent collections)
+/// are left as they are; a present collection replaces the stored one.
+#[derive(Debug, Deserialize, Clone, Default, ToSchema)]
+pub struct CardPatch {
+    /// Rebuilt from the name parts if only they are patched
+    pub name: Option<String>,
+    pub name_prefix: Option<String>,
+    pub given_name: Option<String>,
+    pub middle_name: Option<String>,
+    pub family_name: Option<String>,
+    pub name_suffix: Option<String>,
+    pub name_phonetic: Option<String>,
+    /// Empty to clear
+    pub birthday: Option<String>,
+    pub anniversary: Option<String>,
+    pub title: Option<String>,
+    pub company: Option<String>,
+    pub department: Option<String>,
+    pub role_seniority: Option<String>,
+    pub assistant_name: Option<String>,
+    pub assistant_phone: Option<String>,
+    /// Id of the manager's card; `null` clears it
+    #[serde(default, deserialize_with = "present")]
+    #[schema(value_type = Option<i64>)]
+    pub reports_to: Option<Option<i64>>,
+    /// IANA time zone. Left out, new `addresses` derive it; set empty, it
+    /// is derived from the `addresses` in the same patch, if any
+    pub timezone: Option<String>,
+    pub website: Option<String>,
+    pub notes: Option<String>,
+    pub phones: Option<Vec<CardFormPhoneInput>>,
+    pub emails: Option<Vec<CardFormEmailInput>>,
+    pub addresses: Option<Vec<CardFormAddressInput>>,
+    pub socials: Option<Vec<CardFormSocialInput>>,
+    pub ims: Option<Vec<CardFormImInput>>,
+    pub tags: Option<Vec<String>>,
+    /// Replaces all custom field values
+    pub custom: Option<BTreeMap<String, String>>,
+    /// Expected current version; the update is rejected if the card changed.
+    /// `PUT` and `PATCH` need it, or `If-Match`.
+    pub version: Option<i64>,
+}
+
+/// Kind of value a custom field holds.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldType {
+    Text,
+    /// `YYYY-MM-DD`
+    Date,
+    /// An http(s) URL, normalized like `website`
+    Url,
+    /// One of the field's `options`
+    Select,
+}
+
+impl FieldType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FieldType::Text => "text",
+            FieldType::Date => "date",
+            FieldType::Url => "url",
+            FieldType::Select => "select",
+        }
+    }
+}
+
+impl std::str::FromStr for FieldType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "text" => FieldType::Text,
+            "date" => FieldType::Date,
+            "url" => FieldType::Url,
+            "select" => FieldType::Select,
+            _ => return Err(format!("unknown field type '{s}'")),
+        })
+    }
+}
+
+/// A custom field cards may carry in `custom`.
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct FieldDefinition {
+    pub key: String,
+    pub label: String,
+    #[serde(rename = "type")]
+    pub field_type: FieldType,
+    /// Cards cannot be saved without a value
+    pub required: bool,
+    /// Allowed values of a `select` field
+    pub options: Vec<String>,
+}
+
+/// Body of `PUT /api/schema/fields/{key}`.
+#[derive(Debug, Deserialize, Clone, ToSchema)]
+pub struct FieldDefinitionInput {
+    /// Defaults to the key
+    #[serde(default)]
+    pub label: String,
+    #[serde(rename = "type")]
+    pub field_type: FieldType,
+    #[serde(default)]
+    pub required: bool,
+    /// Required for `select`, ignored otherwise
+    #[serde(default)]
+    pub options: Vec<String>,
+}
+
+/// The custom fields cards may carry, in the order they were defined.
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct FieldSchema {
+    pub fields: Vec<FieldDefinition>,
+}
+
+/// How a contact was in touch.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum InteractionKind {
+    Call,
+    Email,
+    Meeting,
+}
+
+impl InteractionKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            InteractionKind::Call => "call",
+            InteractionKind::Email => "email",
+            InteractionKind::Meeting => "meeting",
+        }
+    }
+}
+
+impl std::str::FromStr for InteractionKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "call" => InteractionKind::Call,
+            "email" => InteractionKind::Email,
+            "meeting" => InteractionKind::Meeting,
+            _ => return Err(format!("unknown interaction kind '{s}'")),
+        })
+    }
+}
+
+/// One entry in a card's contact log.
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct Interaction {
+    pub id: i64,
+    pub card_id: i64,
+    pub kind: InteractionKind,
+    /// `YYYY-MM-DD HH:MM:SS`, UTC
+    pub occurred_at: String,
+    pub note: String,
+    pub created_at: String,
+}
+
+/// Body of `POST /api/cards/{id}/interactions` and of `PUT` on one of them.
+#[derive(Debug, Deserialize, Clone, ToSchema)]
+pub struct InteractionInput {
+    pub kind: InteractionKind,
+    /// Date or RFC 3339 timestamp; defaults to now, and is left unchanged
+    /// by a `PUT` that omits it
+    pub occurred_at: Option<String>,
+    #[serde(default)]
+    pub note: String,
+}
+
+/// A follow-up to do about a card.
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct Reminder {
+    pub id: i64,
+    pub card_id: i64,
+    /// What to do, e.g. "ping Kevin about the pilot"
+    pub title: String,
+    /// `YYYY-MM-DD`
+    pub due_date: String,
+    pub done: bool,
+    pub created_at: String,
+}
+
+/// Body of `POST /api/cards/{id}/reminders` and of `PUT` on one of them.
+#[derive(Debug, Deserialize, Clone, ToSchema)]
+pub struct ReminderInput {
+    pub title: String,
+    /// `YYYY-MM-DD`
+    pub due_date: String,
+    #[serde(default)]
+    pub done: bool,
+}
+
+/// Which open reminders `GET /api/v1/reminders` lists, by due date (UTC).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReminderDue {
+    /// Due before today
+    Overdue,
+    /// Due today
+    Today,
+    /// Due today or in the next six days
+    Week,
+}
+
+/// An open reminder with the card it is about.
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct DueReminder {
+    pub reminder: Reminder,
+    pub card: Card,
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct ShareInput {
+    /// Days until the link expires: 1 to 90, default 7
+    pub expires_in_days: Option<u32>,
+}
+
+/// A public, read-only link to one card.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ShareLink {
+    pub token: String,
+    /// Page showing the card, with a vCard download
+    pub url: String,
+    /// UTC, `YYYY-MM-DD HH:MM:SS`
+    pub expires_at: String,
+}
+
+/// What `POST /api/v1/cards/{id}/enrich` can propose. Industry and size
+/// are stored as the custom fields `industry` and `company_size`, LinkedIn
+/// as the card's `linkedin` social profile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EnrichField {
+    Industry,
+    CompanySize,
+    Linkedin,
+}
+
+impl EnrichField {
+    /// Key and label of the custom field the value is stored in, which is
+    /// defined as a text field on first accept if it does not exist.
+    pub fn custom_field(self) -> Option<(&'static str, &'static str)> {
+        match self {
+            EnrichField::Industry => Some(("industry", "Industry")),
+            EnrichField::CompanySize => Some(("company_size", "Company size")),
+            EnrichField::Linkedin => None,
+        }
+    }
+}
+
+/// A value the enrichment provider has that differs from the card's.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct EnrichProposal {
+    pub field: EnrichField,
+    /// Empty if the card has no value yet
+    pub current: String,
+    pub proposed: String,
+}
+
+/// Proposed updates to a card, for the user to accept or reject.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Enrichment {
+    /// The card version the proposals were made against; send it back
+    /// when accepting
+    pub version: i64,
+    /// Empty if the provider had nothing new
+    pub proposals: Vec<EnrichProposal>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct EnrichValue {
+    pub field: EnrichField,
+    pub value: String,
+}
+
+/// Body of `POST /api/v1/cards/{id}/enrich/accept`: the proposals the user
+/// accepted. Rejected ones are simply left out.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct EnrichAccept {
+    pub accept: Vec<EnrichValue>,
+    /// Expected current version, as for `PATCH`
+    pub version: Option<i64>,
+}
+
+/// Answer of `POST /api/v1/geocode/backfill`.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GeocodeBackfill {
+    /// Addresses without coordinates that the geocoder will now try; some
+    /// may already be cached
+    pub pending: usize,
+}
+
+/// A company's logo, as stored by `POST /api/v1/cards/{id}/fetch-logo`.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CompanyLogo {
+    pub company: String,
+    pub logo_url: String,
+    /// Where on the company's website the logo was found
+    pub source_url: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SendCardInput {
+    /// Recipient's email address
+    pub to: String,
+    /// Note added above the card in the email body
+    #[serde(default)]
+    pub message: String,
+    /// Also attach the card's photo (and embed it in the vCard)
+    #[serde(default)]
+    pub include_photo: bool,
+}
+
+/// Thumbnail of a card's photo.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ThumbSize {
+    /// At most 128 px on its longest side, for lists
+    #[default]
+    Small,
+    /// At most 512 px, for a card's page
+    Medium,
+}
+
+impl ThumbSize {
+    pub const ALL: [ThumbSize; 2] = [ThumbSize::Small, ThumbSize::Medium];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ThumbSize::Small => "small",
+            ThumbSize::Medium => "medium",
+        }
+    }
+
+    /// The longest side, in pixels.
+    pub fn pixels(self) -> u32 {
+        match self {
+            ThumbSize::Small => 128,
+            ThumbSize::Medium => 512,
+        }
+    }
+}
+
+/// What a card's QR code holds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QrFormat {
+    /// vCard 3.0, with every field
+    #[default]
+    Vcard,
+    /// MECARD: the basics only, for a smaller code
+    Mecard,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct QrImportInput {
+    /// Text decoded from the QR code: a vCard or a MECARD
+    pub payload: String,
+    /// Save the card instead of only returning the pre-filled form
+    #[serde(default)]
+    pub create: bool,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct TextImportInput {
+    /// Contact details pasted from anywhere: an email signature, a web page,
+    /// OCR output
+    pub text: String,
+    /// Save the card instead of only returning the pre-filled form
+    #[serde(default)]
+    pub create: bool,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SignatureInput {
+    /// An email signature, pasted as plain text; a sign-off above it and a
+    /// disclaimer below it are skipped
+    pub text: String,
+}
+
+/// What `POST /api/v1/scan` read from a card photo.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ScanResult {
+    /// Everything the OCR engine read, line by line
+    pub text: String,
+    /// Fields picked out of `text`, for the user to confirm before saving
+    pub card: CardInput,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct Webhook {
+    pub id: i64,
+    pub url: String,
+    pub events: Vec<String>,
+    pub active: bool,
+    /// Only returned when the webhook is created.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Deserialize, Clone, ToSchema)]
+pub struct WebhookInput {
+    pub url: String,
+    pub secret: Option<String>,
+    #[serde(default)]
+    pub events: Vec<String>,
+    pub active: Option<bool>,
 }
@@ -0,0 +1,118 @@
+use std::process::Stdio;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use clap::ValueEnum;
+use reqwest::header::CONTENT_TYPE;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+/// A photo of a whole card takes tesseract a few seconds; give up well
+/// after that.
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Which OCR engine reads card photos.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OcrBackend {
+    /// The `tesseract` command, which must be installed on the server
+    Tesseract,
+    /// An HTTP OCR service (`--ocr-url`)
+    Remote,
+    /// No scanning; `POST /scan` answers 503
+    None,
+}
+
+/// Turns an image into text.
+#[async_trait]
+pub trait OcrEngine: Send + Sync {
+    async fn recognize(&self, image: &[u8], mime: &str) -> Result<String>;
+}
+
+/// The tesseract command-line tool, run once per image with the image on
+/// stdin.
+pub struct Tesseract {
+    pub command: String,
+    /// Tesseract language codes joined with `+`, e.g. `eng+chi_sim`
+    pub languages: String,
+}
+
+#[async_trait]
+impl OcrEngine for Tesseract {
+    async fn recognize(&self, image: &[u8], _mime: &str) -> Result<String> {
+        let mut child = Command::new(&self.command)
+            .args(["stdin", "stdout", "-l", &self.languages])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("run {}", self.command))?;
+        let mut stdin = child.stdin.take().context("tesseract stdin")?;
+        stdin.write_all(image).await?;
+        drop(stdin);
+
+        let output = tokio::time::timeout(TIMEOUT, child.wait_with_output())
+            .await
+            .context("tesseract timed out")??;
+        if !output.status.success() {
+            bail!(
+                "tesseract failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+/// An OCR web service. The image is POSTed as the request body with its
+/// content type; the reply is either plain text or JSON with a `text`
+/// field.
+pub struct RemoteOcr {
+    client: reqwest::Client,
+    url: String,
+    api_key: Option<String>,
+}
+
+impl RemoteOcr {
+    pub fn new(url: String, api_key: Option<String>) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(TIMEOUT)
+            .user_agent(concat!("CardVault/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .expect("failed to build OCR HTTP client");
+        Self { client, url, api_key }
+    }
+}
+
+#[async_trait]
+impl OcrEngine for RemoteOcr {
+    async fn recognize(&self, image: &[u8], mime: &str) -> Result<String> {
+        let mut request = self
+            .client
+            .post(&self.url)
+            .header(CONTENT_TYPE, mime)
+            .body(image.to_vec());
+        if let Some(key) = &self.api_key {
+            request = request.bearer_auth(key);
+        }
+        let response = request
+            .send()
+            .await
+            .context("OCR service unreachable")?
+            .error_for_status()?;
+        let is_json = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|ct| ct.starts_with("application/json"));
+        if !is_json {
+            return Ok(response.text().await?);
+        }
+        let body: serde_json::Value = response.json().await?;
+        match body.get("text").and_then(|t| t.as_str()) {
+            Some(text) => Ok(text.to_string()),
+            None => bail!("OCR service reply has no \"text\" field"),
+        }
+    }
+}
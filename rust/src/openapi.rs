@@ -0,0 +1,279 @@
+use utoipa::{OpenApi, ToSchema};
+
+use crate::{
+    handlers,
+    models::{
+        Address, Attachment, UploadSession, UploadStart, BulkTagInput, BulkTagResult, Card, CardMerge, CardRevision, DuplicateGroup, DuplicateMatch, FieldChange, FieldDefinition, FieldDefinitionInput, FieldSchema, FieldType, CardFormAddressInput, CardInput, CardPatch, CardFormEmailInput, CardFormPhoneInput, CardFormSocialInput, CardFormImInput, Email,
+        HealthResponse, Im, Interaction, InteractionInput, InteractionKind, DueReminder, Reminder, ReminderDue, ReminderInput, QrImportInput, ScanResult, SendCardInput, SignatureInput, TextImportInput, ShareInput, ShareLink, CompanyLogo, EnrichAccept, EnrichField, EnrichProposal, EnrichValue, Enrichment, GeocodeBackfill, NearbyCard, CardPhoto, Phone, PhotoRole, PhotoUpdate, Social, SuggestField, Suggestion, TagAliasInput, TagCount, TagMerge, TagUpdate, UndoResult, UpcomingDate, OrgChart, VaultStats, Webhook, WebhookInput,
+    },
+    validation::FieldError,
+};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "CardVault",
+        description = "Business card manager REST API"
+    ),
+    paths(
+        handlers::list_cards,
+        handlers::create_card,
+        handlers::get_card,
+        handlers::update_card,
+        handlers::patch_card,
+        handlers::delete_card,
+        handlers::toggle_favorite,
+        handlers::archive_card,
+        handlers::unarchive_card,
+        handlers::merge_cards,
+        handlers::org_chart,
+        handlers::card_history,
+        handlers::revert_card,
+        handlers::undo,
+        handlers::upload_photo,
+        handlers::delete_photo,
+        handlers::list_photos,
+        handlers::add_photo,
+        handlers::update_photo,
+        handlers::remove_photo,
+        handlers::photo_thumb,
+        handlers::list_attachments,
+        handlers::add_attachment,
+        handlers::download_attachment,
+        handlers::remove_attachment,
+        handlers::create_upload,
+        handlers::get_upload,
+        handlers::append_upload,
+        handlers::cancel_upload,
+        handlers::fetch_avatar,
+        handlers::fetch_logo,
+        handlers::enrich_card,
+        handlers::accept_enrichment,
+        handlers::geocode_backfill,
+        handlers::nearby,
+        handlers::serve_uploads,
+        handlers::list_tags,
+        handlers::update_tag,
+        handlers::delete_tag,
+        handlers::merge_tags,
+        handlers::add_tag_alias,
+        handlers::bulk_tag_cards,
+        handlers::delete_tag_alias,
+        handlers::autocomplete,
+        handlers::stats,
+        handlers::duplicates,
+        handlers::upcoming,
+        handlers::get_schema,
+        handlers::put_schema_field,
+        handlers::delete_schema_field,
+        handlers::list_interactions,
+        handlers::create_interaction,
+        handlers::update_interaction,
+        handlers::delete_interaction,
+        handlers::list_reminders,
+        handlers::create_reminder,
+        handlers::update_reminder,
+        handlers::delete_reminder,
+        handlers::due_reminders,
+        handlers::reminders_calendar,
+        handlers::create_share,
+        handlers::send_card,
+        handlers::card_qr,
+        handlers::import_qr,
+        handlers::scan_card,
+        handlers::import_text,
+        handlers::parse_signature,
+        handlers::list_webhooks,
+        handlers::create_webhook,
+        handlers::get_webhook,
+        handlers::update_webhook,
+        handlers::delete_webhook,
+        handlers::events_feed,
+        handlers::health,
+    ),
+    components(schemas(
+        Card,
+        CardMerge,
+        CardRevision,
+        FieldChange,
+        UndoResult,
+        UpcomingDate,
+        OrgChart,
+        Phone,
+        Email,
+        Address,
+        Social,
+        Im,
+        TagCount,
+        TagUpdate,
+        TagMerge,
+        TagAliasInput,
+        BulkTagInput,
+        BulkTagResult,
+        Suggestion,
+        SuggestField,
+        VaultStats,
+        DuplicateGroup,
+        DuplicateMatch,
+        FieldType,
+        FieldDefinition,
+        FieldDefinitionInput,
+        FieldSchema,
+        HealthResponse,
+        InteractionKind,
+        Interaction,
+        InteractionInput,
+        Reminder,
+        ReminderInput,
+        ReminderDue,
+        DueReminder,
+        ShareInput,
+        ShareLink,
+        CompanyLogo,
+        EnrichAccept,
+        EnrichField,
+        EnrichProposal,
+        EnrichValue,
+        Enrichment,
+        GeocodeBackfill,
+        NearbyCard,
+        SendCardInput,
+        QrImportInput,
+        ScanResult,
+        SignatureInput,
+        TextImportInput,
+        Webhook,
+        WebhookInput,
+        CardInput,
+        CardPatch,
+        CardForm,
+        CardFormPhoneInput,
+        CardFormEmailInput,
+        CardFormAddressInput,
+        CardFormSocialInput,
+        CardFormImInput,
+        CardPhoto,
+        PhotoRole,
+        PhotoUpdate,
+        PhotoForm,
+        CardPhotoForm,
+        Attachment,
+        AttachmentForm,
+        UploadStart,
+        UploadSession,
+        PhotoUrl,
+        ErrorBody,
+        FieldError,
+        ValidationErrorBody,
+    ))
+)]
+pub struct ApiDoc;
+
+// The shapes below only exist to describe requests and responses that the
+// handlers build by hand (multipart forms and `json!` bodies).
+
+/// Multipart form accepted by card create/update. Collection fields are
+/// JSON-encoded strings.
+#[allow(dead_code)]
+#[derive(ToSchema)]
+pub struct CardForm {
+    /// May be left empty when the name parts are given
+    name: Option<String>,
+    name_prefix: Option<String>,
+    given_name: Option<String>,
+    middle_name: Option<String>,
+    family_name: Option<String>,
+    name_suffix: Option<String>,
+    name_phonetic: Option<String>,
+    /// `YYYY-MM-DD` or `--MM-DD`
+    birthday: Option<String>,
+    anniversary: Option<String>,
+    title: Option<String>,
+    company: Option<String>,
+    department: Option<String>,
+    role_seniority: Option<String>,
+    assistant_name: Option<String>,
+    assistant_phone: Option<String>,
+    /// Id of the manager's card
+    reports_to: Option<i64>,
+    /// IANA time zone; derived from the addresses when empty
+    timezone: Option<String>,
+    website: Option<String>,
+    notes: Option<String>,
+    /// JSON array of `CardFormPhoneInput`
+    phones: Option<String>,
+    /// JSON array of `CardFormEmailInput`
+    emails: Option<String>,
+    /// JSON array of `CardFormAddressInput`
+    addresses: Option<String>,
+    /// JSON array of `CardFormSocialInput`
+    socials: Option<String>,
+    /// JSON array of `CardFormImInput`
+    ims: Option<String>,
+    /// JSON array of tag names
+    tags: Option<String>,
+    /// JSON object of custom field values by key
+    custom: Option<String>,
+    /// Expected current version (optimistic concurrency)
+    version: Option<i64>,
+    /// jpg, png, webp, or heic; at most `--max-upload-mb` (5 MB by default)
+    #[schema(value_type = Option<String>, format = Binary)]
+    photo: Option<Vec<u8>>,
+}
+
+#[allow(dead_code)]
+#[derive(ToSchema)]
+pub struct PhotoForm {
+    /// jpg, png, webp, or heic; at most `--max-upload-mb` (5 MB by default)
+    #[schema(value_type = Option<String>, format = Binary)]
+    photo: Option<Vec<u8>>,
+    /// Id of a finished resumable upload, in place of `photo`
+    upload: Option<String>,
+}
+
+/// Multipart form adding one of a card's photos.
+#[allow(dead_code)]
+#[derive(ToSchema)]
+pub struct CardPhotoForm {
+    /// jpg, png, webp, or heic; at most `--max-upload-mb` (5 MB by default)
+    #[schema(value_type = Option<String>, format = Binary)]
+    photo: Option<Vec<u8>>,
+    /// Id of a finished resumable upload, in place of `photo`
+    upload: Option<String>,
+    /// `front` if left out
+    role: Option<PhotoRole>,
+}
+
+/// Multipart form attaching a file to a card.
+#[allow(dead_code)]
+#[derive(ToSchema)]
+pub struct AttachmentForm {
+    /// Any type; at most `--attachment-max-mb` megabytes
+    #[schema(value_type = Option<String>, format = Binary)]
+    file: Option<Vec<u8>>,
+    /// Id of a finished resumable upload, in place of `file`
+    upload: Option<String>,
+}
+
+#[allow(dead_code)]
+#[derive(ToSchema)]
+pub struct PhotoUrl {
+    photo_url: String,
+}
+
+#[allow(dead_code)]
+#[derive(ToSchema)]
+pub struct ErrorBody {
+    error: String,
+    /// The request's `X-Request-Id`, to quote when reporting the failure
+    request_id: Option<String>,
+}
+
+/// 422 response listing each field that failed validation.
+#[allow(dead_code)]
+#[derive(ToSchema)]
+pub struct ValidationErrorBody {
+    /// Every error on one line
+    error: String,
+    errors: Vec<FieldError>,
+}
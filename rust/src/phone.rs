@@ -0,0 +1,29 @@
+use std::sync::OnceLock;
+
+use anyhow::Result;
+use phonenumber::{country, Mode};
+
+/// Region assumed for numbers entered without a country code, set once at
+/// startup from `--default-region`.
+static DEFAULT_REGION: OnceLock<country::Id> = OnceLock::new();
+
+/// Sets the region (an ISO 3166 code such as `SG`) for numbers without a
+/// country code. Fails if the code is unknown.
+pub fn set_default_region(code: &str) -> Result<()> {
+    let region: country::Id = code
+        .trim()
+        .to_uppercase()
+        .parse()
+        .map_err(|_| anyhow::anyhow!("unknown region '{code}'"))?;
+    let _ = DEFAULT_REGION.set(region);
+    Ok(())
+}
+
+/// The number in E.164 form (`+6591234567`), or `None` if it is not a valid
+/// number. Numbers without a `+` country code need a default region.
+pub fn to_e164(number: &str) -> Option<String> {
+    let parsed = phonenumber::parse(DEFAULT_REGION.get().copied(), number).ok()?;
+    parsed
+        .is_valid()
+        .then(|| parsed.format().mode(Mode::E164).to_string())
+}
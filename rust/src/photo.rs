@@ -0,0 +1,297 @@
+use std::io::Cursor;
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use image::{
+    codecs::jpeg::JpegEncoder, imageops::FilterType, metadata::Orientation, DynamicImage, ImageDecoder,
+    ImageFormat, ImageReader,
+};
+
+use tokio::process::Command;
+
+use crate::models::ThumbSize;
+
+/// A large HEIC photo converts in a second or two; give up well after that.
+const CONVERT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Major brands of HEIF files: HEVC-coded images and sequences (what
+/// iPhones take), and plain HEIF.
+const HEIF_BRANDS: &[&[u8]] = &[b"heic", b"heix", b"heim", b"heis", b"hevc", b"hevx", b"mif1", b"msf1"];
+
+/// Photo types that can be uploaded, by file extension.
+const TYPES: &[(&str, ImageFormat)] = &[
+    ("jpg", ImageFormat::Jpeg),
+    ("jpeg", ImageFormat::Jpeg),
+    ("png", ImageFormat::Png),
+    ("webp", ImageFormat::WebP),
+];
+
+/// Checks that `data` is an image of the type `filename` claims, by its
+/// magic bytes rather than the name alone.
+pub fn check_type(filename: &str, data: &[u8]) -> Result<(), String> {
+    let ext = Path::new(filename)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_default();
+    if is_heif_name(filename) {
+        return if is_heif(data) {
+            Ok(())
+        } else {
+            Err(format!("photo is named .{ext} but is not a HEIC image"))
+        };
+    }
+    let Some(&(_, claimed)) = TYPES.iter().find(|(e, _)| *e == ext) else {
+        return Err("only jpg, png, webp, heic photos are allowed".to_string());
+    };
+    match image::guess_format(data) {
+        Ok(actual) if actual == claimed => Ok(()),
+        Ok(actual) => Err(format!(
+            "photo is named .{ext} but is a {} file",
+            actual.extensions_str().first().unwrap_or(&"different")
+        )),
+        Err(_) => Err(format!("photo is named .{ext} but is not an image")),
+    }
+}
+
+/// Whether `filename` names a HEIC photo, which is stored as JPEG.
+pub fn is_heif_name(filename: &str) -> bool {
+    Path::new(filename)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("heic") || e.eq_ignore_ascii_case("heif"))
+}
+
+/// Whether `data` starts with the `ftyp` box of a HEIF file.
+fn is_heif(data: &[u8]) -> bool {
+    data.get(4..8) == Some(b"ftyp") && data.get(8..12).is_some_and(|brand| HEIF_BRANDS.contains(&brand))
+}
+
+/// Why a HEIC photo was not converted.
+pub enum ConvertError {
+    /// The converter could not be run or did not finish, the server's fault
+    Unavailable(anyhow::Error),
+    /// The converter refused the photo, as it does a damaged one
+    Rejected(String),
+}
+
+/// Converts a HEIC photo to JPEG at `quality` with `command`, libheif's
+/// `heif-convert`, which reads and writes files rather than pipes.
+pub async fn heif_to_jpeg(command: &str, data: &[u8], quality: u8) -> Result<Vec<u8>, ConvertError> {
+    let base = std::env::temp_dir().join(format!("cardvault-{}", uuid::Uuid::new_v4()));
+    let (input, output) = (base.with_extension("heic"), base.with_extension("jpg"));
+    tokio::fs::write(&input, data)
+        .await
+        .context("write HEIC photo")
+        .map_err(ConvertError::Unavailable)?;
+    let converted = convert(command, &input, &output, quality).await;
+    let _ = tokio::fs::remove_file(&input).await;
+    let _ = tokio::fs::remove_file(&output).await;
+    converted
+}
+
+async fn convert(command: &str, input: &Path, output: &Path, quality: u8) -> Result<Vec<u8>, ConvertError> {
+    let child = Command::new(command)
+        .arg("-q")
+        .arg(quality.to_string())
+        .arg(input)
+        .arg(output)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| format!("run {command}"))
+        .map_err(ConvertError::Unavailable)?;
+    let result = match tokio::time::timeout(CONVERT_TIMEOUT, child.wait_with_output()).await {
+        Ok(result) => result.with_context(|| format!("run {command}")),
+        Err(_) => Err(anyhow!("HEIC conversion timed out")),
+    }
+    .map_err(ConvertError::Unavailable)?;
+    if !result.status.success() {
+        return Err(ConvertError::Rejected(String::from_utf8_lossy(&result.stderr).trim().to_string()));
+    }
+    tokio::fs::read(output)
+        .await
+        .context("read converted photo")
+        .map_err(ConvertError::Unavailable)
+}
+
+/// How large stored photos may be.
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    /// Longest side, in pixels
+    pub max_dimension: u32,
+    /// JPEG quality, 1-100, of photos that are encoded again
+    pub quality: u8,
+}
+
+/// `data` ready to store: turned upright by its EXIF orientation, scaled
+/// down to fit `limits`, and stripped of its metadata (EXIF, with any GPS
+/// position, and XMP). A photo that needs neither turning nor scaling keeps
+/// its encoding and only has the metadata cut out. Fails for bytes that do
+/// not decode, so a broken photo is refused rather than stored.
+pub fn prepare(data: &[u8], limits: Limits) -> Result<Vec<u8>> {
+    let reader = ImageReader::new(Cursor::new(data)).with_guessed_format()?;
+    let format = reader.format().context("not an image")?;
+    let mut decoder = reader.into_decoder().context("decode photo")?;
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let mut image = DynamicImage::from_decoder(decoder).context("decode photo")?;
+    let max = limits.max_dimension;
+    if orientation == Orientation::NoTransforms && image.width() <= max && image.height() <= max {
+        if let Some(stripped) = strip_metadata(data, format) {
+            return Ok(stripped);
+        }
+    }
+
+    image.apply_orientation(orientation);
+    if image.width() > max || image.height() > max {
+        image = image.resize(max, max, FilterType::Lanczos3);
+    }
+    // The encoders write no metadata.
+    let mut out = Cursor::new(Vec::new());
+    match format {
+        ImageFormat::Jpeg => {
+            // JPEG has no alpha channel.
+            let rgb = DynamicImage::ImageRgb8(image.into_rgb8());
+            rgb.write_with_encoder(JpegEncoder::new_with_quality(&mut out, limits.quality))?;
+        }
+        format => image.write_to(&mut out, format).context("encode photo")?,
+    }
+    Ok(out.into_inner())
+}
+
+/// `data` without its metadata blocks, `None` if it is not laid out as
+/// its format should be.
+fn strip_metadata(data: &[u8], format: ImageFormat) -> Option<Vec<u8>> {
+    match format {
+        ImageFormat::Jpeg => strip_jpeg(data),
+        ImageFormat::Png => strip_png(data),
+        ImageFormat::WebP => strip_webp(data),
+        _ => None,
+    }
+}
+
+/// Drops the APP1 (EXIF, XMP) and APP13 (IPTC) segments and comments.
+/// Everything from the start of the scan on is image data.
+fn strip_jpeg(data: &[u8]) -> Option<Vec<u8>> {
+    let mut out = data.get(..2)?.to_vec();
+    let mut at = 2;
+    loop {
+        if *data.get(at)? != 0xFF {
+            return None;
+        }
+        let marker = *data.get(at + 1)?;
+        match marker {
+            // Fill byte before a marker
+            0xFF => at += 1,
+            // Start of scan, end of image
+            0xDA | 0xD9 => {
+                out.extend_from_slice(&data[at..]);
+                return Some(out);
+            }
+            // Markers without a length
+            0x01 | 0xD0..=0xD7 => {
+                out.extend_from_slice(&data[at..at + 2]);
+                at += 2;
+            }
+            _ => {
+                let len = u16::from_be_bytes([*data.get(at + 2)?, *data.get(at + 3)?]) as usize;
+                let segment = data.get(at..at + 2 + len)?;
+                if !matches!(marker, 0xE1 | 0xED | 0xFE) {
+                    out.extend_from_slice(segment);
+                }
+                at += segment.len();
+            }
+        }
+    }
+}
+
+/// Drops the `eXIf` chunk and the text chunks, which carry XMP.
+fn strip_png(data: &[u8]) -> Option<Vec<u8>> {
+    let mut out = data.get(..8)?.to_vec();
+    let mut at = 8;
+    while at < data.len() {
+        let len = u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?) as usize;
+        // Length, type, data, CRC
+        let chunk = data.get(at..at + 12 + len)?;
+        if !matches!(&chunk[4..8], b"eXIf" | b"tEXt" | b"zTXt" | b"iTXt") {
+            out.extend_from_slice(chunk);
+        }
+        at += chunk.len();
+    }
+    Some(out)
+}
+
+/// Drops the `EXIF` and `XMP ` chunks and clears their flags in `VP8X`.
+fn strip_webp(data: &[u8]) -> Option<Vec<u8>> {
+    if data.get(..4)? != b"RIFF" || data.get(8..12)? != b"WEBP" {
+        return None;
+    }
+    let mut out = data[..12].to_vec();
+    let mut at = 12;
+    while at < data.len() {
+        let len = u32::from_le_bytes(data.get(at + 4..at + 8)?.try_into().ok()?) as usize;
+        // FourCC, size, data, padded to an even length
+        let chunk = data.get(at..(at + 8 + len + len % 2).min(data.len()))?;
+        match &chunk[..4] {
+            b"EXIF" | b"XMP " => {}
+            b"VP8X" => {
+                let flags = out.len() + 8;
+                out.extend_from_slice(chunk);
+                *out.get_mut(flags)? &= !(0x08 | 0x04);
+            }
+            _ => out.extend_from_slice(chunk),
+        }
+        at += chunk.len();
+    }
+    let riff_size = u32::try_from(out.len() - 8).ok()?;
+    out[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    Some(out)
+}
+
+/// Where the thumbnail of `size` of the photo stored as `key` is kept:
+/// beside it, `card_1_17.png` → `card_1_17_small.png`.
+pub fn thumb_key(key: &str, size: ThumbSize) -> String {
+    let path = Path::new(key);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+    let name = format!("{stem}_{}.{ext}", size.as_str());
+    match path.parent().and_then(|p| p.to_str()).filter(|p| !p.is_empty()) {
+        Some(dir) => format!("{dir}/{name}"),
+        None => name,
+    }
+}
+
+/// Every thumbnail of the photo `data` stored as `key`, in its format.
+pub fn make_thumbnails(key: &str, data: &[u8]) -> Result<Vec<(ThumbSize, Vec<u8>)>> {
+    let image = image::load_from_memory(data).context("decode photo")?;
+    ThumbSize::ALL
+        .into_iter()
+        .map(|size| Ok((size, encode_thumbnail(&image, key, size)?)))
+        .collect()
+}
+
+/// The thumbnail of `size` of the photo `data` stored as `key`, for photos
+/// uploaded before thumbnails were.
+pub fn make_thumbnail(key: &str, data: &[u8], size: ThumbSize) -> Result<Vec<u8>> {
+    let image = image::load_from_memory(data).context("decode photo")?;
+    encode_thumbnail(&image, key, size)
+}
+
+/// Scales `image` down to fit `size`; smaller images are kept at their
+/// size rather than blown up.
+fn encode_thumbnail(image: &DynamicImage, key: &str, size: ThumbSize) -> Result<Vec<u8>> {
+    let px = size.pixels();
+    let format = ImageFormat::from_path(key).with_context(|| format!("format of {key}"))?;
+    let mut out = Vec::new();
+    if image.width() > px || image.height() > px {
+        image.thumbnail(px, px).write_to(&mut Cursor::new(&mut out), format)
+    } else {
+        image.write_to(&mut Cursor::new(&mut out), format)
+    }
+    .with_context(|| format!("encode {}", thumb_key(key, size)))?;
+    Ok(out)
+}
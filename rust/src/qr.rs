@@ -0,0 +1,422 @@
+//! A QR code encoder for the card codes: byte mode, error correction level
+//! M, versions 1–40, rendered as SVG.
+
+/// Error-correction codewords per block at level M, by version.
+const ECC_PER_BLOCK: [usize; 41] = [
+    0, 10, 16, 26, 18, 24, 16, 18, 22, 22, 26, 30, 22, 22, 24, 24, 28, 28, 26, 26, 26, 26, 28,
+    28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28,
+];
+
+/// Error-correction blocks at level M, by version.
+const BLOCKS: [usize; 41] = [
+    0, 1, 1, 1, 2, 2, 4, 4, 4, 5, 5, 5, 8, 9, 9, 10, 10, 11, 13, 14, 16, 17, 17, 18, 20, 21, 23,
+    25, 26, 28, 29, 31, 33, 35, 37, 38, 40, 43, 45, 47, 49,
+];
+
+/// Format information bits for level M.
+const LEVEL_M: u32 = 0b00;
+
+/// Light modules around the code, as the spec requires.
+const QUIET_ZONE: usize = 4;
+
+/// The data is longer than the largest QR code holds.
+#[derive(Debug)]
+pub struct TooLong;
+
+pub struct QrCode {
+    size: usize,
+    modules: Vec<Vec<bool>>,
+    /// Finder, timing, alignment, format, and version modules, which
+    /// masking leaves alone
+    function: Vec<Vec<bool>>,
+}
+
+impl QrCode {
+    /// Encodes `data` in the smallest version that holds it.
+    pub fn encode(data: &[u8]) -> Result<Self, TooLong> {
+        let version = (1..=40)
+            .find(|&v| {
+                let count_bits = if v <= 9 { 8 } else { 16 };
+                4 + count_bits + data.len() * 8 <= data_codewords(v) * 8
+            })
+            .ok_or(TooLong)?;
+
+        let mut bits = Bits::default();
+        bits.push(0b0100, 4);
+        bits.push(data.len() as u32, if version <= 9 { 8 } else { 16 });
+        for &byte in data {
+            bits.push(byte as u32, 8);
+        }
+        let capacity = data_codewords(version) * 8;
+        let terminator = (capacity - bits.0.len()).min(4);
+        bits.push(0, terminator);
+        bits.push(0, (8 - bits.0.len() % 8) % 8);
+        let mut codewords: Vec<u8> = bits
+            .0
+            .chunks(8)
+            .map(|byte| byte.iter().fold(0, |acc, &bit| acc << 1 | bit as u8))
+            .collect();
+        for pad in [0xEC, 0x11].into_iter().cycle() {
+            if codewords.len() == data_codewords(version) {
+                break;
+            }
+            codewords.push(pad);
+        }
+
+        let size = version * 4 + 17;
+        let mut code = Self {
+            size,
+            modules: vec![vec![false; size]; size],
+            function: vec![vec![false; size]; size],
+        };
+        code.draw_function_patterns(version);
+        code.draw_codewords(&add_error_correction(version, &codewords));
+
+        let mask = (0..8)
+            .min_by_key(|&mask| {
+                code.apply_mask(mask);
+                code.draw_format(mask);
+                let penalty = code.penalty();
+                code.apply_mask(mask);
+                penalty
+            })
+            .unwrap_or(0);
+        code.apply_mask(mask);
+        code.draw_format(mask);
+        Ok(code)
+    }
+
+    /// The code as a scalable SVG image, one unit per module.
+    pub fn svg(&self) -> String {
+        let width = self.size + QUIET_ZONE * 2;
+        let mut path = String::new();
+        for (y, row) in self.modules.iter().enumerate() {
+            for (x, &dark) in row.iter().enumerate() {
+                if dark {
+                    path.push_str(&format!("M{},{}h1v1h-1z", x + QUIET_ZONE, y + QUIET_ZONE));
+                }
+            }
+        }
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {width} {width}\" \
+             shape-rendering=\"crispEdges\">\n\
+             <rect width=\"100%\" height=\"100%\" fill=\"#fff\"/>\n\
+             <path d=\"{path}\" fill=\"#000\"/>\n\
+             </svg>\n"
+        )
+    }
+
+    fn set_function(&mut self, x: usize, y: usize, dark: bool) {
+        self.modules[y][x] = dark;
+        self.function[y][x] = true;
+    }
+
+    fn draw_function_patterns(&mut self, version: usize) {
+        let size = self.size;
+        for i in 0..size {
+            self.set_function(6, i, i % 2 == 0);
+            self.set_function(i, 6, i % 2 == 0);
+        }
+        for (x, y) in [(3, 3), (size - 4, 3), (3, size - 4)] {
+            self.draw_finder(x, y);
+        }
+
+        let positions = alignment_positions(version);
+        let last = positions.len().saturating_sub(1);
+        for (i, &x) in positions.iter().enumerate() {
+            for (j, &y) in positions.iter().enumerate() {
+                // Skip the three that would overlap the finders.
+                if (i == 0 && (j == 0 || j == last)) || (i == last && j == 0) {
+                    continue;
+                }
+                for dy in -2i32..=2 {
+                    for dx in -2i32..=2 {
+                        let ring = dx.abs().max(dy.abs());
+                        self.set_function(
+                            (x as i32 + dx) as usize,
+                            (y as i32 + dy) as usize,
+                            ring != 1,
+                        );
+                    }
+                }
+            }
+        }
+
+        // Reserve the format areas; the real bits go in once the mask is chosen.
+        self.draw_format(0);
+
+        if version >= 7 {
+            let mut rem = version as u32;
+            for _ in 0..12 {
+                rem = (rem << 1) ^ ((rem >> 11) * 0x1F25);
+            }
+            let bits = (version as u32) << 12 | rem;
+            for i in 0..18 {
+                let dark = (bits >> i) & 1 == 1;
+                let a = size - 11 + i % 3;
+                let b = i / 3;
+                self.set_function(a, b, dark);
+                self.set_function(b, a, dark);
+            }
+        }
+    }
+
+    /// A finder pattern centred on `(x, y)`, with its light separator.
+    fn draw_finder(&mut self, x: usize, y: usize) {
+        for dy in -4i32..=4 {
+            for dx in -4i32..=4 {
+                let (xx, yy) = (x as i32 + dx, y as i32 + dy);
+                if (0..self.size as i32).contains(&xx) && (0..self.size as i32).contains(&yy) {
+                    let ring = dx.abs().max(dy.abs());
+                    self.set_function(xx as usize, yy as usize, ring != 2 && ring != 4);
+                }
+            }
+        }
+    }
+
+    fn draw_format(&mut self, mask: u32) {
+        let data = LEVEL_M << 3 | mask;
+        let mut rem = data;
+        for _ in 0..10 {
+            rem = (rem << 1) ^ ((rem >> 9) * 0x537);
+        }
+        let bits = (data << 10 | rem) ^ 0x5412;
+        let bit = |i: usize| (bits >> i) & 1 == 1;
+        let size = self.size;
+
+        for i in 0..=5 {
+            self.set_function(8, i, bit(i));
+        }
+        self.set_function(8, 7, bit(6));
+        self.set_function(8, 8, bit(7));
+        self.set_function(7, 8, bit(8));
+        for i in 9..15 {
+            self.set_function(14 - i, 8, bit(i));
+        }
+
+        for i in 0..8 {
+            self.set_function(size - 1 - i, 8, bit(i));
+        }
+        for i in 8..15 {
+            self.set_function(8, size - 15 + i, bit(i));
+        }
+        self.set_function(8, size - 8, true);
+    }
+
+    /// Places the codewords in the two-column zigzag from the bottom right.
+    fn draw_codewords(&mut self, data: &[u8]) {
+        let size = self.size;
+        let mut i = 0;
+        let mut right = size - 1;
+        loop {
+            if right == 6 {
+                right = 5;
+            }
+            let upward = (right + 1) & 2 == 0;
+            for vert in 0..size {
+                let y = if upward { size - 1 - vert } else { vert };
+                for x in [right, right - 1] {
+                    if !self.function[y][x] && i < data.len() * 8 {
+                        self.modules[y][x] = (data[i >> 3] >> (7 - (i & 7))) & 1 == 1;
+                        i += 1;
+                    }
+                }
+            }
+            if right < 2 {
+                break;
+            }
+            right -= 2;
+        }
+    }
+
+    /// XORs the data modules with mask pattern `mask`; applying it twice
+    /// undoes it.
+    fn apply_mask(&mut self, mask: u32) {
+        for y in 0..self.size {
+            for x in 0..self.size {
+                let invert = match mask {
+                    0 => (x + y) % 2 == 0,
+                    1 => y % 2 == 0,
+                    2 => x % 3 == 0,
+                    3 => (x + y) % 3 == 0,
+                    4 => (x / 3 + y / 2) % 2 == 0,
+                    5 => x * y % 2 + x * y % 3 == 0,
+                    6 => (x * y % 2 + x * y % 3) % 2 == 0,
+                    _ => ((x + y) % 2 + x * y % 3) % 2 == 0,
+                };
+                if invert && !self.function[y][x] {
+                    self.modules[y][x] = !self.modules[y][x];
+                }
+            }
+        }
+    }
+
+    /// The spec's mask penalty: long runs, 2×2 blocks, finder look-alikes,
+    /// and dark/light imbalance.
+    fn penalty(&self) -> usize {
+        let size = self.size;
+        let at = |x: usize, y: usize| self.modules[y][x];
+        let mut penalty = 0;
+
+        for transpose in [false, true] {
+            let get = |a: usize, b: usize| if transpose { at(b, a) } else { at(a, b) };
+            for b in 0..size {
+                let mut run = 1;
+                for a in 1..size {
+                    if get(a, b) == get(a - 1, b) {
+                        run += 1;
+                        if run == 5 {
+                            penalty += 3;
+                        } else if run > 5 {
+                            penalty += 1;
+                        }
+                    } else {
+                        run = 1;
+                    }
+                }
+                for a in 0..size.saturating_sub(10) {
+                    let window: Vec<bool> = (a..a + 11).map(|i| get(i, b)).collect();
+                    let finder = [true, false, true, true, true, false, true];
+                    if (window[..7] == finder && window[7..].iter().all(|&d| !d))
+                        || (window[4..] == finder && window[..4].iter().all(|&d| !d))
+                    {
+                        penalty += 40;
+                    }
+                }
+            }
+        }
+
+        for y in 0..size - 1 {
+            for x in 0..size - 1 {
+                let c = at(x, y);
+                if c == at(x + 1, y) && c == at(x, y + 1) && c == at(x + 1, y + 1) {
+                    penalty += 3;
+                }
+            }
+        }
+
+        let dark = self.modules.iter().flatten().filter(|&&d| d).count();
+        let total = size * size;
+        // Each 5% away from half dark costs 10.
+        let deviation = (dark * 20).abs_diff(total * 10);
+        penalty += deviation.div_ceil(total).saturating_sub(1) * 10;
+        penalty
+    }
+}
+
+#[derive(Default)]
+struct Bits(Vec<bool>);
+
+impl Bits {
+    fn push(&mut self, value: u32, len: usize) {
+        for i in (0..len).rev() {
+            self.0.push((value >> i) & 1 == 1);
+        }
+    }
+}
+
+/// Modules left for data and error correction once the function patterns
+/// are drawn.
+fn raw_data_modules(version: usize) -> usize {
+    let mut modules = (16 * version + 128) * version + 64;
+    if version >= 2 {
+        let align = version / 7 + 2;
+        modules -= (25 * align - 10) * align - 55;
+        if version >= 7 {
+            modules -= 36;
+        }
+    }
+    modules
+}
+
+fn data_codewords(version: usize) -> usize {
+    raw_data_modules(version) / 8 - ECC_PER_BLOCK[version] * BLOCKS[version]
+}
+
+fn alignment_positions(version: usize) -> Vec<usize> {
+    if version == 1 {
+        return Vec::new();
+    }
+    let count = version / 7 + 2;
+    let step = (version * 8 + count * 3 + 5) / (count * 4 - 4) * 2;
+    let size = version * 4 + 17;
+    let mut positions: Vec<usize> = (0..count - 1).map(|i| size - 7 - i * step).collect();
+    positions.push(6);
+    positions.reverse();
+    positions
+}
+
+/// Splits the data into blocks, appends each block's Reed–Solomon codewords,
+/// and interleaves the result.
+fn add_error_correction(version: usize, data: &[u8]) -> Vec<u8> {
+    let blocks = BLOCKS[version];
+    let ecc_len = ECC_PER_BLOCK[version];
+    let raw = raw_data_modules(version) / 8;
+    let short_blocks = blocks - raw % blocks;
+    let short_len = raw / blocks;
+    let divisor = rs_divisor(ecc_len);
+
+    let mut split = Vec::with_capacity(blocks);
+    let mut offset = 0;
+    for i in 0..blocks {
+        let len = short_len - ecc_len + usize::from(i >= short_blocks);
+        let mut block = data[offset..offset + len].to_vec();
+        offset += len;
+        let ecc = rs_remainder(&block, &divisor);
+        if i < short_blocks {
+            // Placeholder so all blocks line up; skipped when interleaving.
+            block.push(0);
+        }
+        block.extend(ecc);
+        split.push(block);
+    }
+
+    let mut result = Vec::with_capacity(raw);
+    for i in 0..split[0].len() {
+        for (j, block) in split.iter().enumerate() {
+            if i != short_len - ecc_len || j >= short_blocks {
+                result.push(block[i]);
+            }
+        }
+    }
+    result
+}
+
+fn rs_divisor(degree: usize) -> Vec<u8> {
+    let mut result = vec![0u8; degree];
+    result[degree - 1] = 1;
+    let mut root = 1u8;
+    for _ in 0..degree {
+        for j in 0..degree {
+            result[j] = gf_mul(result[j], root);
+            if j + 1 < degree {
+                result[j] ^= result[j + 1];
+            }
+        }
+        root = gf_mul(root, 0x02);
+    }
+    result
+}
+
+fn rs_remainder(data: &[u8], divisor: &[u8]) -> Vec<u8> {
+    let mut result = vec![0u8; divisor.len()];
+    for &byte in data {
+        let factor = byte ^ result.remove(0);
+        result.push(0);
+        for (r, &d) in result.iter_mut().zip(divisor) {
+            *r ^= gf_mul(d, factor);
+        }
+    }
+    result
+}
+
+/// Multiplication in GF(2⁸) modulo x⁸ + x⁴ + x³ + x² + 1.
+fn gf_mul(x: u8, y: u8) -> u8 {
+    let mut z: u32 = 0;
+    for i in (0..8).rev() {
+        z = (z << 1) ^ ((z >> 7) * 0x11D);
+        z ^= ((y as u32 >> i) & 1) * x as u32;
+    }
+    z as u8
+}
@@ -0,0 +1,56 @@
+use std::ops::Range;
+
+/// What a `Range` header asks of a file of `len` bytes.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Requested {
+    /// The whole file: no header, one this does not read, or several ranges,
+    /// which may be answered with the whole file (RFC 9110 §14.2)
+    Full,
+    /// One range of bytes
+    Part(Range<usize>),
+    /// A range that starts past the end (416)
+    Unsatisfiable,
+}
+
+/// Reads a single `bytes=` range: `bytes=0-499`, `bytes=500-` (to the
+/// end), or `bytes=-500` (the last 500 bytes). An end past the file is cut
+/// to its last byte.
+pub fn parse(header: Option<&str>, len: usize) -> Requested {
+    let Some(spec) = header.and_then(|h| h.trim().strip_prefix("bytes=")) else {
+        return Requested::Full;
+    };
+    if spec.contains(',') {
+        return Requested::Full;
+    }
+    let Some((first, last)) = spec.split_once('-') else {
+        return Requested::Full;
+    };
+    let (first, last) = (first.trim(), last.trim());
+    let range = if first.is_empty() {
+        // A suffix: the last `last` bytes
+        match last.parse::<usize>() {
+            Ok(0) => return Requested::Unsatisfiable,
+            Ok(n) => len.saturating_sub(n)..len,
+            Err(_) => return Requested::Full,
+        }
+    } else {
+        let Ok(start) = first.parse::<usize>() else {
+            return Requested::Full;
+        };
+        let end = match last {
+            "" => len,
+            last => match last.parse::<usize>() {
+                Ok(end) if end >= start => end.saturating_add(1).min(len),
+                _ => return Requested::Full,
+            },
+        };
+        if start >= len {
+            return Requested::Unsatisfiable;
+        }
+        start..end
+    };
+    if range.is_empty() {
+        return Requested::Unsatisfiable;
+    }
+    Requested::Part(range)
+}
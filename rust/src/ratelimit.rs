@@ -0,0 +1,37 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Allows at most `max` events in any `window`. In memory, so the count
+/// starts over when the server restarts.
+pub struct RateLimiter {
+    max: usize,
+    window: Duration,
+    events: Mutex<VecDeque<Instant>>,
+}
+
+impl RateLimiter {
+    pub fn new(max: usize, window: Duration) -> Self {
+        Self {
+            max,
+            window,
+            events: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Records an event if the limit allows it; otherwise returns how long
+    /// until it will.
+    pub fn check(&self) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut events = self.events.lock().unwrap();
+        while events.front().is_some_and(|&t| now.duration_since(t) >= self.window) {
+            events.pop_front();
+        }
+        if events.len() >= self.max {
+            let oldest = events.front().copied().unwrap_or(now);
+            return Err(self.window.saturating_sub(now.duration_since(oldest)));
+        }
+        events.push_back(now);
+        Ok(())
+    }
+}
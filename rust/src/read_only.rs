@@ -0,0 +1,40 @@
+use async_graphql::parser::{parse_query, types::OperationType};
+use axum::{
+    extract::Request,
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+
+/// 403 for a change asked of a `--read-only` server.
+pub fn forbidden() -> Response {
+    (
+        StatusCode::FORBIDDEN,
+        Json(json!({"error": "this vault is read-only"})),
+    )
+        .into_response()
+}
+
+/// Middleware for the API under `--read-only`: only GET, HEAD, and OPTIONS
+/// requests get through. Endpoints that take a POST without saving, such
+/// as `/scan`, are refused too, as nothing they draft could be saved.
+pub async fn middleware(request: Request, next: Next) -> Response {
+    if request.method().is_safe() {
+        next.run(request).await
+    } else {
+        forbidden()
+    }
+}
+
+/// Whether a GraphQL request has a mutation in it. A query that does not
+/// parse is left for the schema to reject.
+pub fn is_mutation(request: &async_graphql::Request) -> bool {
+    parse_query(&request.query).is_ok_and(|document| {
+        document
+            .operations
+            .iter()
+            .any(|(_, operation)| operation.node.ty == OperationType::Mutation)
+    })
+}
@@ -0,0 +1,79 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::Request,
+    http::{header, HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use serde_json::Value;
+use tracing::Instrument;
+
+use crate::telemetry;
+
+pub static X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Longest client-sent id that is kept; longer ones are replaced.
+const MAX_LEN: usize = 128;
+
+/// Largest error body rewritten to carry the id.
+const MAX_ERROR_BODY: usize = 64 * 1024;
+
+/// The client's `X-Request-Id` if it is short printable ASCII, else a new
+/// UUID.
+fn request_id(request: &Request) -> String {
+    request
+        .headers()
+        .get(&X_REQUEST_ID)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|id| !id.is_empty() && id.len() <= MAX_LEN && id.bytes().all(|b| b.is_ascii_graphic()))
+        .map(String::from)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string())
+}
+
+/// Tags each request with an id: the one the client sent in `X-Request-Id`,
+/// or a new one. Everything logged while serving it carries the id, it is
+/// sent back as `X-Request-Id`, and JSON error bodies gain a `request_id`,
+/// so a failure a client reports can be found in the logs.
+pub async fn middleware(request: Request, next: Next) -> Response {
+    let id = request_id(&request);
+    let span = tracing::info_span!("request", request_id = %id);
+    telemetry::http_request(&span, &request);
+    let response = next.run(request).instrument(span.clone()).await;
+    telemetry::http_response(&span, &response);
+
+    let mut response = if response.status().is_client_error() || response.status().is_server_error() {
+        with_request_id(response, &id).await
+    } else {
+        response
+    };
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(X_REQUEST_ID.clone(), value);
+    }
+    response
+}
+
+/// Adds `request_id` to a JSON error object; other bodies are left as they
+/// are.
+async fn with_request_id(response: Response, id: &str) -> Response {
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("application/json"));
+    let (mut parts, body) = response.into_parts();
+    if !is_json {
+        return Response::from_parts(parts, body);
+    }
+    let Ok(bytes) = to_bytes(body, MAX_ERROR_BODY).await else {
+        return Response::from_parts(parts, Body::empty());
+    };
+    let mut value = match serde_json::from_slice::<Value>(&bytes) {
+        Ok(Value::Object(map)) => map,
+        _ => return Response::from_parts(parts, Body::from(bytes)),
+    };
+    value.insert("request_id".to_string(), Value::String(id.to_string()));
+    let body = Value::Object(value).to_string();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(body))
+}
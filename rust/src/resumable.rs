@@ -0,0 +1,48 @@
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+
+use tokio::fs::{self, OpenOptions};
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+
+/// How long an unfinished upload is kept.
+pub const TTL_HOURS: i64 = 24;
+
+/// Largest chunk one request may carry.
+pub const MAX_CHUNK: usize = 8 * 1024 * 1024;
+
+/// Content type a chunk is sent with.
+pub const CHUNK_CONTENT_TYPE: &str = "application/offset+octet-stream";
+
+/// Where the bytes received so far of upload `id` are kept.
+pub fn partial_path(uploads_dir: &str, id: &str) -> PathBuf {
+    Path::new(uploads_dir).join("partial").join(id)
+}
+
+/// Writes `data` at `offset` of the partial file at `path`, cutting off
+/// whatever lies past `offset` first: the remains of a chunk whose request
+/// broke off before it was counted. Synced before returning, so a counted
+/// chunk survives a crash.
+pub async fn write_chunk(path: &Path, offset: u64, data: &[u8]) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).await?;
+    }
+    let mut file = OpenOptions::new().create(true).truncate(false).write(true).open(path).await?;
+    file.set_len(offset).await?;
+    file.seek(SeekFrom::Start(offset)).await?;
+    file.write_all(data).await?;
+    file.sync_data().await
+}
+
+/// The whole file of a finished upload, cut to its declared `size`.
+pub async fn read(path: &Path, size: u64) -> std::io::Result<Vec<u8>> {
+    let mut data = fs::read(path).await?;
+    data.truncate(size as usize);
+    Ok(data)
+}
+
+/// Removes the partial file of each of `ids`.
+pub async fn remove(uploads_dir: &str, ids: &[String]) {
+    for id in ids {
+        let _ = fs::remove_file(partial_path(uploads_dir, id)).await;
+    }
+}
@@ -0,0 +1,286 @@
+use crate::models::{CardFormAddressInput, CardFormEmailInput, CardFormPhoneInput, CardInput};
+
+/// Words that mark a line as the company name.
+const COMPANY_MARKERS: &[&str] = &[
+    "pte", "ltd", "limited", "inc", "llc", "llp", "gmbh", "corp", "corporation", "co", "company",
+    "group", "holdings", "plc", "sdn", "bhd", "k.k", "kk", "s.a", "ag", "bv", "pty", "株式会社",
+    "有限公司", "公司",
+];
+
+/// Words that mark a line as a job title.
+const TITLE_MARKERS: &[&str] = &[
+    "ceo", "cto", "cfo", "coo", "cio", "founder", "co-founder", "president", "director",
+    "manager", "head", "lead", "engineer", "developer", "designer", "consultant", "partner",
+    "officer", "executive", "analyst", "specialist", "associate", "vp", "vice", "chairman",
+    "architect", "advisor", "principal", "owner", "scientist", "professor", "representative",
+    "社長", "部長", "課長", "经理", "總監", "总监",
+];
+
+/// Words that mark a line as part of a street address.
+const STREET_MARKERS: &[&str] = &[
+    "street", "st", "road", "rd", "avenue", "ave", "boulevard", "blvd", "lane", "ln", "drive",
+    "dr", "way", "place", "square", "sq", "floor", "fl", "level", "suite", "ste", "unit",
+    "building", "bldg", "tower", "centre", "center", "park", "crescent", "highway", "jalan",
+    "strasse", "straße", "rue", "via", "calle",
+];
+
+/// Countries recognised at the end of an address line.
+const COUNTRIES: &[&str] = &[
+    "singapore", "malaysia", "indonesia", "thailand", "vietnam", "philippines", "japan", "china",
+    "hong kong", "taiwan", "korea", "south korea", "india", "australia", "new zealand", "usa",
+    "united states", "canada", "uk", "united kingdom", "ireland", "germany", "france", "netherlands",
+    "switzerland", "spain", "italy",
+];
+
+/// City-states, where the city is the country too.
+const CITY_STATES: &[&str] = &["singapore", "hong kong", "monaco"];
+
+/// Sign-offs above a signature, skipped when pasted along with it.
+const SIGN_OFFS: &[&str] = &[
+    "best", "best regards", "regards", "kind regards", "warm regards", "warmest regards",
+    "thanks", "thank you", "many thanks", "cheers", "sincerely", "yours sincerely", "br", "--",
+];
+
+/// Starts of the boilerplate below a signature, where it ends.
+const DISCLAIMERS: &[&str] = &[
+    "confidential", "disclaimer", "this email", "this e-mail", "this message", "the information in",
+    "please consider the environment", "sent from my",
+];
+
+/// Free mail providers, whose domain says nothing about the employer.
+const WEBMAIL: &[&str] = &["gmail", "googlemail", "yahoo", "hotmail", "outlook", "live", "icloud", "me", "qq", "163", "126"];
+
+/// Best guesses at a card's fields from the OCR text of a business card:
+/// emails, phones, and website by their shape, then company, title, and
+/// name from what is left. Everything is a candidate for the user to
+/// confirm, so a wrong guess is cheap and a missed one is just left blank.
+pub fn candidates(text: &str) -> CardInput {
+    let mut input = CardInput::default();
+    let mut rest = Vec::new();
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let mut claimed = false;
+        for word in line.split_whitespace() {
+            let word = word.trim_matches(|c: char| ",;()<>[]\"'".contains(c));
+            let lower = word.to_lowercase();
+            // `E:jane@example.com`
+            let unlabeled = word.rsplit(':').next().unwrap_or(word);
+            if let Some((local, domain)) = unlabeled.split_once('@') {
+                if !local.is_empty() && domain.contains('.') {
+                    let address = unlabeled.trim_end_matches('.').to_lowercase();
+                    input.emails.push(CardFormEmailInput {
+                        label: "work".to_string(),
+                        address,
+                    });
+                    claimed = true;
+                }
+            } else if lower.starts_with("www.") || lower.starts_with("http://") || lower.starts_with("https://") {
+                if input.website.is_empty() {
+                    input.website = word.trim_end_matches('.').to_string();
+                }
+                claimed = true;
+            }
+        }
+        // `Tokyo 104-0061` is a postal code, not a phone.
+        if locality(line).is_none() {
+            for (label, number) in phones(line) {
+                input.phones.push(CardFormPhoneInput { label, number });
+                claimed = true;
+            }
+        }
+        if !claimed {
+            rest.push(line);
+        }
+    }
+
+    // Signatures often put title and company on one line: `CTO | Acme`.
+    let parts = rest.iter().flat_map(|line| line.split(['|', '·', '•'])).map(str::trim);
+    let mut address = CardFormAddressInput { label: "office".to_string(), ..Default::default() };
+    for line in parts.filter(|p| !p.is_empty()) {
+        if line.chars().any(|c| c.is_ascii_digit()) {
+            add_address_line(&mut address, line);
+            continue;
+        }
+        if input.company.is_empty() && has_marker(line, COMPANY_MARKERS) {
+            input.company = line.to_string();
+        } else if input.title.is_empty() && has_marker(line, TITLE_MARKERS) {
+            input.title = line.to_string();
+        } else if input.name.is_empty() && looks_like_name(line) {
+            input.name = line.to_string();
+        }
+    }
+
+    if !address.street.is_empty() || !address.postal.is_empty() {
+        input.addresses.push(address);
+    }
+
+    for email in &mut input.emails {
+        let domain = email.address.split_once('@').map_or("", |(_, d)| d);
+        if is_webmail(domain) {
+            email.label = "personal".to_string();
+        }
+    }
+    // `jane.doe@` is a better guess than nothing.
+    let local = input.emails.first().and_then(|e| e.address.split_once('@')).map(|(l, _)| l);
+    if let Some(local) = local.filter(|l| input.name.is_empty() && l.contains('.')) {
+        input.name = local.split('.').map(capitalize).collect::<Vec<_>>().join(" ");
+    }
+    input
+}
+
+/// Whether `domain` belongs to a free mail provider, e.g. `gmail.com`.
+pub fn is_webmail(domain: &str) -> bool {
+    WEBMAIL.contains(&domain.split('.').next().unwrap_or_default())
+}
+
+/// Like [`candidates`], for text pasted from an email: sign-offs above the
+/// signature and disclaimers below it are dropped first.
+pub fn signature(text: &str) -> CardInput {
+    let lines: Vec<&str> = text.lines().collect();
+    let start = lines
+        .iter()
+        .position(|line| {
+            let line = line.trim().trim_end_matches([',', '!', '.']).to_lowercase();
+            !line.is_empty() && !SIGN_OFFS.contains(&line.as_str())
+        })
+        .unwrap_or(lines.len());
+    let end = lines[start..]
+        .iter()
+        .position(|line| {
+            let line = line.trim().to_lowercase();
+            DISCLAIMERS.iter().any(|d| line.starts_with(d))
+        })
+        .map_or(lines.len(), |i| start + i);
+    candidates(&lines[start..end].join("\n"))
+}
+
+/// Folds a line with digits into the address if it looks like part of
+/// one: a street line (`1 Fusionopolis Way #05-01`), or a locality line
+/// with a postal code (`Singapore 138632`, `Tokyo 104-0061 Japan`). Other
+/// numbers, such as company registration numbers, are ignored.
+fn add_address_line(address: &mut CardFormAddressInput, line: &str) {
+    let house_number = line.starts_with(|c: char| c.is_ascii_digit())
+        && line.split(|c: char| !c.is_alphabetic()).any(|w| w.chars().count() >= 3);
+    match locality(line) {
+        Some((postal, mut place)) if address.postal.is_empty() => {
+            address.postal = postal;
+            let lower = place.to_lowercase();
+            if let Some(country) = COUNTRIES.iter().find(|c| lower.ends_with(*c) && lower.len() > c.len()) {
+                address.country = place[place.len() - country.len()..].to_string();
+                place.truncate(place.len() - country.len());
+            }
+            address.city = place.trim().to_string();
+            if address.country.is_empty() && CITY_STATES.contains(&address.city.to_lowercase().as_str()) {
+                address.country = address.city.clone();
+            }
+        }
+        Some(_) => {}
+        None if house_number || has_marker(line, STREET_MARKERS) || line.contains('#') => {
+            if !address.street.is_empty() {
+                address.street.push_str(", ");
+            }
+            address.street.push_str(line.trim_end_matches(','));
+        }
+        None => {}
+    }
+}
+
+/// A postal code and one to four words of place name around it, e.g.
+/// `Singapore 138632` or `75008 Paris`: the postal code and the words.
+fn locality(line: &str) -> Option<(String, String)> {
+    let is_postal = |w: &str| {
+        let digits = w.chars().filter(char::is_ascii_digit).count();
+        (4..=7).contains(&digits) && w.chars().all(|c| c.is_ascii_digit() || c == '-')
+    };
+    let words = line.split(|c: char| c.is_whitespace() || c == ',').filter(|w| !w.is_empty());
+    let (postal, place): (Vec<&str>, Vec<&str>) = words.partition(|w| is_postal(w));
+    let is_place = (1..=4).contains(&place.len())
+        && place.iter().all(|w| w.chars().all(|c| c.is_alphabetic() || c == '.'));
+    match postal.as_slice() {
+        [postal] if is_place => Some((postal.to_string(), place.join(" "))),
+        _ => None,
+    }
+}
+
+/// Phone numbers on a line, with a label from the word before each:
+/// `M: +65 9123 4567  F: +65 6123 4568`.
+fn phones(line: &str) -> Vec<(String, String)> {
+    let is_phone_char = |c: char| c.is_ascii_digit() || " +-().".contains(c);
+    let mut found = Vec::new();
+    let mut start = 0;
+    let chars: Vec<(usize, char)> = line.char_indices().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let (at, c) = chars[i];
+        if !(c.is_ascii_digit() || c == '+' || c == '(') {
+            i += 1;
+            continue;
+        }
+        let mut j = i;
+        while j < chars.len() && is_phone_char(chars[j].1) {
+            j += 1;
+        }
+        let end = chars.get(j).map_or(line.len(), |&(at, _)| at);
+        let run = line[at..end].trim_end_matches(|c: char| !c.is_ascii_digit());
+        let digits = run.chars().filter(char::is_ascii_digit).count();
+        // A digit run glued to letters is a reference number, not a phone.
+        let glued = line[at + run.len()..].chars().next().is_some_and(char::is_alphanumeric);
+        if (7..=15).contains(&digits) && !glued {
+            let label = phone_label(&line[start..at]);
+            found.push((label.to_string(), run.trim().to_string()));
+            start = end;
+        }
+        i = j.max(i + 1);
+    }
+    found
+}
+
+/// The label for a phone number from the text before it: the last word,
+/// e.g. `Mobile:`, `T`, `Fax`.
+fn phone_label(before: &str) -> &'static str {
+    let word = before
+        .split(|c: char| !c.is_alphabetic())
+        .rfind(|w| !w.is_empty())
+        .unwrap_or("")
+        .to_lowercase();
+    match word.as_str() {
+        "m" | "mob" | "mobile" | "cell" | "hp" | "handphone" | "c" | "手机" | "手機" | "携帯" => "mobile",
+        "f" | "fax" | "传真" | "傳真" => "fax",
+        "h" | "home" => "home",
+        _ => "work",
+    }
+}
+
+fn has_marker(line: &str, markers: &[&str]) -> bool {
+    let lower = line.to_lowercase();
+    markers.iter().any(|marker| {
+        if !marker.is_ascii() {
+            return lower.contains(marker);
+        }
+        lower
+            .split(|c: char| c.is_whitespace() || ",;()/&|".contains(c))
+            .any(|word| word.trim_end_matches('.') == *marker)
+    })
+}
+
+/// Two to four capitalized words of letters, or a short run of CJK
+/// characters.
+fn looks_like_name(line: &str) -> bool {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    if words.len() == 1 && !line.is_ascii() {
+        let count = line.chars().count();
+        return (2..=5).contains(&count) && line.chars().all(char::is_alphabetic);
+    }
+    (2..=4).contains(&words.len())
+        && words.iter().all(|w| {
+            w.chars().next().is_some_and(char::is_uppercase)
+                && w.chars().all(|c| c.is_alphabetic() || ".-'".contains(c))
+        })
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
@@ -0,0 +1,352 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use rusqlite::Connection;
+
+use crate::{
+    models::{Card, CardQuery, SearchDoc, SearchMatch},
+    store,
+};
+
+/// Cards scoring below this are not considered a fuzzy match.
+const MIN_SCORE: f64 = 0.7;
+
+/// Values longer than this are cut down to a window around the match.
+const EXCERPT_CHARS: usize = 80;
+const EXCERPT_CONTEXT: usize = 30;
+
+/// Runs a card listing, returning the number of matches before paging and
+/// the requested page. Fuzzy queries are scored in memory and ordered by
+/// relevance; everything else is answered by the store directly.
+pub fn find_cards(conn: &Arc<Mutex<Connection>>, query: &CardQuery) -> Result<(i64, Vec<Card>)> {
+    let fuzzy_terms = query
+        .q
+        .as_deref()
+        .filter(|_| query.fuzzy)
+        .map(words)
+        .filter(|w| !w.is_empty());
+    let Some(terms) = fuzzy_terms else {
+        let total = store::count_cards(conn, query)?;
+        let mut cards = store::list_cards(conn, query)?;
+        if let Some(q) = query.q.as_deref().filter(|q| !q.trim().is_empty()) {
+            for card in &mut cards {
+                card.matches = if query.phonetic {
+                    phonetic_matches(q, card)
+                } else {
+                    substring_matches(q, card)
+                };
+            }
+        }
+        return Ok((total, cards));
+    };
+
+    // Every other filter still applies; only the text match moves here.
+    let candidates = CardQuery {
+        q: None,
+        ..query.clone()
+    };
+    let mut scored: Vec<(f64, i64)> = store::search_docs(conn, &candidates)?
+        .iter()
+        .filter_map(|doc| {
+            let score = score(&terms, doc);
+            (score >= MIN_SCORE).then_some((score, doc.id))
+        })
+        .collect();
+    // Stable sort keeps the store's most-recently-updated order among ties.
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let total = scored.len() as i64;
+    let page: Vec<(f64, i64)> = scored
+        .into_iter()
+        .skip(query.offset.max(0) as usize)
+        .take(query.limit.map_or(usize::MAX, |l| l.max(0) as usize))
+        .collect();
+    let ids: Vec<i64> = page.iter().map(|(_, id)| *id).collect();
+    let mut cards = store::get_cards_by_ids(conn, &ids, query.relations)?;
+    for card in &mut cards {
+        let score = page.iter().find(|(_, id)| *id == card.id).map_or(0.0, |(s, _)| *s);
+        card.score = Some((score * 1000.0).round() / 1000.0);
+        card.matches = fuzzy_matches(&terms, card);
+    }
+    Ok((total, cards))
+}
+
+/// The searchable values of a loaded card, as `(field, text)` pairs. Mirrors
+/// `store::search_docs`.
+fn card_fields(card: &Card) -> Vec<(&'static str, &str)> {
+    let mut fields = vec![
+        ("name", card.name.as_str()),
+        ("company", card.company.as_str()),
+        ("title", card.title.as_str()),
+        ("notes", card.notes.as_str()),
+        ("name_phonetic", card.name_phonetic.as_str()),
+    ];
+    fields.extend(card.emails.iter().map(|e| ("email", e.address.as_str())));
+    fields.extend(card.phones.iter().map(|p| ("phone", p.number.as_str())));
+    for a in &card.addresses {
+        fields.push(("address", a.street.as_str()));
+        fields.push(("address", a.city.as_str()));
+    }
+    fields.extend(card.ims.iter().map(|i| ("im", i.handle.as_str())));
+    fields
+}
+
+/// Case- and accent-insensitive substring matches, plus digit-only matches on phones,
+/// following the rules of the plain `q` filter.
+fn substring_matches(q: &str, card: &Card) -> Vec<SearchMatch> {
+    let needle: Vec<char> = q.chars().map(fold_char).collect();
+    let digits: Vec<char> = q.chars().filter(char::is_ascii_digit).collect();
+    let mut matches = Vec::new();
+    for (field, text) in card_fields(card) {
+        let chars: Vec<char> = text.chars().map(fold_char).collect();
+        if let Some(start) = find(&chars, &needle) {
+            matches.push(excerpt(field, text, start, start + needle.len()));
+        } else if field == "phone" && digits.len() >= 3 {
+            // Match on the digits, then map back to positions in the number.
+            let positions: Vec<usize> = text
+                .chars()
+                .enumerate()
+                .filter(|(_, c)| c.is_ascii_digit())
+                .map(|(i, _)| i)
+                .collect();
+            let number: Vec<char> = text.chars().filter(char::is_ascii_digit).collect();
+            if let Some(at) = find(&number, &digits) {
+                let end = positions[at + digits.len() - 1] + 1;
+                matches.push(excerpt(field, text, positions[at], end));
+            }
+        }
+    }
+    matches
+}
+
+/// Words that are close enough to one of the query terms to count.
+fn fuzzy_matches(terms: &[String], card: &Card) -> Vec<SearchMatch> {
+    let mut matches = Vec::new();
+    for (field, text) in card_fields(card) {
+        let hit = word_spans(text).into_iter().find(|(start, end)| {
+            let word: String = text.chars().skip(*start).take(end - start).map(fold_char).collect();
+            terms.iter().any(|t| word_similarity(t, &word) >= MIN_SCORE)
+        });
+        if let Some((start, end)) = hit {
+            matches.push(excerpt(field, text, start, end));
+        }
+    }
+    matches
+}
+
+/// Words of the name or its reading that sound like a word of the query.
+fn phonetic_matches(q: &str, card: &Card) -> Vec<SearchMatch> {
+    let codes = phonetic_key(q);
+    let codes: Vec<&str> = codes.split_whitespace().collect();
+    [("name", &card.name), ("name_phonetic", &card.name_phonetic)]
+        .into_iter()
+        .flat_map(|(field, text)| {
+            word_spans(text)
+                .into_iter()
+                .filter(|(start, end)| {
+                    let word: String = text.chars().skip(*start).take(end - start).collect();
+                    soundex(&word).is_some_and(|code| codes.contains(&code.as_str()))
+                })
+                .map(move |(start, end)| excerpt(field, text, start, end))
+        })
+        .collect()
+}
+
+/// Latin letters with diacritics and the plain letter they fold to.
+const FOLDS: &[(&str, char)] = &[
+    ("àáâãäåāăąạảấầẩẫậắằẳẵặ", 'a'),
+    ("çćĉċč", 'c'),
+    ("ďđ", 'd'),
+    ("èéêëēĕėęěẹẻẽếềểễệ", 'e'),
+    ("ĝğġģ", 'g'),
+    ("ĥħ", 'h'),
+    ("ìíîïĩīĭįıỉị", 'i'),
+    ("ĵ", 'j'),
+    ("ķ", 'k'),
+    ("ĺļľŀł", 'l'),
+    ("ñńņňŉ", 'n'),
+    ("òóôõöøōŏőơọỏốồổỗộớờởỡợ", 'o'),
+    ("ŕŗř", 'r'),
+    ("śŝşšș", 's'),
+    ("ţťŧț", 't'),
+    ("ùúûüũūŭůűųưụủứừửữự", 'u'),
+    ("ŵ", 'w'),
+    ("ýÿŷỳỵỷỹ", 'y'),
+    ("źżž", 'z'),
+];
+
+/// Lower-cases `c` and strips any diacritic, so "É" folds to "e". Always
+/// one character in, one out, so offsets into folded text stay valid.
+fn fold_char(c: char) -> char {
+    let c = c.to_lowercase().next().unwrap_or(c);
+    if c.is_ascii() {
+        return c;
+    }
+    FOLDS
+        .iter()
+        .find(|(accented, _)| accented.contains(c))
+        .map_or(c, |(_, plain)| *plain)
+}
+
+/// `text` with every character folded: the form used to compare names,
+/// so "jose" finds "José" and "Élodie" sorts next to "Eliza".
+pub fn fold(text: &str) -> String {
+    text.chars().map(fold_char).collect()
+}
+
+fn find(haystack: &[char], needle: &[char]) -> Option<usize> {
+    if needle.is_empty() || needle.len() > haystack.len() {
+        return None;
+    }
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// Character ranges of the alphanumeric words in `text`.
+fn word_spans(text: &str) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut start = None;
+    for (i, c) in text.chars().chain(std::iter::once(' ')).enumerate() {
+        match (c.is_alphanumeric(), start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                spans.push((s, i));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    spans
+}
+
+/// Builds a match for the character span `start..end` of `text`, trimming
+/// long values to a window around the span.
+fn excerpt(field: &str, text: &str, start: usize, end: usize) -> SearchMatch {
+    let chars: Vec<char> = text.chars().collect();
+    if chars.len() <= EXCERPT_CHARS {
+        return SearchMatch {
+            field: field.to_string(),
+            excerpt: text.to_string(),
+            start,
+            end,
+        };
+    }
+    let from = start.saturating_sub(EXCERPT_CONTEXT);
+    let to = (end + EXCERPT_CONTEXT).min(chars.len());
+    let mut excerpt = String::new();
+    let mut offset = start - from;
+    if from > 0 {
+        excerpt.push('…');
+        offset += 1;
+    }
+    excerpt.extend(&chars[from..to]);
+    if to < chars.len() {
+        excerpt.push('…');
+    }
+    SearchMatch {
+        field: field.to_string(),
+        excerpt,
+        start: offset,
+        end: offset + (end - start),
+    }
+}
+
+/// Relevance of a card to the query terms: the mean, over the terms, of the
+/// best similarity between that term and any word on the card.
+fn score(terms: &[String], doc: &SearchDoc) -> f64 {
+    let card_words: Vec<String> = doc.fields.iter().flat_map(|(_, text)| words(text)).collect();
+    let total: f64 = terms
+        .iter()
+        .map(|t| {
+            card_words
+                .iter()
+                .map(|w| word_similarity(t, w))
+                .fold(0.0, f64::max)
+        })
+        .sum();
+    total / terms.len() as f64
+}
+
+/// Folded alphanumeric words of `text`.
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(fold)
+        .collect()
+}
+
+/// 1.0 for a prefix match (so partial names still rank first), otherwise
+/// 1 - normalised edit distance.
+fn word_similarity(term: &str, word: &str) -> f64 {
+    if word.starts_with(term) && term.chars().count() >= 2 {
+        return 1.0;
+    }
+    let len = term.chars().count().max(word.chars().count());
+    if len == 0 {
+        return 0.0;
+    }
+    1.0 - levenshtein(term, word) as f64 / len as f64
+}
+
+/// Space-separated Soundex codes of the words in `name`, stored alongside
+/// each card so names can be found by how they sound ("Jon Smyth" matches
+/// "John Smith"). Words without ASCII letters are skipped.
+pub fn phonetic_key(name: &str) -> String {
+    name.split_whitespace()
+        .filter_map(soundex)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn soundex(word: &str) -> Option<String> {
+    fn digit(c: char) -> Option<char> {
+        match c {
+            'b' | 'f' | 'p' | 'v' => Some('1'),
+            'c' | 'g' | 'j' | 'k' | 'q' | 's' | 'x' | 'z' => Some('2'),
+            'd' | 't' => Some('3'),
+            'l' => Some('4'),
+            'm' | 'n' => Some('5'),
+            'r' => Some('6'),
+            _ => None,
+        }
+    }
+
+    let mut letters = word
+        .chars()
+        .filter(char::is_ascii_alphabetic)
+        .map(|c| c.to_ascii_lowercase());
+    let first = letters.next()?;
+    let mut code = String::from(first.to_ascii_uppercase());
+    let mut last = digit(first);
+    for c in letters {
+        let d = digit(c);
+        if let Some(digit) = d.filter(|_| d != last) {
+            code.push(digit);
+            if code.len() == 4 {
+                break;
+            }
+        }
+        // 'h' and 'w' do not separate equal codes; vowels do.
+        if c != 'h' && c != 'w' {
+            last = d;
+        }
+    }
+    while code.len() < 4 {
+        code.push('0');
+    }
+    Some(code)
+}
+
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut cur = vec![0; b.len() + 1];
+    for (i, ca) in a.chars().enumerate() {
+        cur[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = prev[j] + usize::from(ca != *cb);
+            cur[j + 1] = substitute.min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut cur);
+    }
+    prev[b.len()]
+}
@@ -0,0 +1,102 @@
+use anyhow::Result;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use handlebars::Handlebars;
+use hmac::{Hmac, Mac};
+use serde_json::json;
+use sha2::Sha256;
+
+use crate::{base_path, models::Card};
+
+const PAGE_TEMPLATE: &str = include_str!("templates/share.html.hbs");
+
+/// Default and longest lifetime of a share link, in days.
+pub const DEFAULT_DAYS: u32 = 7;
+pub const MAX_DAYS: u32 = 90;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TokenError {
+    /// Malformed, or not signed with this vault's key
+    Invalid,
+    Expired,
+}
+
+/// A token for `card_id` valid until `expires` (Unix seconds):
+/// `<card id>.<expires>.<signature>`. Tokens are not stored; the signature
+/// is what makes them unforgeable.
+pub fn mint(secret: &str, card_id: i64, expires: u64) -> String {
+    let payload = format!("{card_id}.{expires}");
+    let signature = sign(secret, &payload);
+    format!("{payload}.{signature}")
+}
+
+/// The card id a token grants access to, and when it expires.
+pub fn verify(secret: &str, token: &str, now: u64) -> Result<(i64, u64), TokenError> {
+    let (payload, signature) = token.rsplit_once('.').ok_or(TokenError::Invalid)?;
+    let signature = URL_SAFE_NO_PAD.decode(signature).map_err(|_| TokenError::Invalid)?;
+    mac(secret, payload)
+        .verify_slice(&signature)
+        .map_err(|_| TokenError::Invalid)?;
+    let (card_id, expires) = payload.split_once('.').ok_or(TokenError::Invalid)?;
+    let card_id = card_id.parse().map_err(|_| TokenError::Invalid)?;
+    let expires: u64 = expires.parse().map_err(|_| TokenError::Invalid)?;
+    if now >= expires {
+        return Err(TokenError::Expired);
+    }
+    Ok((card_id, expires))
+}
+
+fn mac(secret: &str, payload: &str) -> Hmac<Sha256> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(payload.as_bytes());
+    mac
+}
+
+fn sign(secret: &str, payload: &str) -> String {
+    URL_SAFE_NO_PAD.encode(mac(secret, payload).finalize().into_bytes())
+}
+
+/// The card with what is not meant for the recipient removed: notes, tags,
+/// custom fields, and org links.
+pub fn public_view(mut card: Card) -> Card {
+    card.notes.clear();
+    card.tags.clear();
+    card.custom.clear();
+    card.reports_to = None;
+    card.last_contacted = None;
+    card
+}
+
+/// The public page for a shared card.
+pub fn page(card: &Card, token: &str, expires_at: &str) -> Result<String> {
+    let mut handlebars = Handlebars::new();
+    handlebars.set_strict_mode(true);
+    handlebars.register_template_string("share", PAGE_TEMPLATE)?;
+    let role = [card.title.as_str(), card.department.as_str(), card.company.as_str()]
+        .into_iter()
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join(" · ");
+    let addresses: Vec<String> = card
+        .addresses
+        .iter()
+        .map(|a| {
+            [a.street.as_str(), a.city.as_str(), a.postal.as_str(), a.country.as_str()]
+                .into_iter()
+                .filter(|s| !s.is_empty())
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+        .collect();
+    Ok(handlebars.render(
+        "share",
+        &json!({
+            "card": card,
+            "base_path": base_path::get(),
+            "role": role,
+            "addresses": addresses,
+            "token": token,
+            "expires_at": expires_at,
+        }),
+    )?)
+}
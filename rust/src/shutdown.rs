@@ -0,0 +1,44 @@
+use std::future::Future;
+use std::time::Duration;
+
+use anyhow::Result;
+use tokio::signal::unix::{signal as unix_signal, SignalKind};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+/// Resolves on Ctrl-C, or on SIGTERM as sent by `docker stop`, systemd, and
+/// Kubernetes.
+pub async fn signal() {
+    let mut term = unix_signal(SignalKind::terminate()).expect("failed to install SIGTERM handler");
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => result.expect("failed to install CTRL+C signal handler"),
+        _ = term.recv() => {}
+    }
+}
+
+/// Cancels `stopping` when the process is told to stop, for the servers
+/// to stop taking connections.
+pub fn on_signal(stopping: CancellationToken) {
+    tokio::spawn(async move {
+        signal().await;
+        info!("Shutting down CardVault...");
+        stopping.cancel();
+    });
+}
+
+/// Runs `serve` until it ends. Once `stopping` is cancelled the requests
+/// in flight get `deadline` to finish; any still running then are dropped.
+pub async fn drain(serve: impl Future<Output = Result<()>>, stopping: &CancellationToken, deadline: Duration) -> Result<()> {
+    tokio::pin!(serve);
+    tokio::select! {
+        result = &mut serve => return result,
+        _ = stopping.cancelled() => {}
+    }
+    match tokio::time::timeout(deadline, serve).await {
+        Ok(result) => result,
+        Err(_) => {
+            warn!("requests still running after {}s, stopping anyway", deadline.as_secs());
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use crate::phone::to_e164;
+
+/// Platforms with a public profile page, as `(platform, hosts, path prefix)`.
+/// The first host is the one profile URLs are built on.
+const PROFILE_PAGES: &[(&str, &[&str], &str)] = &[
+    ("linkedin", &["linkedin.com"], "/in/"),
+    ("twitter", &["x.com", "twitter.com"], "/"),
+    ("github", &["github.com"], "/"),
+    ("instagram", &["instagram.com"], "/"),
+    ("facebook", &["facebook.com"], "/"),
+];
+
+/// Other names a platform goes by.
+const ALIASES: &[(&str, &str)] = &[("x", "twitter"), ("weixin", "wechat")];
+
+/// The platform name lowercased, with aliases such as `x` resolved.
+pub fn canonical_platform(platform: &str) -> String {
+    let platform = platform.trim().to_lowercase();
+    ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == platform)
+        .map_or(platform, |(_, name)| name.to_string())
+}
+
+/// The bare handle on a known platform: a leading `@` is dropped and a
+/// profile URL gives way to the handle in it. Other platforms keep the
+/// handle as entered.
+pub fn canonical_handle(platform: &str, handle: &str) -> String {
+    let handle = handle.trim();
+    let Some((_, hosts, prefix)) = PROFILE_PAGES.iter().find(|(p, ..)| *p == platform) else {
+        return handle.to_string();
+    };
+    let bare = handle
+        .strip_prefix("https://")
+        .or_else(|| handle.strip_prefix("http://"))
+        .unwrap_or(handle);
+    if let Ok(url) = url::Url::parse(&format!("https://{bare}")) {
+        let host = url.host_str().unwrap_or("");
+        let host = host.strip_prefix("www.").unwrap_or(host);
+        if hosts.contains(&host) {
+            if let Some(rest) = url.path().strip_prefix(prefix) {
+                let name = rest.split('/').next().unwrap_or("");
+                if !name.is_empty() {
+                    return name.to_string();
+                }
+            }
+        }
+    }
+    handle.trim_start_matches('@').to_string()
+}
+
+/// Link to the profile, or empty if the platform has no public profile pages
+/// and the handle is not a URL itself.
+pub fn profile_url(platform: &str, handle: &str) -> String {
+    match PROFILE_PAGES.iter().find(|(p, ..)| *p == platform) {
+        Some((_, hosts, prefix)) => format!("https://{}{prefix}{handle}", hosts[0]),
+        None if handle.starts_with("http://") || handle.starts_with("https://") => {
+            handle.to_string()
+        }
+        None => String::new(),
+    }
+}
+
+/// Messengers whose accounts are phone numbers; their handles are stored in
+/// E.164 form when they parse as one.
+const PHONE_MESSENGERS: &[&str] = &["whatsapp", "signal"];
+
+/// The messenger name lowercased, with aliases such as `weixin` resolved.
+pub fn canonical_protocol(protocol: &str) -> String {
+    canonical_platform(protocol)
+}
+
+/// The handle as stored: a phone number for WhatsApp and Signal, without a
+/// leading `@` for Telegram, and as entered otherwise.
+pub fn canonical_im_handle(protocol: &str, handle: &str) -> String {
+    let handle = handle.trim();
+    if PHONE_MESSENGERS.contains(&protocol) {
+        if let Some(e164) = to_e164(handle) {
+            return e164;
+        }
+    }
+    if protocol == "telegram" {
+        return handle.trim_start_matches('@').to_string();
+    }
+    handle.to_string()
+}
@@ -3,35 +3,42 @@ let allCards   = [];
 let allTags    = [];
 let activeFilters = new Set();
 let searchQ    = '';
+let showArchived = false;
 let searchTimer = null;
 let editingId  = null;   // null = creating
+let editingVersion = null; // card version the edit is based on
+let editingExtras = {};  // fields the form does not show, sent back unchanged
 let pendingTags = [];    // tags being edited in modal
 let pendingFile = null;  // File object for new photo
 let removePhotoFlag = false;
 let confirmCb  = null;
 
 /* ─── API ────────────────────────────────────────────────── */
+// Path prefix when served behind a proxy under a sub-path (--base-path)
+const BASE = document.querySelector('meta[name="base-path"]')?.content || '';
+
 async function api(method, path, body) {
   const opts = { method, headers: {} };
   if (body instanceof FormData) { opts.body = body; }
   else if (body)                 { opts.body = JSON.stringify(body); opts.headers['Content-Type'] = 'application/json'; }
-  const r = await fetch(path, opts);
+  const r = await fetch(BASE + path, opts);
   if (!r.ok) { const t = await r.text(); throw new Error(t || r.statusText); }
   const ct = r.headers.get('content-type') || '';
   return ct.includes('json') ? r.json() : null;
 }
 
 async function loadCards() {
-  let url = '/api/cards';
+  let url = '/api/v1/cards';
   const params = [];
   if (searchQ)                 params.push('q=' + encodeURIComponent(searchQ));
   if (activeFilters.size > 0)  [...activeFilters].forEach(t => params.push('tag=' + encodeURIComponent(t)));
+  if (showArchived)            params.push('archived=true');
   if (params.length)           url += '?' + params.join('&');
   return api('GET', url);
 }
 
 async function loadTags() {
-  return api('GET', '/api/tags');
+  return api('GET', '/api/v1/tags');
 }
 
 /* ─── Init ───────────────────────────────────────────────── */
@@ -47,6 +54,19 @@ async function init() {
     toast('Failed to load cards: ' + e.message, 'error');
     document.getElementById('cardGrid').innerHTML = '';
   }
+  // Deep link to a card, e.g. /cards/42
+  const m = location.pathname.slice(BASE.length).match(/^\/cards\/(\d+)\/?$/);
+  if (m) openModal(Number(m[1]));
+}
+
+/* ─── Live updates ───────────────────────────────────────── */
+function subscribeEvents() {
+  if (!window.EventSource) return;
+  const es = new EventSource(BASE + '/api/v1/events');
+  let timer = null;
+  const reload = () => { clearTimeout(timer); timer = setTimeout(refresh, 250); };
+  ['card.created', 'card.updated', 'card.deleted', 'card.photo_updated', 'card.photo_deleted', 'resync']
+    .forEach(ev => es.addEventListener(ev, reload));
 }
 
 /* ─── Theme ──────────────────────────────────────────────── */
@@ -79,6 +99,8 @@ function renderTagBar() {
     const chip = document.createElement('button');
     chip.className = 'tag-chip' + (activeFilters.has(t.name) ? ' active' : '');
     chip.textContent = t.name + (t.count ? ` (${t.count})` : '');
+    if (t.color) chip.style.setProperty('--tag-color', t.color);
+    if (t.description) chip.title = t.description;
     chip.onclick = () => toggleFilter(t.name);
     wrap.appendChild(chip);
   });
@@ -124,7 +146,9 @@ function renderGrid() {
     </div>`;
     return;
   }
-  g.innerHTML = allCards.map(c => cardHTML(c)).join('');
+  // Favorites float to the top; the server's order is kept otherwise.
+  const ordered = [...allCards.filter(c => c.favorite), ...allCards.filter(c => !c.favorite)];
+  g.innerHTML = ordered.map(c => cardHTML(c)).join('');
 }
 
 function initials(name) {
@@ -139,27 +163,41 @@ function avatarColor(name) {
   return palette[Math.abs(h) % palette.length];
 }
 
+// Tag colors are validated as #rrggbb by the server, so safe to inline.
+function tagStyle(name) {
+  const t = allTags.find(x => x.name === name);
+  return t?.color ? ` style="--tag-color:${t.color}"` : '';
+}
+
 function cardHTML(c) {
+  // The version changes with the photo, so a new one is not served from cache.
   const av = c.photo_url
-    ? `<div class="avatar"><img src="${c.photo_url}" alt="" loading="lazy" onerror="this.parentNode.style.background='${avatarColor(c.name)}';this.parentNode.innerHTML='${initials(c.name)}'"></div>`
+    ? `<div class="avatar"><img src="${BASE}/api/v1/cards/${c.id}/photo/thumb?v=${c.version}" alt="" loading="lazy" onerror="this.parentNode.style.background='${avatarColor(c.name)}';this.parentNode.innerHTML='${initials(c.name)}'"></div>`
     : `<div class="avatar" style="background:${avatarColor(c.name)}">${initials(c.name)}</div>`;
   const phone = c.phones?.[0] ? `<div class="contact-row"><span>📱</span><span>${esc(c.phones[0].number)}</span></div>` : '';
   const email = c.emails?.[0] ? `<div class="contact-row"><span>📧</span><span>${esc(c.emails[0].address)}</span></div>` : '';
   const web   = c.website    ? `<div class="contact-row"><span>🌐</span><span>${esc(c.website)}</span></div>` : '';
-  const tags  = (c.tags||[]).map(t => `<span class="tag-pill">${esc(t)}</span>`).join('');
+  // Their wall-clock time, from `2026-10-16T21:04:05+08:00`.
+  const time  = c.local_time ? `<div class="contact-row" title="${esc(c.timezone)}"><span>🕒</span><span>${c.local_time.slice(11, 16)} local time</span></div>` : '';
+  const tags  = (c.tags||[]).map(t => `<span class="tag-pill"${tagStyle(t)}>${esc(t)}</span>`).join('');
+  // Explain search hits that are not already visible on the card face.
+  const hits  = (c.matches||[]).filter(m => !['name','title','company'].includes(m.field)).slice(0, 2)
+    .map(m => `<div class="contact-row match-row"><span>🔎</span><span>${highlight(m)}</span></div>`).join('');
   return `<div class="biz-card" onclick="openModal(${c.id})">
     <div class="biz-card-top">
       ${av}
       <div class="card-info">
         <div class="card-name">${esc(c.name)}</div>
         ${c.title   ? `<div class="card-title">${esc(c.title)}</div>` : ''}
-        ${c.company ? `<div class="card-company">${esc(c.company)}</div>` : ''}
+        ${c.company ? `<div class="card-company">${c.company_logo_url ? `<img class="company-logo" src="${c.company_logo_url}" alt="" loading="lazy" onerror="this.remove()">` : ''}${esc(c.company)}</div>` : ''}
       </div>
     </div>
-    ${phone||email||web ? `<div class="biz-card-mid">${phone}${email}${web}</div>` : ''}
+    ${phone||email||web||time||hits ? `<div class="biz-card-mid">${phone}${email}${web}${time}${hits}</div>` : ''}
     <div class="biz-card-bot">
       ${tags}
       <div class="card-actions" onclick="event.stopPropagation()">
+        <button class="btn-sm fav${c.favorite ? ' on' : ''}" title="${c.favorite ? 'Unpin' : 'Pin to top'}" onclick="toggleFavorite(${c.id})">${c.favorite ? '★' : '☆'}</button>
+        <button class="btn-sm" title="${c.archived_at ? 'Restore' : 'Archive'}" onclick="setArchived(${c.id}, ${!c.archived_at})">${c.archived_at ? '♻️' : '📦'}</button>
         <button class="btn-sm" onclick="openModal(${c.id})">✏️</button>
         <button class="btn-sm danger" onclick="confirmDelete(${c.id},'${esc(c.name)}')">🗑️</button>
       </div>
@@ -167,11 +205,19 @@ function cardHTML(c) {
   </div>`;
 }
 
+function highlight(m) {
+  const chars = [...m.excerpt];
+  return esc(chars.slice(0, m.start).join('')) + '<mark>' + esc(chars.slice(m.start, m.end).join('')) +
+    '</mark>' + esc(chars.slice(m.end).join(''));
+}
+
 function esc(s) { return String(s||'').replace(/&/g,'&amp;').replace(/</g,'&lt;').replace(/>/g,'&gt;').replace(/"/g,'&quot;'); }
 
 /* ─── Modal ──────────────────────────────────────────────── */
 async function openModal(id) {
   editingId = id;
+  editingVersion = null;
+  editingExtras = {};
   pendingTags = [];
   pendingFile = null;
   removePhotoFlag = false;
@@ -180,20 +226,102 @@ async function openModal(id) {
   if (id !== null) {
     document.getElementById('modalTitle').textContent = 'Edit Card';
     document.getElementById('deleteCardBtn').style.display = '';
+    document.getElementById('qrCardBtn').style.display = '';
+    document.getElementById('qrCardBtn').href = `${BASE}/api/v1/cards/${id}/qr`;
     try {
-      const c = await api('GET', `/api/cards/${id}`);
+      const c = await api('GET', `/api/v1/cards/${id}`);
+      editingVersion = c.version;
+      editingExtras = cardExtras(c);
       fillForm(c);
+      renderPhotos(c.photos || []);
+      const hasHeadshot = (c.photos||[]).some(p => p.role === 'headshot');
+      if (!hasHeadshot && (c.emails||[]).length) document.getElementById('fetchAvatarBtn').style.display = '';
+      document.getElementById('fetchLogoBtn').style.display = c.website && c.company ? '' : 'none';
+      document.getElementById('enrichBtn').style.display = '';
     } catch(e) { toast('Failed to load card: ' + e.message, 'error'); return; }
   } else {
     document.getElementById('modalTitle').textContent = 'Add Card';
     document.getElementById('deleteCardBtn').style.display = 'none';
+    document.getElementById('qrCardBtn').style.display = 'none';
+    document.getElementById('fetchLogoBtn').style.display = 'none';
+    document.getElementById('enrichBtn').style.display = 'none';
   }
 
   switchTab('basic');
   document.getElementById('modalOverlay').classList.add('open');
+  if (id !== null) history.replaceState(null, '', `${BASE}/cards/${id}`);
+}
+
+function cardExtras(c) {
+  return {
+    custom: c.custom || {},
+    name: c.name,
+    parts: ['name_prefix', 'given_name', 'middle_name', 'family_name', 'name_suffix', 'name_phonetic']
+      .map(k => [k, c[k] || '']),
+    fields: ['birthday', 'anniversary', 'department', 'role_seniority', 'assistant_name', 'assistant_phone', 'reports_to', 'timezone']
+      .map(k => [k, c[k] || '']),
+    lists: {
+      socials: (c.socials || []).map(({ platform, handle }) => ({ platform, handle })),
+      ims: (c.ims || []).map(({ label, protocol, handle }) => ({ label, protocol, handle })),
+    },
+  };
+}
+
+/* Reads a vCard / MECARD QR code (e.g. a photo of a digital badge) and
+   opens the Add Card form pre-filled with it. */
+async function onQrSelect(e) {
+  const file = e.target.files[0];
+  e.target.value = '';
+  if (!file) return;
+  if (!('BarcodeDetector' in window)) {
+    toast('This browser cannot read QR codes; try Chrome or Safari', 'error');
+    return;
+  }
+  try {
+    const image = await createImageBitmap(file);
+    const codes = await new BarcodeDetector({ formats: ['qr_code'] }).detect(image);
+    if (!codes.length) { toast('No QR code found in the photo', 'error'); return; }
+    const c = await api('POST', '/api/v1/import/qr', { payload: codes[0].rawValue });
+    await openPrefilled(c);
+  } catch(err) { toast('QR import failed: ' + err.message, 'error'); }
+}
+
+/* Reads a photo of a paper business card and opens the Add Card form with
+   what the server's OCR found, for the user to check. */
+async function onScanSelect(e) {
+  const file = e.target.files[0];
+  e.target.value = '';
+  if (!file) return;
+  const fd = new FormData();
+  fd.append('photo', file);
+  toast('Reading card…');
+  try {
+    const r = await api('POST', '/api/v1/scan', fd);
+    await openPrefilled(r.card);
+  } catch(err) { toast('Scan failed: ' + err.message, 'error'); }
 }
 
-function closeModal() { document.getElementById('modalOverlay').classList.remove('open'); }
+/* Fills the Add Card form from contact details on the clipboard, such as
+   an email signature. */
+async function pasteCard() {
+  try {
+    const text = await navigator.clipboard.readText();
+    if (!text.trim()) { toast('The clipboard is empty', 'error'); return; }
+    const c = await api('POST', '/api/v1/import/text', { text });
+    await openPrefilled(c);
+  } catch(err) { toast('Paste failed: ' + err.message, 'error'); }
+}
+
+async function openPrefilled(c) {
+  await openModal(null);
+  editingExtras = cardExtras(c);
+  fillForm(c);
+}
+
+function closeModal() {
+  document.getElementById('modalOverlay').classList.remove('open');
+  if (location.pathname !== BASE + '/') history.replaceState(null, '', BASE + '/');
+}
 function overlayClick(e) { if (e.target.id === 'modalOverlay') closeModal(); }
 
 function switchTab(name) {
@@ -209,6 +337,8 @@ function resetForm() {
   document.getElementById('addressRows').innerHTML = '';
   document.getElementById('photoPreview').innerHTML = '📷';
   document.getElementById('removePhotoBtn').style.display = 'none';
+  document.getElementById('fetchAvatarBtn').style.display = 'none';
+  document.getElementById('photoList').style.display = 'none';
   pendingTags = [];
   renderTagPills();
 }
@@ -287,6 +417,93 @@ function removePhoto() {
   document.getElementById('removePhotoBtn').style.display = 'none';
 }
 
+/* The card's photos in order under the main one (the first), each
+   labelled with what it shows. Adding and removing them saves straight
+   away, unlike the main photo, which is saved with the form. */
+const PHOTO_ROLES = ['front', 'back', 'logo', 'headshot'];
+function renderPhotos(photos) {
+  const list = document.getElementById('photoList');
+  list.style.display = '';
+  list.innerHTML = photos.map(p => `
+    <div class="photo-thumb">
+      <img src="${p.url}" alt="${p.role}" loading="lazy">${p.role}
+      <button class="remove-row-btn" title="Remove photo" onclick="removeCardPhoto(${p.id})">✕</button>
+    </div>`).join('') + `
+    <div class="photo-add">
+      <select id="photoRole" class="label-select">${PHOTO_ROLES.map(r => `<option>${r}</option>`).join('')}</select>
+      <label class="btn-sm">+ Photo<input type="file" accept="image/jpeg,image/png,image/webp,image/heic,image/heif,.heic,.heif" onchange="addCardPhoto(event)"></label>
+    </div>`;
+}
+async function loadPhotos() {
+  const photos = await api('GET', `/api/v1/cards/${editingId}/photos`);
+  renderPhotos(photos);
+  if (!pendingFile && !removePhotoFlag) {
+    document.getElementById('photoPreview').innerHTML = photos.length ? `<img src="${photos[0].url}" alt="">` : '📷';
+    document.getElementById('removePhotoBtn').style.display = photos.length ? '' : 'none';
+  }
+}
+async function addCardPhoto(e) {
+  const file = e.target.files[0];
+  if (!file) return;
+  if (file.size > 5*1024*1024) { toast('Photo must be under 5 MB', 'error'); return; }
+  const fd = new FormData();
+  fd.append('photo', file);
+  fd.append('role', document.getElementById('photoRole').value);
+  try {
+    await api('POST', `/api/v1/cards/${editingId}/photos`, fd);
+    await loadPhotos();
+    toast('Photo added', 'success');
+  } catch(e) { toast('Error: ' + e.message, 'error'); }
+}
+async function removeCardPhoto(photoId) {
+  try {
+    await api('DELETE', `/api/v1/cards/${editingId}/photos/${photoId}`);
+    await loadPhotos();
+  } catch(e) { toast('Error: ' + e.message, 'error'); }
+}
+
+/* Looks the card's emails up on Gravatar / Libravatar and adds the avatar
+   as its headshot straight away. */
+async function fetchAvatar() {
+  try {
+    await api('POST', `/api/v1/cards/${editingId}/photo/fetch-avatar`);
+    document.getElementById('fetchAvatarBtn').style.display = 'none';
+    await loadPhotos();
+    toast('Headshot added', 'success');
+  } catch(e) { toast('Error: ' + e.message, 'error'); }
+}
+
+/* Fetches the icon of the card's website as its company's logo, shown on
+   every card with that company. */
+async function fetchLogo() {
+  try {
+    const { company } = await api('POST', `/api/v1/cards/${editingId}/fetch-logo`);
+    toast(`Logo saved for ${company}`, 'success');
+    await refresh();
+  } catch(e) { toast('Error: ' + e.message, 'error'); }
+}
+
+/* Asks the enrichment provider about the card and, if the user agrees,
+   saves what it proposed. */
+const ENRICH_LABELS = { industry: 'Industry', company_size: 'Company size', linkedin: 'LinkedIn' };
+async function enrichCard() {
+  const id = editingId;
+  try {
+    const { version, proposals } = await api('POST', `/api/v1/cards/${id}/enrich`);
+    if (!proposals.length) { toast('Nothing new found', 'info'); return; }
+    const list = proposals.map(p => `${ENRICH_LABELS[p.field]}: ${p.proposed}${p.current ? ` (was ${p.current})` : ''}`).join('; ');
+    closeModal();
+    showConfirm(`Update the card? ${list}`, async () => {
+      try {
+        const accept = proposals.map(p => ({ field: p.field, value: p.proposed }));
+        await api('POST', `/api/v1/cards/${id}/enrich/accept`, { accept, version });
+        toast('Card enriched', 'success');
+        await refresh();
+      } catch(e) { toast('Error: ' + e.message, 'error'); }
+    });
+  } catch(e) { toast('Error: ' + e.message, 'error'); }
+}
+
 // Drag and drop
 const dropEl = document.getElementById('photoDrop');
 if (dropEl) {
@@ -318,6 +535,23 @@ function onTagInput(e) {
     if (t) addTag(t);
     e.target.value = '';
   }
+  suggest('tag', e.target);
+}
+
+/* ─── Autocomplete ───────────────────────────────────────── */
+let suggestTimer;
+function suggest(field, input) {
+  clearTimeout(suggestTimer);
+  const prefix = input.value.trim();
+  if (!prefix) { input.list.innerHTML = ''; return; }
+  suggestTimer = setTimeout(async () => {
+    try {
+      const res = await fetch(`${BASE}/api/v1/autocomplete?field=${field}&prefix=${encodeURIComponent(prefix)}`);
+      if (!res.ok) return;
+      const items = await res.json();
+      input.list.innerHTML = items.map(s => `<option value="${esc(s.value)}">`).join('');
+    } catch { /* suggestions are best-effort */ }
+  }, 150);
 }
 function addTag(t) {
   t = t.toLowerCase().trim();
@@ -376,16 +610,22 @@ async function saveCard() {
   fd.append('emails',    JSON.stringify(emails));
   fd.append('addresses', JSON.stringify(addresses));
   fd.append('tags',      JSON.stringify(pendingTags));
+  if (editingExtras.custom) fd.append('custom', JSON.stringify(editingExtras.custom));
+  // Name parts and reading only still apply while the display name is unchanged.
+  if (editingExtras.name === name) editingExtras.parts.forEach(([k, v]) => fd.append(k, v));
+  (editingExtras.fields || []).forEach(([k, v]) => fd.append(k, v));
+  Object.entries(editingExtras.lists || {}).forEach(([k, v]) => fd.append(k, JSON.stringify(v)));
   if (pendingFile) fd.append('photo', pendingFile);
+  if (editingId !== null && editingVersion !== null) fd.append('version', editingVersion);
 
   try {
     if (editingId === null) {
-      await api('POST', '/api/cards', fd);
+      await api('POST', '/api/v1/cards', fd);
       toast('Card created', 'success');
     } else {
-      await api('PUT', `/api/cards/${editingId}`, fd);
+      await api('PUT', `/api/v1/cards/${editingId}`, fd);
       if (removePhotoFlag) {
-        try { await api('DELETE', `/api/cards/${editingId}/photo`); } catch(_) {}
+        try { await api('DELETE', `/api/v1/cards/${editingId}/photo`); } catch(_) {}
       }
       toast('Card updated', 'success');
     }
@@ -394,6 +634,32 @@ async function saveCard() {
   } catch(e) { toast('Error: ' + e.message, 'error'); }
 }
 
+/* ─── Favorites ──────────────────────────────────────────── */
+async function toggleFavorite(id) {
+  try {
+    const card = await api('POST', `/api/v1/cards/${id}/favorite`);
+    allCards = allCards.map(c => c.id === id ? card : c);
+    renderGrid();
+  } catch(e) { toast('Error: ' + e.message, 'error'); }
+}
+
+/* ─── Archive ────────────────────────────────────────────── */
+async function toggleArchived() {
+  showArchived = !showArchived;
+  const btn = document.getElementById('archiveToggle');
+  btn.classList.toggle('active', showArchived);
+  btn.title = showArchived ? 'Show active cards' : 'Show archived cards';
+  await refresh();
+}
+async function setArchived(id, archived) {
+  try {
+    await api('POST', `/api/v1/cards/${id}/${archived ? 'archive' : 'unarchive'}`);
+    allCards = allCards.filter(c => c.id !== id);
+    renderGrid();
+    toast(archived ? 'Card archived' : 'Card restored', 'success');
+  } catch(e) { toast('Error: ' + e.message, 'error'); }
+}
+
 /* ─── Delete ─────────────────────────────────────────────── */
 function confirmDelete(id, name) {
   showConfirm(`Delete "${name}"? This cannot be undone.`, async () => {
@@ -401,7 +667,7 @@ function confirmDelete(id, name) {
     allCards = allCards.filter(c => c.id !== id);
     renderGrid();
     try {
-      await api('DELETE', `/api/cards/${id}`);
+      await api('DELETE', `/api/v1/cards/${id}`);
       await refresh();
       toast('Card deleted', 'success');
     } catch(e) { await refresh(); toast('Delete failed: ' + e.message, 'error'); }
@@ -428,3 +694,4 @@ function toast(msg, type='info') {
 }
 
 init();
+subscribeEvents();
@@ -0,0 +1,199 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use clap::ValueEnum;
+use object_store::{
+    aws::AmazonS3Builder, azure::MicrosoftAzureBuilder, gcp::GoogleCloudStorageBuilder, path::Path as ObjectPath,
+    ObjectStore, PutPayload,
+};
+use tokio::fs;
+
+/// Where uploaded photos, attachments, and logos are kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum StorageKind {
+    /// The uploads directory on local disk
+    Local,
+    /// An S3 bucket, or any S3-compatible service such as MinIO
+    S3,
+    /// An Azure Blob Storage container
+    Azure,
+    /// A Google Cloud Storage bucket
+    Gcs,
+}
+
+/// Keeps stored files by key, a relative path such as
+/// `attachments/<sha256>`.
+#[async_trait]
+pub trait BlobStore: Send + Sync {
+    /// The bytes stored under `key`, `None` if there are none.
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>>;
+
+    /// Stores `data` under `key`, replacing what was there. Readers never
+    /// see half of it.
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<()>;
+
+    async fn exists(&self, key: &str) -> Result<bool>;
+
+    /// Removes `key`; a missing key is not an error.
+    async fn delete(&self, key: &str) -> Result<()>;
+}
+
+/// Files in a directory on local disk, keys being paths within it.
+pub struct LocalStore {
+    dir: PathBuf,
+}
+
+impl LocalStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+#[async_trait]
+impl BlobStore for LocalStore {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        match fs::read(self.dir.join(key)).await {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("read {key}")),
+        }
+    }
+
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<()> {
+        let path = self.dir.join(key);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).await.context("create uploads dir")?;
+        }
+        // Written aside and renamed, so a concurrent upload of the same bytes
+        // never sees half a file.
+        let partial = path.with_extension(format!("{}.part", uuid::Uuid::new_v4()));
+        fs::write(&partial, data).await.with_context(|| format!("write {key}"))?;
+        if let Err(e) = fs::rename(&partial, &path).await {
+            let _ = fs::remove_file(&partial).await;
+            return Err(e).with_context(|| format!("store {key}"));
+        }
+        Ok(())
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool> {
+        Ok(fs::try_exists(self.dir.join(key)).await?)
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        match fs::remove_file(self.dir.join(key)).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e).with_context(|| format!("delete {key}")),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// How to reach an S3 bucket. Credentials left out are taken from the
+/// usual `AWS_*` environment variables.
+pub struct S3Config {
+    pub bucket: String,
+    pub region: Option<String>,
+    /// Service URL, for S3-compatible services such as MinIO
+    pub endpoint: Option<String>,
+    pub access_key_id: Option<String>,
+    pub secret_access_key: Option<String>,
+    /// Addresses the bucket as `<endpoint>/<bucket>` rather than
+    /// `<bucket>.<endpoint>`, as MinIO expects
+    pub path_style: bool,
+}
+
+/// How to reach an Azure Blob Storage container. Settings left out are
+/// taken from the `AZURE_STORAGE_*` environment variables.
+pub struct AzureConfig {
+    pub account: Option<String>,
+    pub container: String,
+    pub access_key: Option<String>,
+}
+
+/// How to reach a Google Cloud Storage bucket. Without a service account
+/// key, `GOOGLE_APPLICATION_CREDENTIALS` or the machine's own account is
+/// used.
+pub struct GcsConfig {
+    pub bucket: String,
+    /// Path of a service account key file (JSON)
+    pub service_account: Option<String>,
+}
+
+/// Objects in a cloud bucket or container, keys being object names.
+pub struct CloudStore {
+    store: Box<dyn ObjectStore>,
+}
+
+impl CloudStore {
+    pub fn s3(config: S3Config) -> Result<Self> {
+        let mut builder = AmazonS3Builder::from_env()
+            .with_bucket_name(config.bucket)
+            .with_virtual_hosted_style_request(!config.path_style);
+        if let Some(region) = config.region {
+            builder = builder.with_region(region);
+        }
+        if let Some(endpoint) = config.endpoint {
+            builder = builder.with_allow_http(endpoint.starts_with("http://")).with_endpoint(endpoint);
+        }
+        if let Some(key) = config.access_key_id {
+            builder = builder.with_access_key_id(key);
+        }
+        if let Some(secret) = config.secret_access_key {
+            builder = builder.with_secret_access_key(secret);
+        }
+        Ok(Self { store: Box::new(builder.build().context("configure S3 storage")?) })
+    }
+
+    pub fn azure(config: AzureConfig) -> Result<Self> {
+        let mut builder = MicrosoftAzureBuilder::from_env().with_container_name(config.container);
+        if let Some(account) = config.account {
+            builder = builder.with_account(account);
+        }
+        if let Some(key) = config.access_key {
+            builder = builder.with_access_key(key);
+        }
+        Ok(Self { store: Box::new(builder.build().context("configure Azure storage")?) })
+    }
+
+    pub fn gcs(config: GcsConfig) -> Result<Self> {
+        let mut builder = GoogleCloudStorageBuilder::from_env().with_bucket_name(config.bucket);
+        if let Some(path) = config.service_account {
+            builder = builder.with_service_account_path(path);
+        }
+        Ok(Self { store: Box::new(builder.build().context("configure Google Cloud Storage")?) })
+    }
+}
+
+#[async_trait]
+impl BlobStore for CloudStore {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        match self.store.get(&ObjectPath::from(key)).await {
+            Ok(result) => Ok(Some(result.bytes().await.with_context(|| format!("read {key}"))?.to_vec())),
+            Err(object_store::Error::NotFound { .. }) => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("read {key}")),
+        }
+    }
+
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<()> {
+        self.store
+            .put(&ObjectPath::from(key), PutPayload::from(data))
+            .await
+            .with_context(|| format!("store {key}"))?;
+        Ok(())
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool> {
+        match self.store.head(&ObjectPath::from(key)).await {
+            Ok(_) => Ok(true),
+            Err(object_store::Error::NotFound { .. }) => Ok(false),
+            Err(e) => Err(e).with_context(|| format!("look up {key}")),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        match self.store.delete(&ObjectPath::from(key)).await {
+            Ok(()) | Err(object_store::Error::NotFound { .. }) => Ok(()),
+            Err(e) => Err(e).with_context(|| format!("delete {key}")),
+        }
+    }
+}
@@ -1,13 +1,57 @@
 use std::sync::{Arc, Mutex};
 
 use anyhow::Result;
-use rusqlite::{Connection, OptionalExtension, params};
+use rusqlite::{
+    functions::FunctionFlags, params, params_from_iter, types::Value, Connection, OptionalExtension,
+};
 use tracing::info;
 
-use crate::models::{Address, Card, CardInput, Email, Phone, TagCount};
+use crate::{
+    base_path,
+    duplicates::{email_key, phone_key},
+    geocode,
+    phone::to_e164,
+    models::{
+        display_name, normalize_tag, Address, Attachment, UploadSession, Card, CardDetail, CardPhoto, CardFormAddressInput, CardFormEmailInput, CardFormPhoneInput,
+        CardFormImInput, CardFormSocialInput, Im,
+        CardInput, CardPatch, CardQuery, CardRelations, Email, FieldDefinition, FieldDefinitionInput,
+        DueReminder, Interaction, InteractionInput, Reminder, ReminderInput,
+        NearbyCard, OrgChart, Phone, PhotoRole, PhotoUpdate, SearchDoc, Social, SortField,
+        SuggestField, Suggestion, TagCount, TagUpdate, VaultStats, Webhook, WebhookInput,
+    },
+    search::{fold, phonetic_key},
+    social::profile_url,
+    timezone,
+};
+
+/// Copies the write-ahead log into the database file and empties it, so a
+/// stopped server leaves one complete file behind.
+pub fn checkpoint(conn: &Arc<Mutex<Connection>>) -> Result<()> {
+    let conn = conn.lock().unwrap();
+    let busy: bool = conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |row| row.get(0))?;
+    anyhow::ensure!(!busy, "WAL checkpoint blocked by another connection");
+    info!("Checkpointed the write-ahead log");
+    Ok(())
+}
+
+/// Readies a connection opened read-only: the SQL functions queries call,
+/// with the schema as it is. A database this build has not opened before
+/// may lack columns it reads.
+pub fn init_read_only(conn: &Arc<Mutex<Connection>>) -> Result<()> {
+    let conn = conn.lock().unwrap();
+    register_folding(&conn)?;
+    register_contact_keys(&conn)?;
+    register_haversine(&conn)?;
+    conn.query_row("SELECT count(*) FROM cards", [], |_| Ok(()))
+        .map_err(|e| anyhow::anyhow!("not a CardVault database: {e}"))?;
+    Ok(())
+}
 
 pub fn init_db(conn: &Arc<Mutex<Connection>>) -> Result<()> {
     let conn = conn.lock().unwrap();
+    register_folding(&conn)?;
+    register_contact_keys(&conn)?;
+    register_haversine(&conn)?;
     conn.execute_batch("PRAGMA journal_mode=WAL;")?;
     conn.execute_batch("PRAGMA foreign_keys=ON;")?;
     conn.execute_batch(
@@ -19,7 +63,6 @@ pub fn init_db(conn: &Arc<Mutex<Connection>>) -> Result<()> {
             company     TEXT NOT NULL DEFAULT '',
             website     TEXT NOT NULL DEFAULT '',
             notes       TEXT NOT NULL DEFAULT '',
-            photo_path  TEXT NOT NULL DEFAULT '',
             created_at  DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
             updated_at  DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
         );
@@ -48,6 +91,61 @@ pub fn init_db(conn: &Arc<Mutex<Connection>>) -> Result<()> {
             postal  TEXT NOT NULL DEFAULT ''
         );
 
+        CREATE TABLE IF NOT EXISTS card_socials (
+            id       INTEGER PRIMARY KEY AUTOINCREMENT,
+            card_id  INTEGER NOT NULL REFERENCES cards(id) ON DELETE CASCADE,
+            platform TEXT NOT NULL DEFAULT '',
+            handle   TEXT NOT NULL DEFAULT '',
+            url      TEXT NOT NULL DEFAULT ''
+        );
+
+        CREATE TABLE IF NOT EXISTS card_ims (
+            id       INTEGER PRIMARY KEY AUTOINCREMENT,
+            card_id  INTEGER NOT NULL REFERENCES cards(id) ON DELETE CASCADE,
+            label    TEXT NOT NULL DEFAULT '',
+            protocol TEXT NOT NULL DEFAULT '',
+            handle   TEXT NOT NULL DEFAULT ''
+        );
+
+        CREATE TABLE IF NOT EXISTS card_photos (
+            id         INTEGER PRIMARY KEY AUTOINCREMENT,
+            card_id    INTEGER NOT NULL REFERENCES cards(id) ON DELETE CASCADE,
+            role       TEXT NOT NULL DEFAULT 'front',
+            position   INTEGER NOT NULL DEFAULT 0,
+            path       TEXT NOT NULL,
+            created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE INDEX IF NOT EXISTS idx_card_photos_card ON card_photos(card_id, position);
+
+        CREATE TABLE IF NOT EXISTS card_attachments (
+            id         INTEGER PRIMARY KEY AUTOINCREMENT,
+            card_id    INTEGER NOT NULL REFERENCES cards(id) ON DELETE CASCADE,
+            filename   TEXT NOT NULL,
+            mime       TEXT NOT NULL,
+            size       INTEGER NOT NULL,
+            path       TEXT NOT NULL,
+            created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE INDEX IF NOT EXISTS idx_card_attachments_card ON card_attachments(card_id);
+
+        -- How many photos, attachments, and company logos use each stored
+        -- file. Files are named by the SHA-256 of their contents, so the
+        -- same upload is stored once however many rows point at it.
+        CREATE TABLE IF NOT EXISTS upload_refs (
+            path TEXT PRIMARY KEY,
+            refs INTEGER NOT NULL
+        );
+
+        -- Files being sent in chunks; the bytes so far are kept in
+        -- `partial/<id>` of the uploads directory.
+        CREATE TABLE IF NOT EXISTS upload_sessions (
+            id         TEXT PRIMARY KEY,
+            filename   TEXT NOT NULL,
+            size       INTEGER NOT NULL,
+            received   INTEGER NOT NULL DEFAULT 0,
+            created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+
         CREATE TABLE IF NOT EXISTS tags (
             id   INTEGER PRIMARY KEY AUTOINCREMENT,
             name TEXT NOT NULL UNIQUE
@@ -58,11 +156,489 @@ pub fn init_db(conn: &Arc<Mutex<Connection>>) -> Result<()> {
             tag_id  INTEGER NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
             PRIMARY KEY (card_id, tag_id)
         );
+
+        CREATE TABLE IF NOT EXISTS tag_aliases (
+            alias  TEXT PRIMARY KEY,
+            tag_id INTEGER NOT NULL REFERENCES tags(id) ON DELETE CASCADE
+        );
+
+        -- One snapshot per card version. Not tied to cards by a foreign key,
+        -- so the history outlives the card.
+        CREATE TABLE IF NOT EXISTS card_revisions (
+            card_id    INTEGER NOT NULL,
+            version    INTEGER NOT NULL,
+            snapshot   TEXT NOT NULL,
+            created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY (card_id, version)
+        );
+
+        CREATE TABLE IF NOT EXISTS webhooks (
+            id         INTEGER PRIMARY KEY AUTOINCREMENT,
+            url        TEXT NOT NULL,
+            secret     TEXT NOT NULL,
+            events     TEXT NOT NULL DEFAULT '',
+            active     INTEGER NOT NULL DEFAULT 1,
+            created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+
+        -- Custom fields cards may carry; values live in cards.custom_fields.
+        CREATE TABLE IF NOT EXISTS field_definitions (
+            key      TEXT PRIMARY KEY,
+            label    TEXT NOT NULL,
+            type     TEXT NOT NULL,
+            required INTEGER NOT NULL DEFAULT 0,
+            options  TEXT NOT NULL DEFAULT '[]',
+            position INTEGER NOT NULL
+        );
+
+        -- Contact log. No foreign key, so like the revision history it
+        -- outlives the card and is there again when an undo restores it.
+        CREATE TABLE IF NOT EXISTS interactions (
+            id          INTEGER PRIMARY KEY AUTOINCREMENT,
+            card_id     INTEGER NOT NULL,
+            kind        TEXT NOT NULL,
+            occurred_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            note        TEXT NOT NULL DEFAULT '',
+            created_at  DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE INDEX IF NOT EXISTS idx_interactions_card ON interactions(card_id, occurred_at);
+
+        -- Follow-ups; kept across a card's deletion like interactions.
+        CREATE TABLE IF NOT EXISTS reminders (
+            id         INTEGER PRIMARY KEY AUTOINCREMENT,
+            card_id    INTEGER NOT NULL,
+            title      TEXT NOT NULL,
+            due_date   TEXT NOT NULL,
+            done       INTEGER NOT NULL DEFAULT 0,
+            created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE INDEX IF NOT EXISTS idx_reminders_card ON reminders(card_id);
+        CREATE INDEX IF NOT EXISTS idx_reminders_due ON reminders(done, due_date);
+
+        CREATE TABLE IF NOT EXISTS settings (
+            key   TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        );
+
+        -- Keyed by name: there are no company records, so every card with
+        -- the same company shares the logo.
+        CREATE TABLE IF NOT EXISTS company_logos (
+            company    TEXT PRIMARY KEY COLLATE NOCASE,
+            logo_path  TEXT NOT NULL,
+            source_url TEXT NOT NULL,
+            fetched_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+
+        -- Geocoder answers by address text; NULL coordinates for addresses
+        -- the geocoder does not know.
+        CREATE TABLE IF NOT EXISTS geocode_cache (
+            query      TEXT PRIMARY KEY,
+            latitude   REAL,
+            longitude  REAL,
+            looked_up_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+
+        CREATE TABLE IF NOT EXISTS idempotency_keys (
+            key          TEXT NOT NULL,
+            method       TEXT NOT NULL,
+            path         TEXT NOT NULL,
+            request_hash TEXT NOT NULL,
+            status       INTEGER,
+            content_type TEXT NOT NULL DEFAULT '',
+            etag         TEXT NOT NULL DEFAULT '',
+            body         BLOB,
+            created_at   DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY (key, method, path)
+        );
         "#,
     )?;
+
+    // Columns added after the original schema; existing databases are
+    // migrated in place.
+    add_column_if_missing(&conn, "cards", "version", "INTEGER NOT NULL DEFAULT 1")?;
+    add_column_if_missing(&conn, "cards", "favorite", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column_if_missing(&conn, "cards", "archived_at", "DATETIME")?;
+    add_column_if_missing(&conn, "cards", "custom_fields", "TEXT NOT NULL DEFAULT '{}'")?;
+    add_column_if_missing(&conn, "cards", "name_phonetic", "TEXT NOT NULL DEFAULT ''")?;
+    add_column_if_missing(&conn, "cards", "birthday", "TEXT NOT NULL DEFAULT ''")?;
+    add_column_if_missing(&conn, "cards", "anniversary", "TEXT NOT NULL DEFAULT ''")?;
+    for column in ["department", "role_seniority", "assistant_name", "assistant_phone"] {
+        add_column_if_missing(&conn, "cards", column, "TEXT NOT NULL DEFAULT ''")?;
+    }
+    // No foreign key: like the revision history, a reporting line survives
+    // its manager's deletion, and an undo brings it back.
+    add_column_if_missing(&conn, "cards", "reports_to", "INTEGER")?;
+    conn.execute_batch("CREATE INDEX IF NOT EXISTS idx_cards_reports_to ON cards(reports_to);")?;
+    add_column_if_missing(&conn, "cards", "timezone", "TEXT NOT NULL DEFAULT ''")?;
+    for column in NAME_PART_COLUMNS {
+        add_column_if_missing(&conn, "cards", column, "TEXT NOT NULL DEFAULT ''")?;
+    }
+    add_column_if_missing(&conn, "card_phones", "e164", "TEXT NOT NULL DEFAULT ''")?;
+    add_column_if_missing(&conn, "card_addresses", "latitude", "REAL")?;
+    add_column_if_missing(&conn, "card_addresses", "longitude", "REAL")?;
+    conn.execute_batch("CREATE INDEX IF NOT EXISTS idx_card_addresses_latitude ON card_addresses(latitude);")?;
+    add_column_if_missing(&conn, "tags", "color", "TEXT NOT NULL DEFAULT ''")?;
+    add_column_if_missing(&conn, "tags", "description", "TEXT NOT NULL DEFAULT ''")?;
+    add_column_if_missing(&conn, "card_revisions", "batch", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column_if_missing(&conn, "card_revisions", "kind", "TEXT NOT NULL DEFAULT 'edit'")?;
+    add_column_if_missing(&conn, "card_revisions", "undone", "INTEGER NOT NULL DEFAULT 0")?;
+    if add_column_if_missing(&conn, "cards", "name_sound_key", "TEXT NOT NULL DEFAULT ''")? {
+        backfill_sound_keys(&conn)?;
+    }
+    move_photo_paths(&conn)?;
+    count_upload_refs(&conn)?;
+    normalize_stored_tags(&conn)?;
+    normalize_stored_phones(&conn)?;
+    backfill_revisions(&conn)?;
+    Ok(())
+}
+
+/// Structured name columns, in display order.
+const NAME_PART_COLUMNS: [&str; 5] =
+    ["name_prefix", "given_name", "middle_name", "family_name", "name_suffix"];
+
+/// Registers `fold(text)` and the `FOLD` collation, which compare text
+/// ignoring case and diacritics (see `search::fold`). Searches match on
+/// `fold(column) LIKE ?` and name sorts use `COLLATE FOLD`.
+fn register_folding(conn: &Connection) -> Result<()> {
+    conn.create_scalar_function(
+        "fold",
+        1,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |ctx| Ok(ctx.get::<Option<String>>(0)?.map(|s| fold(&s))),
+    )?;
+    // Ties on the folded form fall back to the raw text, so the order is total.
+    conn.create_collation("FOLD", |a, b| fold(a).cmp(&fold(b)).then_with(|| a.cmp(b)))?;
+    Ok(())
+}
+
+/// Registers `email_key(text)` and `phone_key(text)`, the forms under which
+/// emails and phone numbers are compared for duplicates (see `duplicates`).
+fn register_contact_keys(conn: &Connection) -> Result<()> {
+    for (name, key) in [
+        ("email_key", email_key as fn(&str) -> Option<String>),
+        ("phone_key", phone_key),
+    ] {
+        conn.create_scalar_function(
+            name,
+            1,
+            FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+            move |ctx| Ok(ctx.get::<Option<String>>(0)?.and_then(|s| key(&s))),
+        )?;
+    }
+    Ok(())
+}
+
+/// Registers `haversine_km(lat1, lng1, lat2, lng2)`, the distance between
+/// two points in kilometres (see `geocode::distance_km`), for nearby
+/// searches.
+fn register_haversine(conn: &Connection) -> Result<()> {
+    conn.create_scalar_function(
+        "haversine_km",
+        4,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |ctx| {
+            let [lat1, lng1, lat2, lng2] = [0, 1, 2, 3].map(|i| ctx.get::<Option<f64>>(i));
+            Ok(match (lat1?, lng1?, lat2?, lng2?) {
+                (Some(lat1), Some(lng1), Some(lat2), Some(lng2)) => {
+                    Some(geocode::distance_km(lat1, lng1, lat2, lng2))
+                }
+                _ => None,
+            })
+        },
+    )?;
+    Ok(())
+}
+
+/// Soundex key stored in `name_sound_key`, covering the name and its
+/// reading, so a romanized reading can be searched by sound too.
+fn sound_key(name: &str, reading: &str) -> String {
+    phonetic_key(&format!("{name} {reading}"))
+}
+
+/// Recomputes the card's sound key after a sparse update.
+fn refresh_sound_key(conn: &Connection, id: i64) -> Result<()> {
+    let (name, reading): (String, String) = conn.query_row(
+        "SELECT name, name_phonetic FROM cards WHERE id = ?1",
+        params![id],
+        |r| Ok((r.get(0)?, r.get(1)?)),
+    )?;
+    conn.execute(
+        "UPDATE cards SET name_sound_key = ?1 WHERE id = ?2",
+        params![sound_key(&name, &reading), id],
+    )?;
+    Ok(())
+}
+
+fn backfill_sound_keys(conn: &Connection) -> Result<()> {
+    let names = conn
+        .prepare("SELECT id, name, name_phonetic FROM cards")?
+        .query_map([], |r| Ok((r.get::<_, i64>(0)?, r.get::<_, String>(1)?, r.get::<_, String>(2)?)))?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    for (id, name, reading) in names {
+        conn.execute(
+            "UPDATE cards SET name_sound_key = ?1 WHERE id = ?2",
+            params![sound_key(&name, &reading), id],
+        )?;
+    }
+    Ok(())
+}
+
+/// Brings tags written before normalization to their canonical spelling,
+/// folding ones that now coincide ("Client" and "client") into one.
+fn normalize_stored_tags(conn: &Connection) -> Result<()> {
+    let tags = conn
+        .prepare("SELECT id, name FROM tags")?
+        .query_map([], |r| Ok((r.get::<_, i64>(0)?, r.get::<_, String>(1)?)))?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    for (id, name) in tags {
+        let canonical = normalize_tag(&name);
+        if canonical == name {
+            continue;
+        }
+        match resolve_tag(conn, &canonical)? {
+            Some(target) => move_tag(conn, id, target)?,
+            None => {
+                conn.execute("UPDATE tags SET name = ?1 WHERE id = ?2", params![canonical, id])?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Recomputes the E.164 form of every stored phone number, since it depends
+/// on `--default-region`, which may have changed since it was written.
+fn normalize_stored_phones(conn: &Connection) -> Result<()> {
+    let phones = conn
+        .prepare("SELECT id, number, e164 FROM card_phones")?
+        .query_map([], |r| Ok((r.get::<_, i64>(0)?, r.get::<_, String>(1)?, r.get::<_, String>(2)?)))?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    for (id, number, stored) in phones {
+        let e164 = to_e164(&number).unwrap_or_default();
+        if e164 != stored {
+            conn.execute("UPDATE card_phones SET e164 = ?1 WHERE id = ?2", params![e164, id])?;
+        }
+    }
+    Ok(())
+}
+
+/// Gives cards written before revisions were recorded a first snapshot.
+fn backfill_revisions(conn: &Connection) -> Result<()> {
+    let ids = conn
+        .prepare("SELECT id FROM cards WHERE id NOT IN (SELECT card_id FROM card_revisions)")?
+        .query_map([], |r| r.get(0))?
+        .collect::<std::result::Result<Vec<i64>, _>>()?;
+    for id in ids {
+        write_revision(conn, id, 0, REVISION_BASELINE)?;
+    }
+    Ok(())
+}
+
+/// Moves the single photo cards had in `cards.photo_path` into
+/// `card_photos` as their front side, then drops the column.
+fn move_photo_paths(conn: &Connection) -> Result<()> {
+    let exists: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM pragma_table_info('cards') WHERE name = 'photo_path'",
+        [],
+        |r| r.get(0),
+    )?;
+    if exists {
+        conn.execute_batch(
+            "BEGIN;
+             INSERT INTO card_photos (card_id, role, position, path)
+                 SELECT id, 'front', 0, photo_path FROM cards WHERE photo_path <> '';
+             ALTER TABLE cards DROP COLUMN photo_path;
+             COMMIT;",
+        )?;
+    }
+    Ok(())
+}
+
+/// Counts the references to files stored before they were counted, when
+/// `upload_refs` is new.
+fn count_upload_refs(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "INSERT INTO upload_refs (path, refs)
+         SELECT path, COUNT(*) FROM (
+             SELECT path FROM card_photos
+             UNION ALL SELECT path FROM card_attachments
+             UNION ALL SELECT logo_path FROM company_logos
+         )
+         WHERE NOT EXISTS (SELECT 1 FROM upload_refs)
+         GROUP BY path",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Returns true if the column was added.
+fn add_column_if_missing(conn: &Connection, table: &str, column: &str, decl: &str) -> Result<bool> {
+    let exists: bool = conn.query_row(
+        &format!("SELECT COUNT(*) > 0 FROM pragma_table_info('{table}') WHERE name = ?1"),
+        params![column],
+        |r| r.get(0),
+    )?;
+    if !exists {
+        conn.execute_batch(&format!("ALTER TABLE {table} ADD COLUMN {column} {decl};"))?;
+    }
+    Ok(!exists)
+}
+
+/// Returned (inside `anyhow::Error`) when an update names a `version` that
+/// is no longer current.
+#[derive(Debug)]
+pub struct VersionConflict {
+    pub current: i64,
+}
+
+impl std::fmt::Display for VersionConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "card was modified by someone else (current version {})",
+            self.current
+        )
+    }
+}
+
+impl std::error::Error for VersionConflict {}
+
+/// Returned (inside `anyhow::Error`) when a tag rename or new alias would
+/// collide with another existing tag or alias.
+#[derive(Debug)]
+pub struct TagExists {
+    pub name: String,
+}
+
+impl std::fmt::Display for TagExists {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "\"{}\" is already another tag or alias; merge the tags instead",
+            self.name
+        )
+    }
+}
+
+impl std::error::Error for TagExists {}
+
+/// Returned (inside `anyhow::Error`) when a bulk change names cards that do
+/// not exist; nothing is changed.
+#[derive(Debug)]
+pub struct CardsNotFound {
+    pub ids: Vec<i64>,
+}
+
+impl std::fmt::Display for CardsNotFound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ids: Vec<String> = self.ids.iter().map(i64::to_string).collect();
+        write!(f, "cards not found: {}", ids.join(", "))
+    }
+}
+
+impl std::error::Error for CardsNotFound {}
+
+/// Returned (inside `anyhow::Error`) when `reports_to` names a card that does
+/// not exist or would make a card its own manager, directly or not.
+#[derive(Debug)]
+pub struct InvalidReportsTo {
+    pub reason: &'static str,
+}
+
+impl std::fmt::Display for InvalidReportsTo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "reports_to: {}", self.reason)
+    }
+}
+
+impl std::error::Error for InvalidReportsTo {}
+
+/// Returned (inside `anyhow::Error`) when a photo is added to a card that
+/// already has `MAX_PHOTOS`.
+#[derive(Debug)]
+pub struct PhotosFull;
+
+impl std::fmt::Display for PhotosFull {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "a card holds at most {MAX_PHOTOS} photos")
+    }
+}
+
+impl std::error::Error for PhotosFull {}
+
+/// Returned (inside `anyhow::Error`) when the change `undo_last` would
+/// reverse deleted a card, by a delete or a merge. The card's photos and
+/// attachments went with it, so it cannot be brought back whole.
+#[derive(Debug)]
+pub struct UndoDeletion {
+    pub ids: Vec<i64>,
+}
+
+impl std::fmt::Display for UndoDeletion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ids: Vec<String> = self.ids.iter().map(i64::to_string).collect();
+        write!(
+            f,
+            "the last change deleted cards {}, which cannot be undone",
+            ids.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for UndoDeletion {}
+
+/// Fails with `InvalidReportsTo` unless card `id` (`None` for a new card) may
+/// report to `manager`.
+fn check_reports_to(conn: &Connection, id: Option<i64>, manager: Option<i64>) -> Result<()> {
+    let Some(manager) = manager else {
+        return Ok(());
+    };
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM cards WHERE id = ?1)",
+        params![manager],
+        |r| r.get(0),
+    )?;
+    if !exists {
+        return Err(InvalidReportsTo { reason: "no such card" }.into());
+    }
+    let Some(id) = id else {
+        return Ok(());
+    };
+    // Walk up from the manager; meeting the card itself would close a loop.
+    let cycle: bool = conn.query_row(
+        "WITH RECURSIVE chain(id) AS (
+             SELECT ?1
+             UNION
+             SELECT c.reports_to FROM cards c JOIN chain ON c.id = chain.id
+             WHERE c.reports_to IS NOT NULL
+         )
+         SELECT EXISTS (SELECT 1 FROM chain WHERE id = ?2)",
+        params![manager, id],
+        |r| r.get(0),
+    )?;
+    if cycle {
+        return Err(InvalidReportsTo { reason: "would make the card report to itself" }.into());
+    }
     Ok(())
 }
 
+/// Fails with `VersionConflict` if `expected` is given and differs from the
+/// card's stored version. A missing card passes; callers report 404 themselves.
+fn check_version(conn: &Connection, id: i64, expected: Option<i64>) -> Result<()> {
+    let Some(expected) = expected else {
+        return Ok(());
+    };
+    let current: Option<i64> = conn
+        .query_row("SELECT version FROM cards WHERE id = ?1", params![id], |r| {
+            r.get(0)
+        })
+        .optional()?;
+    match current {
+        Some(current) if current != expected => Err(VersionConflict { current }.into()),
+        _ => Ok(()),
+    }
+}
+
 pub fn is_empty(conn: &Arc<Mutex<Connection>>) -> bool {
     let conn = conn.lock().unwrap();
     let count: i64 = conn
@@ -71,35 +647,70 @@ pub fn is_empty(conn: &Arc<Mutex<Connection>>) -> bool {
     count == 0
 }
 
-fn fetch_card_by_id(conn: &Connection, id: i64) -> Result<Option<Card>> {
+fn fetch_card_by_id(conn: &Connection, id: i64, relations: CardRelations) -> Result<Option<Card>> {
     let mut stmt = conn.prepare(
-        "SELECT id, name, title, company, website, notes, photo_path, created_at, updated_at
+        "SELECT id, name, title, company, website, notes,
+                (SELECT p.path FROM card_photos p WHERE p.card_id = cards.id ORDER BY p.position, p.id LIMIT 1),
+                created_at, updated_at, version,
+                favorite, archived_at, custom_fields,
+                name_prefix, given_name, middle_name, family_name, name_suffix, name_phonetic,
+                birthday, anniversary, department, role_seniority, assistant_name, assistant_phone,
+                (SELECT m.id FROM cards m WHERE m.id = cards.reports_to),
+                (SELECT MAX(i.occurred_at) FROM interactions i WHERE i.card_id = cards.id),
+                (SELECT l.logo_path FROM company_logos l WHERE l.company = cards.company AND cards.company <> ''),
+                timezone
          FROM cards WHERE id = ?1",
     )?;
 
     let card_opt = stmt
         .query_row(params![id], |row| {
+            let timezone: String = row.get(28)?;
             Ok(Card {
                 id: row.get(0)?,
                 name: row.get(1)?,
+                name_prefix: row.get(13)?,
+                given_name: row.get(14)?,
+                middle_name: row.get(15)?,
+                family_name: row.get(16)?,
+                name_suffix: row.get(17)?,
+                name_phonetic: row.get(18)?,
+                birthday: row.get(19)?,
+                anniversary: row.get(20)?,
                 title: row.get(2)?,
                 company: row.get(3)?,
+                department: row.get(21)?,
+                role_seniority: row.get(22)?,
+                assistant_name: row.get(23)?,
+                assistant_phone: row.get(24)?,
+                reports_to: row.get(25)?,
+                last_contacted: row.get(26)?,
+                local_time: timezone::local_time(&timezone),
+                timezone,
                 website: row.get(4)?,
                 notes: row.get(5)?,
-                photo_url: {
-                    let path: String = row.get(6)?;
-                    if path.is_empty() {
-                        String::new()
-                    } else {
-                        format!("/{path}")
-                    }
-                },
+                photo_url: row
+                    .get::<_, Option<String>>(6)?
+                    .map(|path| base_path::url(&format!("/{path}")))
+                    .unwrap_or_default(),
+                photos: vec![],
+                company_logo_url: row
+                    .get::<_, Option<String>>(27)?
+                    .map(|path| base_path::url(&format!("/{path}")))
+                    .unwrap_or_default(),
                 phones: vec![],
                 emails: vec![],
                 addresses: vec![],
+                socials: vec![],
+                ims: vec![],
                 tags: vec![],
+                custom: serde_json::from_str(&row.get::<_, String>(12)?).unwrap_or_default(),
                 created_at: row.get(7)?,
                 updated_at: row.get(8)?,
+                version: row.get(9)?,
+                favorite: row.get(10)?,
+                archived_at: row.get(11)?,
+                score: None,
+                matches: vec![],
             })
         })
         .ok();
@@ -108,319 +719,2874 @@ fn fetch_card_by_id(conn: &Connection, id: i64) -> Result<Option<Card>> {
         return Ok(None);
     };
 
-    // phones
-    let mut stmt = conn.prepare(
-        "SELECT id, label, number FROM card_phones WHERE card_id = ?1 ORDER BY id",
-    )?;
-    card.phones = stmt
-        .query_map(params![id], |row| {
-            Ok(Phone {
+    if relations.phones {
+        let mut stmt = conn.prepare(
+            "SELECT id, label, number, e164 FROM card_phones WHERE card_id = ?1 ORDER BY id",
+        )?;
+        card.phones = stmt
+            .query_map(params![id], |row| {
+                Ok(Phone {
+                    id: row.get(0)?,
+                    label: row.get(1)?,
+                    number: row.get(2)?,
+                    e164: row.get(3)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+    }
+
+    if relations.emails {
+        let mut stmt = conn.prepare(
+            "SELECT id, label, address FROM card_emails WHERE card_id = ?1 ORDER BY id",
+        )?;
+        card.emails = stmt
+            .query_map(params![id], |row| {
+                Ok(Email {
+                    id: row.get(0)?,
+                    label: row.get(1)?,
+                    address: row.get(2)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+    }
+
+    if relations.addresses {
+        let mut stmt = conn.prepare(
+            "SELECT id, label, street, city, country, postal, latitude, longitude
+             FROM card_addresses WHERE card_id = ?1 ORDER BY id",
+        )?;
+        card.addresses = stmt
+            .query_map(params![id], |row| {
+                Ok(Address {
+                    id: row.get(0)?,
+                    label: row.get(1)?,
+                    street: row.get(2)?,
+                    city: row.get(3)?,
+                    country: row.get(4)?,
+                    postal: row.get(5)?,
+                    latitude: row.get(6)?,
+                    longitude: row.get(7)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+    }
+
+    if relations.socials {
+        let mut stmt = conn.prepare(
+            "SELECT id, platform, handle, url FROM card_socials WHERE card_id = ?1 ORDER BY id",
+        )?;
+        card.socials = stmt
+            .query_map(params![id], |row| {
+                Ok(Social {
+                    id: row.get(0)?,
+                    platform: row.get(1)?,
+                    handle: row.get(2)?,
+                    url: row.get(3)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+    }
+
+    if relations.ims {
+        let mut stmt = conn.prepare(
+            "SELECT id, label, protocol, handle FROM card_ims WHERE card_id = ?1 ORDER BY id",
+        )?;
+        card.ims = stmt
+            .query_map(params![id], |row| {
+                Ok(Im {
+                    id: row.get(0)?,
+                    label: row.get(1)?,
+                    protocol: row.get(2)?,
+                    handle: row.get(3)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+    }
+
+    if relations.photos {
+        card.photos = fetch_photos(conn, id)?;
+    }
+
+    if relations.tags {
+        let mut stmt = conn.prepare(
+            "SELECT t.name FROM tags t
+             JOIN card_tags ct ON ct.tag_id = t.id
+             WHERE ct.card_id = ?1
+             ORDER BY t.name",
+        )?;
+        card.tags = stmt
+            .query_map(params![id], |row| row.get(0))?
+            .collect::<std::result::Result<Vec<String>, _>>()?;
+    }
+
+    Ok(Some(card))
+}
+
+/// Whitelisted SQL expression for each sortable field. Text columns sort
+/// ignoring case and accents.
+fn sort_column(field: SortField) -> &'static str {
+    match field {
+        // Names with a reading sort by it, so kanji and hanzi names land
+        // where their pronunciation puts them.
+        SortField::Name => {
+            "(CASE WHEN c.name_phonetic = '' THEN c.name ELSE c.name_phonetic END) COLLATE FOLD"
+        }
+        // Cards without a family name sort by their display name.
+        SortField::FamilyName => {
+            "(CASE WHEN c.family_name = '' THEN c.name
+                   ELSE RTRIM(c.family_name || ' ' || c.given_name) END) COLLATE FOLD"
+        }
+        SortField::Company => "c.company COLLATE FOLD",
+        SortField::CreatedAt => "c.created_at",
+        SortField::UpdatedAt => "c.updated_at",
+    }
+}
+
+/// `column` with the usual phone-number punctuation stripped.
+fn digits_only_sql(column: &str) -> String {
+    ["+", "-", " ", "(", ")", "."]
+        .iter()
+        .fold(column.to_string(), |expr, ch| format!("REPLACE({expr}, '{ch}', '')"))
+}
+
+/// Condition (over `cards c`) that holds when the card lacks `detail`.
+fn missing_sql(detail: CardDetail) -> &'static str {
+    match detail {
+        CardDetail::Email => "NOT EXISTS (SELECT 1 FROM card_emails ce WHERE ce.card_id = c.id)",
+        CardDetail::Phone => "NOT EXISTS (SELECT 1 FROM card_phones cp WHERE cp.card_id = c.id)",
+        CardDetail::Address => {
+            "NOT EXISTS (SELECT 1 FROM card_addresses ca WHERE ca.card_id = c.id)"
+        }
+        CardDetail::Tag => "NOT EXISTS (SELECT 1 FROM card_tags ct WHERE ct.card_id = c.id)",
+        CardDetail::Photo => "NOT EXISTS (SELECT 1 FROM card_photos p WHERE p.card_id = c.id)",
+        CardDetail::Title => "c.title = ''",
+        CardDetail::Company => "c.company = ''",
+        CardDetail::Website => "c.website = ''",
+    }
+}
+
+/// Builds the `WHERE` clause (over `cards c`) and its positional parameters
+/// for a card listing.
+fn card_filter_sql(query: &CardQuery) -> (String, Vec<Value>) {
+    let mut clauses: Vec<String> = Vec::new();
+    let mut args: Vec<Value> = Vec::new();
+
+    clauses.push(if query.archived {
+        "c.archived_at IS NOT NULL".to_string()
+    } else {
+        "c.archived_at IS NULL".to_string()
+    });
+
+    let q = query.q.as_deref().filter(|s| !s.is_empty());
+    if let Some(name) = q.filter(|_| query.phonetic) {
+        // Every word of the query must sound like some word of the name.
+        let key = phonetic_key(name);
+        if key.is_empty() {
+            clauses.push("0".to_string());
+        }
+        for code in key.split_whitespace() {
+            args.push(Value::Text(format!("% {code} %")));
+            clauses.push(format!("(' ' || c.name_sound_key || ' ') LIKE ?{}", args.len()));
+        }
+    } else if let Some(search) = q {
+        args.push(Value::Text(format!("%{}%", fold(search))));
+        let n = args.len();
+        let mut matches = format!(
+            "fold(c.name) LIKE ?{n} OR fold(c.name_phonetic) LIKE ?{n}
+             OR fold(c.company) LIKE ?{n} OR fold(c.title) LIKE ?{n}
+             OR fold(c.notes) LIKE ?{n}
+             OR EXISTS (SELECT 1 FROM card_emails ce WHERE ce.card_id = c.id AND fold(ce.address) LIKE ?{n})
+             OR EXISTS (SELECT 1 FROM card_ims ci WHERE ci.card_id = c.id AND fold(ci.handle) LIKE ?{n})
+             OR EXISTS (SELECT 1 FROM card_addresses ca WHERE ca.card_id = c.id
+                        AND (fold(ca.street) LIKE ?{n} OR fold(ca.city) LIKE ?{n}))"
+        );
+        // Phone numbers match on digits alone, so "9123 4567" finds "+65-9123-4567".
+        let digits: String = search.chars().filter(char::is_ascii_digit).collect();
+        if digits.len() >= 3 {
+            args.push(Value::Text(format!("%{digits}%")));
+            matches.push_str(&format!(
+                " OR EXISTS (SELECT 1 FROM card_phones cp WHERE cp.card_id = c.id AND {} LIKE ?{})",
+                digits_only_sql("cp.number"),
+                args.len()
+            ));
+        }
+        // A complete number also matches however it was written, e.g. "91234567"
+        // finds "+65 9123 4567" when the default region is SG.
+        if let Some(e164) = to_e164(search) {
+            args.push(Value::Text(e164));
+            matches.push_str(&format!(
+                " OR EXISTS (SELECT 1 FROM card_phones cp WHERE cp.card_id = c.id AND cp.e164 = ?{})",
+                args.len()
+            ));
+        }
+        clauses.push(format!("({matches})"));
+    }
+    for (tags, negate) in [(&query.tags, ""), (&query.not_tags, "NOT ")] {
+        for tag in tags.iter().map(|t| normalize_tag(t)).filter(|s| !s.is_empty()) {
+            args.push(Value::Text(tag));
+            let n = args.len();
+            clauses.push(format!(
+                "{negate}EXISTS (SELECT 1 FROM card_tags ct JOIN tags t ON t.id = ct.tag_id
+                                 WHERE ct.card_id = c.id
+                                   AND (t.name = ?{n}
+                                        OR t.id IN (SELECT tag_id FROM tag_aliases WHERE alias = ?{n})))"
+            ));
+        }
+    }
+    if query.untagged {
+        clauses.push(missing_sql(CardDetail::Tag).to_string());
+    }
+    for (column, value) in [("country", &query.country), ("city", &query.city)] {
+        if let Some(value) = value.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+            args.push(Value::Text(fold(value)));
+            let n = args.len();
+            clauses.push(format!(
+                "EXISTS (SELECT 1 FROM card_addresses ca
+                         WHERE ca.card_id = c.id AND fold(ca.{column}) = ?{n})"
+            ));
+        }
+    }
+    match query.has_photo {
+        Some(true) => clauses.push("EXISTS (SELECT 1 FROM card_photos p WHERE p.card_id = c.id)".to_string()),
+        Some(false) => clauses.push("NOT EXISTS (SELECT 1 FROM card_photos p WHERE p.card_id = c.id)".to_string()),
+        None => {}
+    }
+    if let Some(favorite) = query.favorite {
+        args.push(Value::Integer(favorite.into()));
+        clauses.push(format!("c.favorite = ?{}", args.len()));
+    }
+    for detail in &query.missing {
+        clauses.push(missing_sql(*detail).to_string());
+    }
+    for (bound, op) in [
+        (&query.created_after, "c.created_at >="),
+        (&query.created_before, "c.created_at <"),
+        (&query.updated_after, "c.updated_at >="),
+        (&query.updated_before, "c.updated_at <"),
+    ] {
+        if let Some(ts) = bound {
+            args.push(Value::Text(ts.clone()));
+            clauses.push(format!("{op} ?{}", args.len()));
+        }
+    }
+    if let Some(after) = &query.after {
+        args.push(Value::Text(after.value.clone()));
+        args.push(Value::Integer(after.id));
+        let n = args.len();
+        let col = sort_column(after.sort.field);
+        let op = if after.sort.descending { "<" } else { ">" };
+        clauses.push(format!(
+            "({col} {op} ?{m} OR ({col} = ?{m} AND c.id {op} ?{n}))",
+            m = n - 1
+        ));
+    }
+
+    let sql = if clauses.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", clauses.join(" AND "))
+    };
+    (sql, args)
+}
+
+pub fn list_cards(conn: &Arc<Mutex<Connection>>, query: &CardQuery) -> Result<Vec<Card>> {
+    let conn = conn.lock().unwrap();
+
+    let (where_sql, mut args) = card_filter_sql(query);
+    let ranked = query.q.as_deref().filter(|q| query.rank && !q.is_empty());
+    let (score, order) = match ranked {
+        Some(q) => (
+            relevance_sql(q, &mut args),
+            "score DESC, c.updated_at DESC, c.id DESC".to_string(),
+        ),
+        None => {
+            let dir = if query.sort.descending { "DESC" } else { "ASC" };
+            let col = sort_column(query.sort.field);
+            ("NULL".to_string(), format!("{col} {dir}, c.id {dir}"))
+        }
+    };
+    // SQLite needs a LIMIT to accept an OFFSET; -1 means "no limit".
+    args.push(Value::Integer(query.limit.unwrap_or(-1)));
+    args.push(Value::Integer(query.offset));
+    let n = args.len();
+    let sql = format!(
+        "SELECT c.id, {score} AS score FROM cards c {where_sql}
+         ORDER BY {order}
+         LIMIT ?{} OFFSET ?{n}",
+        n - 1,
+    );
+
+    let mut stmt = conn.prepare(&sql)?;
+    let hits = stmt
+        .query_map(params_from_iter(args), |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<std::result::Result<Vec<(i64, Option<f64>)>, _>>()?;
+
+    let mut cards = Vec::with_capacity(hits.len());
+    for (id, score) in hits {
+        if let Some(mut card) = fetch_card_by_id(&conn, id, query.relations)? {
+            card.score = score;
+            cards.push(card);
+        }
+    }
+    Ok(cards)
+}
+
+/// Score (0–1) of how well a card matches the plain search `q`, by the best
+/// field it matches in: exact name, then name prefix, a word of the name,
+/// anywhere in the name or its reading, company, email or IM handle, title,
+/// address, phone, and notes.
+fn relevance_sql(q: &str, args: &mut Vec<Value>) -> String {
+    let q = &fold(q);
+    args.push(Value::Text(q.to_string()));
+    let exact = args.len();
+    args.push(Value::Text(format!("{q}%")));
+    let prefix = args.len();
+    args.push(Value::Text(format!("% {q}%")));
+    let word = args.len();
+    args.push(Value::Text(format!("%{q}%")));
+    let any = args.len();
+    let digits: String = q.chars().filter(char::is_ascii_digit).collect();
+    let phone = if digits.len() >= 3 {
+        args.push(Value::Text(format!("%{digits}%")));
+        format!("{} LIKE ?{}", digits_only_sql("cp.number"), args.len())
+    } else {
+        format!("cp.number LIKE ?{any}")
+    };
+    format!(
+        "CASE
+           WHEN fold(c.name) = ?{exact} THEN 1.0
+           WHEN fold(c.name) LIKE ?{prefix} THEN 0.9
+           WHEN fold(c.name) LIKE ?{word} THEN 0.8
+           WHEN fold(c.name) LIKE ?{any} THEN 0.7
+           WHEN fold(c.name_phonetic) LIKE ?{any} THEN 0.7
+           WHEN fold(c.company) LIKE ?{any} THEN 0.6
+           WHEN EXISTS (SELECT 1 FROM card_emails ce WHERE ce.card_id = c.id AND fold(ce.address) LIKE ?{any}) THEN 0.5
+           WHEN EXISTS (SELECT 1 FROM card_ims ci WHERE ci.card_id = c.id AND fold(ci.handle) LIKE ?{any}) THEN 0.5
+           WHEN fold(c.title) LIKE ?{any} THEN 0.4
+           WHEN EXISTS (SELECT 1 FROM card_addresses ca WHERE ca.card_id = c.id
+                        AND (fold(ca.street) LIKE ?{any} OR fold(ca.city) LIKE ?{any})) THEN 0.3
+           WHEN EXISTS (SELECT 1 FROM card_phones cp WHERE cp.card_id = c.id AND {phone}) THEN 0.2
+           ELSE 0.1
+         END"
+    )
+}
+
+/// Searchable text of every card matching `query` (paging is ignored), most
+/// recently updated first.
+pub fn search_docs(conn: &Arc<Mutex<Connection>>, query: &CardQuery) -> Result<Vec<SearchDoc>> {
+    let conn = conn.lock().unwrap();
+    let (where_sql, args) = card_filter_sql(&CardQuery {
+        after: None,
+        ..query.clone()
+    });
+    let sql = format!(
+        "SELECT c.id, c.name, c.company, c.title, c.notes, c.name_phonetic,
+                (SELECT group_concat(ce.address, char(10)) FROM card_emails ce WHERE ce.card_id = c.id),
+                (SELECT group_concat(cp.number, char(10)) FROM card_phones cp WHERE cp.card_id = c.id),
+                (SELECT group_concat(ca.street || char(10) || ca.city, char(10))
+                   FROM card_addresses ca WHERE ca.card_id = c.id),
+                (SELECT group_concat(ci.handle, char(10)) FROM card_ims ci WHERE ci.card_id = c.id)
+         FROM cards c {where_sql}
+         ORDER BY c.updated_at DESC, c.id DESC"
+    );
+
+    let mut stmt = conn.prepare(&sql)?;
+    let docs = stmt
+        .query_map(params_from_iter(args), |row| {
+            let mut fields: Vec<(&'static str, String)> = vec![
+                ("name", row.get(1)?),
+                ("company", row.get(2)?),
+                ("title", row.get(3)?),
+                ("notes", row.get(4)?),
+                ("name_phonetic", row.get(5)?),
+            ];
+            for (col, field) in [(6, "email"), (7, "phone"), (8, "address"), (9, "im")] {
+                let joined: Option<String> = row.get(col)?;
+                for line in joined.as_deref().unwrap_or("").lines() {
+                    if !line.is_empty() {
+                        fields.push((field, line.to_string()));
+                    }
+                }
+            }
+            Ok(SearchDoc {
                 id: row.get(0)?,
-                label: row.get(1)?,
-                number: row.get(2)?,
+                fields,
             })
         })?
         .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(docs)
+}
+
+/// The card with its manager and direct reports, or `None` if it does not
+/// exist.
+pub fn org_chart(conn: &Arc<Mutex<Connection>>, id: i64) -> Result<Option<OrgChart>> {
+    let conn = conn.lock().unwrap();
+    let Some(card) = fetch_card_by_id(&conn, id, CardRelations::ALL)? else {
+        return Ok(None);
+    };
+    let manager = match card.reports_to {
+        Some(manager) => fetch_card_by_id(&conn, manager, CardRelations::ALL)?,
+        None => None,
+    };
+    let ids: Vec<i64> = conn
+        .prepare(
+            "SELECT id FROM cards WHERE reports_to = ?1 AND archived_at IS NULL
+             ORDER BY name COLLATE FOLD, id",
+        )?
+        .query_map(params![id], |r| r.get(0))?
+        .collect::<std::result::Result<_, _>>()?;
+    let mut reports = Vec::with_capacity(ids.len());
+    for report in ids {
+        if let Some(card) = fetch_card_by_id(&conn, report, CardRelations::ALL)? {
+            reports.push(card);
+        }
+    }
+    Ok(Some(OrgChart {
+        card,
+        manager,
+        reports,
+    }))
+}
 
-    // emails
+/// Id, birthday, and anniversary of every active card with either set.
+pub fn card_dates(conn: &Arc<Mutex<Connection>>) -> Result<Vec<(i64, String, String)>> {
+    let conn = conn.lock().unwrap();
     let mut stmt = conn.prepare(
-        "SELECT id, label, address FROM card_emails WHERE card_id = ?1 ORDER BY id",
+        "SELECT id, birthday, anniversary FROM cards
+         WHERE archived_at IS NULL AND (birthday != '' OR anniversary != '')",
     )?;
-    card.emails = stmt
-        .query_map(params![id], |row| {
-            Ok(Email {
-                id: row.get(0)?,
-                label: row.get(1)?,
-                address: row.get(2)?,
-            })
-        })?
+    let dates = stmt
+        .query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))?
         .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(dates)
+}
 
-    // addresses
+/// Loads the given cards in the order of `ids`, skipping any that are gone.
+pub fn get_cards_by_ids(
+    conn: &Arc<Mutex<Connection>>,
+    ids: &[i64],
+    relations: CardRelations,
+) -> Result<Vec<Card>> {
+    let conn = conn.lock().unwrap();
+    let mut cards = Vec::with_capacity(ids.len());
+    for &id in ids {
+        if let Some(card) = fetch_card_by_id(&conn, id, relations)? {
+            cards.push(card);
+        }
+    }
+    Ok(cards)
+}
+
+/// Number of cards matching `query`, ignoring its limit/offset and cursor.
+pub fn count_cards(conn: &Arc<Mutex<Connection>>, query: &CardQuery) -> Result<i64> {
+    let conn = conn.lock().unwrap();
+    let query = CardQuery {
+        after: None,
+        ..query.clone()
+    };
+    let (where_sql, args) = card_filter_sql(&query);
+    let count = conn.query_row(
+        &format!("SELECT COUNT(*) FROM cards c {where_sql}"),
+        params_from_iter(args),
+        |r| r.get(0),
+    )?;
+    Ok(count)
+}
+
+pub fn get_card(conn: &Arc<Mutex<Connection>>, id: i64) -> Result<Option<Card>> {
+    get_card_with(conn, id, CardRelations::ALL)
+}
+
+/// Like [`get_card`], loading only the requested nested collections.
+pub fn get_card_with(
+    conn: &Arc<Mutex<Connection>>,
+    id: i64,
+    relations: CardRelations,
+) -> Result<Option<Card>> {
+    let conn = conn.lock().unwrap();
+    fetch_card_by_id(&conn, id, relations)
+}
+
+pub fn find_card_id_by_email(conn: &Arc<Mutex<Connection>>, address: &str) -> Result<Option<i64>> {
+    let conn = conn.lock().unwrap();
+    let id = conn
+        .query_row(
+            "SELECT card_id FROM card_emails WHERE address = ?1 COLLATE NOCASE LIMIT 1",
+            params![address.trim()],
+            |r| r.get(0),
+        )
+        .optional()?;
+    Ok(id)
+}
+
+/// The oldest card sharing one of the emails or phone numbers, compared as
+/// `duplicates` does.
+pub fn find_card_id_by_contact(
+    conn: &Arc<Mutex<Connection>>,
+    emails: &[String],
+    phones: &[String],
+) -> Result<Option<i64>> {
+    let conn = conn.lock().unwrap();
+    let mut found: Vec<i64> = Vec::new();
+    for key in emails.iter().filter_map(|e| email_key(e)) {
+        found.extend(
+            conn.query_row(
+                "SELECT MIN(card_id) FROM card_emails WHERE email_key(address) = ?1",
+                params![key],
+                |r| r.get::<_, Option<i64>>(0),
+            )?,
+        );
+    }
+    for key in phones.iter().filter_map(|p| phone_key(p)) {
+        found.extend(
+            conn.query_row(
+                "SELECT MIN(card_id) FROM card_phones WHERE phone_key(number) = ?1",
+                params![key],
+                |r| r.get::<_, Option<i64>>(0),
+            )?,
+        );
+    }
+    Ok(found.into_iter().min())
+}
+
+fn upsert_tags_and_link(
+    conn: &Connection,
+    card_id: i64,
+    tags: &[String],
+) -> Result<()> {
+    conn.execute("DELETE FROM card_tags WHERE card_id = ?1", params![card_id])?;
+    for tag in tags {
+        let tag = normalize_tag(tag);
+        if tag.is_empty() {
+            continue;
+        }
+        let tag_id = ensure_tag(conn, &tag)?;
+        conn.execute(
+            "INSERT OR IGNORE INTO card_tags (card_id, tag_id) VALUES (?1, ?2)",
+            params![card_id, tag_id],
+        )?;
+    }
+    Ok(())
+}
+
+pub fn create_card(conn: &Arc<Mutex<Connection>>, input: &CardInput) -> Result<i64> {
+    let conn = conn.lock().unwrap();
+    check_reports_to(&conn, None, input.reports_to)?;
+    conn.execute(
+        "INSERT INTO cards (name, title, company, website, notes, name_sound_key, custom_fields,
+                            name_prefix, given_name, middle_name, family_name, name_suffix,
+                            name_phonetic, birthday, anniversary,
+                            department, role_seniority, assistant_name, assistant_phone, reports_to,
+                            timezone)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21)",
+        params![
+            input.name,
+            input.title,
+            input.company,
+            input.website,
+            input.notes,
+            sound_key(&input.name, &input.name_phonetic),
+            serde_json::to_string(&input.custom)?,
+            input.name_prefix,
+            input.given_name,
+            input.middle_name,
+            input.family_name,
+            input.name_suffix,
+            input.name_phonetic,
+            input.birthday,
+            input.anniversary,
+            input.department,
+            input.role_seniority,
+            input.assistant_name,
+            input.assistant_phone,
+            input.reports_to,
+            input.timezone
+        ],
+    )?;
+    let id = conn.last_insert_rowid();
+
+    for p in &input.phones {
+        conn.execute(
+            "INSERT INTO card_phones (card_id, label, number, e164) VALUES (?1, ?2, ?3, ?4)",
+            params![id, p.label, p.number, to_e164(&p.number).unwrap_or_default()],
+        )?;
+    }
+    for e in &input.emails {
+        conn.execute(
+            "INSERT INTO card_emails (card_id, label, address) VALUES (?1, ?2, ?3)",
+            params![id, e.label, e.address],
+        )?;
+    }
+    for a in &input.addresses {
+        conn.execute(
+            "INSERT INTO card_addresses (card_id, label, street, city, country, postal) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![id, a.label, a.street, a.city, a.country, a.postal],
+        )?;
+    }
+    replace_socials(&conn, id, &input.socials)?;
+    replace_ims(&conn, id, &input.ims)?;
+    upsert_tags_and_link(&conn, id, &input.tags)?;
+    record_revision(&conn, id)?;
+    Ok(id)
+}
+
+fn replace_phones(conn: &Connection, card_id: i64, phones: &[CardFormPhoneInput]) -> Result<()> {
+    conn.execute("DELETE FROM card_phones WHERE card_id = ?1", params![card_id])?;
+    for p in phones {
+        conn.execute(
+            "INSERT INTO card_phones (card_id, label, number, e164) VALUES (?1, ?2, ?3, ?4)",
+            params![card_id, p.label, p.number, to_e164(&p.number).unwrap_or_default()],
+        )?;
+    }
+    Ok(())
+}
+
+fn replace_emails(conn: &Connection, card_id: i64, emails: &[CardFormEmailInput]) -> Result<()> {
+    conn.execute("DELETE FROM card_emails WHERE card_id = ?1", params![card_id])?;
+    for e in emails {
+        conn.execute(
+            "INSERT INTO card_emails (card_id, label, address) VALUES (?1, ?2, ?3)",
+            params![card_id, e.label, e.address],
+        )?;
+    }
+    Ok(())
+}
+
+fn replace_addresses(
+    conn: &Connection,
+    card_id: i64,
+    addresses: &[CardFormAddressInput],
+) -> Result<()> {
+    conn.execute("DELETE FROM card_addresses WHERE card_id = ?1", params![card_id])?;
+    for a in addresses {
+        conn.execute(
+            "INSERT INTO card_addresses (card_id, label, street, city, country, postal) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![card_id, a.label, a.street, a.city, a.country, a.postal],
+        )?;
+    }
+    Ok(())
+}
+
+fn replace_socials(conn: &Connection, card_id: i64, socials: &[CardFormSocialInput]) -> Result<()> {
+    conn.execute("DELETE FROM card_socials WHERE card_id = ?1", params![card_id])?;
+    for s in socials {
+        conn.execute(
+            "INSERT INTO card_socials (card_id, platform, handle, url) VALUES (?1, ?2, ?3, ?4)",
+            params![card_id, s.platform, s.handle, profile_url(&s.platform, &s.handle)],
+        )?;
+    }
+    Ok(())
+}
+
+fn replace_ims(conn: &Connection, card_id: i64, ims: &[CardFormImInput]) -> Result<()> {
+    conn.execute("DELETE FROM card_ims WHERE card_id = ?1", params![card_id])?;
+    for i in ims {
+        conn.execute(
+            "INSERT INTO card_ims (card_id, label, protocol, handle) VALUES (?1, ?2, ?3, ?4)",
+            params![card_id, i.label, i.protocol, i.handle],
+        )?;
+    }
+    Ok(())
+}
+
+pub fn update_card(
+    conn: &Arc<Mutex<Connection>>,
+    id: i64,
+    input: &CardInput,
+) -> Result<()> {
+    let conn = conn.lock().unwrap();
+    check_version(&conn, id, input.version)?;
+    check_reports_to(&conn, Some(id), input.reports_to)?;
+    let updated = conn.execute(
+        "UPDATE cards SET name=?1, title=?2, company=?3, website=?4, notes=?5, name_sound_key=?7, custom_fields=?8,
+                          name_prefix=?9, given_name=?10, middle_name=?11, family_name=?12, name_suffix=?13,
+                          name_phonetic=?14, birthday=?15, anniversary=?16,
+                          department=?17, role_seniority=?18, assistant_name=?19, assistant_phone=?20,
+                          reports_to=?21, timezone=?22, version=version+1, updated_at=CURRENT_TIMESTAMP
+         WHERE id=?6",
+        params![
+            input.name,
+            input.title,
+            input.company,
+            input.website,
+            input.notes,
+            id,
+            sound_key(&input.name, &input.name_phonetic),
+            serde_json::to_string(&input.custom)?,
+            input.name_prefix,
+            input.given_name,
+            input.middle_name,
+            input.family_name,
+            input.name_suffix,
+            input.name_phonetic,
+            input.birthday,
+            input.anniversary,
+            input.department,
+            input.role_seniority,
+            input.assistant_name,
+            input.assistant_phone,
+            input.reports_to,
+            input.timezone
+        ],
+    )?;
+    if updated == 0 {
+        anyhow::bail!("card not found");
+    }
+
+    replace_phones(&conn, id, &input.phones)?;
+    replace_emails(&conn, id, &input.emails)?;
+    replace_addresses(&conn, id, &input.addresses)?;
+    replace_socials(&conn, id, &input.socials)?;
+    replace_ims(&conn, id, &input.ims)?;
+    upsert_tags_and_link(&conn, id, &input.tags)?;
+    record_revision(&conn, id)?;
+    Ok(())
+}
+
+/// Applies a sparse update: only fields present in `patch` are written.
+/// Returns false if the card does not exist.
+pub fn patch_card(conn: &Arc<Mutex<Connection>>, id: i64, patch: &CardPatch) -> Result<bool> {
+    let conn = conn.lock().unwrap();
+    check_version(&conn, id, patch.version)?;
+    check_reports_to(&conn, Some(id), patch.reports_to.flatten())?;
+    let updated = conn.execute(
+        "UPDATE cards SET
+            name     = COALESCE(?1, name),
+            title    = COALESCE(?2, title),
+            company  = COALESCE(?3, company),
+            website  = COALESCE(?4, website),
+            notes    = COALESCE(?5, notes),
+            name_phonetic = COALESCE(?7, name_phonetic),
+            birthday    = COALESCE(?14, birthday),
+            anniversary = COALESCE(?15, anniversary),
+            custom_fields = COALESCE(?8, custom_fields),
+            name_prefix = COALESCE(?9, name_prefix),
+            given_name  = COALESCE(?10, given_name),
+            middle_name = COALESCE(?11, middle_name),
+            family_name = COALESCE(?12, family_name),
+            name_suffix = COALESCE(?13, name_suffix),
+            department      = COALESCE(?16, department),
+            role_seniority  = COALESCE(?17, role_seniority),
+            assistant_name  = COALESCE(?18, assistant_name),
+            assistant_phone = COALESCE(?19, assistant_phone),
+            reports_to = CASE WHEN ?20 THEN ?21 ELSE reports_to END,
+            timezone = COALESCE(?22, timezone),
+            version  = version + 1,
+            updated_at = CURRENT_TIMESTAMP
+         WHERE id = ?6",
+        params![
+            patch.name,
+            patch.title,
+            patch.company,
+            patch.website,
+            patch.notes,
+            id,
+            patch.name_phonetic,
+            patch.custom.as_ref().map(serde_json::to_string).transpose()?,
+            patch.name_prefix,
+            patch.given_name,
+            patch.middle_name,
+            patch.family_name,
+            patch.name_suffix,
+            patch.birthday,
+            patch.anniversary,
+            patch.department,
+            patch.role_seniority,
+            patch.assistant_name,
+            patch.assistant_phone,
+            patch.reports_to.is_some(),
+            patch.reports_to.flatten(),
+            patch.timezone
+        ],
+    )?;
+    if updated == 0 {
+        return Ok(false);
+    }
+
+    let parts = [
+        &patch.name_prefix,
+        &patch.given_name,
+        &patch.middle_name,
+        &patch.family_name,
+        &patch.name_suffix,
+    ];
+    if patch.name.is_none() && parts.iter().any(|p| p.is_some()) {
+        let name = conn.query_row(
+            "SELECT name_prefix, given_name, middle_name, family_name, name_suffix
+             FROM cards WHERE id = ?1",
+            params![id],
+            |r| {
+                Ok(display_name(
+                    &r.get::<_, String>(0)?,
+                    &r.get::<_, String>(1)?,
+                    &r.get::<_, String>(2)?,
+                    &r.get::<_, String>(3)?,
+                    &r.get::<_, String>(4)?,
+                ))
+            },
+        )?;
+        if !name.is_empty() {
+            conn.execute(
+                "UPDATE cards SET name = ?1 WHERE id = ?2",
+                params![name, id],
+            )?;
+        }
+    }
+    refresh_sound_key(&conn, id)?;
+
+    if let Some(phones) = &patch.phones {
+        replace_phones(&conn, id, phones)?;
+    }
+    if let Some(emails) = &patch.emails {
+        replace_emails(&conn, id, emails)?;
+    }
+    if let Some(addresses) = &patch.addresses {
+        replace_addresses(&conn, id, addresses)?;
+    }
+    if let Some(socials) = &patch.socials {
+        replace_socials(&conn, id, socials)?;
+    }
+    if let Some(ims) = &patch.ims {
+        replace_ims(&conn, id, ims)?;
+    }
+    if let Some(tags) = &patch.tags {
+        upsert_tags_and_link(&conn, id, tags)?;
+    }
+    record_revision(&conn, id)?;
+    Ok(true)
+}
+
+/// Deletes the card, returning the paths of the files of its photos and
+/// attachments that nothing else uses, for the caller to remove, or `None`
+/// if there was no such card.
+pub fn delete_card(conn: &Arc<Mutex<Connection>>, id: i64) -> Result<Option<Vec<String>>> {
+    info!("SQL: SELECT path FROM card_photos WHERE card_id = {}", id);
+    let conn = conn.lock().unwrap();
+
+    // Get the file paths and the card's last state before deleting
+    let photo_paths = file_paths(&conn, id)?;
+    info!("SQL: Result - photo paths: {:?}", photo_paths);
+    let card = fetch_card_by_id(&conn, id, CardRelations::ALL)?;
+
+    // Delete the card and check if it existed
+    info!("SQL: DELETE FROM cards WHERE id = {}", id);
+    let rows_affected = conn.execute("DELETE FROM cards WHERE id = ?1", params![id])?;
+    info!("SQL: Result - rows_affected: {}", rows_affected);
+
+    if rows_affected == 0 {
+        return Ok(None); // Card didn't exist
+    }
+    if let Some(card) = card {
+        let batch = next_batch(&conn)?;
+        record_deletion(&conn, &card, batch, REVISION_DELETE)?;
+    }
+
+    Ok(Some(release_files(&conn, &photo_paths)?))
+}
+
+/// Folds card `source` into `target` and deletes it, in one transaction.
+/// Phones, emails, addresses, social profiles, IM handles, and tags are
+/// unioned (skipping entries the target already has), empty fields of the
+/// target are filled from the source, and differing notes are appended.
+/// Cards reporting to the source then report to the target, and its
+/// interactions and reminders move over. The source's photos are added
+/// after the target's, or before them with `prefer_source_photo`, which
+/// makes the source's first photo the merged card's `photo_url`. Fails with
+/// `CardsNotFound` if either card is missing.
+pub fn merge_cards(
+    conn: &Arc<Mutex<Connection>>,
+    target: i64,
+    source: i64,
+    prefer_source_photo: bool,
+    expected_version: Option<i64>,
+) -> Result<()> {
+    let mut conn = conn.lock().unwrap();
+    let tx = conn.transaction()?;
+    let into = fetch_card_by_id(&tx, target, CardRelations::ALL)?;
+    let from = fetch_card_by_id(&tx, source, CardRelations::ALL)?;
+    let (Some(into), Some(from)) = (into, from) else {
+        let ids = [target, source]
+            .into_iter()
+            .filter(|&id| fetch_card_by_id(&tx, id, CardRelations::default()).ok().flatten().is_none())
+            .collect();
+        return Err(CardsNotFound { ids }.into());
+    };
+    check_version(&tx, target, expected_version)?;
+
+    let pick = |a: &String, b: &String| if a.is_empty() { b.clone() } else { a.clone() };
+    let notes = if into.notes.is_empty() || into.notes.contains(from.notes.as_str()) {
+        pick(&into.notes, &from.notes)
+    } else {
+        format!("{}\n\n{}", into.notes, from.notes)
+    };
+    let mut merged = CardInput::from(into.clone());
+    let extra = CardInput::from(from.clone());
+    let digits = |n: &str| {
+        phone_key(n).unwrap_or_else(|| n.chars().filter(char::is_ascii_digit).collect())
+    };
+    for p in extra.phones {
+        if !merged.phones.iter().any(|q| digits(&q.number) == digits(&p.number)) {
+            merged.phones.push(p);
+        }
+    }
+    for e in extra.emails {
+        if !merged.emails.iter().any(|f| {
+            f.address.eq_ignore_ascii_case(&e.address)
+                || email_key(&f.address).is_some_and(|k| Some(k) == email_key(&e.address))
+        }) {
+            merged.emails.push(e);
+        }
+    }
+    let place = |a: &CardFormAddressInput| {
+        [&a.street, &a.city, &a.country, &a.postal].map(|s| fold(s.trim()))
+    };
+    for a in extra.addresses {
+        if !merged.addresses.iter().any(|b| place(b) == place(&a)) {
+            merged.addresses.push(a);
+        }
+    }
+    for s in extra.socials {
+        if !merged.socials.iter().any(|t| {
+            t.platform == s.platform && t.handle.eq_ignore_ascii_case(&s.handle)
+        }) {
+            merged.socials.push(s);
+        }
+    }
+    for i in extra.ims {
+        if !merged.ims.iter().any(|j| {
+            j.protocol == i.protocol && j.handle.eq_ignore_ascii_case(&i.handle)
+        }) {
+            merged.ims.push(i);
+        }
+    }
+    merged.tags.extend(extra.tags);
+    for (key, value) in extra.custom {
+        merged.custom.entry(key).or_insert(value);
+    }
+    // Name parts go together, so they are only taken when the target has none.
+    let has_parts = |c: &CardInput| {
+        [&c.name_prefix, &c.given_name, &c.middle_name, &c.family_name, &c.name_suffix]
+            .iter()
+            .any(|p| !p.is_empty())
+    };
+    if !has_parts(&merged) {
+        merged.name_prefix = extra.name_prefix;
+        merged.given_name = extra.given_name;
+        merged.middle_name = extra.middle_name;
+        merged.family_name = extra.family_name;
+        merged.name_suffix = extra.name_suffix;
+    }
+
+    let photo_ids = |id: i64| -> Result<Vec<i64>> {
+        Ok(tx
+            .prepare("SELECT id FROM card_photos WHERE card_id = ?1 ORDER BY position, id")?
+            .query_map(params![id], |r| r.get(0))?
+            .collect::<std::result::Result<_, _>>()?)
+    };
+    let (target_photos, source_photos) = (photo_ids(target)?, photo_ids(source)?);
+    let photos = if prefer_source_photo {
+        [source_photos, target_photos].concat()
+    } else {
+        [target_photos, source_photos].concat()
+    };
+    tx.execute("UPDATE card_photos SET card_id = ?1 WHERE card_id = ?2", params![target, source])?;
+    renumber_photos(&tx, &photos)?;
+    tx.execute("UPDATE card_attachments SET card_id = ?1 WHERE card_id = ?2", params![target, source])?;
+    // The target keeps its manager, else takes the source's unless that
+    // would close a loop.
+    let reports_to = [into.reports_to, from.reports_to].into_iter().flatten().find(|&m| {
+        m != target && m != source && check_reports_to(&tx, Some(target), Some(m)).is_ok()
+    });
+
+    tx.execute(
+        "UPDATE cards SET name=?1, title=?2, company=?3, website=?4, notes=?5, name_sound_key=?6,
+                          timezone=?7, favorite = favorite OR ?8,
+                          created_at = MIN(created_at, ?9), custom_fields=?11,
+                          name_prefix=?12, given_name=?13, middle_name=?14, family_name=?15,
+                          name_suffix=?16, name_phonetic=?17, birthday=?18, anniversary=?19,
+                          department=?20, role_seniority=?21, assistant_name=?22, assistant_phone=?23,
+                          reports_to=?24, version=version+1, updated_at=CURRENT_TIMESTAMP
+         WHERE id=?10",
+        params![
+            pick(&into.name, &from.name),
+            pick(&into.title, &from.title),
+            pick(&into.company, &from.company),
+            pick(&into.website, &from.website),
+            notes,
+            sound_key(
+                &pick(&into.name, &from.name),
+                &pick(&into.name_phonetic, &from.name_phonetic)
+            ),
+            pick(&into.timezone, &from.timezone),
+            from.favorite,
+            from.created_at,
+            target,
+            serde_json::to_string(&merged.custom)?,
+            merged.name_prefix,
+            merged.given_name,
+            merged.middle_name,
+            merged.family_name,
+            merged.name_suffix,
+            pick(&into.name_phonetic, &from.name_phonetic),
+            pick(&into.birthday, &from.birthday),
+            pick(&into.anniversary, &from.anniversary),
+            pick(&into.department, &from.department),
+            pick(&into.role_seniority, &from.role_seniority),
+            pick(&into.assistant_name, &from.assistant_name),
+            pick(&into.assistant_phone, &from.assistant_phone),
+            reports_to
+        ],
+    )?;
+    replace_phones(&tx, target, &merged.phones)?;
+    replace_emails(&tx, target, &merged.emails)?;
+    replace_addresses(&tx, target, &merged.addresses)?;
+    replace_socials(&tx, target, &merged.socials)?;
+    replace_ims(&tx, target, &merged.ims)?;
+    upsert_tags_and_link(&tx, target, &merged.tags)?;
+    // The source's reports now report to the target.
+    let reports: Vec<i64> = tx
+        .prepare("SELECT id FROM cards WHERE reports_to = ?1 AND id != ?2")?
+        .query_map(params![source, target], |r| r.get(0))?
+        .collect::<std::result::Result<_, _>>()?;
+    tx.execute(
+        "UPDATE cards SET reports_to=?1, version=version+1, updated_at=CURRENT_TIMESTAMP
+         WHERE reports_to=?2 AND id != ?1",
+        params![target, source],
+    )?;
+    tx.execute("UPDATE interactions SET card_id = ?1 WHERE card_id = ?2", params![target, source])?;
+    tx.execute("UPDATE reminders SET card_id = ?1 WHERE card_id = ?2", params![target, source])?;
+    tx.execute("DELETE FROM cards WHERE id = ?1", params![source])?;
+
+    // One batch, so an undo brings the source back as well.
+    let batch = next_batch(&tx)?;
+    write_revision(&tx, target, batch, REVISION_EDIT)?;
+    for id in reports {
+        write_revision(&tx, id, batch, REVISION_EDIT)?;
+    }
+    record_deletion(&tx, &from, batch, REVISION_DELETE)?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Archives the card, or with `archived` false restores it. Archiving an
+/// archived card keeps its original `archived_at`. Returns false if there
+/// is no such card.
+pub fn set_archived(conn: &Arc<Mutex<Connection>>, id: i64, archived: bool) -> Result<bool> {
+    let conn = conn.lock().unwrap();
+    let sql = if archived {
+        "UPDATE cards SET archived_at = COALESCE(archived_at, CURRENT_TIMESTAMP),
+                          version=version+1, updated_at=CURRENT_TIMESTAMP
+         WHERE id = ?1"
+    } else {
+        "UPDATE cards SET archived_at = NULL, version=version+1, updated_at=CURRENT_TIMESTAMP
+         WHERE id = ?1"
+    };
+    if conn.execute(sql, params![id])? == 0 {
+        return Ok(false);
+    }
+    record_revision(&conn, id)?;
+    Ok(true)
+}
+
+/// Flips the card's favorite flag. Returns false if there is no such card.
+pub fn toggle_favorite(conn: &Arc<Mutex<Connection>>, id: i64) -> Result<bool> {
+    let conn = conn.lock().unwrap();
+    let updated = conn.execute(
+        "UPDATE cards SET favorite = NOT favorite, version=version+1, updated_at=CURRENT_TIMESTAMP
+         WHERE id = ?1",
+        params![id],
+    )?;
+    if updated == 0 {
+        return Ok(false);
+    }
+    record_revision(&conn, id)?;
+    Ok(true)
+}
+
+/// Replaces the file of the card's first photo, keeping its role, or adds
+/// the card's first photo as its front. Returns the replaced file's path,
+/// empty if there was none or something else still uses it.
+pub fn update_card_photo(
+    conn: &Arc<Mutex<Connection>>,
+    id: i64,
+    path: &str,
+) -> Result<String> {
+    let conn = conn.lock().unwrap();
+    retain_file(&conn, path)?;
+    let first: Option<(i64, String)> = conn
+        .query_row(
+            "SELECT id, path FROM card_photos WHERE card_id = ?1 ORDER BY position, id LIMIT 1",
+            params![id],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )
+        .optional()?;
+    let old_path = match first {
+        Some((photo_id, old_path)) => {
+            conn.execute("UPDATE card_photos SET path = ?1 WHERE id = ?2", params![path, photo_id])?;
+            release_file(&conn, &old_path)?.unwrap_or_default()
+        }
+        None => {
+            conn.execute(
+                "INSERT INTO card_photos (card_id, role, position, path) VALUES (?1, 'front', 0, ?2)",
+                params![id, path],
+            )?;
+            String::new()
+        }
+    };
+    touch_card(&conn, id)?;
+    Ok(old_path)
+}
+
+/// Removes the card's first photo. Returns its path, empty if the card has
+/// no photo or its file is still used, or `None` if there is no such card.
+pub fn delete_card_photo(conn: &Arc<Mutex<Connection>>, id: i64) -> Result<Option<String>> {
+    let conn = conn.lock().unwrap();
+    if !card_exists(&conn, id)? {
+        return Ok(None);
+    }
+    let first: Option<i64> = conn
+        .query_row(
+            "SELECT id FROM card_photos WHERE card_id = ?1 ORDER BY position, id LIMIT 1",
+            params![id],
+            |r| r.get(0),
+        )
+        .optional()?;
+    match first {
+        Some(photo_id) => remove_photo(&conn, id, photo_id),
+        None => Ok(Some(String::new())),
+    }
+}
+
+/// The card's photos in order, or `None` if there is no such card.
+pub fn list_photos(conn: &Arc<Mutex<Connection>>, card_id: i64) -> Result<Option<Vec<CardPhoto>>> {
+    let conn = conn.lock().unwrap();
+    if !card_exists(&conn, card_id)? {
+        return Ok(None);
+    }
+    Ok(Some(fetch_photos(&conn, card_id)?))
+}
+
+/// Photos a card can hold: both sides of the paper card, a logo, a
+/// headshot, and a few spares.
+pub const MAX_PHOTOS: usize = 10;
+
+/// Adds a photo after the card's others. `None` if there is no such card;
+/// fails with `PhotosFull` if it has `MAX_PHOTOS` already.
+pub fn add_photo(
+    conn: &Arc<Mutex<Connection>>,
+    card_id: i64,
+    role: PhotoRole,
+    path: &str,
+) -> Result<Option<CardPhoto>> {
+    let conn = conn.lock().unwrap();
+    if !card_exists(&conn, card_id)? {
+        return Ok(None);
+    }
+    let count: usize =
+        conn.query_row("SELECT COUNT(*) FROM card_photos WHERE card_id = ?1", params![card_id], |r| r.get(0))?;
+    if count >= MAX_PHOTOS {
+        return Err(PhotosFull.into());
+    }
+    conn.execute(
+        "INSERT INTO card_photos (card_id, role, position, path)
+         VALUES (?1, ?2, (SELECT COUNT(*) FROM card_photos WHERE card_id = ?1), ?3)",
+        params![card_id, role.as_str(), path],
+    )?;
+    let photo_id = conn.last_insert_rowid();
+    retain_file(&conn, path)?;
+    touch_card(&conn, card_id)?;
+    Ok(fetch_photos(&conn, card_id)?.into_iter().find(|p| p.id == photo_id))
+}
+
+/// Changes a photo's role and moves it to `position`, shifting the others.
+/// `None` if the card has no such photo.
+pub fn update_photo(
+    conn: &Arc<Mutex<Connection>>,
+    card_id: i64,
+    photo_id: i64,
+    update: &PhotoUpdate,
+) -> Result<Option<CardPhoto>> {
+    let conn = conn.lock().unwrap();
+    let mut ids: Vec<i64> = fetch_photos(&conn, card_id)?.iter().map(|p| p.id).collect();
+    let Some(at) = ids.iter().position(|&id| id == photo_id) else {
+        return Ok(None);
+    };
+    if let Some(role) = update.role {
+        conn.execute("UPDATE card_photos SET role = ?1 WHERE id = ?2", params![role.as_str(), photo_id])?;
+    }
+    if let Some(position) = update.position {
+        ids.remove(at);
+        ids.insert((position.max(0) as usize).min(ids.len()), photo_id);
+        renumber_photos(&conn, &ids)?;
+    }
+    touch_card(&conn, card_id)?;
+    Ok(fetch_photos(&conn, card_id)?.into_iter().find(|p| p.id == photo_id))
+}
+
+/// Removes a photo, closing the gap it leaves. Returns its path, empty if
+/// its file is still used, or `None` if the card has no such photo.
+pub fn delete_photo(conn: &Arc<Mutex<Connection>>, card_id: i64, photo_id: i64) -> Result<Option<String>> {
+    remove_photo(&conn.lock().unwrap(), card_id, photo_id)
+}
+
+fn remove_photo(conn: &Connection, card_id: i64, photo_id: i64) -> Result<Option<String>> {
+    let path: Option<String> = conn
+        .query_row(
+            "DELETE FROM card_photos WHERE id = ?1 AND card_id = ?2 RETURNING path",
+            params![photo_id, card_id],
+            |r| r.get(0),
+        )
+        .optional()?;
+    let Some(path) = path else {
+        return Ok(None);
+    };
+    let ids: Vec<i64> = fetch_photos(conn, card_id)?.iter().map(|p| p.id).collect();
+    renumber_photos(conn, &ids)?;
+    touch_card(conn, card_id)?;
+    Ok(Some(release_file(conn, &path)?.unwrap_or_default()))
+}
+
+fn fetch_photos(conn: &Connection, card_id: i64) -> Result<Vec<CardPhoto>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, role, position, path FROM card_photos WHERE card_id = ?1 ORDER BY position, id",
+    )?;
+    let photos = stmt
+        .query_map(params![card_id], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get(2)?, row.get::<_, String>(3)?))
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(photos
+        .into_iter()
+        .map(|(id, role, position, path)| CardPhoto {
+            id,
+            role: role.parse().unwrap_or(PhotoRole::Front),
+            position,
+            url: base_path::url(&format!("/{path}")),
+        })
+        .collect())
+}
+
+/// Paths of the card's photo and attachment files.
+fn file_paths(conn: &Connection, card_id: i64) -> Result<Vec<String>> {
+    Ok(conn
+        .prepare(
+            "SELECT path FROM card_photos WHERE card_id = ?1
+             UNION ALL SELECT path FROM card_attachments WHERE card_id = ?1",
+        )?
+        .query_map(params![card_id], |r| r.get(0))?
+        .collect::<std::result::Result<_, _>>()?)
+}
+
+/// Counts a new reference to the stored file at `path`.
+fn retain_file(conn: &Connection, path: &str) -> Result<()> {
+    conn.execute(
+        "INSERT INTO upload_refs (path, refs) VALUES (?1, 1)
+         ON CONFLICT(path) DO UPDATE SET refs = refs + 1",
+        params![path],
+    )?;
+    Ok(())
+}
+
+/// Drops a reference to the stored file at `path`. Returns the path once
+/// nothing uses the file any more, for the caller to delete it.
+fn release_file(conn: &Connection, path: &str) -> Result<Option<String>> {
+    let refs: Option<i64> = conn
+        .query_row(
+            "UPDATE upload_refs SET refs = refs - 1 WHERE path = ?1 RETURNING refs",
+            params![path],
+            |r| r.get(0),
+        )
+        .optional()?;
+    match refs {
+        Some(refs) if refs > 0 => Ok(None),
+        _ => {
+            conn.execute("DELETE FROM upload_refs WHERE path = ?1", params![path])?;
+            Ok(Some(path.to_string()))
+        }
+    }
+}
+
+/// Drops a reference to each of `paths`, returning those of files nothing
+/// uses any more.
+fn release_files(conn: &Connection, paths: &[String]) -> Result<Vec<String>> {
+    let mut unused = Vec::new();
+    for path in paths {
+        unused.extend(release_file(conn, path)?);
+    }
+    Ok(unused)
+}
+
+/// Calls `remove` for each of `paths` no photo, attachment, or company
+/// logo uses, holding the connection meanwhile so no row can take the file
+/// before it is gone.
+pub fn remove_unused_files(
+    conn: &Arc<Mutex<Connection>>,
+    paths: &[String],
+    mut remove: impl FnMut(&str),
+) -> Result<()> {
+    let conn = conn.lock().unwrap();
+    for path in paths.iter().filter(|path| !path.is_empty()) {
+        let used: bool =
+            conn.query_row("SELECT EXISTS (SELECT 1 FROM upload_refs WHERE path = ?1)", params![path], |r| {
+                r.get(0)
+            })?;
+        if !used {
+            remove(path);
+        }
+    }
+    Ok(())
+}
+
+/// Numbers the photos `ids` 0, 1, 2, ... in the order given.
+fn renumber_photos(conn: &Connection, ids: &[i64]) -> Result<()> {
+    let mut stmt = conn.prepare("UPDATE card_photos SET position = ?1 WHERE id = ?2")?;
+    for (position, id) in ids.iter().enumerate() {
+        stmt.execute(params![position as i64, id])?;
+    }
+    Ok(())
+}
+
+/// Bumps the card's version after a change to its photos and records it.
+fn touch_card(conn: &Connection, id: i64) -> Result<()> {
+    conn.execute(
+        "UPDATE cards SET version=version+1, updated_at=CURRENT_TIMESTAMP WHERE id=?1",
+        params![id],
+    )?;
+    record_revision(conn, id)
+}
+
+/// Unarchived cards with an address within `radius_km` of the point,
+/// nearest first, each with its nearest address.
+pub fn nearby_cards(
+    conn: &Arc<Mutex<Connection>>,
+    lat: f64,
+    lng: f64,
+    radius_km: f64,
+    limit: i64,
+) -> Result<Vec<NearbyCard>> {
+    let conn = conn.lock().unwrap();
+    // A degree of latitude is about 111 km everywhere, so a latitude band
+    // narrows the scan with the index; longitude degrees shrink toward the
+    // poles and are left to the distance itself.
+    let band = radius_km / 111.0;
     let mut stmt = conn.prepare(
-        "SELECT id, label, street, city, country, postal FROM card_addresses WHERE card_id = ?1 ORDER BY id",
+        "SELECT a.card_id, a.id, MIN(haversine_km(?1, ?2, a.latitude, a.longitude)) AS distance
+         FROM card_addresses a JOIN cards c ON c.id = a.card_id
+         WHERE a.latitude BETWEEN ?1 - ?5 AND ?1 + ?5 AND a.longitude IS NOT NULL
+           AND c.archived_at IS NULL
+         GROUP BY a.card_id
+         HAVING distance <= ?3
+         ORDER BY distance, a.card_id
+         LIMIT ?4",
+    )?;
+    let hits = stmt
+        .query_map(params![lat, lng, radius_km, limit, band], |r| {
+            Ok((r.get::<_, i64>(0)?, r.get::<_, i64>(1)?, r.get::<_, f64>(2)?))
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    let mut nearby = Vec::with_capacity(hits.len());
+    for (card_id, address_id, distance_km) in hits {
+        let Some(card) = fetch_card_by_id(&conn, card_id, CardRelations::ALL)? else {
+            continue;
+        };
+        let Some(address) = card.addresses.iter().find(|a| a.id == address_id).cloned() else {
+            continue;
+        };
+        // To the metre.
+        let distance_km = (distance_km * 1000.0).round() / 1000.0;
+        nearby.push(NearbyCard { distance_km, address, card });
+    }
+    Ok(nearby)
+}
+
+/// Addresses without coordinates, as `(id, one-line address)`, leaving out
+/// empty ones and ones the geocoder is known not to find.
+pub fn addresses_to_geocode(conn: &Arc<Mutex<Connection>>) -> Result<Vec<(i64, String)>> {
+    let conn = conn.lock().unwrap();
+    let mut stmt = conn.prepare(
+        "SELECT id, street, city, postal, country FROM card_addresses WHERE latitude IS NULL ORDER BY id",
+    )?;
+    let rows = stmt
+        .query_map([], |r| {
+            let part = |i| r.get::<_, String>(i);
+            Ok((r.get(0)?, geocode::query(&part(1)?, &part(2)?, &part(3)?, &part(4)?)))
+        })?
+        .collect::<std::result::Result<Vec<(i64, String)>, _>>()?;
+    let mut miss = conn.prepare("SELECT 1 FROM geocode_cache WHERE query = ?1 AND latitude IS NULL")?;
+    let mut pending = Vec::new();
+    for (id, query) in rows {
+        if !query.is_empty() && !miss.exists(params![query])? {
+            pending.push((id, query));
+        }
+    }
+    Ok(pending)
+}
+
+/// The cached answer for an address: `None` if it was never looked up,
+/// `Some(None)` if the geocoder did not find it.
+pub fn cached_geocode(conn: &Arc<Mutex<Connection>>, query: &str) -> Result<Option<Option<(f64, f64)>>> {
+    let conn = conn.lock().unwrap();
+    let row: Option<(Option<f64>, Option<f64>)> = conn
+        .query_row(
+            "SELECT latitude, longitude FROM geocode_cache WHERE query = ?1",
+            params![query],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )
+        .optional()?;
+    Ok(row.map(|(lat, lng)| lat.zip(lng)))
+}
+
+pub fn cache_geocode(conn: &Arc<Mutex<Connection>>, query: &str, coordinates: Option<(f64, f64)>) -> Result<()> {
+    let conn = conn.lock().unwrap();
+    let (lat, lng) = coordinates.unzip();
+    conn.execute(
+        "INSERT OR REPLACE INTO geocode_cache (query, latitude, longitude) VALUES (?1, ?2, ?3)",
+        params![query, lat, lng],
     )?;
-    card.addresses = stmt
-        .query_map(params![id], |row| {
-            Ok(Address {
-                id: row.get(0)?,
-                label: row.get(1)?,
-                street: row.get(2)?,
-                city: row.get(3)?,
-                country: row.get(4)?,
-                postal: row.get(5)?,
+    Ok(())
+}
+
+/// Stores an address's coordinates. Derived data: the card's version is
+/// left alone.
+pub fn set_address_coordinates(conn: &Arc<Mutex<Connection>>, id: i64, (lat, lng): (f64, f64)) -> Result<()> {
+    let conn = conn.lock().unwrap();
+    conn.execute(
+        "UPDATE card_addresses SET latitude = ?1, longitude = ?2 WHERE id = ?3",
+        params![lat, lng, id],
+    )?;
+    Ok(())
+}
+
+/// Forgets the addresses the geocoder did not find, so they are tried
+/// again. Returns how many there were.
+pub fn forget_geocode_misses(conn: &Arc<Mutex<Connection>>) -> Result<usize> {
+    let conn = conn.lock().unwrap();
+    Ok(conn.execute("DELETE FROM geocode_cache WHERE latitude IS NULL", [])?)
+}
+
+/// Records `path` as the logo of `company`, replacing any earlier one.
+/// Returns the replaced logo's path, to delete, if nothing else uses it,
+/// and the ids of the cards with that company.
+pub fn set_company_logo(
+    conn: &Arc<Mutex<Connection>>,
+    company: &str,
+    path: &str,
+    source_url: &str,
+) -> Result<(Option<String>, Vec<i64>)> {
+    let conn = conn.lock().unwrap();
+    let old_path: Option<String> = conn
+        .query_row(
+            "SELECT logo_path FROM company_logos WHERE company = ?1",
+            params![company],
+            |r| r.get(0),
+        )
+        .optional()?;
+    conn.execute(
+        "INSERT INTO company_logos (company, logo_path, source_url) VALUES (?1, ?2, ?3)
+         ON CONFLICT(company) DO UPDATE SET logo_path = excluded.logo_path,
+             source_url = excluded.source_url, fetched_at = CURRENT_TIMESTAMP",
+        params![company, path, source_url],
+    )?;
+    retain_file(&conn, path)?;
+    let old_path = match old_path {
+        Some(old_path) => release_file(&conn, &old_path)?,
+        None => None,
+    };
+    let mut stmt = conn.prepare("SELECT id FROM cards WHERE company = ?1 COLLATE NOCASE ORDER BY id")?;
+    let ids = stmt
+        .query_map(params![company], |r| r.get(0))?
+        .collect::<std::result::Result<Vec<i64>, _>>()?;
+    Ok((old_path, ids))
+}
+
+/// Revision kinds: a user edit (including creation), a deletion, a write
+/// made by undo, and the first snapshot of a seeded card or one that
+/// predates history. Only edits and deletions can be undone.
+const REVISION_EDIT: &str = "edit";
+const REVISION_DELETE: &str = "delete";
+const REVISION_UNDO: &str = "undo";
+const REVISION_BASELINE: &str = "baseline";
+
+/// A recorded card version as stored.
+pub struct StoredRevision {
+    pub version: i64,
+    pub kind: String,
+    pub created_at: String,
+    pub card: Card,
+}
+
+/// Revisions written by one operation share a batch, so undo can reverse
+/// the whole operation.
+fn next_batch(conn: &Connection) -> Result<i64> {
+    Ok(conn.query_row("SELECT COALESCE(MAX(batch), 0) + 1 FROM card_revisions", [], |r| {
+        r.get(0)
+    })?)
+}
+
+fn write_revision(conn: &Connection, id: i64, batch: i64, kind: &str) -> Result<()> {
+    let Some(card) = fetch_card_by_id(conn, id, CardRelations::ALL)? else {
+        return Ok(());
+    };
+    conn.execute(
+        "INSERT OR REPLACE INTO card_revisions (card_id, version, snapshot, batch, kind)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![id, card.version, serde_json::to_string(&card)?, batch, kind],
+    )?;
+    Ok(())
+}
+
+/// Records that `card` was deleted, as a version after its last one that
+/// keeps its final contents.
+fn record_deletion(conn: &Connection, card: &Card, batch: i64, kind: &str) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO card_revisions (card_id, version, snapshot, batch, kind)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![card.id, card.version + 1, serde_json::to_string(card)?, batch, kind],
+    )?;
+    Ok(())
+}
+
+/// Saves the card as it now stands under its current version. Called after
+/// every write that bumps the version.
+fn record_revision(conn: &Connection, id: i64) -> Result<()> {
+    record_revisions(conn, &[id])
+}
+
+fn record_revisions(conn: &Connection, ids: &[i64]) -> Result<()> {
+    let batch = next_batch(conn)?;
+    ids.iter()
+        .try_for_each(|&id| write_revision(conn, id, batch, REVISION_EDIT))
+}
+
+/// Every recorded revision of the card, oldest first. Empty if the card
+/// never existed.
+pub fn card_revisions(conn: &Arc<Mutex<Connection>>, id: i64) -> Result<Vec<StoredRevision>> {
+    let conn = conn.lock().unwrap();
+    let rows = conn
+        .prepare(
+            "SELECT version, kind, created_at, snapshot FROM card_revisions
+             WHERE card_id = ?1 ORDER BY version",
+        )?
+        .query_map(params![id], |r| {
+            Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get::<_, String>(3)?))
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    rows.into_iter()
+        .map(|(version, kind, created_at, snapshot)| {
+            Ok(StoredRevision {
+                version,
+                kind,
+                created_at,
+                card: serde_json::from_str(&snapshot)?,
+            })
+        })
+        .collect()
+}
+
+/// What `undo_last` reversed.
+pub struct UndoOutcome {
+    /// Cards put back to their previous contents
+    pub updated: Vec<i64>,
+    /// Created cards removed again, with their photo paths
+    pub deleted: Vec<(i64, Vec<String>)>,
+}
+
+/// Reverses the most recent edit or deletion that was not undone yet and
+/// was made within the last `window_secs` seconds. Every card the operation
+/// touched goes back to its previous revision, and a card it created is
+/// deleted. Undoing again reaches further back. Fails with `UndoDeletion`
+/// if the operation deleted a card, as a delete or merge does. Returns
+/// `None` if there is nothing to undo.
+pub fn undo_last(conn: &Arc<Mutex<Connection>>, window_secs: i64) -> Result<Option<UndoOutcome>> {
+    let mut conn = conn.lock().unwrap();
+    let tx = conn.transaction()?;
+    let batch: Option<i64> = tx
+        .query_row(
+            "SELECT batch FROM card_revisions
+             WHERE kind IN (?1, ?2) AND undone = 0
+               AND created_at >= datetime('now', ?3)
+             ORDER BY batch DESC LIMIT 1",
+            params![REVISION_EDIT, REVISION_DELETE, format!("-{window_secs} seconds")],
+            |r| r.get(0),
+        )
+        .optional()?;
+    let Some(batch) = batch else {
+        return Ok(None);
+    };
+    let targets = tx
+        .prepare("SELECT card_id, version FROM card_revisions WHERE batch = ?1 ORDER BY card_id")?
+        .query_map(params![batch], |r| Ok((r.get::<_, i64>(0)?, r.get::<_, i64>(1)?)))?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    let deleted = tx
+        .prepare("SELECT card_id FROM card_revisions WHERE batch = ?1 AND kind = ?2 ORDER BY card_id")?
+        .query_map(params![batch, REVISION_DELETE], |r| r.get::<_, i64>(0))?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    if !deleted.is_empty() {
+        return Err(UndoDeletion { ids: deleted }.into());
+    }
+
+    let undo_batch = next_batch(&tx)?;
+    let mut outcome = UndoOutcome {
+        updated: Vec::new(),
+        deleted: Vec::new(),
+    };
+    for (id, version) in targets {
+        let previous: Option<String> = tx
+            .query_row(
+                "SELECT snapshot FROM card_revisions WHERE card_id = ?1 AND version = ?2",
+                params![id, version - 1],
+                |r| r.get(0),
+            )
+            .optional()?;
+        let previous: Option<Card> = previous.map(|s| serde_json::from_str(&s)).transpose()?;
+        let current = fetch_card_by_id(&tx, id, CardRelations::ALL)?;
+        match (previous, current) {
+            // The operation created the card
+            (None, Some(card)) if version == 1 => {
+                let photo_paths = file_paths(&tx, id)?;
+                tx.execute("DELETE FROM cards WHERE id = ?1", params![id])?;
+                record_deletion(&tx, &card, undo_batch, REVISION_UNDO)?;
+                outcome.deleted.push((id, release_files(&tx, &photo_paths)?));
+            }
+            (Some(previous), Some(_)) => {
+                restore_card(&tx, &previous)?;
+                write_revision(&tx, id, undo_batch, REVISION_UNDO)?;
+                outcome.updated.push(id);
+            }
+            // History does not reach back far enough, or the card is gone
+            // for a reason undo cannot see.
+            _ => {}
+        }
+    }
+    tx.execute("UPDATE card_revisions SET undone = 1 WHERE batch = ?1", params![batch])?;
+    tx.commit()?;
+    Ok(Some(outcome))
+}
+
+/// Writes a snapshot's contents, favorite flag, and archive state back
+/// under a new version. The photo is left as it is.
+fn restore_card(conn: &Connection, card: &Card) -> Result<()> {
+    conn.execute(
+        "UPDATE cards SET version=version+1, updated_at=CURRENT_TIMESTAMP WHERE id = ?1",
+        params![card.id],
+    )?;
+    conn.execute(
+        "UPDATE cards SET name=?1, title=?2, company=?3, website=?4, notes=?5, name_sound_key=?6,
+                          favorite=?7, archived_at=?8, custom_fields=?10,
+                          name_prefix=?11, given_name=?12, middle_name=?13, family_name=?14,
+                          name_suffix=?15, name_phonetic=?16, birthday=?17, anniversary=?18,
+                          department=?19, role_seniority=?20, assistant_name=?21, assistant_phone=?22,
+                          reports_to=?23, timezone=?24
+         WHERE id=?9",
+        params![
+            card.name,
+            card.title,
+            card.company,
+            card.website,
+            card.notes,
+            sound_key(&card.name, &card.name_phonetic),
+            card.favorite,
+            card.archived_at,
+            card.id,
+            serde_json::to_string(&card.custom)?,
+            card.name_prefix,
+            card.given_name,
+            card.middle_name,
+            card.family_name,
+            card.name_suffix,
+            card.name_phonetic,
+            card.birthday,
+            card.anniversary,
+            card.department,
+            card.role_seniority,
+            card.assistant_name,
+            card.assistant_phone,
+            card.reports_to,
+            card.timezone
+        ],
+    )?;
+    let input = CardInput::from(card.clone());
+    replace_phones(conn, card.id, &input.phones)?;
+    replace_emails(conn, card.id, &input.emails)?;
+    replace_addresses(conn, card.id, &input.addresses)?;
+    replace_socials(conn, card.id, &input.socials)?;
+    replace_ims(conn, card.id, &input.ims)?;
+    upsert_tags_and_link(conn, card.id, &input.tags)?;
+    Ok(())
+}
+
+/// The card as it was at `version`, if that revision was recorded.
+pub fn card_revision(conn: &Arc<Mutex<Connection>>, id: i64, version: i64) -> Result<Option<Card>> {
+    let conn = conn.lock().unwrap();
+    let snapshot: Option<String> = conn
+        .query_row(
+            "SELECT snapshot FROM card_revisions WHERE card_id = ?1 AND version = ?2",
+            params![id, version],
+            |r| r.get(0),
+        )
+        .optional()?;
+    Ok(snapshot.map(|s| serde_json::from_str(&s)).transpose()?)
+}
+
+/// Columns for a `TagCount` read through `tag_from_row`, over
+/// `tags t LEFT JOIN card_tags ct` grouped by tag.
+const TAG_COLUMNS: &str = "t.name, COUNT(ct.card_id) AS cnt, t.color, t.description,
+     (SELECT json_group_array(alias)
+      FROM (SELECT alias FROM tag_aliases WHERE tag_id = t.id ORDER BY alias))";
+
+fn tag_from_row(row: &rusqlite::Row) -> rusqlite::Result<TagCount> {
+    let aliases: String = row.get(4)?;
+    Ok(TagCount {
+        name: row.get(0)?,
+        count: row.get(1)?,
+        color: row.get(2)?,
+        description: row.get(3)?,
+        aliases: serde_json::from_str(&aliases).unwrap_or_default(),
+    })
+}
+
+fn read_tag(conn: &Connection, id: i64) -> Result<TagCount> {
+    Ok(conn.query_row(
+        &format!(
+            "SELECT {TAG_COLUMNS} FROM tags t
+             LEFT JOIN card_tags ct ON ct.tag_id = t.id
+             WHERE t.id = ?1
+             GROUP BY t.id"
+        ),
+        params![id],
+        tag_from_row,
+    )?)
+}
+
+pub fn list_tags(conn: &Arc<Mutex<Connection>>) -> Result<Vec<TagCount>> {
+    let conn = conn.lock().unwrap();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {TAG_COLUMNS}
+         FROM tags t
+         LEFT JOIN card_tags ct ON ct.tag_id = t.id
+         GROUP BY t.id, t.name
+         ORDER BY t.name"
+    ))?;
+    let tags: Vec<TagCount> = stmt
+        .query_map([], tag_from_row)?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(tags)
+}
+
+pub fn stats(conn: &Arc<Mutex<Connection>>) -> Result<VaultStats> {
+    let conn = conn.lock().unwrap();
+    let (total_cards, cards_with_photo, companies, added_this_week, added_this_month) = conn
+        .query_row(
+            "SELECT COUNT(*),
+                    COUNT(CASE WHEN EXISTS (SELECT 1 FROM card_photos p WHERE p.card_id = cards.id) THEN 1 END),
+                    COUNT(DISTINCT CASE WHEN company <> '' THEN fold(company) END),
+                    COUNT(CASE WHEN created_at >= date('now', '-6 days', 'weekday 1') THEN 1 END),
+                    COUNT(CASE WHEN created_at >= date('now', 'start of month') THEN 1 END)
+             FROM cards",
+            [],
+            |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?, r.get(4)?)),
+        )?;
+    // Pages in use by the main database file; the WAL is not counted.
+    let db_size_bytes = conn.query_row(
+        "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+        [],
+        |r| r.get(0),
+    )?;
+    let tags = conn
+        .prepare(&format!(
+            "SELECT {TAG_COLUMNS}
+             FROM tags t
+             LEFT JOIN card_tags ct ON ct.tag_id = t.id
+             GROUP BY t.id, t.name
+             ORDER BY cnt DESC, t.name"
+        ))?
+        .query_map([], tag_from_row)?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(VaultStats {
+        total_cards,
+        cards_with_photo,
+        companies,
+        added_this_week,
+        added_this_month,
+        db_size_bytes,
+        tags,
+    })
+}
+
+fn tag_id(conn: &Connection, name: &str) -> Result<Option<i64>> {
+    Ok(conn
+        .query_row("SELECT id FROM tags WHERE name = ?1", params![name], |r| r.get(0))
+        .optional()?)
+}
+
+/// The tag a normalized name refers to, either directly or as an alias.
+fn resolve_tag(conn: &Connection, name: &str) -> Result<Option<i64>> {
+    Ok(conn
+        .query_row(
+            "SELECT id FROM tags WHERE name = ?1
+             UNION ALL
+             SELECT tag_id FROM tag_aliases WHERE alias = ?1
+             LIMIT 1",
+            params![name],
+            |r| r.get(0),
+        )
+        .optional()?)
+}
+
+/// Resolves a normalized name to its tag, creating the tag if needed.
+fn ensure_tag(conn: &Connection, name: &str) -> Result<i64> {
+    if let Some(id) = resolve_tag(conn, name)? {
+        return Ok(id);
+    }
+    conn.execute("INSERT INTO tags (name) VALUES (?1)", params![name])?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Moves the cards and aliases of tag `from` onto `into`, then deletes it.
+fn move_tag(conn: &Connection, from: i64, into: i64) -> Result<()> {
+    conn.execute(
+        "INSERT OR IGNORE INTO card_tags (card_id, tag_id)
+         SELECT card_id, ?1 FROM card_tags WHERE tag_id = ?2",
+        params![into, from],
+    )?;
+    conn.execute(
+        "UPDATE tag_aliases SET tag_id = ?1 WHERE tag_id = ?2",
+        params![into, from],
+    )?;
+    conn.execute("DELETE FROM tags WHERE id = ?1", params![from])?;
+    Ok(())
+}
+
+/// Bumps the version of every card carrying the tag, since its tag list is
+/// about to change, and returns their ids.
+fn touch_tagged_cards(conn: &Connection, tag_id: i64) -> Result<Vec<i64>> {
+    let ids = conn
+        .prepare("SELECT card_id FROM card_tags WHERE tag_id = ?1 ORDER BY card_id")?
+        .query_map(params![tag_id], |r| r.get(0))?
+        .collect::<std::result::Result<Vec<i64>, _>>()?;
+    conn.execute(
+        "UPDATE cards SET version=version+1, updated_at=CURRENT_TIMESTAMP
+         WHERE id IN (SELECT card_id FROM card_tags WHERE tag_id = ?1)",
+        params![tag_id],
+    )?;
+    Ok(ids)
+}
+
+/// Applies the fields present in `update` to a tag; a new name applies to
+/// every card carrying it. Returns the updated tag and the cards whose tag
+/// list changed, or `None` if there is no such tag. Fails with `TagExists`
+/// if the new name is already another tag.
+pub fn update_tag(
+    conn: &Arc<Mutex<Connection>>,
+    name: &str,
+    update: &TagUpdate,
+) -> Result<Option<(TagCount, Vec<i64>)>> {
+    let name = normalize_tag(name);
+    let mut conn = conn.lock().unwrap();
+    let tx = conn.transaction()?;
+    let Some(id) = tag_id(&tx, &name)? else {
+        return Ok(None);
+    };
+    let mut card_ids = Vec::new();
+    let new_name = update.name.as_deref().map(normalize_tag);
+    if let Some(new_name) = new_name.filter(|n| *n != name) {
+        if resolve_tag(&tx, &new_name)?.is_some_and(|other| other != id) {
+            return Err(TagExists { name: new_name }.into());
+        }
+        // The tag may be renamed to one of its own aliases.
+        tx.execute("DELETE FROM tag_aliases WHERE alias = ?1", params![new_name])?;
+        tx.execute("UPDATE tags SET name = ?1 WHERE id = ?2", params![new_name, id])?;
+        card_ids = touch_tagged_cards(&tx, id)?;
+    }
+    if let Some(color) = &update.color {
+        tx.execute("UPDATE tags SET color = ?1 WHERE id = ?2", params![color, id])?;
+    }
+    if let Some(description) = &update.description {
+        tx.execute(
+            "UPDATE tags SET description = ?1 WHERE id = ?2",
+            params![description, id],
+        )?;
+    }
+    record_revisions(&tx, &card_ids)?;
+    let tag = read_tag(&tx, id)?;
+    tx.commit()?;
+    Ok(Some((tag, card_ids)))
+}
+
+/// Removes a tag from every card. Returns the affected card ids, or `None`
+/// if there is no such tag.
+pub fn delete_tag(conn: &Arc<Mutex<Connection>>, name: &str) -> Result<Option<Vec<i64>>> {
+    let mut conn = conn.lock().unwrap();
+    let tx = conn.transaction()?;
+    let Some(id) = tag_id(&tx, &normalize_tag(name))? else {
+        return Ok(None);
+    };
+    let card_ids = touch_tagged_cards(&tx, id)?;
+    // card_tags and tag_aliases rows go with it (ON DELETE CASCADE).
+    tx.execute("DELETE FROM tags WHERE id = ?1", params![id])?;
+    record_revisions(&tx, &card_ids)?;
+    tx.commit()?;
+    Ok(Some(card_ids))
+}
+
+/// Moves every card tagged with one of `from` onto `into` (created if
+/// needed) and deletes the `from` tags, keeping their names as aliases of
+/// `into` so later writes of them land on it too. Returns the merged tag
+/// and the affected card ids, or `None` if one of `from` does not exist.
+pub fn merge_tags(
+    conn: &Arc<Mutex<Connection>>,
+    from: &[String],
+    into: &str,
+) -> Result<Option<(TagCount, Vec<i64>)>> {
+    let mut conn = conn.lock().unwrap();
+    let tx = conn.transaction()?;
+    let target = ensure_tag(&tx, &normalize_tag(into))?;
+    let mut card_ids = Vec::new();
+    for name in from.iter().map(|n| normalize_tag(n)) {
+        let Some(source) = tag_id(&tx, &name)? else {
+            return Ok(None);
+        };
+        if source == target {
+            continue;
+        }
+        card_ids.extend(touch_tagged_cards(&tx, source)?);
+        // A fresh target keeps the look of the first tag merged into it.
+        tx.execute(
+            "UPDATE tags SET
+               color = CASE WHEN color = '' THEN (SELECT color FROM tags WHERE id = ?2) ELSE color END,
+               description = CASE WHEN description = ''
+                                  THEN (SELECT description FROM tags WHERE id = ?2)
+                                  ELSE description END
+             WHERE id = ?1",
+            params![target, source],
+        )?;
+        move_tag(&tx, source, target)?;
+        tx.execute(
+            "INSERT OR REPLACE INTO tag_aliases (alias, tag_id) VALUES (?1, ?2)",
+            params![name, target],
+        )?;
+    }
+    card_ids.sort_unstable();
+    card_ids.dedup();
+    record_revisions(&tx, &card_ids)?;
+    let tag = read_tag(&tx, target)?;
+    tx.commit()?;
+    Ok(Some((tag, card_ids)))
+}
+
+/// Adds and removes tags on many cards in one transaction. Returns the
+/// cards whose tags changed; their versions are bumped. Fails with
+/// `CardsNotFound`, changing nothing, if any id is unknown.
+pub fn bulk_tag_cards(
+    conn: &Arc<Mutex<Connection>>,
+    ids: &[i64],
+    add: &[String],
+    remove: &[String],
+) -> Result<Vec<i64>> {
+    let mut conn = conn.lock().unwrap();
+    let tx = conn.transaction()?;
+    let mut missing = Vec::new();
+    for &id in ids {
+        let exists: bool =
+            tx.query_row("SELECT COUNT(*) > 0 FROM cards WHERE id = ?1", params![id], |r| {
+                r.get(0)
+            })?;
+        if !exists {
+            missing.push(id);
+        }
+    }
+    if !missing.is_empty() {
+        return Err(CardsNotFound { ids: missing }.into());
+    }
+
+    let add = add
+        .iter()
+        .map(|t| normalize_tag(t))
+        .filter(|t| !t.is_empty())
+        .map(|t| ensure_tag(&tx, &t))
+        .collect::<Result<Vec<i64>>>()?;
+    let mut remove_ids = Vec::new();
+    for tag in remove.iter().map(|t| normalize_tag(t)) {
+        remove_ids.extend(resolve_tag(&tx, &tag)?);
+    }
+
+    let mut updated = Vec::new();
+    for &card_id in ids {
+        let mut changed = 0;
+        for &tag_id in &add {
+            changed += tx.execute(
+                "INSERT OR IGNORE INTO card_tags (card_id, tag_id) VALUES (?1, ?2)",
+                params![card_id, tag_id],
+            )?;
+        }
+        for &tag_id in &remove_ids {
+            changed += tx.execute(
+                "DELETE FROM card_tags WHERE card_id = ?1 AND tag_id = ?2",
+                params![card_id, tag_id],
+            )?;
+        }
+        if changed > 0 && !updated.contains(&card_id) {
+            tx.execute(
+                "UPDATE cards SET version=version+1, updated_at=CURRENT_TIMESTAMP WHERE id = ?1",
+                params![card_id],
+            )?;
+            updated.push(card_id);
+        }
+    }
+    record_revisions(&tx, &updated)?;
+    tx.commit()?;
+    Ok(updated)
+}
+
+/// Makes `alias` another spelling of the tag, so cards saved with it get
+/// the tag instead. Returns the updated tag, or `None` if there is no such
+/// tag. Fails with `TagExists` if `alias` is already another tag or alias.
+pub fn add_tag_alias(
+    conn: &Arc<Mutex<Connection>>,
+    name: &str,
+    alias: &str,
+) -> Result<Option<TagCount>> {
+    let alias = normalize_tag(alias);
+    let conn = conn.lock().unwrap();
+    let Some(id) = tag_id(&conn, &normalize_tag(name))? else {
+        return Ok(None);
+    };
+    match resolve_tag(&conn, &alias)? {
+        Some(other) if other != id => return Err(TagExists { name: alias }.into()),
+        Some(_) => {}
+        None => {
+            conn.execute(
+                "INSERT INTO tag_aliases (alias, tag_id) VALUES (?1, ?2)",
+                params![alias, id],
+            )?;
+        }
+    }
+    Ok(Some(read_tag(&conn, id)?))
+}
+
+/// Returns false if the tag has no such alias.
+pub fn remove_tag_alias(conn: &Arc<Mutex<Connection>>, name: &str, alias: &str) -> Result<bool> {
+    let conn = conn.lock().unwrap();
+    let removed = conn.execute(
+        "DELETE FROM tag_aliases
+         WHERE alias = ?1 AND tag_id = (SELECT id FROM tags WHERE name = ?2)",
+        params![normalize_tag(alias), normalize_tag(name)],
+    )?;
+    Ok(removed > 0)
+}
+
+/// Distinct values of `field` starting with `prefix` (case-insensitive), most
+/// used first. Archived cards do not count.
+pub fn suggest(
+    conn: &Arc<Mutex<Connection>>,
+    field: SuggestField,
+    prefix: &str,
+    limit: i64,
+) -> Result<Vec<Suggestion>> {
+    let conn = conn.lock().unwrap();
+    let (value, from) = match field {
+        SuggestField::Company => ("c.company", "cards c"),
+        SuggestField::Title => ("c.title", "cards c"),
+        SuggestField::City => ("a.city", "card_addresses a JOIN cards c ON c.id = a.card_id"),
+        SuggestField::Country => ("a.country", "card_addresses a JOIN cards c ON c.id = a.card_id"),
+        SuggestField::Tag => (
+            "t.name",
+            "tags t JOIN card_tags ct ON ct.tag_id = t.id JOIN cards c ON c.id = ct.card_id",
+        ),
+    };
+    let pattern = format!(
+        "{}%",
+        fold(prefix.trim()).replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+    );
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {value}, COUNT(DISTINCT c.id) AS cnt
+         FROM {from}
+         WHERE {value} <> '' AND fold({value}) LIKE ?1 ESCAPE '\\' AND c.archived_at IS NULL
+         GROUP BY {value}
+         ORDER BY cnt DESC, {value} COLLATE FOLD
+         LIMIT ?2"
+    ))?;
+    let suggestions = stmt
+        .query_map(params![pattern, limit], |row| {
+            Ok(Suggestion {
+                value: row.get(0)?,
+                count: row.get(1)?,
             })
         })?
         .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(suggestions)
+}
+
+// ────────────────────────────────────────────────────────────────────────────
+// Custom field schema
+// ────────────────────────────────────────────────────────────────────────────
+
+fn row_to_field_definition(row: &rusqlite::Row) -> rusqlite::Result<FieldDefinition> {
+    let field_type: String = row.get(2)?;
+    let options: String = row.get(4)?;
+    Ok(FieldDefinition {
+        key: row.get(0)?,
+        label: row.get(1)?,
+        field_type: field_type.parse().unwrap_or(crate::models::FieldType::Text),
+        required: row.get(3)?,
+        options: serde_json::from_str(&options).unwrap_or_default(),
+    })
+}
+
+/// Every defined custom field, in the order they were first defined.
+pub fn field_definitions(conn: &Arc<Mutex<Connection>>) -> Result<Vec<FieldDefinition>> {
+    let conn = conn.lock().unwrap();
+    let mut stmt = conn.prepare(
+        "SELECT key, label, type, required, options FROM field_definitions ORDER BY position",
+    )?;
+    let fields = stmt
+        .query_map([], row_to_field_definition)?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(fields)
+}
+
+/// Defines the field `key`, or replaces its definition in place. Values
+/// already on cards are not rechecked. Returns the definition and whether
+/// it is new.
+pub fn put_field_definition(
+    conn: &Arc<Mutex<Connection>>,
+    key: &str,
+    input: &FieldDefinitionInput,
+) -> Result<(FieldDefinition, bool)> {
+    let conn = conn.lock().unwrap();
+    let created = conn.execute(
+        "INSERT INTO field_definitions (key, label, type, required, options, position)
+         VALUES (?1, ?2, ?3, ?4, ?5, (SELECT COALESCE(MAX(position), 0) + 1 FROM field_definitions))
+         ON CONFLICT(key) DO NOTHING",
+        params![
+            key,
+            input.label,
+            input.field_type.as_str(),
+            input.required,
+            serde_json::to_string(&input.options)?
+        ],
+    )? == 1;
+    if !created {
+        conn.execute(
+            "UPDATE field_definitions SET label=?2, type=?3, required=?4, options=?5 WHERE key=?1",
+            params![
+                key,
+                input.label,
+                input.field_type.as_str(),
+                input.required,
+                serde_json::to_string(&input.options)?
+            ],
+        )?;
+    }
+    let field = conn.query_row(
+        "SELECT key, label, type, required, options FROM field_definitions WHERE key = ?1",
+        params![key],
+        row_to_field_definition,
+    )?;
+    Ok((field, created))
+}
+
+/// Removes the field and its values from every card, returning the ids of
+/// the cards that had one, or `None` if the field was not defined.
+pub fn delete_field_definition(conn: &Arc<Mutex<Connection>>, key: &str) -> Result<Option<Vec<i64>>> {
+    let mut conn = conn.lock().unwrap();
+    let tx = conn.transaction()?;
+    if tx.execute("DELETE FROM field_definitions WHERE key = ?1", params![key])? == 0 {
+        return Ok(None);
+    }
+    // Keys are restricted to [a-z0-9_], so they are safe in a JSON path.
+    let path = format!("$.{key}");
+    let ids = tx
+        .prepare(
+            "UPDATE cards SET custom_fields = json_remove(custom_fields, ?1),
+                              version = version + 1, updated_at = CURRENT_TIMESTAMP
+             WHERE json_extract(custom_fields, ?1) IS NOT NULL
+             RETURNING id",
+        )?
+        .query_map(params![path], |row| row.get(0))?
+        .collect::<std::result::Result<Vec<i64>, _>>()?;
+    record_revisions(&tx, &ids)?;
+    tx.commit()?;
+    Ok(Some(ids))
+}
+
+// ────────────────────────────────────────────────────────────────────────────
+// Attachments
+// ────────────────────────────────────────────────────────────────────────────
+
+const ATTACHMENT_COLUMNS: &str = "id, card_id, filename, mime, size, created_at";
+
+fn row_to_attachment(row: &rusqlite::Row) -> rusqlite::Result<Attachment> {
+    let (id, card_id): (i64, i64) = (row.get(0)?, row.get(1)?);
+    Ok(Attachment {
+        id,
+        card_id,
+        filename: row.get(2)?,
+        mime: row.get(3)?,
+        size: row.get(4)?,
+        url: base_path::url(&format!("/api/v1/cards/{card_id}/attachments/{id}")),
+        created_at: row.get(5)?,
+    })
+}
+
+/// The card's attachments, oldest first, or `None` if there is no such card.
+pub fn list_attachments(conn: &Arc<Mutex<Connection>>, card_id: i64) -> Result<Option<Vec<Attachment>>> {
+    let conn = conn.lock().unwrap();
+    if !card_exists(&conn, card_id)? {
+        return Ok(None);
+    }
+    let attachments = conn
+        .prepare(&format!(
+            "SELECT {ATTACHMENT_COLUMNS} FROM card_attachments WHERE card_id = ?1 ORDER BY id"
+        ))?
+        .query_map(params![card_id], row_to_attachment)?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(Some(attachments))
+}
+
+/// Records a file stored at `path` as attached to the card. Returns `None`
+/// if there is no such card.
+pub fn add_attachment(
+    conn: &Arc<Mutex<Connection>>,
+    card_id: i64,
+    filename: &str,
+    mime: &str,
+    size: i64,
+    path: &str,
+) -> Result<Option<Attachment>> {
+    let conn = conn.lock().unwrap();
+    if !card_exists(&conn, card_id)? {
+        return Ok(None);
+    }
+    let attachment = conn.query_row(
+        &format!(
+            "INSERT INTO card_attachments (card_id, filename, mime, size, path)
+             VALUES (?1, ?2, ?3, ?4, ?5) RETURNING {ATTACHMENT_COLUMNS}"
+        ),
+        params![card_id, filename, mime, size, path],
+        row_to_attachment,
+    )?;
+    retain_file(&conn, path)?;
+    Ok(Some(attachment))
+}
+
+/// The attachment and the path of its file, or `None` if the card has no
+/// such attachment.
+pub fn get_attachment(
+    conn: &Arc<Mutex<Connection>>,
+    card_id: i64,
+    id: i64,
+) -> Result<Option<(Attachment, String)>> {
+    let conn = conn.lock().unwrap();
+    Ok(conn
+        .query_row(
+            &format!("SELECT {ATTACHMENT_COLUMNS}, path FROM card_attachments WHERE id = ?1 AND card_id = ?2"),
+            params![id, card_id],
+            |row| Ok((row_to_attachment(row)?, row.get(6)?)),
+        )
+        .optional()?)
+}
+
+/// Removes an attachment, returning the path of its file, empty if the
+/// file is still used, or `None` if the card has no such attachment.
+pub fn delete_attachment(conn: &Arc<Mutex<Connection>>, card_id: i64, id: i64) -> Result<Option<String>> {
+    let conn = conn.lock().unwrap();
+    let path: Option<String> = conn
+        .query_row(
+            "DELETE FROM card_attachments WHERE id = ?1 AND card_id = ?2 RETURNING path",
+            params![id, card_id],
+            |r| r.get(0),
+        )
+        .optional()?;
+    match path {
+        Some(path) => Ok(Some(release_file(&conn, &path)?.unwrap_or_default())),
+        None => Ok(None),
+    }
+}
+
+// ────────────────────────────────────────────────────────────────────────────
+// Resumable uploads
+// ────────────────────────────────────────────────────────────────────────────
 
-    // tags
-    let mut stmt = conn.prepare(
-        "SELECT t.name FROM tags t
-         JOIN card_tags ct ON ct.tag_id = t.id
-         WHERE ct.card_id = ?1
-         ORDER BY t.name",
-    )?;
-    card.tags = stmt
-        .query_map(params![id], |row| row.get(0))?
-        .collect::<std::result::Result<Vec<String>, _>>()?;
+const UPLOAD_SESSION_COLUMNS: &str = "id, filename, size, received, created_at";
 
-    Ok(Some(card))
+fn row_to_upload_session(row: &rusqlite::Row) -> rusqlite::Result<UploadSession> {
+    Ok(UploadSession {
+        id: row.get(0)?,
+        filename: row.get(1)?,
+        size: row.get(2)?,
+        offset: row.get(3)?,
+        created_at: row.get(4)?,
+    })
 }
 
-pub fn list_cards(
+/// Starts a resumable upload of `size` bytes named `filename`, first
+/// dropping the ones older than `ttl_hours`. Returns it and the ids of the
+/// dropped ones, whose partial files the caller removes.
+pub fn create_upload_session(
     conn: &Arc<Mutex<Connection>>,
-    q: Option<&str>,
-    tag: Option<&str>,
-) -> Result<Vec<Card>> {
+    id: &str,
+    filename: &str,
+    size: i64,
+    ttl_hours: i64,
+) -> Result<(UploadSession, Vec<String>)> {
     let conn = conn.lock().unwrap();
+    let expired = conn
+        .prepare("DELETE FROM upload_sessions WHERE created_at < datetime('now', ?1) RETURNING id")?
+        .query_map(params![format!("-{ttl_hours} hours")], |r| r.get(0))?
+        .collect::<std::result::Result<Vec<String>, _>>()?;
+    let session = conn.query_row(
+        &format!(
+            "INSERT INTO upload_sessions (id, filename, size) VALUES (?1, ?2, ?3)
+             RETURNING {UPLOAD_SESSION_COLUMNS}"
+        ),
+        params![id, filename, size],
+        row_to_upload_session,
+    )?;
+    Ok((session, expired))
+}
 
-    let ids: Vec<i64> = match (q, tag) {
-        (Some(search), Some(tag_filter)) => {
-            let pattern = format!("%{search}%");
-            let mut stmt = conn.prepare(
-                r#"SELECT DISTINCT c.id FROM cards c
-                   LEFT JOIN card_emails ce ON ce.card_id = c.id
-                   JOIN card_tags ct ON ct.card_id = c.id
-                   JOIN tags t ON t.id = ct.tag_id
-                   WHERE t.name = ?1
-                     AND (c.name LIKE ?2 OR c.company LIKE ?2 OR ce.address LIKE ?2)
-                   ORDER BY c.updated_at DESC"#,
-            )?;
-            let ids = stmt
-                .query_map(params![tag_filter, pattern], |row| row.get(0))?
-                .collect::<std::result::Result<Vec<i64>, _>>()?;
-            ids
-        }
-        (Some(search), None) => {
-            let pattern = format!("%{search}%");
-            let mut stmt = conn.prepare(
-                r#"SELECT DISTINCT c.id FROM cards c
-                   LEFT JOIN card_emails ce ON ce.card_id = c.id
-                   WHERE c.name LIKE ?1 OR c.company LIKE ?1 OR ce.address LIKE ?1
-                   ORDER BY c.updated_at DESC"#,
-            )?;
-            let ids = stmt
-                .query_map(params![pattern], |row| row.get(0))?
-                .collect::<std::result::Result<Vec<i64>, _>>()?;
-            ids
-        }
-        (None, Some(tag_filter)) => {
-            let mut stmt = conn.prepare(
-                r#"SELECT DISTINCT c.id FROM cards c
-                   JOIN card_tags ct ON ct.card_id = c.id
-                   JOIN tags t ON t.id = ct.tag_id
-                   WHERE t.name = ?1
-                   ORDER BY c.updated_at DESC"#,
-            )?;
-            let ids = stmt
-                .query_map(params![tag_filter], |row| row.get(0))?
-                .collect::<std::result::Result<Vec<i64>, _>>()?;
-            ids
-        }
-        (None, None) => {
-            let mut stmt =
-                conn.prepare("SELECT id FROM cards ORDER BY updated_at DESC")?;
-            let ids = stmt
-                .query_map([], |row| row.get(0))?
-                .collect::<std::result::Result<Vec<i64>, _>>()?;
-            ids
-        }
-    };
+pub fn get_upload_session(conn: &Arc<Mutex<Connection>>, id: &str) -> Result<Option<UploadSession>> {
+    let conn = conn.lock().unwrap();
+    Ok(conn
+        .query_row(
+            &format!("SELECT {UPLOAD_SESSION_COLUMNS} FROM upload_sessions WHERE id = ?1"),
+            params![id],
+            row_to_upload_session,
+        )
+        .optional()?)
+}
 
-    let mut cards = Vec::with_capacity(ids.len());
-    for id in ids {
-        if let Some(card) = fetch_card_by_id(&conn, id)? {
-            cards.push(card);
-        }
-    }
-    Ok(cards)
+/// Moves the upload's offset from `from` to `to` once a chunk is written.
+/// Returns `None` if the offset is no longer `from`, as when another
+/// request for the same chunk got there first.
+pub fn advance_upload_session(
+    conn: &Arc<Mutex<Connection>>,
+    id: &str,
+    from: i64,
+    to: i64,
+) -> Result<Option<UploadSession>> {
+    let conn = conn.lock().unwrap();
+    Ok(conn
+        .query_row(
+            &format!(
+                "UPDATE upload_sessions SET received = ?3 WHERE id = ?1 AND received = ?2
+                 RETURNING {UPLOAD_SESSION_COLUMNS}"
+            ),
+            params![id, from, to],
+            row_to_upload_session,
+        )
+        .optional()?)
 }
 
-pub fn get_card(conn: &Arc<Mutex<Connection>>, id: i64) -> Result<Option<Card>> {
+/// Forgets an upload, once used or abandoned. Returns whether it existed.
+pub fn delete_upload_session(conn: &Arc<Mutex<Connection>>, id: &str) -> Result<bool> {
     let conn = conn.lock().unwrap();
-    fetch_card_by_id(&conn, id)
+    Ok(conn.execute("DELETE FROM upload_sessions WHERE id = ?1", params![id])? > 0)
 }
 
-fn upsert_tags_and_link(
-    conn: &Connection,
+// ────────────────────────────────────────────────────────────────────────────
+// Interactions
+// ────────────────────────────────────────────────────────────────────────────
+
+const INTERACTION_COLUMNS: &str = "id, card_id, kind, occurred_at, note, created_at";
+
+fn row_to_interaction(row: &rusqlite::Row) -> rusqlite::Result<Interaction> {
+    let kind: String = row.get(2)?;
+    Ok(Interaction {
+        id: row.get(0)?,
+        card_id: row.get(1)?,
+        kind: kind.parse().map_err(|e: String| {
+            rusqlite::Error::FromSqlConversionFailure(2, rusqlite::types::Type::Text, e.into())
+        })?,
+        occurred_at: row.get(3)?,
+        note: row.get(4)?,
+        created_at: row.get(5)?,
+    })
+}
+
+fn card_exists(conn: &Connection, id: i64) -> Result<bool> {
+    Ok(conn.query_row("SELECT EXISTS (SELECT 1 FROM cards WHERE id = ?1)", params![id], |r| {
+        r.get(0)
+    })?)
+}
+
+fn read_interaction(conn: &Connection, card_id: i64, id: i64) -> Result<Option<Interaction>> {
+    Ok(conn
+        .query_row(
+            &format!("SELECT {INTERACTION_COLUMNS} FROM interactions WHERE id = ?1 AND card_id = ?2"),
+            params![id, card_id],
+            row_to_interaction,
+        )
+        .optional()?)
+}
+
+/// The card's interactions, most recent first, or `None` if there is no
+/// such card.
+pub fn list_interactions(
+    conn: &Arc<Mutex<Connection>>,
     card_id: i64,
-    tags: &[String],
-) -> Result<()> {
-    conn.execute("DELETE FROM card_tags WHERE card_id = ?1", params![card_id])?;
-    for tag in tags {
-        if tag.trim().is_empty() {
-            continue;
-        }
-        conn.execute(
-            "INSERT OR IGNORE INTO tags (name) VALUES (?1)",
-            params![tag],
-        )?;
-        let tag_id: i64 =
-            conn.query_row("SELECT id FROM tags WHERE name = ?1", params![tag], |r| {
-                r.get(0)
-            })?;
-        conn.execute(
-            "INSERT OR IGNORE INTO card_tags (card_id, tag_id) VALUES (?1, ?2)",
-            params![card_id, tag_id],
-        )?;
+) -> Result<Option<Vec<Interaction>>> {
+    let conn = conn.lock().unwrap();
+    if !card_exists(&conn, card_id)? {
+        return Ok(None);
     }
-    Ok(())
+    let interactions = conn
+        .prepare(&format!(
+            "SELECT {INTERACTION_COLUMNS} FROM interactions WHERE card_id = ?1
+             ORDER BY occurred_at DESC, id DESC"
+        ))?
+        .query_map(params![card_id], row_to_interaction)?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(Some(interactions))
 }
 
-pub fn create_card(conn: &Arc<Mutex<Connection>>, input: &CardInput) -> Result<i64> {
+/// Logs an interaction with the card. Returns `None` if there is no such card.
+pub fn add_interaction(
+    conn: &Arc<Mutex<Connection>>,
+    card_id: i64,
+    input: &InteractionInput,
+) -> Result<Option<Interaction>> {
     let conn = conn.lock().unwrap();
+    if !card_exists(&conn, card_id)? {
+        return Ok(None);
+    }
     conn.execute(
-        "INSERT INTO cards (name, title, company, website, notes) VALUES (?1, ?2, ?3, ?4, ?5)",
-        params![input.name, input.title, input.company, input.website, input.notes],
+        "INSERT INTO interactions (card_id, kind, occurred_at, note)
+         VALUES (?1, ?2, COALESCE(?3, CURRENT_TIMESTAMP), ?4)",
+        params![card_id, input.kind.as_str(), input.occurred_at, input.note],
     )?;
-    let id = conn.last_insert_rowid();
+    read_interaction(&conn, card_id, conn.last_insert_rowid())
+}
 
-    for p in &input.phones {
-        conn.execute(
-            "INSERT INTO card_phones (card_id, label, number) VALUES (?1, ?2, ?3)",
-            params![id, p.label, p.number],
-        )?;
+/// Replaces an interaction, keeping its time if `occurred_at` is omitted.
+/// Returns `None` if the card has no such interaction.
+pub fn update_interaction(
+    conn: &Arc<Mutex<Connection>>,
+    card_id: i64,
+    id: i64,
+    input: &InteractionInput,
+) -> Result<Option<Interaction>> {
+    let conn = conn.lock().unwrap();
+    let updated = conn.execute(
+        "UPDATE interactions SET kind = ?1, occurred_at = COALESCE(?2, occurred_at), note = ?3
+         WHERE id = ?4 AND card_id = ?5",
+        params![input.kind.as_str(), input.occurred_at, input.note, id, card_id],
+    )?;
+    if updated == 0 {
+        return Ok(None);
     }
-    for e in &input.emails {
-        conn.execute(
-            "INSERT INTO card_emails (card_id, label, address) VALUES (?1, ?2, ?3)",
-            params![id, e.label, e.address],
-        )?;
+    read_interaction(&conn, card_id, id)
+}
+
+pub fn delete_interaction(conn: &Arc<Mutex<Connection>>, card_id: i64, id: i64) -> Result<bool> {
+    let conn = conn.lock().unwrap();
+    let deleted = conn.execute(
+        "DELETE FROM interactions WHERE id = ?1 AND card_id = ?2",
+        params![id, card_id],
+    )?;
+    Ok(deleted > 0)
+}
+
+// ────────────────────────────────────────────────────────────────────────────
+// Reminders
+// ────────────────────────────────────────────────────────────────────────────
+
+const REMINDER_COLUMNS: &str = "r.id, r.card_id, r.title, r.due_date, r.done, r.created_at";
+
+fn row_to_reminder(row: &rusqlite::Row) -> rusqlite::Result<Reminder> {
+    Ok(Reminder {
+        id: row.get(0)?,
+        card_id: row.get(1)?,
+        title: row.get(2)?,
+        due_date: row.get(3)?,
+        done: row.get(4)?,
+        created_at: row.get(5)?,
+    })
+}
+
+fn read_reminder(conn: &Connection, card_id: i64, id: i64) -> Result<Option<Reminder>> {
+    Ok(conn
+        .query_row(
+            &format!("SELECT {REMINDER_COLUMNS} FROM reminders r WHERE r.id = ?1 AND r.card_id = ?2"),
+            params![id, card_id],
+            row_to_reminder,
+        )
+        .optional()?)
+}
+
+/// The card's reminders, open ones first, each by due date; `None` if there
+/// is no such card.
+pub fn list_reminders(conn: &Arc<Mutex<Connection>>, card_id: i64) -> Result<Option<Vec<Reminder>>> {
+    let conn = conn.lock().unwrap();
+    if !card_exists(&conn, card_id)? {
+        return Ok(None);
     }
-    for a in &input.addresses {
-        conn.execute(
-            "INSERT INTO card_addresses (card_id, label, street, city, country, postal) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-            params![id, a.label, a.street, a.city, a.country, a.postal],
-        )?;
+    let reminders = conn
+        .prepare(&format!(
+            "SELECT {REMINDER_COLUMNS} FROM reminders r WHERE r.card_id = ?1
+             ORDER BY r.done, r.due_date, r.id"
+        ))?
+        .query_map(params![card_id], row_to_reminder)?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(Some(reminders))
+}
+
+/// Adds a reminder to the card. Returns `None` if there is no such card.
+pub fn add_reminder(
+    conn: &Arc<Mutex<Connection>>,
+    card_id: i64,
+    input: &ReminderInput,
+) -> Result<Option<Reminder>> {
+    let conn = conn.lock().unwrap();
+    if !card_exists(&conn, card_id)? {
+        return Ok(None);
     }
-    upsert_tags_and_link(&conn, id, &input.tags)?;
-    Ok(id)
+    conn.execute(
+        "INSERT INTO reminders (card_id, title, due_date, done) VALUES (?1, ?2, ?3, ?4)",
+        params![card_id, input.title, input.due_date, input.done],
+    )?;
+    read_reminder(&conn, card_id, conn.last_insert_rowid())
 }
 
-pub fn update_card(
+/// Replaces a reminder. Returns `None` if the card has no such reminder.
+pub fn update_reminder(
     conn: &Arc<Mutex<Connection>>,
+    card_id: i64,
     id: i64,
-    input: &CardInput,
-) -> Result<()> {
+    input: &ReminderInput,
+) -> Result<Option<Reminder>> {
     let conn = conn.lock().unwrap();
     let updated = conn.execute(
-        "UPDATE cards SET name=?1, title=?2, company=?3, website=?4, notes=?5, updated_at=CURRENT_TIMESTAMP WHERE id=?6",
-        params![input.name, input.title, input.company, input.website, input.notes, id],
+        "UPDATE reminders SET title = ?1, due_date = ?2, done = ?3 WHERE id = ?4 AND card_id = ?5",
+        params![input.title, input.due_date, input.done, id, card_id],
     )?;
     if updated == 0 {
-        anyhow::bail!("card not found");
+        return Ok(None);
     }
+    read_reminder(&conn, card_id, id)
+}
 
-    conn.execute("DELETE FROM card_phones WHERE card_id = ?1", params![id])?;
-    for p in &input.phones {
-        conn.execute(
-            "INSERT INTO card_phones (card_id, label, number) VALUES (?1, ?2, ?3)",
-            params![id, p.label, p.number],
-        )?;
-    }
+pub fn delete_reminder(conn: &Arc<Mutex<Connection>>, card_id: i64, id: i64) -> Result<bool> {
+    let conn = conn.lock().unwrap();
+    let deleted = conn.execute(
+        "DELETE FROM reminders WHERE id = ?1 AND card_id = ?2",
+        params![id, card_id],
+    )?;
+    Ok(deleted > 0)
+}
 
-    conn.execute("DELETE FROM card_emails WHERE card_id = ?1", params![id])?;
-    for e in &input.emails {
-        conn.execute(
-            "INSERT INTO card_emails (card_id, label, address) VALUES (?1, ?2, ?3)",
-            params![id, e.label, e.address],
-        )?;
+/// Open reminders of active cards due on or after `from` and before
+/// `before` (`YYYY-MM-DD`, either bound optional), soonest first.
+pub fn due_reminders(
+    conn: &Arc<Mutex<Connection>>,
+    from: Option<&str>,
+    before: Option<&str>,
+) -> Result<Vec<DueReminder>> {
+    let conn = conn.lock().unwrap();
+    let reminders = conn
+        .prepare(&format!(
+            "SELECT {REMINDER_COLUMNS} FROM reminders r JOIN cards c ON c.id = r.card_id
+             WHERE r.done = 0 AND c.archived_at IS NULL
+               AND (?1 IS NULL OR r.due_date >= ?1) AND (?2 IS NULL OR r.due_date < ?2)
+             ORDER BY r.due_date, r.id"
+        ))?
+        .query_map(params![from, before], row_to_reminder)?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    let mut due = Vec::with_capacity(reminders.len());
+    for reminder in reminders {
+        if let Some(card) = fetch_card_by_id(&conn, reminder.card_id, CardRelations::ALL)? {
+            due.push(DueReminder { reminder, card });
+        }
     }
+    Ok(due)
+}
 
-    conn.execute("DELETE FROM card_addresses WHERE card_id = ?1", params![id])?;
-    for a in &input.addresses {
-        conn.execute(
-            "INSERT INTO card_addresses (card_id, label, street, city, country, postal) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-            params![id, a.label, a.street, a.city, a.country, a.postal],
-        )?;
-    }
+// ────────────────────────────────────────────────────────────────────────────
+// Share links
+// ────────────────────────────────────────────────────────────────────────────
 
-    upsert_tags_and_link(&conn, id, &input.tags)?;
-    Ok(())
+/// Key share links are signed with, if one has been made. Only reads, so
+/// shared links still open on a `--read-only` vault.
+pub fn share_secret(conn: &Arc<Mutex<Connection>>) -> Result<Option<String>> {
+    let conn = conn.lock().unwrap();
+    let secret = conn
+        .query_row("SELECT value FROM settings WHERE key = 'share_secret'", [], |r| r.get(0))
+        .optional()?;
+    Ok(secret)
+}
+
+/// Key to sign a new share link with. Generated for the first link and kept
+/// in the database, so links stay valid across restarts.
+pub fn mint_share_secret(conn: &Arc<Mutex<Connection>>) -> Result<String> {
+    let conn = conn.lock().unwrap();
+    let generated = format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple());
+    conn.execute(
+        "INSERT OR IGNORE INTO settings (key, value) VALUES ('share_secret', ?1)",
+        params![generated],
+    )?;
+    let secret = conn.query_row(
+        "SELECT value FROM settings WHERE key = 'share_secret'",
+        [],
+        |r| r.get(0),
+    )?;
+    Ok(secret)
+}
+
+// ────────────────────────────────────────────────────────────────────────────
+// Webhooks
+// ────────────────────────────────────────────────────────────────────────────
+
+fn row_to_webhook(row: &rusqlite::Row) -> rusqlite::Result<Webhook> {
+    let events: String = row.get(2)?;
+    Ok(Webhook {
+        id: row.get(0)?,
+        url: row.get(1)?,
+        events: events
+            .split(',')
+            .filter(|e| !e.is_empty())
+            .map(|e| e.to_string())
+            .collect(),
+        active: row.get(3)?,
+        secret: None,
+        created_at: row.get(4)?,
+    })
 }
 
-pub fn delete_card(conn: &Arc<Mutex<Connection>>, id: i64) -> Result<Option<String>> {
-    info!("SQL: SELECT photo_path FROM cards WHERE id = {}", id);
+pub fn list_webhooks(conn: &Arc<Mutex<Connection>>) -> Result<Vec<Webhook>> {
     let conn = conn.lock().unwrap();
+    let mut stmt = conn.prepare(
+        "SELECT id, url, events, active, created_at FROM webhooks ORDER BY id",
+    )?;
+    let hooks = stmt
+        .query_map([], row_to_webhook)?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(hooks)
+}
 
-    // Get photo_path before deleting
-    let photo_path: Option<String> = conn
+pub fn get_webhook(conn: &Arc<Mutex<Connection>>, id: i64) -> Result<Option<Webhook>> {
+    let conn = conn.lock().unwrap();
+    let hook = conn
         .query_row(
-            "SELECT photo_path FROM cards WHERE id = ?1",
+            "SELECT id, url, events, active, created_at FROM webhooks WHERE id = ?1",
             params![id],
-            |r| r.get(0),
+            row_to_webhook,
         )
         .optional()?;
-    info!("SQL: Result - photo_path: {:?}", photo_path);
-
-    // Delete the card and check if it existed
-    info!("SQL: DELETE FROM cards WHERE id = {}", id);
-    let rows_affected = conn.execute("DELETE FROM cards WHERE id = ?1", params![id])?;
-    info!("SQL: Result - rows_affected: {}", rows_affected);
-
-    if rows_affected == 0 {
-        return Ok(None); // Card didn't exist
-    }
+    Ok(hook)
+}
 
-    Ok(photo_path)
+pub fn create_webhook(
+    conn: &Arc<Mutex<Connection>>,
+    input: &WebhookInput,
+    secret: &str,
+) -> Result<i64> {
+    let conn = conn.lock().unwrap();
+    conn.execute(
+        "INSERT INTO webhooks (url, secret, events, active) VALUES (?1, ?2, ?3, ?4)",
+        params![
+            input.url,
+            secret,
+            input.events.join(","),
+            input.active.unwrap_or(true)
+        ],
+    )?;
+    Ok(conn.last_insert_rowid())
 }
 
-pub fn update_card_photo(
+pub fn update_webhook(
     conn: &Arc<Mutex<Connection>>,
     id: i64,
-    path: &str,
-) -> Result<()> {
+    input: &WebhookInput,
+) -> Result<bool> {
     let conn = conn.lock().unwrap();
     let updated = conn.execute(
-        "UPDATE cards SET photo_path=?1, updated_at=CURRENT_TIMESTAMP WHERE id=?2",
-        params![path, id],
+        "UPDATE webhooks SET url=?1, events=?2, active=?3, secret=COALESCE(?4, secret) WHERE id=?5",
+        params![
+            input.url,
+            input.events.join(","),
+            input.active.unwrap_or(true),
+            input.secret,
+            id
+        ],
     )?;
-    if updated == 0 {
-        anyhow::bail!("card not found");
-    }
-    Ok(())
+    Ok(updated > 0)
 }
 
-pub fn delete_card_photo(conn: &Arc<Mutex<Connection>>, id: i64) -> Result<Option<String>> {
+pub fn delete_webhook(conn: &Arc<Mutex<Connection>>, id: i64) -> Result<bool> {
     let conn = conn.lock().unwrap();
-    let old_path: Option<String> = conn
+    let deleted = conn.execute("DELETE FROM webhooks WHERE id = ?1", params![id])?;
+    Ok(deleted > 0)
+}
+
+/// Active webhooks subscribed to `event` (an empty event list means "all"),
+/// returned as `(url, secret)` pairs for the dispatcher.
+pub fn webhook_targets(conn: &Arc<Mutex<Connection>>, event: &str) -> Result<Vec<(String, String)>> {
+    let conn = conn.lock().unwrap();
+    let mut stmt = conn.prepare(
+        "SELECT url, secret FROM webhooks
+         WHERE active = 1 AND (events = '' OR ',' || events || ',' LIKE '%,' || ?1 || ',%')
+         ORDER BY id",
+    )?;
+    let targets = stmt
+        .query_map(params![event], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(targets)
+}
+
+// ────────────────────────────────────────────────────────────────────────────
+// Idempotency keys
+// ────────────────────────────────────────────────────────────────────────────
+
+pub enum IdempotencyClaim {
+    /// First time this key is seen; the caller should run the request and
+    /// then `complete_idempotency_key` or `release_idempotency_key`.
+    Claimed,
+    /// A request with this key is still being processed.
+    InFlight,
+    /// The key was already used for a different request body.
+    Mismatch,
+    /// A stored response to replay.
+    Replay(StoredResponse),
+}
+
+pub struct StoredResponse {
+    pub status: u16,
+    pub content_type: String,
+    pub etag: String,
+    pub body: Vec<u8>,
+}
+
+pub fn claim_idempotency_key(
+    conn: &Arc<Mutex<Connection>>,
+    key: &str,
+    method: &str,
+    path: &str,
+    request_hash: &str,
+    ttl_hours: i64,
+) -> Result<IdempotencyClaim> {
+    let conn = conn.lock().unwrap();
+    conn.execute(
+        "DELETE FROM idempotency_keys WHERE created_at < datetime('now', ?1)",
+        params![format!("-{ttl_hours} hours")],
+    )?;
+
+    let existing = conn
         .query_row(
-            "SELECT photo_path FROM cards WHERE id = ?1",
-            params![id],
-            |r| r.get(0),
+            "SELECT request_hash, status, content_type, etag, body FROM idempotency_keys
+             WHERE key = ?1 AND method = ?2 AND path = ?3",
+            params![key, method, path],
+            |r| {
+                Ok((
+                    r.get::<_, String>(0)?,
+                    r.get::<_, Option<u16>>(1)?,
+                    r.get::<_, String>(2)?,
+                    r.get::<_, String>(3)?,
+                    r.get::<_, Option<Vec<u8>>>(4)?,
+                ))
+            },
         )
         .optional()?;
-    if old_path.is_none() {
-        return Ok(None);
+
+    match existing {
+        None => {
+            conn.execute(
+                "INSERT INTO idempotency_keys (key, method, path, request_hash) VALUES (?1, ?2, ?3, ?4)",
+                params![key, method, path, request_hash],
+            )?;
+            Ok(IdempotencyClaim::Claimed)
+        }
+        Some((hash, _, _, _, _)) if hash != request_hash => Ok(IdempotencyClaim::Mismatch),
+        Some((_, None, _, _, _)) => Ok(IdempotencyClaim::InFlight),
+        Some((_, Some(status), content_type, etag, body)) => {
+            Ok(IdempotencyClaim::Replay(StoredResponse {
+                status,
+                content_type,
+                etag,
+                body: body.unwrap_or_default(),
+            }))
+        }
     }
+}
+
+pub fn complete_idempotency_key(
+    conn: &Arc<Mutex<Connection>>,
+    key: &str,
+    method: &str,
+    path: &str,
+    response: &StoredResponse,
+) -> Result<()> {
+    let conn = conn.lock().unwrap();
     conn.execute(
-        "UPDATE cards SET photo_path='', updated_at=CURRENT_TIMESTAMP WHERE id=?1",
-        params![id],
+        "UPDATE idempotency_keys SET status=?1, content_type=?2, etag=?3, body=?4
+         WHERE key=?5 AND method=?6 AND path=?7",
+        params![
+            response.status,
+            response.content_type,
+            response.etag,
+            response.body,
+            key,
+            method,
+            path
+        ],
     )?;
-    Ok(old_path)
+    Ok(())
 }
 
-pub fn list_tags(conn: &Arc<Mutex<Connection>>) -> Result<Vec<TagCount>> {
+/// Forgets a claimed key so the client may retry (used after server errors
+/// and requests cut off before they finished). A stored response is kept.
+pub fn release_idempotency_key(
+    conn: &Arc<Mutex<Connection>>,
+    key: &str,
+    method: &str,
+    path: &str,
+) -> Result<()> {
     let conn = conn.lock().unwrap();
-    let mut stmt = conn.prepare(
-        "SELECT t.name, COUNT(ct.card_id) as cnt
-         FROM tags t
-         LEFT JOIN card_tags ct ON ct.tag_id = t.id
-         GROUP BY t.id, t.name
-         ORDER BY t.name",
+    conn.execute(
+        "DELETE FROM idempotency_keys WHERE key=?1 AND method=?2 AND path=?3 AND status IS NULL",
+        params![key, method, path],
     )?;
-    let tags: Vec<TagCount> = stmt
-        .query_map([], |row| {
-            Ok(TagCount {
-                name: row.get(0)?,
-                count: row.get(1)?,
-            })
-        })?
-        .collect::<std::result::Result<Vec<_>, _>>()?;
-    Ok(tags)
+    Ok(())
 }
 
 pub fn seed_data(conn: &Arc<Mutex<Connection>>) -> Result<()> {
+    let conn_guard = conn.lock().unwrap();
+    insert_seed_cards(&conn_guard)
+}
+
+/// Empties every table but the geocoding cache and seeds it afresh, as
+/// `--demo` does on each reset. Returns the stored files and the resumable
+/// uploads in progress, for the caller to delete.
+pub fn reset_demo(conn: &Arc<Mutex<Connection>>) -> Result<(Vec<String>, Vec<String>)> {
+    let mut conn = conn.lock().unwrap();
+    let tx = conn.transaction()?;
+    let files = tx
+        .prepare("SELECT path FROM upload_refs")?
+        .query_map([], |r| r.get(0))?
+        .collect::<std::result::Result<Vec<String>, _>>()?;
+    let uploads = tx
+        .prepare("SELECT id FROM upload_sessions")?
+        .query_map([], |r| r.get(0))?
+        .collect::<std::result::Result<Vec<String>, _>>()?;
+    let tables = tx
+        .prepare(
+            "SELECT name FROM sqlite_master
+             WHERE type = 'table' AND name NOT LIKE 'sqlite_%' AND name <> 'geocode_cache'",
+        )?
+        .query_map([], |r| r.get(0))?
+        .collect::<std::result::Result<Vec<String>, _>>()?;
+    // Every table ends up empty, so the order of the deletes does not matter
+    tx.execute_batch("PRAGMA defer_foreign_keys = ON;")?;
+    for table in &tables {
+        tx.execute(&format!("DELETE FROM \"{table}\""), [])?;
+    }
+    tx.execute("DELETE FROM sqlite_sequence", [])?;
+    insert_seed_cards(&tx)?;
+    tx.commit()?;
+    Ok((files, uploads))
+}
+
+fn insert_seed_cards(conn_guard: &Connection) -> Result<()> {
     struct SeedCard {
         name: &'static str,
         title: &'static str,
@@ -612,20 +3778,25 @@ pub fn seed_data(conn: &Arc<Mutex<Connection>>) -> Result<()> {
         },
     ];
 
-    let conn_arc = conn.clone();
-    let conn_guard = conn_arc.lock().unwrap();
-
     for seed in &seeds {
         conn_guard.execute(
-            "INSERT INTO cards (name, title, company, website, notes) VALUES (?1, ?2, ?3, ?4, ?5)",
-            params![seed.name, seed.title, seed.company, seed.website, seed.notes],
+            "INSERT INTO cards (name, title, company, website, notes, name_sound_key)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                seed.name,
+                seed.title,
+                seed.company,
+                seed.website,
+                seed.notes,
+                phonetic_key(seed.name)
+            ],
         )?;
         let card_id = conn_guard.last_insert_rowid();
 
         for (label, number) in &seed.phones {
             conn_guard.execute(
-                "INSERT INTO card_phones (card_id, label, number) VALUES (?1, ?2, ?3)",
-                params![card_id, label, number],
+                "INSERT INTO card_phones (card_id, label, number, e164) VALUES (?1, ?2, ?3, ?4)",
+                params![card_id, label, number, to_e164(number).unwrap_or_default()],
             )?;
         }
         for (label, address) in &seed.emails {
@@ -655,7 +3826,40 @@ pub fn seed_data(conn: &Arc<Mutex<Connection>>) -> Result<()> {
                 params![card_id, tag_id],
             )?;
         }
+        write_revision(conn_guard, card_id, 0, REVISION_BASELINE)?;
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::OpenFlags;
+
+    #[test]
+    fn share_token_opens_on_read_only_database() {
+        let path = std::env::temp_dir().join(format!("cardvault-share-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let conn = Arc::new(Mutex::new(Connection::open(&path).unwrap()));
+        init_db(&conn).unwrap();
+        let input = CardInput { name: "Ada Lovelace".into(), ..Default::default() };
+        let id = create_card(&conn, &input).unwrap();
+        let token = crate::share::mint(&mint_share_secret(&conn).unwrap(), id, u64::MAX);
+        drop(conn);
+
+        let conn = Arc::new(Mutex::new(
+            Connection::open_with_flags(&path, OpenFlags::SQLITE_OPEN_READ_ONLY).unwrap(),
+        ));
+        init_read_only(&conn).unwrap();
+        let secret = share_secret(&conn).unwrap().expect("secret was minted");
+        let (shared, _) = crate::share::verify(&secret, &token, 0).unwrap();
+        assert_eq!(get_card(&conn, shared).unwrap().unwrap().name, "Ada Lovelace");
+
+        drop(conn);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{suffix}", path.display()));
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use std::cell::RefCell;
+use std::time::Duration;
+
+use anyhow::Result;
+use axum::{extract::Request, response::Response};
+use clap::ValueEnum;
+use opentelemetry::{trace::TracerProvider as _, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{runtime, trace::TracerProvider, Resource};
+use tracing::Span;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{
+    field::RecordFields,
+    fmt::{
+        format::{self, DefaultFields},
+        FormatFields, MakeWriter,
+    },
+    layer::SubscriberExt,
+    util::SubscriberInitExt,
+    EnvFilter, Layer, Registry,
+};
+
+use crate::logfile::{self, LogFileConfig};
+
+/// How log lines are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Human-readable lines
+    Pretty,
+    /// One JSON object per line, for Loki, Elasticsearch, and the like
+    Json,
+}
+
+/// Where spans are exported over OTLP/gRPC, e.g. to Jaeger or Tempo.
+pub struct OtlpConfig {
+    /// Collector URL, e.g. `http://localhost:4317`
+    pub endpoint: String,
+    /// `service.name` the spans are filed under
+    pub service_name: String,
+}
+
+/// What `init` set up that must outlive the server: the span exporter and
+/// the log file's writer thread.
+pub struct Telemetry {
+    tracer: Option<TracerProvider>,
+    _log_file: Option<WorkerGuard>,
+}
+
+impl Telemetry {
+    /// Sends the last spans. Queued log lines are written when this is
+    /// dropped.
+    pub fn shutdown(&self) -> Result<()> {
+        if let Some(tracer) = &self.tracer {
+            tracer.shutdown()?;
+        }
+        Ok(())
+    }
+}
+
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// `DefaultFields` under another type. Span fields are formatted once per
+/// field formatter type and reused by every layer with that type, so the
+/// log file needs its own to not get stdout's colour codes.
+struct PlainFields(DefaultFields);
+
+impl<'w> FormatFields<'w> for PlainFields {
+    fn format_fields<R: RecordFields>(&self, writer: format::Writer<'w>, fields: R) -> std::fmt::Result {
+        self.0.format_fields(writer, fields)
+    }
+}
+
+/// A log layer writing `format` lines to `writer`.
+///
+/// A JSON line carries the event's fields at the top level, and the spans
+/// it happened in, such as the request with its `request_id`, under
+/// `spans`, outermost first.
+fn log_layer<W>(format: LogFormat, writer: W, ansi: bool) -> BoxedLayer
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer().with_writer(writer).with_ansi(ansi);
+    match format {
+        LogFormat::Pretty if ansi => Box::new(layer),
+        LogFormat::Pretty => Box::new(layer.fmt_fields(PlainFields(DefaultFields::new()))),
+        LogFormat::Json => Box::new(
+            layer
+                .json()
+                .flatten_event(true)
+                .with_current_span(false)
+                .with_span_list(true),
+        ),
+    }
+}
+
+/// Sets up logging to stdout in `format`, also to `log_file` if given, and
+/// with `otlp`, span export.
+pub fn init(format: LogFormat, log_file: Option<&LogFileConfig>, otlp: Option<&OtlpConfig>) -> Result<Telemetry> {
+    let filter = EnvFilter::from_default_env().add_directive("cardvault=info".parse()?);
+    let tracer = match otlp {
+        Some(config) => {
+            let exporter = opentelemetry_otlp::SpanExporter::builder()
+                .with_tonic()
+                .with_endpoint(&config.endpoint)
+                .build()?;
+            Some(
+                TracerProvider::builder()
+                    .with_batch_exporter(exporter, runtime::Tokio)
+                    .with_resource(Resource::new([KeyValue::new("service.name", config.service_name.clone())]))
+                    .build(),
+            )
+        }
+        None => None,
+    };
+    let otel = tracer
+        .as_ref()
+        .map(|provider| tracing_opentelemetry::layer().with_tracer(provider.tracer("cardvault")));
+    let mut layers = vec![log_layer(format, std::io::stdout, true)];
+    let guard = match log_file {
+        Some(config) => {
+            let (writer, guard) = logfile::writer(config)?;
+            layers.push(log_layer(format, writer, false));
+            Some(guard)
+        }
+        None => None,
+    };
+    tracing_subscriber::registry()
+        .with(layers)
+        .with(filter)
+        .with(otel)
+        .try_init()?;
+    Ok(Telemetry { tracer, _log_file: guard })
+}
+
+/// Notes a request's method and path on its span, for export. They are
+/// left out of log lines, which the access log covers.
+pub fn http_request(span: &Span, request: &Request) {
+    span.set_attribute("http.request.method", request.method().to_string());
+    span.set_attribute("url.path", request.uri().path().to_string());
+}
+
+/// Notes the status a request was answered with on its span.
+pub fn http_response(span: &Span, response: &Response) {
+    span.set_attribute("http.response.status_code", i64::from(response.status().as_u16()));
+}
+
+thread_local! {
+    /// SQL run by the store call on this thread, while one is traced.
+    static STATEMENTS: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+}
+
+/// rusqlite profile hook: notes each statement the current store call runs.
+pub fn record_statement(sql: &str, _: Duration) {
+    STATEMENTS.with(|statements| {
+        if let Some(statements) = statements.borrow_mut().as_mut() {
+            statements.push(sql.to_string());
+        }
+    });
+}
+
+/// Runs a blocking store call off the async runtime, in a `db` span below
+/// the caller's that exports the SQL it ran as `db.statement`.
+pub fn spawn_db<T, F>(f: F) -> tokio::task::JoinHandle<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let parent = Span::current();
+    tokio::task::spawn_blocking(move || {
+        let span = tracing::info_span!(parent: &parent, "db");
+        span.set_attribute("db.system", "sqlite");
+        let _entered = span.enter();
+        STATEMENTS.with(|statements| *statements.borrow_mut() = Some(Vec::new()));
+        let result = f();
+        let statements = STATEMENTS.with(|statements| statements.borrow_mut().take()).unwrap_or_default();
+        if !statements.is_empty() {
+            span.set_attribute("db.statement", statements.join(";\n"));
+        }
+        result
+    })
+}
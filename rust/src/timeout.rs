@@ -0,0 +1,35 @@
+use std::time::Duration;
+
+use axum::{
+    extract::{Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+use tracing::warn;
+
+/// Answers 503 if a request has no response within `limit`, so one stuck
+/// on a busy SQLite lock or a slow outside service does not hold its
+/// connection open for good. The limit covers reading the request body and
+/// running the handler. Attachment and chunk uploads and the change feed
+/// are routed around it, since they last as long as their body or stream.
+///
+/// Work the handler handed to a blocking thread still runs to the end, so
+/// a timed-out write may yet be applied.
+pub async fn middleware(State(limit): State<Duration>, request: Request, next: Next) -> Response {
+    let (method, path) = (request.method().clone(), request.uri().path().to_string());
+    match tokio::time::timeout(limit, next.run(request)).await {
+        Ok(response) => response,
+        Err(_) => {
+            warn!("{method} {path} timed out after {}s", limit.as_secs());
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                [(header::RETRY_AFTER, "5")],
+                Json(json!({"error": "request timed out"})),
+            )
+                .into_response()
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use chrono::DateTime;
+use chrono_tz::{Tz, TZ_VARIANTS};
+
+use crate::models::CardFormAddressInput;
+
+/// The time zones of a country.
+enum Zones {
+    /// The whole country keeps one time.
+    One(&'static str),
+    /// The country spans several; a city's zone must be under one of these.
+    Many(&'static [&'static str]),
+}
+
+/// Countries by lowercase English name and ISO 3166 code.
+const COUNTRIES: &[(&[&str], Zones)] = &[
+    (&["singapore", "sg"], Zones::One("Asia/Singapore")),
+    (&["malaysia", "my"], Zones::One("Asia/Kuala_Lumpur")),
+    (&["thailand", "th"], Zones::One("Asia/Bangkok")),
+    (&["vietnam", "viet nam", "vn"], Zones::One("Asia/Ho_Chi_Minh")),
+    (&["philippines", "ph"], Zones::One("Asia/Manila")),
+    (&["japan", "jp"], Zones::One("Asia/Tokyo")),
+    (&["china", "prc", "cn"], Zones::One("Asia/Shanghai")),
+    (&["hong kong", "hk"], Zones::One("Asia/Hong_Kong")),
+    (&["macau", "macao", "mo"], Zones::One("Asia/Macau")),
+    (&["taiwan", "tw"], Zones::One("Asia/Taipei")),
+    (&["korea", "south korea", "republic of korea", "kr"], Zones::One("Asia/Seoul")),
+    (&["india", "in"], Zones::One("Asia/Kolkata")),
+    (&["sri lanka", "lk"], Zones::One("Asia/Colombo")),
+    (&["bangladesh", "bd"], Zones::One("Asia/Dhaka")),
+    (&["pakistan", "pk"], Zones::One("Asia/Karachi")),
+    (&["nepal", "np"], Zones::One("Asia/Kathmandu")),
+    (&["united arab emirates", "uae", "ae"], Zones::One("Asia/Dubai")),
+    (&["saudi arabia", "sa"], Zones::One("Asia/Riyadh")),
+    (&["qatar", "qa"], Zones::One("Asia/Qatar")),
+    (&["israel", "il"], Zones::One("Asia/Jerusalem")),
+    (&["turkey", "türkiye", "tr"], Zones::One("Europe/Istanbul")),
+    (&["new zealand", "nz"], Zones::One("Pacific/Auckland")),
+    (&["united kingdom", "uk", "gb", "great britain", "england", "scotland", "wales"], Zones::One("Europe/London")),
+    (&["ireland", "ie"], Zones::One("Europe/Dublin")),
+    (&["germany", "deutschland", "de"], Zones::One("Europe/Berlin")),
+    (&["france", "fr"], Zones::One("Europe/Paris")),
+    (&["netherlands", "the netherlands", "nl"], Zones::One("Europe/Amsterdam")),
+    (&["belgium", "be"], Zones::One("Europe/Brussels")),
+    (&["luxembourg", "lu"], Zones::One("Europe/Luxembourg")),
+    (&["switzerland", "ch"], Zones::One("Europe/Zurich")),
+    (&["austria", "at"], Zones::One("Europe/Vienna")),
+    (&["spain", "es"], Zones::One("Europe/Madrid")),
+    (&["portugal", "pt"], Zones::One("Europe/Lisbon")),
+    (&["italy", "it"], Zones::One("Europe/Rome")),
+    (&["denmark", "dk"], Zones::One("Europe/Copenhagen")),
+    (&["sweden", "se"], Zones::One("Europe/Stockholm")),
+    (&["norway", "no"], Zones::One("Europe/Oslo")),
+    (&["finland", "fi"], Zones::One("Europe/Helsinki")),
+    (&["poland", "pl"], Zones::One("Europe/Warsaw")),
+    (&["czechia", "czech republic", "cz"], Zones::One("Europe/Prague")),
+    (&["hungary", "hu"], Zones::One("Europe/Budapest")),
+    (&["greece", "gr"], Zones::One("Europe/Athens")),
+    (&["romania", "ro"], Zones::One("Europe/Bucharest")),
+    (&["ukraine", "ua"], Zones::One("Europe/Kyiv")),
+    (&["egypt", "eg"], Zones::One("Africa/Cairo")),
+    (&["south africa", "za"], Zones::One("Africa/Johannesburg")),
+    (&["nigeria", "ng"], Zones::One("Africa/Lagos")),
+    (&["kenya", "ke"], Zones::One("Africa/Nairobi")),
+    (&["argentina", "ar"], Zones::One("America/Argentina/Buenos_Aires")),
+    (&["colombia", "co"], Zones::One("America/Bogota")),
+    (&["peru", "pe"], Zones::One("America/Lima")),
+    (
+        &["united states", "united states of america", "usa", "us"],
+        Zones::Many(&["America/", "Pacific/Honolulu"]),
+    ),
+    (&["canada", "ca"], Zones::Many(&["America/"])),
+    (&["mexico", "mx"], Zones::Many(&["America/"])),
+    (&["brazil", "brasil", "br"], Zones::Many(&["America/"])),
+    (&["australia", "au"], Zones::Many(&["Australia/"])),
+    (&["indonesia", "id"], Zones::Many(&["Asia/"])),
+    (&["russia", "russian federation", "ru"], Zones::Many(&["Europe/", "Asia/"])),
+];
+
+/// The IANA name for `name`, matched without regard to case
+/// (`asia/singapore` is `Asia/Singapore`).
+pub fn canonical(name: &str) -> Option<&'static str> {
+    let name = name.trim();
+    TZ_VARIANTS.iter().map(|tz| tz.name()).find(|tz| tz.eq_ignore_ascii_case(name))
+}
+
+/// The time zone of the first address it can be told for: by its country,
+/// or, in countries spanning several zones, by its city when that names a
+/// zone (`Chicago` is `America/Chicago`).
+pub fn for_addresses(addresses: &[CardFormAddressInput]) -> Option<&'static str> {
+    addresses.iter().find_map(|a| for_address(&a.city, &a.country))
+}
+
+fn for_address(city: &str, country: &str) -> Option<&'static str> {
+    let country = country.trim().to_lowercase();
+    let zones = COUNTRIES.iter().find(|(names, _)| names.contains(&country.as_str()));
+    match zones.map(|(_, zones)| zones) {
+        Some(Zones::One(zone)) => Some(zone),
+        Some(Zones::Many(areas)) => by_city(city).filter(|zone| areas.iter().any(|a| zone.starts_with(a))),
+        None => by_city(city),
+    }
+}
+
+/// The zone named after `city`, e.g. `Asia/Tokyo` for Tokyo.
+fn by_city(city: &str) -> Option<&'static str> {
+    let city = city.trim().replace(' ', "_");
+    if city.is_empty() {
+        return None;
+    }
+    TZ_VARIANTS
+        .iter()
+        .map(|tz| tz.name())
+        .find(|zone| zone.rsplit('/').next().is_some_and(|c| c.eq_ignore_ascii_case(&city)) && zone.contains('/'))
+}
+
+/// The time now in `timezone`, as RFC 3339 with its UTC offset, e.g.
+/// `2026-10-16T21:04:05+08:00`; `None` without a valid zone.
+pub fn local_time(timezone: &str) -> Option<String> {
+    let tz: Tz = timezone.parse().ok()?;
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let now = DateTime::from_timestamp(secs as i64, 0)?;
+    Some(now.with_timezone(&tz).to_rfc3339())
+}
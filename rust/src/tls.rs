@@ -0,0 +1,93 @@
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use axum::{
+    extract::Request,
+    http::{header, StatusCode, Uri},
+    response::{IntoResponse, Redirect},
+    Router,
+};
+use tokio_rustls::rustls::{
+    self,
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
+    server::WebPkiClientVerifier,
+};
+use tracing::info;
+
+/// The certificates in a PEM file.
+fn read_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .with_context(|| format!("read certificate {}", path.display()))?;
+    anyhow::ensure!(!certs.is_empty(), "no certificate in {}", path.display());
+    Ok(certs)
+}
+
+/// The HTTPS settings for a PEM certificate chain and its private key
+/// (PKCS#8, PKCS#1, or SEC1). HTTP/2 and HTTP/1.1 are offered. With
+/// `client_ca`, a PEM file of CA certificates, the handshake fails unless
+/// the client presents a certificate one of them issued.
+pub fn server_config(cert: &Path, key: &Path, client_ca: Option<&Path>) -> Result<Arc<rustls::ServerConfig>> {
+    let chain = read_certs(cert)?;
+    let key = PrivateKeyDer::from_pem_file(key).with_context(|| format!("read private key {}", key.display()))?;
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let builder = rustls::ServerConfig::builder_with_provider(provider.clone()).with_safe_default_protocol_versions()?;
+    let builder = match client_ca {
+        Some(path) => {
+            let mut roots = rustls::RootCertStore::empty();
+            for ca in read_certs(path)? {
+                roots.add(ca).with_context(|| format!("bad CA certificate in {}", path.display()))?;
+            }
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider).build()?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+    let mut config = builder
+        .with_single_cert(chain, key)
+        .context("certificate and key do not match")?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(Arc::new(config))
+}
+
+/// Where a plain HTTP request goes on the HTTPS port: same host, path, and
+/// query.
+fn https_url(request: &Request, https_port: u16) -> Option<String> {
+    let host = request
+        .headers()
+        .get(header::HOST)
+        .and_then(|v| v.to_str().ok())
+        .or_else(|| request.uri().host())?;
+    // Drop the port, keeping an IPv6 address's brackets
+    let host = match host.find(']') {
+        Some(end) if host.starts_with('[') => &host[..=end],
+        _ => match host.split_once(':') {
+            Some((name, port)) if !port.contains(':') => name,
+            _ => host,
+        },
+    };
+    let path = request.uri().path_and_query().map_or("/", |pq| pq.as_str());
+    let url = match https_port {
+        443 => format!("https://{host}{path}"),
+        port => format!("https://{host}:{port}{path}"),
+    };
+    url.parse::<Uri>().ok().map(|_| url)
+}
+
+/// Answers every plain HTTP request on `port` of `ip` with a permanent
+/// redirect to the same URL over HTTPS on `https_port`.
+pub async fn redirect_http(ip: IpAddr, port: u16, https_port: u16) -> Result<()> {
+    let app = Router::new().fallback(move |request: Request| async move {
+        match https_url(&request, https_port) {
+            Some(url) => Redirect::permanent(&url).into_response(),
+            None => (StatusCode::BAD_REQUEST, "missing Host header").into_response(),
+        }
+    });
+    let listener = tokio::net::TcpListener::bind((ip, port)).await?;
+    info!("Redirecting http://localhost:{port}/ to HTTPS");
+    axum::serve(listener, app).await?;
+    Ok(())
+}
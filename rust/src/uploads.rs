@@ -0,0 +1,124 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::{base_path, models::ThumbSize, photo, storage::BlobStore};
+
+/// Stored photos, attachments, and logos, by the paths rows keep for them
+/// (`uploads/<sha256>.jpg`). Handlers only go through this, so a backend,
+/// a test double, or a cache in front of one can be swapped in.
+#[async_trait]
+pub trait PhotoStorage: Send + Sync {
+    /// Stores `data` in `subdir` (none if empty), named by its SHA-256 and
+    /// `ext`, so the same bytes always land in the same file. Returns the
+    /// stored path and whether the file is new; an existing one is left as
+    /// it is.
+    async fn store(&self, subdir: &str, data: &[u8], ext: &str) -> Result<(String, bool)>;
+
+    /// Stores a photo, ready as it is, with its thumbnails. A photo that
+    /// cannot be decoded is kept without them; they are retried when asked
+    /// for.
+    async fn save_photo(&self, data: &[u8], ext: &str) -> Result<String>;
+
+    /// The bytes of the file at `path`, `None` if there is none.
+    async fn read(&self, path: &str) -> Result<Option<Vec<u8>>>;
+
+    /// The thumbnail of `size` of the photo at `path`, made now if it is
+    /// missing, as for photos uploaded before thumbnails were. `None` if
+    /// there is no such photo.
+    async fn thumbnail(&self, path: &str, size: ThumbSize) -> Result<Option<Vec<u8>>>;
+
+    /// Removes the file at `path` and any thumbnails of it. Nothing for an
+    /// empty path, as the store returns for a file still in use. Callers
+    /// go through `AppState::remove_files`, which checks that no row took
+    /// the file meanwhile.
+    async fn remove(&self, path: &str);
+}
+
+/// The key of a stored path in the blob store, `uploads/abc.jpg` →
+/// `abc.jpg`.
+fn key(path: &str) -> &str {
+    base_path::strip(path).trim_start_matches('/').trim_start_matches("uploads/")
+}
+
+/// Uploads kept in a `BlobStore`, thumbnails beside their photo.
+pub struct Uploads {
+    blobs: Box<dyn BlobStore>,
+}
+
+impl Uploads {
+    pub fn new(blobs: Box<dyn BlobStore>) -> Self {
+        Self { blobs }
+    }
+}
+
+#[async_trait]
+impl PhotoStorage for Uploads {
+    async fn store(&self, subdir: &str, data: &[u8], ext: &str) -> Result<(String, bool)> {
+        let mut name = hex::encode(Sha256::digest(data));
+        if !ext.is_empty() {
+            name = format!("{name}.{ext}");
+        }
+        let name = if subdir.is_empty() { name } else { format!("{subdir}/{name}") };
+        if self.blobs.exists(&name).await.unwrap_or(false) {
+            return Ok((format!("uploads/{name}"), false));
+        }
+        self.blobs.put(&name, data.to_vec()).await.context("store upload")?;
+        Ok((format!("uploads/{name}"), true))
+    }
+
+    async fn save_photo(&self, data: &[u8], ext: &str) -> Result<String> {
+        let (stored, new) = self.store("", data, ext).await?;
+        if !new {
+            return Ok(stored);
+        }
+        let (photo_key, data) = (key(&stored).to_string(), data.to_vec());
+        let thumbs = tokio::task::spawn_blocking(move || photo::make_thumbnails(&photo_key, &data)).await;
+        match thumbs {
+            Ok(Ok(thumbs)) => {
+                for (size, thumb) in thumbs {
+                    if let Err(e) = self.blobs.put(&photo::thumb_key(key(&stored), size), thumb).await {
+                        warn!("thumbnails of {stored}: {e:#}");
+                    }
+                }
+            }
+            Ok(Err(e)) => warn!("thumbnails of {stored}: {e:#}"),
+            Err(e) => warn!("thumbnails of {stored}: {e}"),
+        }
+        Ok(stored)
+    }
+
+    async fn read(&self, path: &str) -> Result<Option<Vec<u8>>> {
+        self.blobs.get(key(path)).await
+    }
+
+    async fn thumbnail(&self, path: &str, size: ThumbSize) -> Result<Option<Vec<u8>>> {
+        let photo_key = key(path).to_string();
+        let thumb_key = photo::thumb_key(&photo_key, size);
+        if let Some(thumb) = self.blobs.get(&thumb_key).await? {
+            return Ok(Some(thumb));
+        }
+        let Some(data) = self.blobs.get(&photo_key).await? else {
+            return Ok(None);
+        };
+        let thumb = tokio::task::spawn_blocking(move || photo::make_thumbnail(&photo_key, &data, size)).await??;
+        if let Err(e) = self.blobs.put(&thumb_key, thumb.clone()).await {
+            warn!("store {thumb_key}: {e:#}");
+        }
+        Ok(Some(thumb))
+    }
+
+    async fn remove(&self, path: &str) {
+        if path.is_empty() {
+            return;
+        }
+        let photo_key = key(path);
+        for size in ThumbSize::ALL {
+            let _ = self.blobs.delete(&photo::thumb_key(photo_key, size)).await;
+        }
+        if let Err(e) = self.blobs.delete(photo_key).await {
+            warn!("remove {path}: {e:#}");
+        }
+    }
+}
@@ -0,0 +1,499 @@
+use std::{collections::BTreeMap, fmt};
+
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    dates, events, share, social, timezone,
+    models::{
+        canonical_email, canonical_website, display_name, BulkTagInput, CardFormAddressInput, CardFormEmailInput,
+        CardFormPhoneInput,
+        CardFormImInput, CardFormSocialInput,
+        parse_timestamp, CardInput, CardPatch, FieldDefinition, FieldDefinitionInput, FieldType,
+        InteractionInput, ReminderInput, SendCardInput, ShareInput, TagMerge,
+        TagUpdate, WebhookInput,
+    },
+};
+
+/// Most cards a single bulk request may touch.
+pub const MAX_BULK_CARDS: usize = 1000;
+
+/// One problem with a field of a request body.
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct FieldError {
+    /// Dotted path of the field, e.g. `emails.address`
+    pub field: String,
+    /// Position in the list, for fields inside one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index: Option<usize>,
+    /// `required`, `invalid`, `too_many`, or `unknown`
+    pub code: String,
+    /// What is wrong, without the field name
+    pub message: String,
+}
+
+/// Renders as `emails[1].address: <message>`.
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.index, self.field.split_once('.')) {
+            (Some(i), Some((list, rest))) => write!(f, "{list}[{i}].{rest}")?,
+            (Some(i), None) => write!(f, "{}[{i}]", self.field)?,
+            (None, _) => f.write_str(&self.field)?,
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Every problem found in a request body, in field order.
+pub type FieldErrors = Vec<FieldError>;
+
+/// All the errors as one line, for APIs that only carry a message.
+pub fn message(errors: &[FieldError]) -> String {
+    errors.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ")
+}
+
+#[derive(Default)]
+struct Collector(FieldErrors);
+
+impl Collector {
+    fn add(&mut self, field: &str, index: Option<usize>, code: &str, message: impl Into<String>) {
+        self.0.push(FieldError {
+            field: field.to_string(),
+            index,
+            code: code.to_string(),
+            message: message.into(),
+        });
+    }
+
+    fn finish(self) -> Result<(), FieldErrors> {
+        if self.0.is_empty() {
+            Ok(())
+        } else {
+            Err(self.0)
+        }
+    }
+
+    fn name(&mut self, name: &str) {
+        if name.trim().is_empty() {
+            self.add("name", None, "required", "cannot be empty");
+        }
+    }
+
+    fn website(&mut self, website: &mut String) {
+        match canonical_website(website) {
+            Some(canonical) => *website = canonical,
+            None => self.add(
+                "website",
+                None,
+                "invalid",
+                format!("'{website}' is not an http(s) URL"),
+            ),
+        }
+    }
+
+    fn date(&mut self, field: &str, date: &mut String) {
+        match dates::canonical(date) {
+            Some(canonical) => *date = canonical,
+            None => self.add(field, None, "invalid", "must be YYYY-MM-DD, or --MM-DD without a year"),
+        }
+    }
+
+    /// Canonicalizes the zone's name, or derives it from `addresses` when
+    /// empty (leaving it empty if none tells).
+    fn timezone(&mut self, zone: &mut String, addresses: &[CardFormAddressInput]) {
+        if zone.trim().is_empty() {
+            *zone = timezone::for_addresses(addresses).unwrap_or_default().to_string();
+            return;
+        }
+        match timezone::canonical(zone) {
+            Some(canonical) => *zone = canonical.to_string(),
+            None => self.add(
+                "timezone",
+                None,
+                "invalid",
+                format!("'{zone}' is not an IANA time zone, such as Asia/Singapore"),
+            ),
+        }
+    }
+
+    fn phones(&mut self, phones: &[CardFormPhoneInput]) {
+        for (i, phone) in phones.iter().enumerate() {
+            if phone.number.trim().is_empty() {
+                self.add("phones.number", Some(i), "required", "cannot be empty");
+            }
+        }
+    }
+
+    fn emails(&mut self, emails: &mut [CardFormEmailInput]) {
+        for (i, email) in emails.iter_mut().enumerate() {
+            match canonical_email(&email.address) {
+                Some(canonical) => email.address = canonical,
+                None if email.address.trim().is_empty() => {
+                    self.add("emails.address", Some(i), "required", "cannot be empty")
+                }
+                None => self.add(
+                    "emails.address",
+                    Some(i),
+                    "invalid",
+                    format!("'{}' is not a valid email address", email.address),
+                ),
+            }
+        }
+    }
+
+    fn socials(&mut self, socials: &mut [CardFormSocialInput]) {
+        for (i, profile) in socials.iter_mut().enumerate() {
+            profile.platform = social::canonical_platform(&profile.platform);
+            profile.handle = social::canonical_handle(&profile.platform, &profile.handle);
+            if profile.platform.is_empty() {
+                self.add("socials.platform", Some(i), "required", "cannot be empty");
+            }
+            if profile.handle.is_empty() {
+                self.add("socials.handle", Some(i), "required", "cannot be empty");
+            }
+        }
+    }
+
+    fn ims(&mut self, ims: &mut [CardFormImInput]) {
+        for (i, im) in ims.iter_mut().enumerate() {
+            im.protocol = social::canonical_protocol(&im.protocol);
+            im.handle = social::canonical_im_handle(&im.protocol, &im.handle);
+            if im.protocol.is_empty() {
+                self.add("ims.protocol", Some(i), "required", "cannot be empty");
+            }
+            if im.handle.is_empty() {
+                self.add("ims.handle", Some(i), "required", "cannot be empty");
+            }
+        }
+    }
+
+    /// Checks custom values against `schema`, trimming them, dropping empty
+    /// ones, and normalizing dates and URLs.
+    fn custom(&mut self, values: &mut BTreeMap<String, String>, schema: &[FieldDefinition]) {
+        values.retain(|_, value| {
+            *value = value.trim().to_string();
+            !value.is_empty()
+        });
+        for key in values.keys() {
+            if !schema.iter().any(|f| &f.key == key) {
+                self.add(&format!("custom.{key}"), None, "unknown", "no such field in the schema");
+            }
+        }
+        for field in schema {
+            let path = format!("custom.{}", field.key);
+            let Some(value) = values.get_mut(&field.key) else {
+                if field.required {
+                    self.add(&path, None, "required", "cannot be empty");
+                }
+                continue;
+            };
+            match field.field_type {
+                FieldType::Text => {}
+                FieldType::Date => match chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+                    Ok(date) => *value = date.format("%Y-%m-%d").to_string(),
+                    Err(_) => self.add(&path, None, "invalid", "must be a date (YYYY-MM-DD)"),
+                },
+                FieldType::Url => match canonical_website(value) {
+                    Some(url) => *value = url,
+                    None => self.add(&path, None, "invalid", format!("'{value}' is not an http(s) URL")),
+                },
+                FieldType::Select => {
+                    if !field.options.contains(value) {
+                        self.add(
+                            &path,
+                            None,
+                            "invalid",
+                            format!("must be one of {}", field.options.join(", ")),
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Checks a full card against the built-in rules and the custom field
+/// `schema`, canonicalizing its emails, website, time zone, social profiles, IM handles,
+/// and custom values in place.
+pub fn card_input(input: &mut CardInput, schema: &[FieldDefinition]) -> Result<(), FieldErrors> {
+    let mut errors = Collector::default();
+    for part in [
+        &mut input.name_prefix,
+        &mut input.given_name,
+        &mut input.middle_name,
+        &mut input.family_name,
+        &mut input.name_suffix,
+        &mut input.name_phonetic,
+        &mut input.department,
+        &mut input.role_seniority,
+        &mut input.assistant_name,
+        &mut input.assistant_phone,
+    ] {
+        *part = part.trim().to_string();
+    }
+    if input.name.trim().is_empty() {
+        input.name = display_name(
+            &input.name_prefix,
+            &input.given_name,
+            &input.middle_name,
+            &input.family_name,
+            &input.name_suffix,
+        );
+    }
+    errors.name(&input.name);
+    errors.date("birthday", &mut input.birthday);
+    errors.date("anniversary", &mut input.anniversary);
+    errors.timezone(&mut input.timezone, &input.addresses);
+    errors.website(&mut input.website);
+    errors.phones(&input.phones);
+    errors.emails(&mut input.emails);
+    errors.socials(&mut input.socials);
+    errors.ims(&mut input.ims);
+    errors.custom(&mut input.custom, schema);
+    errors.finish()
+}
+
+/// Like `card_input`, for the fields a patch sets.
+pub fn card_patch(patch: &mut CardPatch, schema: &[FieldDefinition]) -> Result<(), FieldErrors> {
+    let mut errors = Collector::default();
+    for part in [
+        &mut patch.name_prefix,
+        &mut patch.given_name,
+        &mut patch.middle_name,
+        &mut patch.family_name,
+        &mut patch.name_suffix,
+        &mut patch.name_phonetic,
+        &mut patch.department,
+        &mut patch.role_seniority,
+        &mut patch.assistant_name,
+        &mut patch.assistant_phone,
+    ]
+    .into_iter()
+    .flatten()
+    {
+        *part = part.trim().to_string();
+    }
+    if let Some(name) = &patch.name {
+        errors.name(name);
+    }
+    if let Some(birthday) = &mut patch.birthday {
+        errors.date("birthday", birthday);
+    }
+    if let Some(anniversary) = &mut patch.anniversary {
+        errors.date("anniversary", anniversary);
+    }
+    // New addresses move the contact, so they re-derive the zone unless
+    // the patch sets one.
+    if let Some(zone) = &mut patch.timezone {
+        errors.timezone(zone, patch.addresses.as_deref().unwrap_or_default());
+    } else if let Some(addresses) = &patch.addresses {
+        patch.timezone = timezone::for_addresses(addresses).map(str::to_string);
+    }
+    if let Some(website) = &mut patch.website {
+        errors.website(website);
+    }
+    if let Some(phones) = &patch.phones {
+        errors.phones(phones);
+    }
+    if let Some(emails) = &mut patch.emails {
+        errors.emails(emails);
+    }
+    if let Some(socials) = &mut patch.socials {
+        errors.socials(socials);
+    }
+    if let Some(ims) = &mut patch.ims {
+        errors.ims(ims);
+    }
+    if let Some(custom) = &mut patch.custom {
+        errors.custom(custom, schema);
+    }
+    errors.finish()
+}
+
+/// Checks a custom field definition, trimming its label and options.
+pub fn field_definition(key: &str, input: &mut FieldDefinitionInput) -> Result<(), FieldErrors> {
+    let mut errors = Collector::default();
+    let mut chars = key.chars();
+    let key_ok = key.len() <= 64
+        && chars.next().is_some_and(|c| c.is_ascii_lowercase())
+        && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if !key_ok {
+        errors.add(
+            "key",
+            None,
+            "invalid",
+            "must start with a-z and use only a-z, 0-9, and _ (at most 64)",
+        );
+    }
+    input.label = input.label.trim().to_string();
+    if input.label.is_empty() {
+        input.label = key.to_string();
+    }
+    if input.field_type == FieldType::Select {
+        input.options = input.options.iter().map(|o| o.trim().to_string()).collect();
+        for (i, option) in input.options.iter().enumerate() {
+            if option.is_empty() {
+                errors.add("options", Some(i), "required", "cannot be empty");
+            } else if input.options[..i].contains(option) {
+                errors.add("options", Some(i), "invalid", format!("'{option}' is listed twice"));
+            }
+        }
+        if input.options.is_empty() {
+            errors.add("options", None, "required", "a select field needs at least one option");
+        }
+    } else {
+        input.options.clear();
+    }
+    errors.finish()
+}
+
+/// Checks a tag update, trimming it and lower-casing the color.
+pub fn tag_update(update: &mut TagUpdate) -> Result<(), FieldErrors> {
+    let mut errors = Collector::default();
+    if let Some(name) = &mut update.name {
+        *name = name.trim().to_string();
+        if name.is_empty() {
+            errors.add("name", None, "required", "cannot be empty");
+        }
+    }
+    if let Some(color) = &mut update.color {
+        *color = color.trim().to_lowercase();
+        let hex = color.strip_prefix('#').unwrap_or_default();
+        if !color.is_empty() && (hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit())) {
+            errors.add("color", None, "invalid", "must look like #1a2b3c");
+        }
+    }
+    if let Some(description) = &mut update.description {
+        *description = description.trim().to_string();
+    }
+    errors.finish()
+}
+
+pub fn tag_alias(alias: &str) -> Result<(), FieldErrors> {
+    let mut errors = Collector::default();
+    if alias.trim().is_empty() {
+        errors.add("alias", None, "required", "cannot be empty");
+    }
+    errors.finish()
+}
+
+pub fn tag_merge(merge: &TagMerge) -> Result<(), FieldErrors> {
+    let mut errors = Collector::default();
+    if merge.from.is_empty() {
+        errors.add("from", None, "required", "must name at least one tag");
+    }
+    if merge.into.trim().is_empty() {
+        errors.add("into", None, "required", "cannot be empty");
+    }
+    errors.finish()
+}
+
+pub fn bulk_tags(input: &BulkTagInput) -> Result<(), FieldErrors> {
+    let mut errors = Collector::default();
+    if input.ids.is_empty() {
+        errors.add("ids", None, "required", "must name at least one card");
+    } else if input.ids.len() > MAX_BULK_CARDS {
+        errors.add(
+            "ids",
+            None,
+            "too_many",
+            format!("at most {MAX_BULK_CARDS} cards per request"),
+        );
+    }
+    if input.add.iter().chain(&input.remove).all(|t| t.trim().is_empty()) {
+        errors.add("add", None, "required", "must name at least one tag, here or in remove");
+    }
+    errors.finish()
+}
+
+pub fn webhook_input(input: &WebhookInput) -> Result<(), FieldErrors> {
+    let mut errors = Collector::default();
+    if !(input.url.starts_with("http://") || input.url.starts_with("https://")) {
+        errors.add("url", None, "invalid", "must be an http(s) URL");
+    }
+    for (i, event) in input.events.iter().enumerate() {
+        if !events::CARD_EVENTS.contains(&event.as_str()) {
+            errors.add(
+                "events",
+                Some(i),
+                "invalid",
+                format!(
+                    "unknown event '{event}'; expected one of {}",
+                    events::CARD_EVENTS.join(", ")
+                ),
+            );
+        }
+    }
+    errors.finish()
+}
+
+/// Checks a logged interaction, storing `occurred_at` in the database's
+/// timestamp form and trimming the note.
+pub fn interaction(input: &mut InteractionInput) -> Result<(), FieldErrors> {
+    let mut errors = Collector::default();
+    if let Some(occurred_at) = &mut input.occurred_at {
+        match parse_timestamp(occurred_at) {
+            Some(stored) => *occurred_at = stored,
+            None => errors.add(
+                "occurred_at",
+                None,
+                "invalid",
+                "must be a date (YYYY-MM-DD) or RFC 3339 timestamp",
+            ),
+        }
+    }
+    input.note = input.note.trim().to_string();
+    errors.finish()
+}
+
+pub fn share_input(input: &ShareInput) -> Result<(), FieldErrors> {
+    let mut errors = Collector::default();
+    if let Some(days) = input.expires_in_days {
+        if !(1..=share::MAX_DAYS).contains(&days) {
+            errors.add(
+                "expires_in_days",
+                None,
+                "invalid",
+                format!("must be between 1 and {}", share::MAX_DAYS),
+            );
+        }
+    }
+    errors.finish()
+}
+
+/// Longest note `POST /cards/:id/send` puts in the email, in characters.
+const MAX_SEND_MESSAGE: usize = 2000;
+
+/// Checks the recipient of an emailed card, canonicalizing the address and
+/// trimming the note.
+pub fn send_card_input(input: &mut SendCardInput) -> Result<(), FieldErrors> {
+    let mut errors = Collector::default();
+    match canonical_email(&input.to) {
+        Some(to) => input.to = to,
+        None => errors.add("to", None, "invalid", "must be an email address"),
+    }
+    input.message = input.message.trim().to_string();
+    if input.message.chars().count() > MAX_SEND_MESSAGE {
+        errors.add(
+            "message",
+            None,
+            "too_long",
+            format!("must be at most {MAX_SEND_MESSAGE} characters"),
+        );
+    }
+    errors.finish()
+}
+
+/// Checks a reminder, trimming its title and normalizing the due date.
+pub fn reminder(input: &mut ReminderInput) -> Result<(), FieldErrors> {
+    let mut errors = Collector::default();
+    input.title = input.title.trim().to_string();
+    if input.title.is_empty() {
+        errors.add("title", None, "required", "cannot be empty");
+    }
+    match chrono::NaiveDate::parse_from_str(input.due_date.trim(), "%Y-%m-%d") {
+        Ok(date) => input.due_date = date.format("%Y-%m-%d").to_string(),
+        Err(_) => errors.add("due_date", None, "invalid", "must be a date (YYYY-MM-DD)"),
+    }
+    errors.finish()
+}
@@ -0,0 +1,481 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+
+use crate::{
+    ical::{escape, push, stamp},
+    models::{
+        Card, CardFormAddressInput, CardFormEmailInput, CardFormImInput, CardFormPhoneInput,
+        CardFormSocialInput, CardInput,
+    },
+    timezone,
+};
+
+/// An embedded `PHOTO`: its MIME type and the image bytes.
+pub struct Photo<'a> {
+    pub mime: &'a str,
+    pub data: &'a [u8],
+}
+
+/// The card as a vCard 3.0 (RFC 2426), the version address books import
+/// most reliably. Fields 3.0 lacks use the extensions Apple Contacts,
+/// Outlook, and Google read: `X-PHONETIC-*`, `X-ANNIVERSARY`,
+/// `X-SOCIALPROFILE`, `X-ASSISTANT`; `IMPP` comes from RFC 4770.
+pub fn render(card: &Card, photo: Option<Photo>) -> String {
+    let mut out = String::new();
+    push(&mut out, "BEGIN:VCARD");
+    push(&mut out, "VERSION:3.0");
+    push(&mut out, &format!("FN:{}", escape(&card.name)));
+    push(
+        &mut out,
+        &format!(
+            "N:{}",
+            structured(&[
+                &card.family_name,
+                &card.given_name,
+                &card.middle_name,
+                &card.name_prefix,
+                &card.name_suffix,
+            ])
+        ),
+    );
+    for (property, reading) in phonetic(card) {
+        push(&mut out, &format!("{property}:{}", escape(reading)));
+    }
+    match (card.company.as_str(), card.department.as_str()) {
+        ("", "") => {}
+        (company, "") => push(&mut out, &format!("ORG:{}", escape(company))),
+        (company, department) => {
+            push(&mut out, &format!("ORG:{}", structured(&[company, department])))
+        }
+    }
+    text(&mut out, "TITLE", &card.title);
+    text(&mut out, "ROLE", &card.role_seniority);
+    text(&mut out, "BDAY", &date(&card.birthday));
+    text(&mut out, "X-ANNIVERSARY", &date(&card.anniversary));
+    if !card.timezone.is_empty() {
+        push(&mut out, &format!("TZ;VALUE=text:{}", escape(&card.timezone)));
+    }
+
+    for phone in &card.phones {
+        let number = if phone.e164.is_empty() { &phone.number } else { &phone.e164 };
+        push(&mut out, &format!("TEL{}:{}", type_param(&phone.label), escape(number)));
+    }
+    for email in &card.emails {
+        let types = match type_value(&email.label) {
+            Some(t) => format!(";TYPE=INTERNET,{t}"),
+            None => ";TYPE=INTERNET".to_string(),
+        };
+        push(&mut out, &format!("EMAIL{types}:{}", escape(&email.address)));
+    }
+    for address in &card.addresses {
+        let adr = structured(&["", "", &address.street, &address.city, "", &address.postal, &address.country]);
+        push(&mut out, &format!("ADR{}:{adr}", type_param(&address.label)));
+    }
+    text(&mut out, "URL", &card.website);
+    for social in &card.socials {
+        let value = if social.url.is_empty() { &social.handle } else { &social.url };
+        push(
+            &mut out,
+            &format!("X-SOCIALPROFILE;TYPE={}:{}", param(&social.platform), escape(value)),
+        );
+    }
+    for im in &card.ims {
+        push(
+            &mut out,
+            &format!(
+                "IMPP;X-SERVICE-TYPE={}{}:{}:{}",
+                param(&service_name(&im.protocol)),
+                type_param(&im.label),
+                im.protocol,
+                im.handle
+            ),
+        );
+    }
+    text(&mut out, "X-ASSISTANT", &card.assistant_name);
+    if !card.assistant_phone.is_empty() {
+        push(&mut out, &format!("TEL;TYPE=X-ASSISTANT:{}", escape(&card.assistant_phone)));
+    }
+    text(&mut out, "NOTE", &card.notes);
+    if !card.tags.is_empty() {
+        let tags: Vec<String> = card.tags.iter().map(|t| escape(t)).collect();
+        push(&mut out, &format!("CATEGORIES:{}", tags.join(",")));
+    }
+    if let Some(photo) = photo {
+        let kind = photo.mime.rsplit('/').next().unwrap_or("jpeg").to_uppercase();
+        push(&mut out, &format!("PHOTO;ENCODING=b;TYPE={kind}:{}", BASE64.encode(photo.data)));
+    }
+    push(&mut out, &format!("UID:cardvault-{}", card.id));
+    push(&mut out, &format!("REV:{}", stamp(&card.updated_at)));
+    push(&mut out, "END:VCARD");
+    out
+}
+
+/// A file name for the card's `.vcf`, from its name.
+pub fn filename(card: &Card) -> String {
+    let stem: String = card
+        .name
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
+    let stem = stem.trim_matches('_');
+    if stem.is_empty() {
+        format!("card-{}.vcf", card.id)
+    } else {
+        format!("{stem}.vcf")
+    }
+}
+
+/// `X-PHONETIC-FIRST-NAME`/`-LAST-NAME` when the reading has one word per
+/// name part (in the order the display name uses), `SORT-STRING` otherwise.
+fn phonetic(card: &Card) -> Vec<(&'static str, &str)> {
+    let reading = card.name_phonetic.trim();
+    if reading.is_empty() {
+        return vec![];
+    }
+    let words: Vec<&str> = reading.split_whitespace().collect();
+    if words.len() == 2 && !card.given_name.is_empty() && !card.family_name.is_empty() {
+        let family_first = card.name.starts_with(&card.family_name);
+        let (family, given) = if family_first { (words[0], words[1]) } else { (words[1], words[0]) };
+        return vec![("X-PHONETIC-FIRST-NAME", given), ("X-PHONETIC-LAST-NAME", family)];
+    }
+    vec![("SORT-STRING", reading)]
+}
+
+/// Birthday or anniversary in vCard form; `--MMDD` without a year.
+fn date(date: &str) -> String {
+    match date.strip_prefix("--") {
+        Some(month_day) => format!("--{}", month_day.replace('-', "")),
+        None => date.to_string(),
+    }
+}
+
+/// The vCard `TYPE` for a free-form label, if it maps to one.
+fn type_value(label: &str) -> Option<&'static str> {
+    match label.trim().to_lowercase().as_str() {
+        "work" | "office" => Some("WORK"),
+        "home" | "personal" => Some("HOME"),
+        "mobile" | "cell" => Some("CELL"),
+        "fax" => Some("FAX"),
+        _ => None,
+    }
+}
+
+fn type_param(label: &str) -> String {
+    type_value(label).map_or_else(String::new, |t| format!(";TYPE={t}"))
+}
+
+/// Display name of a messenger as `X-SERVICE-TYPE` expects it.
+fn service_name(protocol: &str) -> String {
+    match protocol {
+        "whatsapp" => "WhatsApp".to_string(),
+        "wechat" => "WeChat".to_string(),
+        "qq" => "QQ".to_string(),
+        _ => {
+            let mut chars = protocol.chars();
+            chars
+                .next()
+                .map(|c| c.to_uppercase().chain(chars).collect())
+                .unwrap_or_default()
+        }
+    }
+}
+
+fn text(out: &mut String, property: &str, value: &str) {
+    if !value.is_empty() {
+        push(out, &format!("{property}:{}", escape(value)));
+    }
+}
+
+/// A parameter value, with the characters that would end it dropped.
+fn param(value: &str) -> String {
+    value.chars().filter(|c| !matches!(c, ';' | ':' | ',' | '"')).collect()
+}
+
+fn structured(parts: &[&str]) -> String {
+    parts.iter().map(|p| escape(p)).collect::<Vec<_>>().join(";")
+}
+
+/// One content line: the property name (group dropped, uppercased), its
+/// parameters as `(NAME, value)` pairs, and the raw value.
+struct Property {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    /// Values of `TYPE` parameters, lowercased; vCard 2.1 bare parameters
+    /// (`TEL;CELL:`) count as types too.
+    fn types(&self) -> Vec<String> {
+        self.params
+            .iter()
+            .filter(|(name, _)| name == "TYPE" || name.is_empty())
+            .flat_map(|(_, value)| value.split(','))
+            .map(|t| t.trim().to_lowercase())
+            .collect()
+    }
+
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+}
+
+/// Reads the first vCard (2.1, 3.0, or 4.0) in `text` into a card body,
+/// or `None` if there is none. Values are taken as they come; the usual
+/// validation canonicalizes them when the card is saved.
+pub fn parse(text: &str) -> Option<CardInput> {
+    let start = text.to_ascii_uppercase().find("BEGIN:VCARD")?;
+    let mut input = CardInput::default();
+    let mut phonetic = (String::new(), String::new());
+    for property in properties(&text[start..]) {
+        let value = &property.value;
+        match property.name.as_str() {
+            "END" => break,
+            "FN" => input.name = unescape(value),
+            "N" => {
+                let parts = components(value);
+                let part = |i: usize| parts.get(i).cloned().unwrap_or_default();
+                input.family_name = part(0);
+                input.given_name = part(1);
+                input.middle_name = part(2);
+                input.name_prefix = part(3);
+                input.name_suffix = part(4);
+            }
+            "X-PHONETIC-FIRST-NAME" => phonetic.0 = unescape(value),
+            "X-PHONETIC-LAST-NAME" => phonetic.1 = unescape(value),
+            "SORT-STRING" => input.name_phonetic = unescape(value),
+            "ORG" => {
+                let parts = components(value);
+                input.company = parts.first().cloned().unwrap_or_default();
+                input.department = parts.get(1).cloned().unwrap_or_default();
+            }
+            "TITLE" => input.title = unescape(value),
+            "ROLE" => input.role_seniority = unescape(value),
+            "BDAY" => input.birthday = parse_date(value),
+            "ANNIVERSARY" | "X-ANNIVERSARY" => input.anniversary = parse_date(value),
+            // A UTC offset (`+08:00`) names no zone; the address may.
+            "TZ" => {
+                if let Some(zone) = timezone::canonical(&unescape(value)) {
+                    input.timezone = zone.to_string();
+                }
+            }
+            "TEL" if property.types().iter().any(|t| t == "x-assistant") => {
+                input.assistant_phone = unescape(value)
+            }
+            "TEL" => input.phones.push(CardFormPhoneInput {
+                label: phone_label(&property.types()).to_string(),
+                number: unescape(value).trim_start_matches("tel:").to_string(),
+            }),
+            "EMAIL" => {
+                let home = property.types().iter().any(|t| t == "home");
+                input.emails.push(CardFormEmailInput {
+                    label: if home { "personal" } else { "work" }.to_string(),
+                    address: unescape(value),
+                });
+            }
+            "ADR" => {
+                let parts = components(value);
+                let part = |i: usize| parts.get(i).cloned().unwrap_or_default();
+                let home = property.types().iter().any(|t| t == "home");
+                // PO box and extended address come first; keep them with the street.
+                let street = [part(0), part(1), part(2)]
+                    .into_iter()
+                    .filter(|s| !s.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                input.addresses.push(CardFormAddressInput {
+                    label: if home { "home" } else { "office" }.to_string(),
+                    street,
+                    city: part(3),
+                    postal: part(5),
+                    country: part(6),
+                });
+            }
+            "URL" if input.website.is_empty() => input.website = unescape(value),
+            "NOTE" => input.notes = unescape(value),
+            "CATEGORIES" => input.tags.extend(components_by(value, ',')),
+            "X-SOCIALPROFILE" => input.socials.push(CardFormSocialInput {
+                platform: property.types().into_iter().next().unwrap_or_default(),
+                handle: unescape(value),
+            }),
+            "IMPP" => {
+                let value = unescape(value);
+                let (scheme, handle) = value.split_once(':').unwrap_or(("", value.as_str()));
+                let protocol = property
+                    .param("X-SERVICE-TYPE")
+                    .map(str::to_lowercase)
+                    .unwrap_or_else(|| scheme.to_lowercase());
+                let label = property.types().into_iter().find(|t| t == "home" || t == "work");
+                input.ims.push(CardFormImInput {
+                    label: label.unwrap_or_default(),
+                    protocol,
+                    handle: handle.to_string(),
+                });
+            }
+            "X-ASSISTANT" => input.assistant_name = unescape(value),
+            _ => {}
+        }
+    }
+    if input.name.is_empty() {
+        // vCard 2.1 makes FN optional.
+        input.name = [
+            &input.name_prefix,
+            &input.given_name,
+            &input.middle_name,
+            &input.family_name,
+            &input.name_suffix,
+        ]
+        .iter()
+        .filter(|s| !s.is_empty())
+        .map(|s| s.as_str())
+        .collect::<Vec<_>>()
+        .join(" ");
+    }
+    if input.name_phonetic.is_empty() {
+        let (given, family) = &phonetic;
+        let family_first = input.name.starts_with(&input.family_name) && !input.family_name.is_empty();
+        let words = if family_first { [family, given] } else { [given, family] };
+        input.name_phonetic = words
+            .iter()
+            .filter(|w| !w.is_empty())
+            .map(|w| w.as_str())
+            .collect::<Vec<_>>()
+            .join(" ");
+    }
+    Some(input)
+}
+
+/// Unfolds continuation lines and splits each content line into a
+/// [`Property`], decoding quoted-printable (vCard 2.1) values.
+fn properties(text: &str) -> Vec<Property> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        match lines.last_mut() {
+            Some(last) if line.starts_with([' ', '\t']) => last.push_str(&line[1..]),
+            // A quoted-printable soft line break continues the value.
+            Some(last) if last.ends_with('=') && last.to_uppercase().contains("QUOTED-PRINTABLE") => {
+                last.pop();
+                last.push_str(line);
+            }
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    lines
+        .iter()
+        .filter_map(|line| {
+            let (head, value) = split_unquoted(line, ':')?;
+            let mut parts = head.split(';');
+            let name = parts.next()?;
+            let name = name.rsplit('.').next().unwrap_or(name).trim().to_uppercase();
+            let params: Vec<(String, String)> = parts
+                .map(|p| match p.split_once('=') {
+                    Some((n, v)) => (n.trim().to_uppercase(), v.trim_matches('"').to_string()),
+                    None => (String::new(), p.to_string()),
+                })
+                .collect();
+            let quoted = params
+                .iter()
+                .any(|(n, v)| n == "ENCODING" && v.eq_ignore_ascii_case("QUOTED-PRINTABLE"));
+            let value = if quoted { quoted_printable(value) } else { value.to_string() };
+            Some(Property { name, params, value })
+        })
+        .collect()
+}
+
+/// Splits at the first `sep` outside double quotes.
+fn split_unquoted(line: &str, sep: char) -> Option<(&str, &str)> {
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c == sep && !quoted => return Some((&line[..i], &line[i + 1..])),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn quoted_printable(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'=' && i + 2 < bytes.len() {
+            if let Some(byte) = value.get(i + 1..i + 3).and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Undoes TEXT escaping.
+fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => {}
+        }
+    }
+    out.trim().to_string()
+}
+
+/// The `;`-separated components of a structured value, unescaped.
+fn components(value: &str) -> Vec<String> {
+    components_by(value, ';')
+}
+
+fn components_by(value: &str, sep: char) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                current.push('\\');
+                if let Some(next) = chars.next() {
+                    current.push(next);
+                }
+            }
+            c if c == sep => parts.push(unescape(&std::mem::take(&mut current))),
+            c => current.push(c),
+        }
+    }
+    parts.push(unescape(&current));
+    parts.retain(|p| sep == ';' || !p.is_empty());
+    parts
+}
+
+/// A vCard date (`1985-04-12`, `19850412`, `--0412`, `--04-12`, possibly
+/// with a time) in the card's `YYYY-MM-DD` / `--MM-DD` form. Anything else
+/// is passed through for validation to report.
+fn parse_date(value: &str) -> String {
+    let value = value.trim();
+    let date = value.split('T').next().unwrap_or(value);
+    let digits: String = date.chars().filter(char::is_ascii_digit).collect();
+    match (date.starts_with("--"), digits.len()) {
+        (true, 4) => format!("--{}-{}", &digits[..2], &digits[2..]),
+        (false, 8) => format!("{}-{}-{}", &digits[..4], &digits[4..6], &digits[6..]),
+        _ => value.to_string(),
+    }
+}
+
+fn phone_label(types: &[String]) -> &'static str {
+    for (vcard_type, label) in [("cell", "mobile"), ("fax", "fax"), ("home", "home"), ("work", "work")] {
+        if types.iter().any(|t| t == vcard_type) {
+            return label;
+        }
+    }
+    "mobile"
+}
@@ -0,0 +1,89 @@
+use axum::{
+    extract::Request,
+    http::{HeaderName, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+
+use crate::base_path;
+
+/// API versions this build can serve, newest last.
+pub const SUPPORTED: &[&str] = &["v1"];
+
+/// Version served on the unprefixed `/api/...` compatibility routes.
+pub const LEGACY_DEFAULT: &str = "v1";
+
+/// Versions scheduled for removal, with their RFC 8594 `Sunset` date
+/// (`None` = deprecated, no removal date yet). Add an entry here when a
+/// version is superseded and every response it serves will carry the
+/// deprecation headers.
+pub const DEPRECATED: &[(&str, Option<&str>)] = &[];
+
+static API_VERSION: HeaderName = HeaderName::from_static("api-version");
+static DEPRECATION: HeaderName = HeaderName::from_static("deprecation");
+static SUNSET: HeaderName = HeaderName::from_static("sunset");
+
+fn stamp(response: &mut Response, version: &str) {
+    let headers = response.headers_mut();
+    if let Ok(v) = HeaderValue::from_str(version) {
+        headers.insert(API_VERSION.clone(), v);
+    }
+    if let Some((_, sunset)) = DEPRECATED.iter().find(|(v, _)| *v == version) {
+        headers.insert(DEPRECATION.clone(), HeaderValue::from_static("true"));
+        if let Some(date) = sunset.and_then(|d| HeaderValue::from_str(d).ok()) {
+            headers.insert(SUNSET.clone(), date);
+        }
+    }
+}
+
+/// Middleware for the `/api/v1` mount.
+pub async fn v1(request: Request, next: Next) -> Response {
+    let mut response = next.run(request).await;
+    stamp(&mut response, "v1");
+    response
+}
+
+/// Middleware for the unprefixed `/api/...` routes kept for older clients.
+///
+/// Clients may pin a version with `Accept-Version: v1`; an unsupported value
+/// is rejected with 406 rather than silently served a different shape.
+/// Responses point at the versioned URL and are marked deprecated.
+pub async fn legacy(request: Request, next: Next) -> Response {
+    let requested = request
+        .headers()
+        .get("accept-version")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim().to_ascii_lowercase());
+
+    let version = match requested.as_deref() {
+        None | Some("") => LEGACY_DEFAULT.to_string(),
+        Some(v) if SUPPORTED.contains(&v) => v.to_string(),
+        Some(v) => {
+            return (
+                StatusCode::NOT_ACCEPTABLE,
+                Json(json!({
+                    "error": format!("unsupported API version '{v}'"),
+                    "supported": SUPPORTED,
+                })),
+            )
+                .into_response();
+        }
+    };
+
+    // Inside the `/api` nest the URI has already had its prefix stripped.
+    let successor = format!(
+        "<{}>; rel=\"successor-version\"",
+        base_path::url(&format!("/api/{version}{}", request.uri().path()))
+    );
+
+    let mut response = next.run(request).await;
+    stamp(&mut response, &version);
+    let headers = response.headers_mut();
+    headers.insert(DEPRECATION.clone(), HeaderValue::from_static("true"));
+    if let Ok(link) = HeaderValue::from_str(&successor) {
+        headers.insert(axum::http::header::LINK, link);
+    }
+    response
+}
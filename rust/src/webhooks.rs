@@ -0,0 +1,114 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use rusqlite::Connection;
+use serde_json::json;
+use sha2::Sha256;
+use tracing::{info, warn};
+
+use crate::{models::Card, store, telemetry};
+
+const MAX_ATTEMPTS: u32 = 5;
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Fans card events out to the registered webhooks. Deliveries run on
+/// background tasks so handlers never wait on remote endpoints.
+#[derive(Clone)]
+pub struct WebhookDispatcher {
+    conn: Arc<Mutex<Connection>>,
+    client: reqwest::Client,
+}
+
+impl WebhookDispatcher {
+    pub fn new(conn: Arc<Mutex<Connection>>) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .user_agent(concat!("CardVault-Webhooks/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .expect("failed to build webhook HTTP client");
+        Self { conn, client }
+    }
+
+    pub fn dispatch(&self, event: &'static str, card_id: i64, card: Option<Card>) {
+        let this = self.clone();
+        tokio::spawn(async move {
+            let conn = this.conn.clone();
+            let targets =
+                match telemetry::spawn_db(move || store::webhook_targets(&conn, event))
+                    .await
+                {
+                    Ok(Ok(t)) => t,
+                    Ok(Err(e)) => return warn!("webhook lookup failed: {e}"),
+                    Err(e) => return warn!("webhook lookup failed: {e}"),
+                };
+            if targets.is_empty() {
+                return;
+            }
+
+            let timestamp = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            let body = json!({
+                "event": event,
+                "card_id": card_id,
+                "card": card,
+                "timestamp": timestamp,
+            })
+            .to_string();
+
+            for (url, secret) in targets {
+                let this = this.clone();
+                let body = body.clone();
+                tokio::spawn(async move { this.deliver(event, &url, &secret, body).await });
+            }
+        });
+    }
+
+    async fn deliver(&self, event: &str, url: &str, secret: &str, body: String) {
+        let signature = sign(secret, body.as_bytes());
+        let delivery_id = uuid::Uuid::new_v4().to_string();
+        let mut backoff = INITIAL_BACKOFF;
+
+        for attempt in 1..=MAX_ATTEMPTS {
+            let result = self
+                .client
+                .post(url)
+                .header("content-type", "application/json")
+                .header("x-cardvault-event", event)
+                .header("x-cardvault-delivery", &delivery_id)
+                .header("x-cardvault-signature", format!("sha256={signature}"))
+                .body(body.clone())
+                .send()
+                .await;
+
+            match result {
+                Ok(resp) if resp.status().is_success() => {
+                    info!("webhook {event} delivered to {url} (attempt {attempt})");
+                    return;
+                }
+                Ok(resp) => warn!(
+                    "webhook {event} to {url} returned {} (attempt {attempt}/{MAX_ATTEMPTS})",
+                    resp.status()
+                ),
+                Err(e) => warn!("webhook {event} to {url} failed: {e} (attempt {attempt}/{MAX_ATTEMPTS})"),
+            }
+
+            if attempt < MAX_ATTEMPTS {
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+        }
+        warn!("webhook {event} to {url} dropped after {MAX_ATTEMPTS} attempts");
+    }
+}
+
+/// Hex-encoded HMAC-SHA256 of the request body, sent as
+/// `X-CardVault-Signature: sha256=<hex>`.
+fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}